@@ -0,0 +1,42 @@
+//! Procedural macros re-exported by `debug_services` with the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, ItemFn, LitStr};
+
+/// Wraps the function body in a timed block named after the function,
+/// e.g. `game::physics::step`, the name can be overridden with `#[profile("physics")]`.
+#[proc_macro_attribute]
+pub fn profile(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut function = parse_macro_input!(item as ItemFn);
+    let custom_name = if attr.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(attr as LitStr))
+    };
+
+    // The block would be kept open across await points and closed on another thread.
+    if let Some(asyncness) = &function.sig.asyncness {
+        return syn::Error::new_spanned(asyncness, "#[profile] doesn't support async functions")
+            .to_compile_error()
+            .into();
+    }
+
+    let name: TokenStream2 = match custom_name {
+        Some(name) => quote!(#name),
+        None => {
+            let ident = function.sig.ident.to_string();
+            quote!(concat!(module_path!(), "::", #ident))
+        }
+    };
+
+    let block = &function.block;
+
+    function.block = parse_quote!({
+        let _timed_block = ::debug_services::profiler::TimedBlock::new(#name, file!(), line!());
+        #block
+    });
+
+    TokenStream::from(quote!(#function))
+}
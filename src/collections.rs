@@ -0,0 +1,320 @@
+use std::ops::Index;
+
+/// Fixed capacity buffer, pushing into a full buffer overwrites the oldest item.
+#[derive(Clone, Debug)]
+pub struct RingBuffer<T> {
+    items: Vec<T>,
+    capacity: usize,
+    // Index of the oldest item once the buffer is full.
+    head: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ring buffer capacity should be positive");
+
+        RingBuffer {
+            items: Vec::with_capacity(capacity),
+            capacity,
+            head: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            self.items[self.head] = item;
+            self.head = (self.head + 1) % self.capacity;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns item by index, `0` is the oldest item.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.items.len() {
+            Some(&self.items[(self.head + index) % self.items.len()])
+        } else {
+            None
+        }
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        match self.items.len() {
+            0 => None,
+            len => self.get(len - 1),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.head = 0;
+    }
+
+    /// Iterates from the oldest to the newest item.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            buffer: self,
+            front: 0,
+            back: self.items.len(),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> Index<usize> for RingBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(item) => item,
+            None => panic!(
+                "index out of bounds: the len is {} but the index is {}",
+                self.len(),
+                index
+            ),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RingBuffer<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a, T> {
+    buffer: &'a RingBuffer<T>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front < self.back {
+            self.front += 1;
+            self.buffer.get(self.front - 1)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.front < self.back {
+            self.back -= 1;
+            self.buffer.get(self.back)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Storage with stable keys, a key packs the slot index and the slot generation,
+/// so keys of removed items don't match the items reusing their slots.
+#[derive(Clone, Debug)]
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T> {
+    generation: u32,
+    item: Option<T>,
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        SlotMap {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, item: T) -> u64 {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    item: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut self.slots[index as usize];
+        slot.item = Some(item);
+        self.len += 1;
+
+        slot_key(index, slot.generation)
+    }
+
+    pub fn get(&self, key: u64) -> Option<&T> {
+        let (index, generation) = split_slot_key(key);
+
+        match self.slots.get(index as usize) {
+            Some(slot) if slot.generation == generation => slot.item.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, key: u64) -> Option<T> {
+        let (index, generation) = split_slot_key(key);
+
+        match self.slots.get(index as usize) {
+            Some(slot) if slot.generation == generation && slot.item.is_some() => {
+                Some(self.free_slot(index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes items the `predicate` returns true for, returns the removed items.
+    pub fn remove_where<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) -> Vec<T> {
+        let mut removed = Vec::new();
+
+        for index in 0..self.slots.len() {
+            if self.slots[index]
+                .item
+                .as_ref()
+                .map_or(false, &mut predicate)
+            {
+                removed.push(self.free_slot(index as u32));
+            }
+        }
+
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates in the slot order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.item.as_ref())
+    }
+
+    fn free_slot(&mut self, index: u32) -> T {
+        let slot = &mut self.slots[index as usize];
+        let item = slot.item.take().expect("slot should be occupied");
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+        self.len -= 1;
+
+        item
+    }
+}
+
+fn slot_key(index: u32, generation: u32) -> u64 {
+    (generation as u64) << 32 | index as u64
+}
+
+fn split_slot_key(key: u64) -> (u32, u32) {
+    (key as u32, (key >> 32) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collections;
+
+    #[test]
+    fn ring_buffer_overwrites_oldest() {
+        let mut buffer = collections::RingBuffer::new(3);
+
+        for i in 0..5 {
+            buffer.push(i);
+        }
+
+        assert_eq!(3, buffer.len());
+        assert_eq!(vec![2, 3, 4], buffer.to_vec());
+        assert_eq!(2, buffer[0]);
+        assert_eq!(Some(&4), buffer.last());
+        assert_eq!(None, buffer.get(3));
+        assert_eq!(
+            vec![(2, &4), (1, &3), (0, &2)],
+            buffer.iter().enumerate().rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn slot_map_reuses_slots() {
+        let mut map = collections::SlotMap::new();
+
+        let a = map.insert("a");
+        let b = map.insert("b");
+
+        assert_eq!(Some("a"), map.remove(a));
+        assert_eq!(None, map.remove(a));
+
+        let c = map.insert("c");
+
+        assert_ne!(a, c);
+        assert_eq!(None, map.get(a));
+        assert_eq!(Some(&"c"), map.get(c));
+        assert_eq!(2, map.len());
+
+        assert_eq!(vec!["b"], map.remove_where(|item| *item == "b"));
+        assert_eq!(None, map.get(b));
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn ring_buffer_clear() {
+        let mut buffer = collections::RingBuffer::new(2);
+        buffer.push("a");
+        buffer.push("b");
+        buffer.push("c");
+        buffer.clear();
+
+        assert!(buffer.is_empty());
+
+        buffer.push("d");
+        assert_eq!(vec!["d"], buffer.to_vec());
+    }
+}
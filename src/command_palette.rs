@@ -0,0 +1,220 @@
+use std::sync::PoisonError;
+
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::commands::{self, CommandRegistryEntry, CommandsState};
+use crate::input::{InputEvent, Key};
+use crate::render::{self, DrawList};
+use crate::state::DebugState;
+
+pub const PALETTE_TOGGLE_KEY: Key = Key::F2;
+pub const PALETTE_VISIBLE_ROWS: usize = 12;
+
+const PALETTE_LINE_HEIGHT: f32 = 18.;
+const PALETTE_WIDTH: f32 = 640.;
+const PALETTE_TOP: f32 = 40.;
+
+#[derive(Default)]
+pub struct CommandPaletteState {
+    pub visible: bool,
+    pub query: String,
+    /// Index of the selected command in the filtered list.
+    pub selected: usize,
+}
+
+/// Handles palette input, returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    if let InputEvent::KeyDown(key) = event {
+        if *key == PALETTE_TOGGLE_KEY {
+            let palette = &mut debug_state.palette;
+
+            palette.visible = !palette.visible;
+            palette.query.clear();
+            palette.selected = 0;
+            return true;
+        }
+    }
+
+    if !debug_state.palette.visible {
+        return false;
+    }
+
+    match event {
+        InputEvent::Char(ch) if !ch.is_control() => {
+            debug_state.palette.query.push(*ch);
+            debug_state.palette.selected = 0;
+        }
+        InputEvent::KeyDown(Key::Backspace) => {
+            debug_state.palette.query.pop();
+            debug_state.palette.selected = 0;
+        }
+        InputEvent::KeyDown(Key::Escape) => {
+            debug_state.palette.visible = false;
+        }
+        InputEvent::KeyDown(Key::Up) => {
+            let palette = &mut debug_state.palette;
+            palette.selected = palette.selected.saturating_sub(1);
+        }
+        InputEvent::KeyDown(Key::Down) => {
+            let count = matches(&debug_state.commands, &debug_state.palette.query).len();
+            let palette = &mut debug_state.palette;
+
+            palette.selected = (palette.selected + 1).min(count.saturating_sub(1));
+        }
+        InputEvent::KeyDown(Key::Enter) => submit(debug_state),
+        // Keys pressed while the palette is open shouldn't leak to the game.
+        InputEvent::Char(_) | InputEvent::KeyDown(_) | InputEvent::KeyUp(_) => {}
+        _ => return false,
+    }
+
+    true
+}
+
+/// Executes the selected command, commands with required arguments
+/// are put into the console input instead, so the arguments can be typed.
+fn submit(debug_state: &mut DebugState) {
+    let command = match matches(&debug_state.commands, &debug_state.palette.query)
+        .get(debug_state.palette.selected)
+    {
+        Some(entry) => format!("{}::{}", entry.namespace, entry.name),
+        None => return,
+    };
+
+    debug_state.palette.visible = false;
+
+    let has_required_args = debug_state
+        .commands
+        .index
+        .get_mut(&command)
+        .map(|command| command.get_mut().unwrap_or_else(PoisonError::into_inner))
+        .map_or(false, |command| {
+            command.args.iter().any(|spec| !spec.optional)
+        });
+
+    if has_required_args {
+        debug_state.console.visible = true;
+        debug_state.console.input = format!("{} ", command);
+        return;
+    }
+
+    debug_state.console.print(&format!("> {}", command));
+
+    match commands::execute(debug_state, &command) {
+        Ok(output) => {
+            let text = output.to_string();
+            debug_state.console.print(&text);
+        }
+        Err(err) => debug_state.console.print(&format!("error: {}", err)),
+    }
+}
+
+/// Registered commands fuzzy matching the `query`, best matches go first.
+pub(crate) fn matches<'a>(
+    commands: &'a CommandsState,
+    query: &str,
+) -> Vec<&'a CommandRegistryEntry> {
+    let query = query.trim();
+    let mut matches: Vec<(usize, &CommandRegistryEntry)> = commands
+        .registry
+        .iter()
+        .filter_map(|entry| {
+            let name = format!("{}::{}", entry.namespace, entry.name);
+            commands::fuzzy_score(query, &name).map(|score| (score, entry))
+        })
+        .collect();
+
+    // Stable sort keeps the registration order for equal scores.
+    matches.sort_by_key(|(score, _)| *score);
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Renders the query and the matched commands with their arguments and descriptions.
+pub fn update(
+    debug_state: &DebugState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+) {
+    let palette = &debug_state.palette;
+
+    if !palette.visible {
+        return;
+    }
+
+    let matches = matches(&debug_state.commands, &palette.query);
+    // Scroll so the selected command stays visible.
+    let first = (palette.selected + 1).saturating_sub(PALETTE_VISIBLE_ROWS);
+    let rows = matches.len().min(PALETTE_VISIBLE_ROWS);
+    let padding = debug_state.theme.padding;
+
+    let x = ((viewport_size.x - PALETTE_WIDTH) / 2.).max(0.);
+    let height = (rows + 1) as f32 * PALETTE_LINE_HEIGHT + padding * 2.;
+    let top = viewport_size.y - PALETTE_TOP;
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(x, top - height),
+        Vec2f::new(PALETTE_WIDTH, height),
+        render::color(0., 0., 0., 0.85),
+    );
+
+    let text_x = x + padding;
+    let mut y = top - padding - PALETTE_LINE_HEIGHT;
+
+    draw_list.text(
+        camera,
+        Vec2f::new(text_x, y),
+        format!("> {}_", palette.query),
+    );
+
+    for (i, entry) in matches.iter().enumerate().skip(first).take(rows) {
+        y -= PALETTE_LINE_HEIGHT;
+
+        if i == palette.selected {
+            draw_list.rect(
+                camera,
+                Vec2f::new(x, y),
+                Vec2f::new(PALETTE_WIDTH, PALETTE_LINE_HEIGHT),
+                render::color(1., 1., 1., 0.2),
+            );
+        }
+
+        draw_list.text(
+            camera,
+            Vec2f::new(text_x, y),
+            format!(
+                "{}::{} {} - {}",
+                entry.namespace, entry.name, entry.args, entry.desc
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command_palette;
+    use crate::commands;
+
+    #[test]
+    fn fuzzy_matches() {
+        let mut state = commands::CommandsState::default();
+
+        for (namespace, name) in &[("profile", "filter"), ("log", "filter"), ("time", "pause")] {
+            state.registry.push(commands::CommandRegistryEntry {
+                namespace: String::from(*namespace),
+                name: String::from(*name),
+                args: String::new(),
+                arg_values: Vec::new(),
+                desc: "",
+            });
+        }
+
+        let names: Vec<String> = command_palette::matches(&state, "filter")
+            .iter()
+            .map(|entry| format!("{}::{}", entry.namespace, entry.name))
+            .collect();
+
+        assert_eq!(vec!["log::filter", "profile::filter"], names);
+        assert_eq!(3, command_palette::matches(&state, "").len());
+    }
+}
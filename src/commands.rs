@@ -1,148 +1,1641 @@
 use regex::Regex;
 
 use std::collections::HashMap;
-use std::sync::MutexGuard;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
 
-use crate::state::{DebugState, DEBUG_STATE};
+use crate::collections::RingBuffer;
+use crate::cvars::CvarValue;
+use crate::format;
+use crate::state::{write_debug_state, DebugState};
 
 pub const COMMANDS_HISTORY_CAPACITY: usize = 100;
+pub const COMMANDS_OUTPUT_CAPACITY: usize = 100;
 
 pub struct CommandsState {
-    pub history: Vec<String>,
+    pub history: RingBuffer<String>,
+    pub output: Vec<CommandOutputEntry>,
     pub registry: Vec<CommandRegistryEntry>,
-    pub index: HashMap<String, Command>,
+    /// Commands are kept in a `Mutex`, so the executors don't have to be `Sync`.
+    pub index: HashMap<String, Mutex<Command>>,
+    /// Values of unquoted `$name` command arguments, see `set_substitution`.
+    pub substitutions: HashMap<String, String>,
 }
 
 impl Default for CommandsState {
     fn default() -> Self {
         CommandsState {
-            history: Vec::with_capacity(COMMANDS_HISTORY_CAPACITY),
+            history: RingBuffer::new(COMMANDS_HISTORY_CAPACITY),
+            output: Vec::with_capacity(COMMANDS_OUTPUT_CAPACITY),
             registry: Vec::new(),
             index: HashMap::new(),
+            substitutions: HashMap::new(),
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+pub enum CommandOutput {
+    None,
+    Text(String),
+    Number(f64),
+    Table {
+        header: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+impl fmt::Display for CommandOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandOutput::None => Ok(()),
+            CommandOutput::Text(text) => write!(f, "{}", text),
+            CommandOutput::Number(val) => write!(f, "{}", val),
+            CommandOutput::Table { header, rows } => {
+                let mut widths: Vec<usize> = header.iter().map(|c| c.chars().count()).collect();
+
+                for row in rows.iter() {
+                    for (i, cell) in row.iter().enumerate() {
+                        if i < widths.len() {
+                            widths[i] = widths[i].max(cell.chars().count());
+                        } else {
+                            widths.push(cell.chars().count());
+                        }
+                    }
+                }
+
+                let lines = std::iter::once(header).chain(rows.iter());
+
+                for (n, line) in lines.enumerate() {
+                    if n > 0 {
+                        writeln!(f)?;
+                    }
+
+                    let cells: Vec<String> = line
+                        .iter()
+                        .enumerate()
+                        .map(|(i, cell)| format::align_left(cell, widths[i]))
+                        .collect();
+
+                    write!(f, "{}", cells.join("  ").trim_end())?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct CommandOutputEntry {
+    pub command: String,
+    pub result: Result<CommandOutput, CommandError>,
+}
+
+/// Byte range in the command string.
+pub type Span = std::ops::Range<usize>;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum CommandError {
+    ParseError {
+        message: String,
+        span: Span,
+    },
+    UnknownCommand {
+        command: String,
+        suggestions: Vec<String>,
+    },
+    WrongArgumentCount {
+        min: usize,
+        max: usize,
+        found: usize,
+    },
+    ArgumentTypeMismatch {
+        index: usize,
+        expected: ArgType,
+        found: &'static str,
+    },
+    UnknownArgument {
+        name: String,
+        expected: Vec<&'static str>,
+    },
+    DuplicateArgument {
+        name: String,
+    },
+    MissingArgument {
+        name: &'static str,
+    },
+    PermissionDenied {
+        command: String,
+        required: PermissionLevel,
+    },
+    ExecutorError(String),
+    SubCommandFailed {
+        index: usize,
+        command: String,
+        error: Box<CommandError>,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::ParseError { message, span } => {
+                write!(f, "{} at {}..{}", message, span.start, span.end)
+            }
+            CommandError::UnknownCommand {
+                command,
+                suggestions,
+            } => {
+                write!(f, "Command '{}' not found", command)?;
+
+                if !suggestions.is_empty() {
+                    write!(f, ", did you mean `{}`?", suggestions.join("`, `"))?;
+                }
+
+                Ok(())
+            }
+            CommandError::WrongArgumentCount { min, max, found } if min == max => {
+                write!(f, "expected {} arguments, found {}", min, found)
+            }
+            CommandError::WrongArgumentCount { min, max, found } if *max == usize::MAX => {
+                write!(f, "expected at least {} arguments, found {}", min, found)
+            }
+            CommandError::WrongArgumentCount { min, max, found } => {
+                write!(f, "expected {}..{} arguments, found {}", min, max, found)
+            }
+            CommandError::ArgumentTypeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} should be {}, found {}",
+                index + 1,
+                expected,
+                found
+            ),
+            CommandError::UnknownArgument { name, expected } if expected.is_empty() => {
+                write!(f, "unknown argument '{}', command has no arguments", name)
+            }
+            CommandError::UnknownArgument { name, expected } => write!(
+                f,
+                "unknown argument '{}', expected one of: {}",
+                name,
+                expected.join(", ")
+            ),
+            CommandError::DuplicateArgument { name } => {
+                write!(f, "argument '{}' is passed more than once", name)
+            }
+            CommandError::MissingArgument { name } => write!(f, "missing argument '{}'", name),
+            CommandError::PermissionDenied { command, required } => {
+                write!(f, "Command '{}' requires {} mode", command, required)
+            }
+            CommandError::ExecutorError(message) => write!(f, "{}", message),
+            CommandError::SubCommandFailed {
+                index,
+                command,
+                error,
+            } => write!(f, "command #{} '{}' failed: {}", index + 1, command, error),
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::ExecutorError(message)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum CommandArgument {
     Number(f64),
     String(String),
     Bool(bool),
+    Vec2(f64, f64),
+    Vec3(f64, f64, f64),
+    Color(u8, u8, u8, u8),
+    List(Vec<CommandArgument>),
+}
+
+impl CommandArgument {
+    pub fn as_number(&self) -> Result<f64, String> {
+        match self {
+            CommandArgument::Number(val) => Ok(*val),
+            _ => Err(String::from("Argument should be number")),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, String> {
+        match self {
+            CommandArgument::String(val) => Ok(val),
+            _ => Err(String::from("Argument should be string")),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, String> {
+        match self {
+            CommandArgument::Bool(val) => Ok(*val),
+            _ => Err(String::from("Argument should be bool")),
+        }
+    }
+
+    pub fn as_vec2(&self) -> Result<(f64, f64), String> {
+        match self {
+            CommandArgument::Vec2(x, y) => Ok((*x, *y)),
+            _ => Err(String::from("Argument should be vec2")),
+        }
+    }
+
+    pub fn as_vec3(&self) -> Result<(f64, f64, f64), String> {
+        match self {
+            CommandArgument::Vec3(x, y, z) => Ok((*x, *y, *z)),
+            _ => Err(String::from("Argument should be vec3")),
+        }
+    }
+
+    pub fn as_color(&self) -> Result<(u8, u8, u8, u8), String> {
+        match self {
+            CommandArgument::Color(r, g, b, a) => Ok((*r, *g, *b, *a)),
+            _ => Err(String::from("Argument should be color")),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[CommandArgument], String> {
+        match self {
+            CommandArgument::List(values) => Ok(values),
+            _ => Err(String::from("Argument should be list")),
+        }
+    }
+}
+
+impl CommandArgument {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CommandArgument::Number(_) => "number",
+            CommandArgument::String(_) => "string",
+            CommandArgument::Bool(_) => "bool",
+            CommandArgument::Vec2(_, _) => "vec2",
+            CommandArgument::Vec3(_, _, _) => "vec3",
+            CommandArgument::Color(_, _, _, _) => "color",
+            CommandArgument::List(_) => "list",
+        }
+    }
+}
+
+impl fmt::Display for CommandArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandArgument::Number(val) => write!(f, "{}", val),
+            CommandArgument::String(val) => write!(f, "\"{}\"", val),
+            CommandArgument::Bool(val) => write!(f, "{}", val),
+            CommandArgument::Vec2(x, y) => write!(f, "({}, {})", x, y),
+            CommandArgument::Vec3(x, y, z) => write!(f, "({}, {}, {})", x, y, z),
+            CommandArgument::Color(r, g, b, a) => {
+                write!(f, "#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+            }
+            CommandArgument::List(values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArgType {
+    Number,
+    Int,
+    String,
+    Bool,
+    Vec2,
+    Vec3,
+    Color,
+    /// String argument restricted to one of the given values.
+    Enum(&'static [&'static str]),
+    /// List of the elements of the given type, e.g. `[(0, 0), (10, 5)]`.
+    List(&'static ArgType),
+    Any,
+}
+
+impl ArgType {
+    fn coerce(self, argument: &CommandArgument) -> Option<CommandArgument> {
+        match (self, argument) {
+            (ArgType::Number, CommandArgument::Number(_)) => Some(argument.clone()),
+            (ArgType::Int, CommandArgument::Number(val)) if val.fract() == 0. => {
+                Some(argument.clone())
+            }
+            (ArgType::String, CommandArgument::String(_)) => Some(argument.clone()),
+            (ArgType::Bool, CommandArgument::Bool(_)) => Some(argument.clone()),
+            (ArgType::Vec2, CommandArgument::Vec2(_, _)) => Some(argument.clone()),
+            (ArgType::Vec3, CommandArgument::Vec3(_, _, _)) => Some(argument.clone()),
+            (ArgType::Color, CommandArgument::Color(_, _, _, _)) => Some(argument.clone()),
+            (ArgType::Enum(values), CommandArgument::String(val)) if values.contains(&&val[..]) => {
+                Some(argument.clone())
+            }
+            (ArgType::List(element), CommandArgument::List(values)) => values
+                .iter()
+                .map(|value| element.coerce(value))
+                .collect::<Option<Vec<CommandArgument>>>()
+                .map(CommandArgument::List),
+            (ArgType::Any, _) => Some(argument.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgType::Number => write!(f, "number"),
+            ArgType::Int => write!(f, "int"),
+            ArgType::String => write!(f, "string"),
+            ArgType::Bool => write!(f, "bool"),
+            ArgType::Vec2 => write!(f, "vec2"),
+            ArgType::Vec3 => write!(f, "vec3"),
+            ArgType::Color => write!(f, "color"),
+            ArgType::Enum(values) => write!(f, "{}", values.join("|")),
+            ArgType::List(element) => write!(f, "[{}]", element),
+            ArgType::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub arg_type: ArgType,
+    pub optional: bool,
+    pub default: Option<CommandArgument>,
+    /// Takes the rest of the arguments, only the last spec can be variadic.
+    pub variadic: bool,
+}
+
+impl ArgSpec {
+    pub fn required(name: &'static str, arg_type: ArgType) -> Self {
+        ArgSpec {
+            name,
+            arg_type,
+            optional: false,
+            default: None,
+            variadic: false,
+        }
+    }
+
+    pub fn optional(name: &'static str, arg_type: ArgType, default: CommandArgument) -> Self {
+        ArgSpec {
+            name,
+            arg_type,
+            optional: true,
+            default: Some(default),
+            variadic: false,
+        }
+    }
+
+    /// Zero or more trailing arguments of the same type, e.g. `log::targets "net" "render"`,
+    /// executors get them as the slice after the other arguments.
+    pub fn variadic(name: &'static str, arg_type: ArgType) -> Self {
+        ArgSpec {
+            name,
+            arg_type,
+            optional: true,
+            default: None,
+            variadic: true,
+        }
+    }
+
+    fn coerce(&self, argument: &CommandArgument) -> Option<CommandArgument> {
+        self.arg_type.coerce(argument)
+    }
+}
+
+impl fmt::Display for ArgSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.variadic {
+            return write!(f, "[{}: {}...]", self.name, self.arg_type);
+        }
+
+        match (self.optional, &self.default) {
+            (true, Some(default)) => write!(f, "[{}: {} = {}]", self.name, self.arg_type, default),
+            (true, None) => write!(f, "[{}: {}]", self.name, self.arg_type),
+            (false, _) => write!(f, "<{}: {}>", self.name, self.arg_type),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Token<'a> {
+    Number(f64),
+    Id(&'a str),
+    String(&'a str),
+    Bool(bool),
+    Vector(Vec<f64>),
+    Color([u8; 4]),
+    /// Content of the list literal between the brackets, e.g. `1, 2` in `[1, 2]`.
+    List(&'a str),
+    /// Name of the `$name` or `${name}` argument, see `set_substitution`.
+    Reference(&'a str),
+    /// Name of the named argument, e.g. `width` in `width=1920`.
+    Key(&'a str),
+    /// Input the tokenizer doesn't recognize, e.g. `@` or an unterminated string.
+    Unknown(&'a str),
+}
+
+pub type CommandExecutor =
+    Box<dyn Fn(&mut DebugState, &[CommandArgument]) -> Result<CommandOutput, String> + Send>;
+
+/// Level required to execute a command, compared with `DebugState::permission_level`
+/// so shipping builds can keep harmless commands while blocking cheats.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PermissionLevel {
+    Normal,
+    /// Commands which affect the gameplay, e.g. the game time scale.
+    Cheat,
+    /// Commands which access files or lift the protection, e.g. `debug::exec`.
+    Developer,
+}
+
+impl fmt::Display for PermissionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionLevel::Normal => write!(f, "normal"),
+            PermissionLevel::Cheat => write!(f, "cheat"),
+            PermissionLevel::Developer => write!(f, "developer"),
+        }
+    }
+}
+
+pub struct Command {
+    pub namespace: String,
+    pub name: String,
+    pub args: Vec<ArgSpec>,
+    pub protection: PermissionLevel,
+    pub executor: CommandExecutor,
+}
+
+impl Command {
+    /// Creates the command from a typed function, argument specs are derived
+    /// from the parameter types, e.g. `fn sum(a: f64, b: f64) -> Result<String, String>`
+    /// registered with `Command::from_fn("math", "sum", &["a", "b"], sum)`.
+    /// `Option<T>` parameters become optional arguments.
+    pub fn from_fn<Args, F>(namespace: &str, name: &str, arg_names: &[&'static str], f: F) -> Self
+    where
+        Args: 'static,
+        F: CommandFn<Args> + Send + 'static,
+    {
+        let args = F::arg_specs(arg_names);
+
+        assert_eq!(
+            arg_names.len(),
+            args.len(),
+            "command {}::{} expects {} argument names",
+            namespace,
+            name,
+            args.len()
+        );
+
+        Command {
+            namespace: String::from(namespace),
+            name: String::from(name),
+            args,
+            protection: PermissionLevel::Normal,
+            executor: Box::new(move |_: &mut DebugState, arguments: &[CommandArgument]| {
+                f.call(arguments)
+            }),
+        }
+    }
+
+    /// Sets the level required to execute the command, `PermissionLevel::Normal` by default.
+    pub fn with_protection(mut self, protection: PermissionLevel) -> Self {
+        self.protection = protection;
+        self
+    }
+}
+
+/// Parameter of the function passed to `Command::from_fn`.
+pub trait FromArgument: Sized {
+    fn arg_spec(name: &'static str) -> ArgSpec;
+
+    /// `argument` is `None` when an optional argument is omitted.
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String>;
+}
+
+fn required_argument(argument: Option<&CommandArgument>) -> Result<&CommandArgument, String> {
+    argument.ok_or_else(|| String::from("Missing argument"))
+}
+
+impl FromArgument for f64 {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Number)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_number()
+    }
+}
+
+impl FromArgument for f32 {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Number)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        Ok(required_argument(argument)?.as_number()? as f32)
+    }
+}
+
+macro_rules! impl_int_from_argument {
+    ($($int:ty),*) => {
+        $(
+            impl FromArgument for $int {
+                fn arg_spec(name: &'static str) -> ArgSpec {
+                    ArgSpec::required(name, ArgType::Int)
+                }
+
+                fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+                    let value = required_argument(argument)?.as_number()?;
+
+                    require(
+                        value >= <$int>::MIN as f64 && value <= <$int>::MAX as f64,
+                        concat!("Argument should fit into ", stringify!($int)),
+                    )?;
+
+                    Ok(value as $int)
+                }
+            }
+        )*
+    };
+}
+
+impl_int_from_argument!(i32, i64, u32, u64, usize);
+
+impl FromArgument for bool {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Bool)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_bool()
+    }
+}
+
+impl FromArgument for String {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::String)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_str().map(String::from)
+    }
 }
 
-#[derive(PartialEq, Debug)]
-pub enum Token<'a> {
-    Number(f64),
-    Id(&'a str),
-    String(&'a str),
-    Bool(bool),
-}
+impl FromArgument for (f64, f64) {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Vec2)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_vec2()
+    }
+}
+
+impl FromArgument for (f64, f64, f64) {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Vec3)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_vec3()
+    }
+}
+
+impl FromArgument for (u8, u8, u8, u8) {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec::required(name, ArgType::Color)
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        required_argument(argument)?.as_color()
+    }
+}
+
+impl<T: FromArgument> FromArgument for Option<T> {
+    fn arg_spec(name: &'static str) -> ArgSpec {
+        ArgSpec {
+            optional: true,
+            default: None,
+            ..T::arg_spec(name)
+        }
+    }
+
+    fn from_argument(argument: Option<&CommandArgument>) -> Result<Self, String> {
+        match argument {
+            Some(_) => T::from_argument(argument).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Return value of the function passed to `Command::from_fn`.
+pub trait IntoCommandOutput {
+    fn into_command_output(self) -> Result<CommandOutput, String>;
+}
+
+impl IntoCommandOutput for CommandOutput {
+    fn into_command_output(self) -> Result<CommandOutput, String> {
+        Ok(self)
+    }
+}
+
+impl IntoCommandOutput for () {
+    fn into_command_output(self) -> Result<CommandOutput, String> {
+        Ok(CommandOutput::None)
+    }
+}
+
+impl IntoCommandOutput for String {
+    fn into_command_output(self) -> Result<CommandOutput, String> {
+        Ok(CommandOutput::Text(self))
+    }
+}
+
+impl IntoCommandOutput for f64 {
+    fn into_command_output(self) -> Result<CommandOutput, String> {
+        Ok(CommandOutput::Number(self))
+    }
+}
+
+impl<T: IntoCommandOutput> IntoCommandOutput for Result<T, String> {
+    fn into_command_output(self) -> Result<CommandOutput, String> {
+        self.and_then(IntoCommandOutput::into_command_output)
+    }
+}
+
+/// Function with `FromArgument` parameters, implemented for up to 6 parameters.
+pub trait CommandFn<Args> {
+    fn arg_specs(arg_names: &[&'static str]) -> Vec<ArgSpec>;
+
+    fn call(&self, arguments: &[CommandArgument]) -> Result<CommandOutput, String>;
+}
+
+macro_rules! impl_command_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> CommandFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+            R: IntoCommandOutput,
+            $($arg: FromArgument),*
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn arg_specs(arg_names: &[&'static str]) -> Vec<ArgSpec> {
+                let mut arg_names = arg_names.iter().copied();
+                vec![$($arg::arg_spec(arg_names.next().unwrap_or("arg"))),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn call(&self, arguments: &[CommandArgument]) -> Result<CommandOutput, String> {
+                let mut arguments = arguments.iter();
+                self($($arg::from_argument(arguments.next())?),*).into_command_output()
+            }
+        }
+    };
+}
+
+impl_command_fn!();
+impl_command_fn!(A);
+impl_command_fn!(A, B);
+impl_command_fn!(A, B, C);
+impl_command_fn!(A, B, C, D);
+impl_command_fn!(A, B, C, D, E);
+impl_command_fn!(A, B, C, D, E, G);
+
+#[derive(PartialEq, Debug)]
+pub struct CommandRequest {
+    pub command: String,
+    pub arguments: Vec<CommandArgument>,
+    /// Arguments passed by name after the positional ones, e.g. `height=1080`.
+    pub named_arguments: Vec<(String, CommandArgument)>,
+}
+
+pub struct CommandRegistryEntry {
+    pub namespace: String,
+    pub name: String,
+    pub args: String,
+    pub arg_values: Vec<Vec<String>>,
+    pub desc: &'static str,
+}
+
+/// Registers the command, panics if its argument specs are invalid, see `check_arg_specs`.
+pub fn register_command(debug_state: &mut DebugState, desc: &'static str, command: Command) {
+    if let Err(err) = check_arg_specs(&command.args) {
+        panic!("command {}::{}: {}", command.namespace, command.name, err);
+    }
+
+    let args = command
+        .args
+        .iter()
+        .map(|spec| spec.to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let arg_values = command
+        .args
+        .iter()
+        .map(|spec| match spec.arg_type {
+            ArgType::Enum(values) => values.iter().map(|v| String::from(*v)).collect(),
+            ArgType::Bool => vec![String::from("true"), String::from("false")],
+            _ => Vec::new(),
+        })
+        .collect();
+
+    debug_state.commands.registry.push(CommandRegistryEntry {
+        namespace: command.namespace.clone(),
+        name: command.name.clone(),
+        args,
+        arg_values,
+        desc,
+    });
+
+    debug_state.commands.index.insert(
+        format!("{}::{}", &command.namespace, &command.name),
+        Mutex::new(command),
+    );
+}
+
+/// Moves history cursor to the previous (older) entry, `None` cursor means
+/// a new input line, so the last entry is returned.
+pub fn history_prev(commands: &CommandsState, cursor: Option<usize>) -> Option<usize> {
+    match cursor {
+        _ if commands.history.is_empty() => None,
+        None => Some(commands.history.len() - 1),
+        Some(cursor) => Some(cursor.saturating_sub(1).min(commands.history.len() - 1)),
+    }
+}
+
+/// Moves history cursor to the next (newer) entry,
+/// returns `None` when the cursor goes past the last entry.
+pub fn history_next(commands: &CommandsState, cursor: Option<usize>) -> Option<usize> {
+    match cursor {
+        Some(cursor) if cursor + 1 < commands.history.len() => Some(cursor + 1),
+        _ => None,
+    }
+}
+
+/// Returns history entries starting with `prefix`, most recent first, without duplicates.
+pub fn history_search<'a>(commands: &'a CommandsState, prefix: &str) -> Vec<(usize, &'a str)> {
+    let mut result: Vec<(usize, &str)> = Vec::new();
+
+    for (i, entry) in commands.history.iter().enumerate().rev() {
+        if entry.starts_with(prefix) && !result.iter().any(|(_, found)| found == entry) {
+            result.push((i, entry.as_str()));
+        }
+    }
+
+    result
+}
+
+/// Registers possible values of the argument at `argument_index`,
+/// they will be suggested by `complete`.
+pub fn register_argument_values(
+    debug_state: &mut DebugState,
+    command: &str,
+    argument_index: usize,
+    values: &[&str],
+) {
+    let entry = debug_state
+        .commands
+        .registry
+        .iter_mut()
+        .find(|entry| format!("{}::{}", entry.namespace, entry.name) == command);
+
+    match entry {
+        Some(entry) => {
+            if entry.arg_values.len() <= argument_index {
+                entry.arg_values.resize(argument_index + 1, Vec::new());
+            }
+
+            entry.arg_values[argument_index] = values.iter().map(|v| String::from(*v)).collect();
+        }
+        None => log::warn!(
+            "Couldn't register argument values, command '{}' not found",
+            command
+        ),
+    }
+}
+
+/// Returns completed input lines for the `partial` input,
+/// best matches go first.
+pub fn complete(partial: &str) -> Vec<String> {
+    DebugState::with_read(|debug_state| complete_in(&debug_state.commands, partial))
+}
+
+pub(crate) fn complete_in(commands: &CommandsState, partial: &str) -> Vec<String> {
+    let partial = partial.trim_start();
+    let current_start = partial
+        .rfind(char::is_whitespace)
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+
+    let (head, current) = partial.split_at(current_start);
+    let previous: Vec<&str> = head.split_whitespace().collect();
+
+    let candidates: Vec<String> = if previous.is_empty() {
+        let mut candidates = Vec::new();
+
+        for entry in commands.registry.iter() {
+            let namespace = format!("{}::", entry.namespace);
+
+            if !candidates.contains(&namespace) && namespace != current {
+                candidates.push(namespace);
+            }
+
+            candidates.push(format!("{}::{}", entry.namespace, entry.name));
+        }
+
+        candidates
+    } else {
+        let command = previous[0];
+        let argument_index = previous.len() - 1;
+
+        commands
+            .registry
+            .iter()
+            .find(|entry| format!("{}::{}", entry.namespace, entry.name) == command)
+            .and_then(|entry| entry.arg_values.get(argument_index))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let mut matches: Vec<(bool, usize, String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let is_prefix = candidate.starts_with(current);
+
+            fuzzy_score(current, &candidate).map(|score| (!is_prefix, score, candidate))
+        })
+        .collect();
+
+    matches.sort();
+
+    matches
+        .into_iter()
+        .map(|(_, _, candidate)| format!("{}{}", head, candidate))
+        .collect()
+}
+
+/// Returns the number of skipped characters if all characters of `pattern`
+/// are found in `candidate` in the same order, lower is better.
+pub(crate) fn fuzzy_score(pattern: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut candidate_chars = candidate.chars();
+
+    for pattern_char in pattern.chars() {
+        loop {
+            match candidate_chars.next() {
+                Some(ch) if ch.eq_ignore_ascii_case(&pattern_char) => break,
+                Some(_) => score += 1,
+                None => return None,
+            }
+        }
+    }
+
+    Some(score)
+}
+
+pub fn execute_command(command: &str) -> Result<CommandOutput, CommandError> {
+    let debug_state = &mut write_debug_state();
+    execute(debug_state, command)
+}
+
+/// Same as `execute_command` but for callers which already hold the debug state.
+pub fn execute(debug_state: &mut DebugState, command: &str) -> Result<CommandOutput, CommandError> {
+    debug_state.commands.history.push(String::from(command));
+
+    let result = execute_sequence(debug_state, command);
+
+    push_output(debug_state, command, result.clone());
+    result
+}
+
+/// Executes script line by line, empty lines and lines starting with `#` are skipped,
+/// returns line numbers and errors of the failed lines.
+pub fn execute_script(debug_state: &mut DebugState, script: &str) -> Vec<(usize, CommandError)> {
+    let mut errors = Vec::new();
+    let mut line = String::new();
+    let mut line_number = 0;
+
+    for (i, source_line) in script.lines().enumerate() {
+        if line.is_empty() {
+            line_number = i + 1;
+        }
+
+        // Line continuation
+        if let Some(head) = source_line.strip_suffix('\\') {
+            line.push_str(head);
+            line.push(' ');
+            continue;
+        }
+
+        line.push_str(source_line);
+
+        let command = line.trim();
+
+        if !command.is_empty() && !command.starts_with('#') {
+            if let Err(err) = execute(debug_state, command) {
+                errors.push((line_number, err));
+            }
+        }
+
+        line.clear();
+    }
+
+    errors
+}
+
+/// Sets the value of unquoted `$name` and `${name}` command arguments, e.g. `PROJECT_DIR`.
+/// Environment variables aren't read, since commands come from remote clients too,
+/// the host can expose them with this function.
+pub fn set_substitution(debug_state: &mut DebugState, name: &str, value: &str) {
+    debug_state
+        .commands
+        .substitutions
+        .insert(String::from(name), String::from(value));
+}
+
+/// Value of the `$name` argument, substitutions go first, then the cvars.
+fn substitution(debug_state: &DebugState, name: &str) -> Option<String> {
+    if let Some(value) = debug_state.commands.substitutions.get(name) {
+        return Some(value.clone());
+    }
+
+    debug_state
+        .cvars
+        .cvars
+        .get(name)
+        .map(|cvar| match &cvar.value {
+            CvarValue::String(value) => value.clone(),
+            value => value.to_string(),
+        })
+}
+
+/// Executes commands separated by `;` or new lines one by one,
+/// stops at the first failed command.
+pub(crate) fn execute_sequence(
+    debug_state: &mut DebugState,
+    input: &str,
+) -> Result<CommandOutput, CommandError> {
+    let commands = split_commands(input);
+
+    if commands.len() <= 1 {
+        let command = commands.first().map_or(input, |command| command.as_str());
+
+        return parse_and_execute(debug_state, command);
+    }
+
+    let mut outputs: Vec<String> = Vec::new();
+
+    for (index, command) in commands.iter().enumerate() {
+        let result = parse_and_execute(debug_state, command);
+
+        match result {
+            Ok(CommandOutput::None) => {}
+            Ok(output) => outputs.push(output.to_string()),
+            Err(error) => {
+                return Err(CommandError::SubCommandFailed {
+                    index,
+                    command: command.clone(),
+                    error: Box::new(error),
+                })
+            }
+        }
+    }
+
+    if outputs.is_empty() {
+        Ok(CommandOutput::None)
+    } else {
+        Ok(CommandOutput::Text(outputs.join("\n")))
+    }
+}
+
+/// Splits input into separate commands by `;` and new lines outside of strings,
+/// a line ending with `\` continues on the next line.
+fn split_commands(input: &str) -> Vec<String> {
+    let input = input.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut commands = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for ch in input.chars() {
+        if in_string {
+            current.push(ch);
+
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+
+            continue;
+        }
+
+        match ch {
+            ';' | '\n' => {
+                let command = current.trim();
+
+                if !command.is_empty() {
+                    commands.push(String::from(command));
+                }
 
-pub struct Command {
-    pub namespace: String,
-    pub name: String,
-    pub executor: fn(&mut MutexGuard<DebugState>, &[CommandArgument]) -> Result<(), String>,
-}
+                current.clear();
+            }
+            '"' => {
+                in_string = true;
+                current.push(ch);
+            }
+            ch => current.push(ch),
+        }
+    }
 
-#[derive(PartialEq, Debug)]
-pub struct CommandRequest {
-    pub command: String,
-    pub arguments: Vec<CommandArgument>,
-}
+    let command = current.trim();
 
-pub struct CommandRegistryEntry {
-    pub namespace: String,
-    pub name: String,
-    pub args: String,
-    pub _desc: &'static str,
+    if !command.is_empty() {
+        commands.push(String::from(command));
+    }
+
+    commands
 }
 
-pub fn register_command(
-    debug_state: &mut MutexGuard<DebugState>,
-    desc: &'static str,
-    command: Command,
+fn push_output(
+    debug_state: &mut DebugState,
+    command: &str,
+    result: Result<CommandOutput, CommandError>,
 ) {
-    debug_state.commands.registry.push(CommandRegistryEntry {
-        namespace: command.namespace.clone(),
-        name: command.name.clone(),
-        args: String::from("<arguments: int>"),
-        _desc: desc,
+    let output = &mut debug_state.commands.output;
+
+    if output.len() >= COMMANDS_OUTPUT_CAPACITY {
+        output.remove(0);
+    }
+
+    output.push(CommandOutputEntry {
+        command: String::from(command),
+        result,
     });
+}
 
-    debug_state.commands.index.insert(
-        format!("{}::{}", &command.namespace, &command.name),
-        command,
-    );
+/// Takes buffered outputs of executed commands, used by
+/// the console and remote clients to show results.
+pub fn take_output(debug_state: &mut DebugState) -> Vec<CommandOutputEntry> {
+    debug_state.commands.output.drain(..).collect()
 }
 
-pub fn execute_command(command: &str) -> Result<(), String> {
-    let debug_state = &mut DEBUG_STATE.lock().expect("failed to get debug state");
-    debug_state.commands.history.push(String::from(command));
-    let request = parse_command(command)?;
+fn parse_and_execute(
+    debug_state: &mut DebugState,
+    command: &str,
+) -> Result<CommandOutput, CommandError> {
+    let request = parse_command(command, &|name| substitution(debug_state, name))?;
     execute_command_request(debug_state, &request)
 }
 
-fn parse_command(command: &str) -> Result<CommandRequest, String> {
-    let tokens = tokenize(command);
+/// Returns the value of the `$name` argument, `None` if it's unknown.
+type Substitute<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn parse_command(command: &str, substitute: Substitute) -> Result<CommandRequest, CommandError> {
+    let tokens = tokenize_spanned(command);
+
+    if let Some((Token::Unknown(text), span)) = tokens
+        .iter()
+        .find(|(token, _)| matches!(token, Token::Unknown(_)))
+    {
+        return Err(if text.starts_with('"') {
+            CommandError::ParseError {
+                message: String::from("Unterminated string"),
+                span: span.start..command.len(),
+            }
+        } else {
+            CommandError::ParseError {
+                message: format!("Unrecognized input '{}'", text),
+                span: span.clone(),
+            }
+        });
+    }
 
     if tokens.is_empty() {
-        Err(String::from("Command can't be empty"))
+        Err(CommandError::ParseError {
+            message: String::from("Command can't be empty"),
+            span: 0..command.len(),
+        })
     } else {
-        let command = if let Token::Id(id) = tokens[0] {
+        let command = if let (Token::Id(id), _) = tokens[0] {
             id
         } else {
-            return Err(String::from("Parse error"));
+            return Err(CommandError::ParseError {
+                message: String::from("Expected command name"),
+                span: tokens[0].1.clone(),
+            });
         };
 
         let command = String::from(command);
         let mut arguments = Vec::new();
+        let mut named_arguments = Vec::new();
+        let mut tokens = tokens.iter().skip(1);
 
-        for token in tokens.iter().skip(1) {
-            match *token {
-                Token::String(value) => {
-                    arguments.push(CommandArgument::String(String::from(value)))
+        while let Some((token, span)) = tokens.next() {
+            match token {
+                Token::Key(name) => match tokens.next() {
+                    Some((token, span)) if !matches!(token, Token::Key(_)) => {
+                        named_arguments.push((
+                            String::from(*name),
+                            parse_argument(token, span, substitute)?,
+                        ));
+                    }
+                    _ => {
+                        return Err(CommandError::ParseError {
+                            message: format!("Expected value of '{}'", name),
+                            span: span.clone(),
+                        })
+                    }
+                },
+                _ if !named_arguments.is_empty() => {
+                    return Err(CommandError::ParseError {
+                        message: String::from("Positional argument after named arguments"),
+                        span: span.clone(),
+                    })
                 }
-                Token::Number(value) => arguments.push(CommandArgument::Number(value)),
-                Token::Bool(value) => arguments.push(CommandArgument::Bool(value)),
-                _ => return Err(String::from("Parse error")),
+                _ => arguments.push(parse_argument(token, span, substitute)?),
             }
         }
 
-        Ok(CommandRequest { command, arguments })
+        Ok(CommandRequest {
+            command,
+            arguments,
+            named_arguments,
+        })
+    }
+}
+
+fn parse_argument(
+    token: &Token<'_>,
+    span: &Span,
+    substitute: Substitute,
+) -> Result<CommandArgument, CommandError> {
+    match token {
+        Token::String(value) => match unescape(value) {
+            Ok(value) => Ok(CommandArgument::String(value)),
+            Err(message) => Err(CommandError::ParseError {
+                message,
+                span: span.clone(),
+            }),
+        },
+        Token::Number(value) if value.is_nan() => Err(CommandError::ParseError {
+            message: String::from("Number out of range"),
+            span: span.clone(),
+        }),
+        Token::Number(value) => Ok(CommandArgument::Number(*value)),
+        Token::Bool(value) => Ok(CommandArgument::Bool(*value)),
+        Token::Vector(values) if values.iter().any(|value| value.is_nan()) => {
+            Err(CommandError::ParseError {
+                message: String::from("Vector components should be numbers"),
+                span: span.clone(),
+            })
+        }
+        Token::Vector(values) => match values[..] {
+            [x, y] => Ok(CommandArgument::Vec2(x, y)),
+            [x, y, z] => Ok(CommandArgument::Vec3(x, y, z)),
+            _ => Err(CommandError::ParseError {
+                message: format!(
+                    "Vector should have 2 or 3 components, found {}",
+                    values.len()
+                ),
+                span: span.clone(),
+            }),
+        },
+        Token::Color([r, g, b, a]) => Ok(CommandArgument::Color(*r, *g, *b, *a)),
+        Token::List(content) => parse_list(content, span.start + 1, substitute),
+        Token::Reference(name) => match substitute(name) {
+            Some(value) => Ok(substituted_argument(&value)),
+            None => Err(CommandError::ParseError {
+                message: format!("Unknown substitution '{}'", name),
+                span: span.clone(),
+            }),
+        },
+        _ => Err(CommandError::ParseError {
+            message: String::from("Expected argument value"),
+            span: span.clone(),
+        }),
+    }
+}
+
+/// The substituted value is always a single argument, e.g. a number,
+/// values which aren't a single literal become strings as is.
+fn substituted_argument(value: &str) -> CommandArgument {
+    let tokens = tokenize_spanned(value);
+
+    let argument = match tokens.as_slice() {
+        [(token, span)] => parse_argument(token, span, &|_| None).ok(),
+        _ => None,
+    };
+
+    argument.unwrap_or_else(|| CommandArgument::String(String::from(value)))
+}
+
+/// Parses comma separated elements of the list literal,
+/// `offset` is the position of the `content` in the command.
+fn parse_list(
+    content: &str,
+    offset: usize,
+    substitute: Substitute,
+) -> Result<CommandArgument, CommandError> {
+    let mut values = Vec::new();
+    let mut tokens = tokenize_spanned(content).into_iter();
+
+    while let Some((token, span)) = tokens.next() {
+        let span = span.start + offset..span.end + offset;
+        values.push(parse_argument(&token, &span, substitute)?);
+
+        match tokens.next() {
+            Some((Token::Unknown(","), _)) | None => {}
+            Some((_, span)) => {
+                return Err(CommandError::ParseError {
+                    message: String::from("Expected ',' between list elements"),
+                    span: span.start + offset..span.end + offset,
+                })
+            }
+        }
     }
+
+    Ok(CommandArgument::List(values))
 }
 
-fn tokenize(command: &str) -> Vec<Token<'_>> {
+/// Tokenizes the command, non-whitespace text between the recognized tokens
+/// becomes `Token::Unknown`.
+fn tokenize_spanned(command: &str) -> Vec<(Token<'_>, Span)> {
     let mut tokens = Vec::new();
+    let mut last_end = 0;
 
-    let re = Regex::new(r###"(?P<bool>true|false)|("(?P<string>[^"]*)")|(?P<id>[a-zA-Z_][a-zA-Z:0-9_-]+)|(?P<number>[0-9]+(\.[0-9]+)?)"###).unwrap();
+    let re = Regex::new(r###"\$\{(?P<braced>[a-zA-Z_][a-zA-Z0-9_]*)\}|\$(?P<reference>[a-zA-Z_][a-zA-Z0-9_]*)|(?P<key>[a-zA-Z_][a-zA-Z0-9_]*)=|(?P<bool>true|false)|("(?P<string>(\\.|[^"\\])*)")|(?P<id>[a-zA-Z_][a-zA-Z:0-9_-]+)|(?P<number>-?(0[xX][0-9a-fA-F]+|[0-9]+(\.[0-9]+)?([eE][+-]?[0-9]+)?))|(?P<vector>\([^()]*\))|(?P<list>\[(?:"(?:\\.|[^"\\])*"|[^\[\]"])*\])|(?P<color>#[0-9a-fA-F]{6}([0-9a-fA-F]{2})?\b)"###).unwrap();
 
     for cap in re.captures_iter(command) {
-        if let Some(m) = cap.name("id") {
-            tokens.push(Token::Id(m.as_str()));
+        let span = cap.get(0).unwrap().range();
+
+        push_unknown(&mut tokens, command, last_end..span.start);
+        last_end = span.end;
+
+        if let Some(m) = cap.name("reference").or_else(|| cap.name("braced")) {
+            tokens.push((Token::Reference(m.as_str()), span));
+        } else if let Some(m) = cap.name("key") {
+            tokens.push((Token::Key(m.as_str()), span));
+        } else if let Some(m) = cap.name("id") {
+            tokens.push((Token::Id(m.as_str()), span));
         } else if let Some(m) = cap.name("string") {
-            tokens.push(Token::String(m.as_str()));
+            tokens.push((Token::String(m.as_str()), span));
         } else if let Some(m) = cap.name("number") {
-            tokens.push(Token::Number(m.as_str().parse().unwrap()));
+            tokens.push((Token::Number(parse_number(m.as_str())), span));
         } else if let Some(m) = cap.name("bool") {
-            tokens.push(Token::Bool(m.as_str().parse().unwrap()));
+            tokens.push((Token::Bool(m.as_str().parse().unwrap()), span));
+        } else if let Some(m) = cap.name("vector") {
+            tokens.push((Token::Vector(parse_vector(m.as_str())), span));
+        } else if let Some(m) = cap.name("list") {
+            let text = m.as_str();
+            tokens.push((Token::List(&text[1..text.len() - 1]), span));
+        } else if let Some(m) = cap.name("color") {
+            tokens.push((Token::Color(parse_color(m.as_str()).unwrap()), span));
         }
     }
 
+    push_unknown(&mut tokens, command, last_end..command.len());
     tokens
 }
 
+fn push_unknown<'a>(tokens: &mut Vec<(Token<'a>, Span)>, command: &'a str, gap: Span) {
+    let text = &command[gap.clone()];
+    let trimmed = text.trim_start();
+    let start = gap.start + text.len() - trimmed.len();
+    let end = start + trimmed.trim_end().len();
+
+    if start < end {
+        tokens.push((Token::Unknown(&command[start..end]), start..end));
+    }
+}
+
+/// Parses decimal, scientific or hex number matched by the tokenizer,
+/// hex numbers which don't fit into `i64` are NaN.
+fn parse_number(text: &str) -> f64 {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1., digits),
+        None => (1., text),
+    };
+
+    let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+        i64::from_str_radix(&digits[2..], 16)
+            .map(|value| value as f64)
+            .unwrap_or(f64::NAN)
+    } else {
+        digits.parse().unwrap_or(f64::NAN)
+    };
+
+    sign * value
+}
+
+/// Replaces `\"`, `\\`, `\n` and `\t` escape sequences in a quoted string.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(ch) => return Err(format!("Unknown escape sequence '\\{}'", ch)),
+            None => return Err(String::from("Unexpected end of string after '\\'")),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Parses vector components from `(x, y, ...)`, invalid components are NaN.
+fn parse_vector(text: &str) -> Vec<f64> {
+    let components = text.trim_start_matches('(').trim_end_matches(')');
+
+    if components.trim().is_empty() {
+        return Vec::new();
+    }
+
+    components
+        .split(',')
+        .map(|component| parse_number(component.trim()))
+        .collect()
+}
+
+/// Parses color from `#RRGGBB` or `#RRGGBBAA`, `None` if the text is malformed.
+pub(crate) fn parse_color(text: &str) -> Option<[u8; 4]> {
+    let digits = text.strip_prefix('#')?;
+
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut color = [255; 4];
+
+    for (i, channel) in color.iter_mut().enumerate().take(digits.len() / 2) {
+        *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(color)
+}
+
 fn execute_command_request(
-    debug_state: &mut MutexGuard<DebugState>,
+    debug_state: &mut DebugState,
     request: &CommandRequest,
-) -> Result<(), String> {
-    match debug_state.commands.index.get(&request.command) {
+) -> Result<CommandOutput, CommandError> {
+    // Command is taken out of the index while it's executed,
+    // so the executor can get mutable access to the whole debug state.
+    match debug_state.commands.index.remove(&request.command) {
         Some(command) => {
-            let executor = command.executor;
-            executor(debug_state, &request.arguments)
+            let command = command.into_inner().unwrap_or_else(PoisonError::into_inner);
+            let result = if command.protection > debug_state.permission_level {
+                Ok(Err(CommandError::PermissionDenied {
+                    command: request.command.clone(),
+                    required: command.protection,
+                }))
+            } else {
+                // Panics are caught so the command is put back before unwinding further.
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    place_named_arguments(&command.args, request)
+                        .and_then(|arguments| bind_arguments(&command.args, &arguments))
+                        .and_then(|arguments| {
+                            (command.executor)(debug_state, &arguments)
+                                .map_err(CommandError::ExecutorError)
+                        })
+                }))
+            };
+
+            debug_state
+                .commands
+                .index
+                .entry(request.command.clone())
+                .or_insert_with(|| Mutex::new(command));
+
+            result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+        }
+        None => Err(CommandError::UnknownCommand {
+            command: request.command.clone(),
+            suggestions: suggest_commands(&debug_state.commands, &request.command),
+        }),
+    }
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Suggests registered commands which start with `command`
+/// or are within a few typos from it.
+fn suggest_commands(commands: &CommandsState, command: &str) -> Vec<String> {
+    let max_distance = (command.chars().count() / 3).max(2);
+
+    let mut candidates: Vec<(usize, &String)> = commands
+        .index
+        .keys()
+        .filter_map(|candidate| {
+            if candidate.starts_with(command) {
+                Some((0, candidate))
+            } else {
+                let distance = levenshtein_distance(command, candidate);
+
+                if distance <= max_distance {
+                    Some((distance, candidate))
+                } else {
+                    None
+                }
+            }
+        })
+        .collect();
+
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + if a_char == *b_char { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Places named arguments at the positions of their specs, skipped optional ones get defaults.
+fn place_named_arguments(
+    specs: &[ArgSpec],
+    request: &CommandRequest,
+) -> Result<Vec<CommandArgument>, CommandError> {
+    if request.named_arguments.is_empty() {
+        return Ok(request.arguments.clone());
+    }
+
+    let mut slots: Vec<Option<CommandArgument>> =
+        request.arguments.iter().cloned().map(Some).collect();
+
+    for (name, argument) in request.named_arguments.iter() {
+        let index = specs
+            .iter()
+            .position(|spec| spec.name == name)
+            .ok_or_else(|| CommandError::UnknownArgument {
+                name: name.clone(),
+                expected: specs.iter().map(|spec| spec.name).collect(),
+            })?;
+
+        if slots.len() <= index {
+            slots.resize(index + 1, None);
+        }
+
+        if slots[index].is_some() {
+            return Err(CommandError::DuplicateArgument { name: name.clone() });
+        }
+
+        slots[index] = Some(argument.clone());
+    }
+
+    let mut arguments = Vec::with_capacity(slots.len());
+
+    for (index, slot) in slots.into_iter().enumerate() {
+        // Skipped slots precede a named argument, so they always have specs.
+        match (slot, &specs[index.min(specs.len() - 1)]) {
+            (Some(argument), _) => arguments.push(argument),
+            (None, spec) => match &spec.default {
+                Some(default) => arguments.push(default.clone()),
+                None => return Err(CommandError::MissingArgument { name: spec.name }),
+            },
+        }
+    }
+
+    if let Some(spec) = specs
+        .iter()
+        .skip(arguments.len())
+        .find(|spec| !spec.optional)
+    {
+        return Err(CommandError::MissingArgument { name: spec.name });
+    }
+
+    Ok(arguments)
+}
+
+/// Optional arguments should be trailing, so omitted arguments are always
+/// filled from the end, and their defaults should match their types.
+/// Variadic argument should be the last one with a concrete element type.
+fn check_arg_specs(specs: &[ArgSpec]) -> Result<(), String> {
+    if let Some(spec) = specs.iter().rev().skip(1).find(|spec| spec.variadic) {
+        return Err(format!(
+            "variadic argument '{}' should be the last",
+            spec.name
+        ));
+    }
+
+    if let Some(spec) = specs
+        .last()
+        .filter(|spec| spec.variadic && spec.arg_type == ArgType::Any)
+    {
+        return Err(format!(
+            "variadic argument '{}' should have the element type",
+            spec.name
+        ));
+    }
+
+    if let Some(pair) = specs
+        .windows(2)
+        .find(|pair| pair[0].optional && !pair[1].optional)
+    {
+        return Err(format!(
+            "required argument '{}' follows optional '{}'",
+            pair[1].name, pair[0].name
+        ));
+    }
+
+    for spec in specs {
+        if let Some(default) = &spec.default {
+            if spec.coerce(default).is_none() {
+                return Err(format!(
+                    "default {} of '{}' should be {}",
+                    default, spec.name, spec.arg_type
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates `arguments` against the command schema, coerces them
+/// and fills omitted optional arguments with defaults.
+fn bind_arguments(
+    specs: &[ArgSpec],
+    arguments: &[CommandArgument],
+) -> Result<Vec<CommandArgument>, CommandError> {
+    let required = specs.iter().filter(|spec| !spec.optional).count();
+    let max = match specs.last() {
+        Some(spec) if spec.variadic => usize::MAX,
+        _ => specs.len(),
+    };
+
+    if arguments.len() < required || arguments.len() > max {
+        return Err(CommandError::WrongArgumentCount {
+            min: required,
+            max,
+            found: arguments.len(),
+        });
+    }
+
+    let count = specs.len().max(arguments.len());
+    let mut bound = Vec::with_capacity(count);
+
+    for i in 0..count {
+        // Arguments past the specs are the elements of the variadic argument.
+        let spec = &specs[i.min(specs.len() - 1)];
+
+        match arguments.get(i) {
+            Some(argument) => match spec.coerce(argument) {
+                Some(argument) => bound.push(argument),
+                None => {
+                    return Err(CommandError::ArgumentTypeMismatch {
+                        index: i,
+                        expected: spec.arg_type,
+                        found: argument.type_name(),
+                    })
+                }
+            },
+            None => {
+                if let Some(default) = &spec.default {
+                    bound.push(default.clone());
+                }
+            }
         }
-        None => Err(format!("Command '{}' not found", request.command)),
     }
+
+    Ok(bound)
 }
 
 pub fn require(cond: bool, msg: &str) -> Result<(), String> {
@@ -156,11 +1649,13 @@ pub fn require(cond: bool, msg: &str) -> Result<(), String> {
 #[cfg(test)]
 mod tests {
     use crate::commands;
-    use std::sync::MutexGuard;
+    use crate::cvars::{self, CvarFlags, CvarValue};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn tokenize() {
-        let tokens = commands::tokenize("greet::hello test 12 55.9 \"Hello World!\" false true");
+        let tokens = tokens("greet::hello test 12 55.9 \"Hello World!\" false true");
         assert_eq!(
             tokens,
             vec![
@@ -176,50 +1671,273 @@ mod tests {
     }
 
     #[test]
-    fn parse_command_without_arguments() {
-        let request = commands::parse_command("greet::say_hello").unwrap();
+    fn split_commands() {
+        assert_eq!(
+            vec![
+                String::from("profile::pause"),
+                String::from("vars::set \"a;b\" 1"),
+                String::from("ui::pan 10 20"),
+                String::from("ui::zoom 2"),
+            ],
+            commands::split_commands(
+                "profile::pause; vars::set \"a;b\" 1;\nui::pan 10\\\n20\nui::zoom 2;"
+            )
+        );
+    }
+
+    #[test]
+    fn execute_multiple_commands() {
+        {
+            let debug_state = &mut commands::write_debug_state();
+
+            commands::register_command(
+                debug_state,
+                "Test commands",
+                commands::Command {
+                    namespace: String::from("math"),
+                    name: String::from("sum"),
+                    args: vec![
+                        commands::ArgSpec::required("a", commands::ArgType::Number),
+                        commands::ArgSpec::required("b", commands::ArgType::Number),
+                    ],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(sum_command),
+                },
+            );
+        }
+
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from("3\n7"))),
+            commands::execute_command("math::sum 1 2; math::sum 3 4")
+        );
+
+        assert_eq!(
+            Err(commands::CommandError::SubCommandFailed {
+                index: 1,
+                command: String::from("math::sum 1"),
+                error: Box::new(commands::CommandError::WrongArgumentCount {
+                    min: 2,
+                    max: 2,
+                    found: 1
+                }),
+            }),
+            commands::execute_command("math::sum 1 2; math::sum 1")
+        );
+    }
+
+    #[test]
+    fn tokenize_numbers() {
+        let tokens = tokens("camera::set_zoom -0.5 -12 1e3 2.5E-2 0xFF -0x10");
+        assert_eq!(
+            tokens,
+            vec![
+                commands::Token::Id("camera::set_zoom"),
+                commands::Token::Number(-0.5),
+                commands::Token::Number(-12.0),
+                commands::Token::Number(1000.0),
+                commands::Token::Number(0.025),
+                commands::Token::Number(255.0),
+                commands::Token::Number(-16.0),
+            ]
+        )
+    }
+
+    #[test]
+    fn parse_command_error_span() {
+        let res = parse("math::max 12 value");
+
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Expected argument value"),
+                span: 13..18,
+            }),
+            res
+        );
+    }
+
+    #[test]
+    fn parse_command_lists() {
+        let request = parse(r#"ai::set_waypoints [(0,0), (10, 5)] ["a", "b]"] [1,2,] []"#).unwrap();
+        assert_eq!(
+            vec![
+                commands::CommandArgument::List(vec![
+                    commands::CommandArgument::Vec2(0.0, 0.0),
+                    commands::CommandArgument::Vec2(10.0, 5.0),
+                ]),
+                commands::CommandArgument::List(vec![
+                    commands::CommandArgument::String(String::from("a")),
+                    commands::CommandArgument::String(String::from("b]")),
+                ]),
+                commands::CommandArgument::List(vec![
+                    commands::CommandArgument::Number(1.0),
+                    commands::CommandArgument::Number(2.0),
+                ]),
+                commands::CommandArgument::List(vec![]),
+            ],
+            request.arguments
+        );
+
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Expected ',' between list elements"),
+                span: 18..19,
+            }),
+            parse("ai::set_speeds [1 2]")
+        );
+
+        let waypoints = commands::ArgSpec::required(
+            "waypoints",
+            commands::ArgType::List(&commands::ArgType::Vec2),
+        );
+        assert_eq!("<waypoints: [vec2]>", waypoints.to_string());
+        assert!(commands::bind_arguments(&[waypoints.clone()], &request.arguments[..1]).is_ok());
+        assert!(commands::bind_arguments(&[waypoints], &request.arguments[2..3]).is_err());
+    }
+
+    #[test]
+    fn parse_command_unrecognized_input() {
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Unrecognized input '@@'"),
+                span: 13..15,
+            }),
+            parse("math::max 12 @@ 3")
+        );
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Unterminated string"),
+                span: 11..20,
+            }),
+            parse("log::print \"unclosed")
+        );
+        assert_eq!(
+            vec![
+                commands::Token::Id("math::max"),
+                commands::Token::Number(1.0),
+                commands::Token::Unknown("$"),
+            ],
+            tokens("math::max 1 $ ")
+        );
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Number out of range"),
+                span: 10..29,
+            }),
+            parse("math::max 0x10000000000000000 1")
+        );
+    }
+
+    #[test]
+    fn parse_command_without_arguments() {
+        let request = parse("greet::say_hello").unwrap();
+        assert_eq!(
+            request,
+            commands::CommandRequest {
+                command: String::from("greet::say_hello"),
+                arguments: vec![],
+                named_arguments: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_command_number() {
+        let request = parse("math::max 12 88.12").unwrap();
+        assert_eq!(
+            request,
+            commands::CommandRequest {
+                command: String::from("math::max"),
+                arguments: vec![
+                    commands::CommandArgument::Number(12.0),
+                    commands::CommandArgument::Number(88.12)
+                ],
+                named_arguments: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_command_string() {
+        let request = parse("greet::print_hello \"<User Name>\"").unwrap();
+        assert_eq!(
+            request,
+            commands::CommandRequest {
+                command: String::from("greet::print_hello"),
+                arguments: vec![commands::CommandArgument::String(String::from(
+                    "<User Name>"
+                ))],
+                named_arguments: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_command_string_escapes() {
+        let request = parse(r#"log::print "say \"hi\"\n\tpath: C:\\tmp""#).unwrap();
         assert_eq!(
             request,
             commands::CommandRequest {
-                command: String::from("greet::say_hello"),
-                arguments: vec![],
+                command: String::from("log::print"),
+                arguments: vec![commands::CommandArgument::String(String::from(
+                    "say \"hi\"\n\tpath: C:\\tmp"
+                ))],
+                named_arguments: vec![],
             }
         );
+
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Unknown escape sequence '\\q'"),
+                span: 11..16,
+            }),
+            parse(r#"log::print "\qa""#)
+        );
     }
 
     #[test]
-    fn parse_command_number() {
-        let request = commands::parse_command("math::max 12 88.12").unwrap();
+    fn parse_command_vectors_and_colors() {
+        let request =
+            parse("camera::set_position (100, 250) (1, -2.5, 0x10) #FF00FFCC #102030").unwrap();
         assert_eq!(
             request,
             commands::CommandRequest {
-                command: String::from("math::max"),
+                command: String::from("camera::set_position"),
                 arguments: vec![
-                    commands::CommandArgument::Number(12.0),
-                    commands::CommandArgument::Number(88.12)
+                    commands::CommandArgument::Vec2(100.0, 250.0),
+                    commands::CommandArgument::Vec3(1.0, -2.5, 16.0),
+                    commands::CommandArgument::Color(255, 0, 255, 204),
+                    commands::CommandArgument::Color(16, 32, 48, 255),
                 ],
+                named_arguments: vec![],
             }
         );
-    }
 
-    #[test]
-    fn parse_command_string() {
-        let request = commands::parse_command("greet::print_hello \"<User Name>\"").unwrap();
         assert_eq!(
-            request,
-            commands::CommandRequest {
-                command: String::from("greet::print_hello"),
-                arguments: vec![commands::CommandArgument::String(String::from(
-                    "<User Name>"
-                ))],
-            }
+            Err(commands::CommandError::ParseError {
+                message: String::from("Vector should have 2 or 3 components, found 1"),
+                span: 21..24,
+            }),
+            parse("camera::set_position (1)")
+        );
+
+        assert_eq!(Some([255, 0, 255, 204]), commands::parse_color("#FF00FFCC"));
+        assert_eq!(None, commands::parse_color("#FF"));
+        assert_eq!(None, commands::parse_color("#GG0000"));
+        assert_eq!(None, commands::parse_color("#ÿÿÿ"));
+        assert_eq!(None, commands::parse_color("FF00FF"));
+
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Vector components should be numbers"),
+                span: 21..27,
+            }),
+            parse("camera::set_position (1, x)")
         );
     }
 
     #[test]
     fn parse_command() {
-        let request =
-            commands::parse_command("math::max_and_print 12 88.12 \"Max: \" true").unwrap();
+        let request = parse("math::max_and_print 12 88.12 \"Max: \" true").unwrap();
 
         assert_eq!(
             request,
@@ -231,6 +1949,7 @@ mod tests {
                     commands::CommandArgument::String(String::from("Max: ")),
                     commands::CommandArgument::Bool(true)
                 ],
+                named_arguments: vec![],
             }
         );
     }
@@ -238,9 +1957,7 @@ mod tests {
     #[test]
     fn execute_command() {
         {
-            let debug_state = &mut commands::DEBUG_STATE
-                .lock()
-                .expect("failed to get debug state");
+            let debug_state = &mut commands::write_debug_state();
 
             commands::register_command(
                 debug_state,
@@ -248,19 +1965,93 @@ mod tests {
                 commands::Command {
                     namespace: String::from("math"),
                     name: String::from("sum"),
-                    executor: sum_command,
+                    args: vec![
+                        commands::ArgSpec::required("a", commands::ArgType::Number),
+                        commands::ArgSpec::required("b", commands::ArgType::Number),
+                    ],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(sum_command),
+                },
+            );
+        }
+        assert_eq!(
+            Ok(commands::CommandOutput::Number(4.0)),
+            commands::execute_command("math::sum 2 2")
+        );
+    }
+
+    #[test]
+    fn execute_closure_command() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        {
+            let debug_state = &mut commands::write_debug_state();
+
+            let executor_counter = counter.clone();
+
+            commands::register_command(
+                debug_state,
+                "Test commands",
+                commands::Command {
+                    namespace: String::from("test"),
+                    name: String::from("count"),
+                    args: vec![],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(move |_, _| {
+                        let count = executor_counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        Ok(commands::CommandOutput::Number(count as f64))
+                    }),
                 },
             );
         }
-        assert_eq!(true, commands::execute_command("math::sum 2 2").is_ok());
+
+        assert!(commands::execute_command("test::count").is_ok());
+        assert_eq!(
+            Ok(commands::CommandOutput::Number(2.0)),
+            commands::execute_command("test::count")
+        );
+        assert_eq!(2, counter.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn execute_script() {
+        let debug_state = &mut commands::write_debug_state();
+
+        commands::register_command(
+            debug_state,
+            "Test commands",
+            commands::Command {
+                namespace: String::from("math"),
+                name: String::from("sum"),
+                args: vec![
+                    commands::ArgSpec::required("a", commands::ArgType::Number),
+                    commands::ArgSpec::required("b", commands::ArgType::Number),
+                ],
+                protection: commands::PermissionLevel::Normal,
+                executor: Box::new(sum_command),
+            },
+        );
+
+        let script = "# autoexec\n\nmath::sum 1 2\nmath::sum \\\n1\nmath::unknown\n";
+        let errors = commands::execute_script(debug_state, script);
+
+        assert_eq!(2, errors.len());
+        assert_eq!(4, errors[0].0);
+        assert_eq!(
+            commands::CommandError::WrongArgumentCount {
+                min: 2,
+                max: 2,
+                found: 1,
+            },
+            errors[0].1
+        );
+        assert_eq!(6, errors[1].0);
     }
 
     #[test]
     fn execute_command_failed_type() {
         {
-            let debug_state = &mut commands::DEBUG_STATE
-                .lock()
-                .expect("failed to get debug state");
+            let debug_state = &mut commands::write_debug_state();
 
             commands::register_command(
                 debug_state,
@@ -268,23 +2059,32 @@ mod tests {
                 commands::Command {
                     namespace: String::from("math"),
                     name: String::from("sum"),
-                    executor: sum_command,
+                    args: vec![
+                        commands::ArgSpec::required("a", commands::ArgType::Number),
+                        commands::ArgSpec::required("b", commands::ArgType::Number),
+                    ],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(sum_command),
                 },
             );
         }
 
         let res = commands::execute_command("math::sum 2 \"2\"");
 
-        assert_eq!(true, res.is_err());
-        assert_eq!("second argument should be number", res.err().unwrap());
+        assert_eq!(
+            Err(commands::CommandError::ArgumentTypeMismatch {
+                index: 1,
+                expected: commands::ArgType::Number,
+                found: "string",
+            }),
+            res
+        );
     }
 
     #[test]
     fn execute_command_failed() {
         {
-            let debug_state = &mut commands::DEBUG_STATE
-                .lock()
-                .expect("failed to get debug state");
+            let debug_state = &mut commands::write_debug_state();
 
             commands::register_command(
                 debug_state,
@@ -292,34 +2092,560 @@ mod tests {
                 commands::Command {
                     namespace: String::from("math"),
                     name: String::from("sum"),
-                    executor: sum_command,
+                    args: vec![
+                        commands::ArgSpec::required("a", commands::ArgType::Number),
+                        commands::ArgSpec::required("b", commands::ArgType::Number),
+                    ],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(sum_command),
                 },
             );
         }
 
         let res = commands::execute_command("math::sum 2");
 
-        assert_eq!(true, res.is_err());
-        assert_eq!("bad arguments length", res.err().unwrap());
+        assert_eq!(
+            Err(commands::CommandError::WrongArgumentCount {
+                min: 2,
+                max: 2,
+                found: 1
+            }),
+            res
+        );
     }
 
-    fn sum_command(
-        _: &mut MutexGuard<commands::DebugState>,
-        arguments: &[commands::CommandArgument],
-    ) -> Result<(), String> {
-        commands::require(arguments.len() == 2, "bad arguments length")?;
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(0, commands::levenshtein_distance("ui::zoom", "ui::zoom"));
+        assert_eq!(1, commands::levenshtein_distance("ui::zom", "ui::zoom"));
+        assert_eq!(2, commands::levenshtein_distance("ui::zomo", "ui::zoom"));
+        assert_eq!(3, commands::levenshtein_distance("", "abc"));
+    }
 
-        let a = match arguments[0] {
-            commands::CommandArgument::Number(val) => Ok(val),
-            _ => Err(String::from("first argument should be number")),
-        }?;
+    #[test]
+    fn suggest_commands() {
+        let mut state = commands::CommandsState::default();
 
-        let b = match arguments[1] {
-            commands::CommandArgument::Number(val) => Ok(val),
-            _ => Err(String::from("second argument should be number")),
-        }?;
+        for name in &["set_snapshot_interval", "export_trace", "export_flamegraph"] {
+            state.index.insert(
+                format!("profile::{}", name),
+                Mutex::new(commands::Command {
+                    namespace: String::from("profile"),
+                    name: String::from(*name),
+                    args: vec![],
+                    protection: commands::PermissionLevel::Normal,
+                    executor: Box::new(sum_command),
+                }),
+            );
+        }
 
-        println!("{} + {} = {}", a, b, a + b);
-        Ok(())
+        assert_eq!(
+            vec![String::from("profile::set_snapshot_interval")],
+            commands::suggest_commands(&state, "profile::set_snapshot_intreval")
+        );
+        assert_eq!(
+            vec![
+                String::from("profile::export_flamegraph"),
+                String::from("profile::export_trace"),
+            ],
+            commands::suggest_commands(&state, "profile::export")
+        );
+        assert_eq!(
+            Vec::<String>::new(),
+            commands::suggest_commands(&state, "render::wireframe")
+        );
+
+        let error = commands::CommandError::UnknownCommand {
+            command: String::from("profile::set_snapshot_intreval"),
+            suggestions: commands::suggest_commands(&state, "profile::set_snapshot_intreval"),
+        };
+        assert_eq!(
+            "Command 'profile::set_snapshot_intreval' not found, did you mean `profile::set_snapshot_interval`?",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn output_table() {
+        let output = commands::CommandOutput::Table {
+            header: vec![String::from("name"), String::from("hits")],
+            rows: vec![
+                vec![String::from("update"), String::from("12")],
+                vec![String::from("render_frame"), String::from("1")],
+            ],
+        };
+
+        assert_eq!(
+            "name          hits\nupdate        12\nrender_frame  1",
+            output.to_string()
+        );
+    }
+
+    #[test]
+    fn history_navigation() {
+        let mut state = commands::CommandsState::default();
+
+        assert_eq!(None, commands::history_prev(&state, None));
+
+        state.history.push(String::from("ui::zoom 2"));
+        state
+            .history
+            .push(String::from("profile::export_trace \"a.json\""));
+        state.history.push(String::from("ui::zoom 1"));
+
+        assert_eq!(Some(2), commands::history_prev(&state, None));
+        assert_eq!(Some(1), commands::history_prev(&state, Some(2)));
+        assert_eq!(Some(0), commands::history_prev(&state, Some(0)));
+        assert_eq!(Some(2), commands::history_next(&state, Some(1)));
+        assert_eq!(None, commands::history_next(&state, Some(2)));
+        assert_eq!(None, commands::history_next(&state, None));
+    }
+
+    #[test]
+    fn history_search() {
+        let mut state = commands::CommandsState::default();
+        state.history.push(String::from("ui::zoom 2"));
+        state
+            .history
+            .push(String::from("profile::export_trace \"a.json\""));
+        state.history.push(String::from("ui::zoom 1"));
+        state.history.push(String::from("ui::zoom 2"));
+
+        assert_eq!(
+            vec![(3, "ui::zoom 2"), (2, "ui::zoom 1")],
+            commands::history_search(&state, "ui::")
+        );
+    }
+
+    fn registry_entry(namespace: &str, name: &str) -> commands::CommandRegistryEntry {
+        commands::CommandRegistryEntry {
+            namespace: String::from(namespace),
+            name: String::from(name),
+            args: String::new(),
+            arg_values: Vec::new(),
+            desc: "",
+        }
+    }
+
+    #[test]
+    fn complete_command() {
+        let mut state = commands::CommandsState::default();
+        state
+            .registry
+            .push(registry_entry("profile", "set_snapshot_interval"));
+        state.registry.push(registry_entry("ui", "zoom"));
+        state.registry.push(registry_entry("ui", "pan"));
+
+        assert_eq!(
+            commands::complete_in(&state, "pro"),
+            vec![
+                String::from("profile::"),
+                String::from("profile::set_snapshot_interval")
+            ]
+        );
+        assert_eq!(
+            commands::complete_in(&state, "ui::"),
+            vec![String::from("ui::pan"), String::from("ui::zoom")]
+        );
+    }
+
+    #[test]
+    fn complete_command_fuzzy() {
+        let mut state = commands::CommandsState::default();
+        state
+            .registry
+            .push(registry_entry("profile", "set_snapshot_interval"));
+        state.registry.push(registry_entry("ui", "zoom"));
+
+        assert_eq!(
+            commands::complete_in(&state, "prof::interval"),
+            vec![String::from("profile::set_snapshot_interval")]
+        );
+    }
+
+    #[test]
+    fn complete_argument_values() {
+        let mut state = commands::CommandsState::default();
+        let mut entry = registry_entry("log", "filter");
+        entry.arg_values = vec![vec![
+            String::from("error"),
+            String::from("warn"),
+            String::from("info"),
+        ]];
+        state.registry.push(entry);
+
+        assert_eq!(
+            commands::complete_in(&state, "log::filter w"),
+            vec![String::from("log::filter warn")]
+        );
+        assert_eq!(commands::complete_in(&state, "log::filter warn ").len(), 0);
+    }
+
+    #[test]
+    fn bind_arguments_defaults() {
+        let specs = vec![
+            commands::ArgSpec::required("name", commands::ArgType::String),
+            commands::ArgSpec::optional(
+                "count",
+                commands::ArgType::Int,
+                commands::CommandArgument::Number(10.0),
+            ),
+        ];
+
+        let arguments = commands::bind_arguments(
+            &specs,
+            &[commands::CommandArgument::String(String::from("render"))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            arguments,
+            vec![
+                commands::CommandArgument::String(String::from("render")),
+                commands::CommandArgument::Number(10.0),
+            ]
+        );
+        assert_eq!("[count: int = 10]", specs[1].to_string());
+    }
+
+    #[test]
+    fn check_arg_specs() {
+        let optional = commands::ArgSpec::optional(
+            "count",
+            commands::ArgType::Int,
+            commands::CommandArgument::Number(10.0),
+        );
+        let required = commands::ArgSpec::required("name", commands::ArgType::String);
+
+        assert_eq!(
+            Ok(()),
+            commands::check_arg_specs(&[required.clone(), optional.clone()])
+        );
+        assert_eq!(
+            Err(String::from(
+                "required argument 'name' follows optional 'count'"
+            )),
+            commands::check_arg_specs(&[optional, required])
+        );
+        assert!(commands::check_arg_specs(&[commands::ArgSpec::optional(
+            "count",
+            commands::ArgType::Int,
+            commands::CommandArgument::Number(2.5),
+        )])
+        .is_err());
+    }
+
+    #[test]
+    fn bind_arguments_variadic() {
+        let specs = vec![
+            commands::ArgSpec::required("level", commands::ArgType::String),
+            commands::ArgSpec::variadic("targets", commands::ArgType::String),
+        ];
+        let string = |value: &str| commands::CommandArgument::String(String::from(value));
+
+        assert_eq!(
+            Ok(vec![string("warn"), string("net"), string("render")]),
+            commands::bind_arguments(&specs, &[string("warn"), string("net"), string("render")])
+        );
+        assert_eq!(
+            Ok(vec![string("warn")]),
+            commands::bind_arguments(&specs, &[string("warn")])
+        );
+        assert_eq!(
+            Err(commands::CommandError::ArgumentTypeMismatch {
+                index: 2,
+                expected: commands::ArgType::String,
+                found: "number",
+            }),
+            commands::bind_arguments(
+                &specs,
+                &[
+                    string("warn"),
+                    string("net"),
+                    commands::CommandArgument::Number(1.0)
+                ]
+            )
+        );
+        assert_eq!("[targets: string...]", specs[1].to_string());
+
+        assert_eq!(Ok(()), commands::check_arg_specs(&specs));
+        assert!(commands::check_arg_specs(&[specs[1].clone(), specs[0].clone()]).is_err());
+        assert!(commands::check_arg_specs(&[commands::ArgSpec::variadic(
+            "values",
+            commands::ArgType::Any
+        )])
+        .is_err());
+    }
+
+    #[test]
+    fn bind_arguments_int() {
+        let specs = vec![commands::ArgSpec::required(
+            "interval",
+            commands::ArgType::Int,
+        )];
+
+        let res = commands::bind_arguments(&specs, &[commands::CommandArgument::Number(2.5)]);
+
+        assert_eq!(
+            Err(commands::CommandError::ArgumentTypeMismatch {
+                index: 0,
+                expected: commands::ArgType::Int,
+                found: "number",
+            }),
+            res
+        );
+    }
+
+    #[test]
+    fn bind_arguments_enum() {
+        let specs = vec![commands::ArgSpec::required(
+            "level",
+            commands::ArgType::Enum(&["error", "warn"]),
+        )];
+
+        assert!(commands::bind_arguments(
+            &specs,
+            &[commands::CommandArgument::String(String::from("warn"))]
+        )
+        .is_ok());
+        assert!(commands::bind_arguments(
+            &specs,
+            &[commands::CommandArgument::String(String::from("trace"))]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn typed_command() {
+        fn scale(value: f64, factor: Option<u32>) -> Result<String, String> {
+            commands::require(value >= 0., "value should be positive")?;
+            Ok((value * factor.unwrap_or(2) as f64).to_string())
+        }
+
+        let command = commands::Command::from_fn("typed", "scale", &["value", "factor"], scale);
+
+        assert_eq!(
+            vec![
+                commands::ArgSpec::required("value", commands::ArgType::Number),
+                commands::ArgSpec {
+                    name: "factor",
+                    arg_type: commands::ArgType::Int,
+                    optional: true,
+                    default: None,
+                    variadic: false,
+                },
+            ],
+            command.args
+        );
+
+        {
+            let debug_state = &mut commands::write_debug_state();
+
+            commands::register_command(debug_state, "Scale the value", command);
+        }
+
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from("3"))),
+            commands::execute_command("typed::scale 1.5")
+        );
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from("6"))),
+            commands::execute_command("typed::scale 2 3")
+        );
+        assert_eq!(
+            Err(commands::CommandError::ExecutorError(String::from(
+                "value should be positive"
+            ))),
+            commands::execute_command("typed::scale -1")
+        );
+    }
+
+    #[test]
+    fn execute_named_arguments() {
+        fn set_resolution(width: u32, height: u32, fullscreen: Option<bool>) -> String {
+            format!("{}x{} {:?}", width, height, fullscreen)
+        }
+
+        let mut debug_state = commands::DebugState::default();
+
+        commands::register_command(
+            &mut debug_state,
+            "Set the window resolution",
+            commands::Command::from_fn(
+                "render",
+                "set_resolution",
+                &["width", "height", "fullscreen"],
+                set_resolution,
+            ),
+        );
+
+        let mut execute = |command| commands::execute(&mut debug_state, command);
+
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from(
+                "1920x1080 None"
+            ))),
+            execute("render::set_resolution height=1080 width=1920")
+        );
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from(
+                "1280x720 Some(true)"
+            ))),
+            execute("render::set_resolution 1280 fullscreen=true height=720")
+        );
+        assert_eq!(
+            Err(commands::CommandError::UnknownArgument {
+                name: String::from("depth"),
+                expected: vec!["width", "height", "fullscreen"],
+            }),
+            execute("render::set_resolution 1280 720 depth=32")
+        );
+        assert_eq!(
+            Err(commands::CommandError::DuplicateArgument {
+                name: String::from("width"),
+            }),
+            execute("render::set_resolution 1280 720 width=1920")
+        );
+        assert_eq!(
+            Err(commands::CommandError::MissingArgument { name: "height" }),
+            execute("render::set_resolution width=1920 fullscreen=false")
+        );
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Positional argument after named arguments"),
+                span: 34..38,
+            }),
+            execute("render::set_resolution width=1920 1080")
+        );
+    }
+
+    #[test]
+    fn parse_command_substitutions() {
+        let mut debug_state = commands::DebugState::default();
+
+        commands::set_substitution(&mut debug_state, "PROJECT_DIR", "/games/demo");
+        commands::set_substitution(&mut debug_state, "INJECTED", "\"a\"; debug::exec \"b\"");
+        cvars::register_in(
+            &mut debug_state.cvars,
+            "snapshot_idx",
+            CvarValue::Int(12),
+            CvarFlags::NONE,
+        );
+
+        let substitute = |name: &str| commands::substitution(&debug_state, name);
+        let request = commands::parse_command(
+            r#"profile::load $PROJECT_DIR ${snapshot_idx} $INJECTED "cost $5""#,
+            &substitute,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                commands::CommandArgument::String(String::from("/games/demo")),
+                commands::CommandArgument::Number(12.0),
+                // Quotes and separators in the value don't split it.
+                commands::CommandArgument::String(String::from("\"a\"; debug::exec \"b\"")),
+                // Quoted strings aren't substituted.
+                commands::CommandArgument::String(String::from("cost $5")),
+            ],
+            request.arguments
+        );
+        assert_eq!(
+            Err(commands::CommandError::ParseError {
+                message: String::from("Unknown substitution 'UNKNOWN_DIR'"),
+                span: 12..24,
+            }),
+            commands::parse_command("debug::exec $UNKNOWN_DIR", &substitute)
+        );
+    }
+
+    #[test]
+    fn panicking_command_stays_registered() {
+        fn panic_command(
+            _: &mut commands::DebugState,
+            _: &[commands::CommandArgument],
+        ) -> Result<commands::CommandOutput, String> {
+            panic!("executor panicked")
+        }
+
+        let mut debug_state = commands::DebugState::default();
+
+        commands::register_command(
+            &mut debug_state,
+            "Test commands",
+            commands::Command {
+                namespace: String::from("test"),
+                name: String::from("panic"),
+                args: vec![],
+                protection: commands::PermissionLevel::Normal,
+                executor: Box::new(panic_command),
+            },
+        );
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            commands::execute(&mut debug_state, "test::panic")
+        }));
+
+        assert!(result.is_err());
+        assert!(debug_state.commands.index.contains_key("test::panic"));
+    }
+
+    #[test]
+    fn permission_level() {
+        fn step() -> Result<String, String> {
+            Ok(String::from("stepped"))
+        }
+
+        let mut debug_state = commands::DebugState {
+            permission_level: commands::PermissionLevel::Normal,
+            ..Default::default()
+        };
+
+        commands::register_command(
+            &mut debug_state,
+            "Advance the game by a frame",
+            commands::Command::from_fn("game", "step", &[], step)
+                .with_protection(commands::PermissionLevel::Cheat),
+        );
+
+        let denied = commands::execute(&mut debug_state, "game::step");
+        assert_eq!(
+            Err(commands::CommandError::PermissionDenied {
+                command: String::from("game::step"),
+                required: commands::PermissionLevel::Cheat,
+            }),
+            denied
+        );
+        assert_eq!(
+            "Command 'game::step' requires cheat mode",
+            denied.unwrap_err().to_string()
+        );
+
+        debug_state.permission_level = commands::PermissionLevel::Developer;
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from("stepped"))),
+            commands::execute(&mut debug_state, "game::step")
+        );
+    }
+
+    fn parse(command: &str) -> Result<commands::CommandRequest, commands::CommandError> {
+        commands::parse_command(command, &|_| None)
+    }
+
+    fn tokens(command: &str) -> Vec<commands::Token<'_>> {
+        commands::tokenize_spanned(command)
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    fn sum_command(
+        _: &mut commands::DebugState,
+        arguments: &[commands::CommandArgument],
+    ) -> Result<commands::CommandOutput, String> {
+        let a = arguments[0].as_number()?;
+        let b = arguments[1].as_number()?;
+
+        Ok(commands::CommandOutput::Number(a + b))
     }
 }
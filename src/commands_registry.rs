@@ -1,34 +1,2180 @@
-use std::sync::MutexGuard;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+use vm_math::{Vec2f, Vec4f};
 
 use crate::commands::*;
+use crate::conditions;
+use crate::cvars;
+use crate::debug_variables::{self, DebugVariable};
+use crate::debug_watch;
+use crate::format;
+use crate::inspector;
+use crate::key_bindings;
+use crate::logging;
 use crate::profiler;
-use crate::state::DebugState;
+use crate::profiler_pins;
+use crate::render;
+use crate::sampling;
+use crate::scheduler;
+use crate::screenshot;
+use crate::state::{DebugState, OverlayAnchor};
+use crate::theme::Theme;
+use crate::time_control;
+
+pub fn init(debug_state: &mut DebugState) {
+    register_command(
+        debug_state,
+        "Show available commands or help for the given command",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("help"),
+            args: vec![ArgSpec::optional(
+                "command",
+                ArgType::String,
+                CommandArgument::String(String::new()),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(help_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Execute commands from the script file line by line",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("exec"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(exec_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Execute the command if the condition on cvars or variables holds, e.g. 'r_fog && fog_density > 0.5'",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("if"),
+            args: vec![
+                ArgSpec::required("condition", ArgType::String),
+                ArgSpec::required("command", ArgType::String),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(if_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Execute the command after the delay in seconds",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("defer"),
+            args: vec![
+                ArgSpec::required("seconds", ArgType::Number),
+                ArgSpec::required("command", ArgType::String),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(defer_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Execute the command every interval in seconds",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("every"),
+            args: vec![
+                ArgSpec::required("seconds", ArgType::Number),
+                ArgSpec::required("command", ArgType::String),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(every_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Cancel the deferred or repeated command by id",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("cancel"),
+            args: vec![ArgSpec::required("id", ArgType::Int)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(cancel_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List deferred and repeated commands",
+        Command {
+            namespace: String::from("debug"),
+            name: String::from("scheduled"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(scheduled_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set the value of $name references in commands",
+        Command {
+            namespace: String::from("env"),
+            name: String::from("set"),
+            args: vec![
+                ArgSpec::required("name", ArgType::String),
+                ArgSpec::required("value", ArgType::Any),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(env_set_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Remove the value of $name references in commands",
+        Command {
+            namespace: String::from("env"),
+            name: String::from("unset"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(env_unset_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show values of $name references in commands",
+        Command {
+            namespace: String::from("env"),
+            name: String::from("list"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(env_list_command),
+        },
+    );
 
-pub fn init(debug_state: &mut MutexGuard<DebugState>) {
     register_command(
         debug_state,
         "Update snapshot interval",
         Command {
             namespace: String::from("profile"),
             name: String::from("set_snapshot_interval"),
-            executor: set_snapshot_interval_command,
+            args: vec![ArgSpec::required("interval", ArgType::Int)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(set_snapshot_interval_command),
         },
     );
-}
 
-fn set_snapshot_interval_command(
-    _: &mut MutexGuard<DebugState>,
-    arguments: &[CommandArgument],
-) -> Result<(), String> {
-    require(arguments.len() == 1, "bad arguments length")?;
+    register_command(
+        debug_state,
+        "Resize profiler buffers, collected frames and snapshots are dropped",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("configure"),
+            args: vec![
+                ArgSpec::required(
+                    "option",
+                    ArgType::Enum(profiler::ProfilerConfig::OPTION_NAMES),
+                ),
+                ArgSpec::required("value", ArgType::Int),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(configure_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set frame time in milliseconds above which frames are captured as spikes",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("set_spike_threshold"),
+            args: vec![ArgSpec::required("ms", ArgType::Number)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(set_spike_threshold_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set frame time budget in milliseconds, 0 disables the budget",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("set_budget_ms"),
+            args: vec![
+                ArgSpec::required("ms", ArgType::Number),
+                ArgSpec::optional("log_offenders", ArgType::Bool, CommandArgument::Bool(false)),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(set_budget_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set expected upper bound of the counter time per frame in milliseconds, 0 removes it",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("assert_budget"),
+            args: vec![
+                ArgSpec::required("counter", ArgType::String),
+                ArgSpec::required("ms", ArgType::Number),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(assert_budget_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show counters found over their budgets after the snapshots",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("budget_violations"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(budget_violations_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show time the debug services spent on themselves in the last frame",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("overhead"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(overhead_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show captured frame spikes with their slowest top level block",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("spikes"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(spikes_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Compare two snapshots from the profiler log",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("diff"),
+            args: vec![
+                ArgSpec::required("snapshot_a", ArgType::Int),
+                ArgSpec::required("snapshot_b", ArgType::Int),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(diff_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show the most expensive blocks of the latest snapshot",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("top"),
+            args: vec![ArgSpec::optional(
+                "count",
+                ArgType::Int,
+                CommandArgument::Number(10.),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(top_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show only profiler counters with names containing the substring",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("filter"),
+            args: vec![ArgSpec::optional(
+                "substring",
+                ArgType::String,
+                CommandArgument::String(String::new()),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(filter_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show only profiler counters of the category, no category shows all counters",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("category"),
+            args: vec![ArgSpec::optional(
+                "category",
+                ArgType::String,
+                CommandArgument::String(String::new()),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(category_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Pin the latest time and hits of the profiler counter to the overlay",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("pin"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(pin_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Remove the profiler counter from the overlay",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("unpin"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(unpin_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Pause profiler, snapshots are kept until resume",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("pause"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(|debug_state, _| set_profiler_enabled(debug_state, false)),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Resume profiler",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("resume"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(|debug_state, _| set_profiler_enabled(debug_state, true)),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Export profiler data in chrome://tracing format",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("export_trace"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(export_trace_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Record every timed block of the last frames for the frame timeline",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("detail"),
+            args: vec![ArgSpec::required("mode", ArgType::Enum(&["on", "off"]))],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(detail_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Start sampling open timed blocks with the interval in milliseconds, resets the samples",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("sampling"),
+            args: vec![
+                ArgSpec::required("mode", ArgType::Enum(&["on", "off"])),
+                ArgSpec::optional("interval_ms", ArgType::Number, CommandArgument::Number(1.)),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(sampling_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show the most sampled timed blocks with their estimated time",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("samples"),
+            args: vec![ArgSpec::optional(
+                "count",
+                ArgType::Int,
+                CommandArgument::Number(20.),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(samples_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Export frames recorded in the detail mode in chrome://tracing format",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("export_detail"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(export_detail_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Export performance counter log in CSV format",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("export_csv"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(export_csv_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Export profiler data in collapsed stacks format for flamegraphs",
+        Command {
+            namespace: String::from("profile"),
+            name: String::from("export_flamegraph"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(export_flamegraph_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set the most verbose level of log records shown in the overlay",
+        Command {
+            namespace: String::from("log"),
+            name: String::from("filter"),
+            args: vec![ArgSpec::required("level", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(log_filter_command),
+        },
+    );
+
+    register_argument_values(
+        debug_state,
+        "log::filter",
+        0,
+        &["off", "error", "warn", "info", "debug", "trace"],
+    );
+
+    register_command(
+        debug_state,
+        "Show only log records with targets containing one of the substrings, all if none are given",
+        Command {
+            namespace: String::from("log"),
+            name: String::from("targets"),
+            args: vec![ArgSpec::variadic("targets", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(log_targets_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set debug variable value",
+        Command {
+            namespace: String::from("vars"),
+            name: String::from("set"),
+            args: vec![
+                ArgSpec::required("path", ArgType::String),
+                ArgSpec::required("value", ArgType::Any),
+            ],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(vars_set_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show debug variable value",
+        Command {
+            namespace: String::from("vars"),
+            name: String::from("get"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(vars_get_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List debug variables under the given path",
+        Command {
+            namespace: String::from("vars"),
+            name: String::from("list"),
+            args: vec![ArgSpec::optional(
+                "path",
+                ArgType::String,
+                CommandArgument::String(String::new()),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(vars_list_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set console variable value",
+        Command {
+            namespace: String::from("cvar"),
+            name: String::from("set"),
+            args: vec![
+                ArgSpec::required("name", ArgType::String),
+                ArgSpec::required("value", ArgType::Any),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(cvar_set_command),
+        },
+    );
 
-    let interval = match arguments[0] {
-        CommandArgument::Number(val) => Ok(val),
-        _ => Err(String::from("Argument should be int")),
-    }?;
+    register_command(
+        debug_state,
+        "Show console variable value",
+        Command {
+            namespace: String::from("cvar"),
+            name: String::from("get"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(cvar_get_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Reset console variable to its default value",
+        Command {
+            namespace: String::from("cvar"),
+            name: String::from("reset"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(cvar_reset_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List console variables containing the filter in the name",
+        Command {
+            namespace: String::from("cvar"),
+            name: String::from("list"),
+            args: vec![ArgSpec::optional(
+                "filter",
+                ArgType::String,
+                CommandArgument::String(String::new()),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(cvar_list_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Allow changing cheat protected console variables, off resets them",
+        Command {
+            namespace: String::from("cvar"),
+            name: String::from("cheats"),
+            args: vec![ArgSpec::required("mode", ArgType::Enum(&["on", "off"]))],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(cvar_cheats_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Execute the command when the key is pressed, e.g. F3",
+        Command {
+            namespace: String::from("input"),
+            name: String::from("bind"),
+            args: vec![
+                ArgSpec::required("key", ArgType::String),
+                ArgSpec::required("command", ArgType::String),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(bind_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Remove the command bound to the key",
+        Command {
+            namespace: String::from("input"),
+            name: String::from("unbind"),
+            args: vec![ArgSpec::required("key", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(unbind_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List keys with their bound commands",
+        Command {
+            namespace: String::from("input"),
+            name: String::from("bind_list"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(bind_list_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show published value in the overlay",
+        Command {
+            namespace: String::from("watch"),
+            name: String::from("add"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(watch_add_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Stop showing published value in the overlay",
+        Command {
+            namespace: String::from("watch"),
+            name: String::from("remove"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(watch_remove_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List published values and whether they are watched",
+        Command {
+            namespace: String::from("watch"),
+            name: String::from("list"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(watch_list_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Pause game time",
+        Command {
+            namespace: String::from("time"),
+            name: String::from("pause"),
+            args: vec![],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(|_, _| {
+                time_control::pause(&mut time_control::get_time_control_state());
+                Ok(CommandOutput::None)
+            }),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Resume game time",
+        Command {
+            namespace: String::from("time"),
+            name: String::from("resume"),
+            args: vec![],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(|_, _| {
+                time_control::resume(&mut time_control::get_time_control_state());
+                Ok(CommandOutput::None)
+            }),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set game time scale, 1 is the real time",
+        Command {
+            namespace: String::from("time"),
+            name: String::from("scale"),
+            args: vec![ArgSpec::required("scale", ArgType::Number)],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(time_scale_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Advance paused game time by a single frame",
+        Command {
+            namespace: String::from("time"),
+            name: String::from("step"),
+            args: vec![],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(|_, _| {
+                time_control::request_step(&mut time_control::get_time_control_state())?;
+                Ok(CommandOutput::None)
+            }),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "List registered inspector objects",
+        Command {
+            namespace: String::from("inspect"),
+            name: String::from("list"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(inspect_list_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Show fields of the object in the overlay",
+        Command {
+            namespace: String::from("inspect"),
+            name: String::from("show"),
+            args: vec![ArgSpec::required("name", ArgType::String)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(inspect_show_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Hide the inspected object from the overlay",
+        Command {
+            namespace: String::from("inspect"),
+            name: String::from("hide"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(inspect_hide_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set field value of the inspected object",
+        Command {
+            namespace: String::from("inspect"),
+            name: String::from("set"),
+            args: vec![
+                ArgSpec::required("name", ArgType::String),
+                ArgSpec::required("field", ArgType::String),
+                ArgSpec::required("value", ArgType::Any),
+            ],
+            protection: PermissionLevel::Cheat,
+            executor: Box::new(inspect_set_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Save the next rendered frame as PNG, the timestamp is appended to the file name",
+        Command {
+            namespace: String::from("render"),
+            name: String::from("screenshot"),
+            args: vec![ArgSpec::required("path", ArgType::String)],
+            protection: PermissionLevel::Developer,
+            executor: Box::new(screenshot_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay camera zoom",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("zoom"),
+            args: vec![ArgSpec::required("zoom", ArgType::Number)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(zoom_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay camera position",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("pan"),
+            args: vec![
+                ArgSpec::required("x", ArgType::Number),
+                ArgSpec::required("y", ArgType::Number),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(pan_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Enable or disable overlay zoom with mouse wheel",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("mouse_zoom"),
+            args: vec![ArgSpec::required("enabled", ArgType::Bool)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(mouse_zoom_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Anchor the overlay to the screen corner",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("anchor"),
+            args: vec![ArgSpec::required(
+                "corner",
+                ArgType::Enum(OverlayAnchor::NAMES),
+            )],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(anchor_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay distance from the anchored corner",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("margin"),
+            args: vec![
+                ArgSpec::required("x", ArgType::Number),
+                ArgSpec::required("y", ArgType::Number),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(margin_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay scale",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("scale"),
+            args: vec![ArgSpec::required("scale", ArgType::Number)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(scale_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay theme color",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("theme_color"),
+            args: vec![
+                ArgSpec::required("name", ArgType::Enum(Theme::COLOR_NAMES)),
+                ArgSpec::required("color", ArgType::Color),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(theme_color_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay theme font",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("theme_font"),
+            args: vec![
+                ArgSpec::required("font_id", ArgType::Int),
+                ArgSpec::optional(
+                    "size",
+                    ArgType::Number,
+                    CommandArgument::Number(f64::from(render::FONT_SIZE)),
+                ),
+            ],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(theme_font_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Set overlay theme padding",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("theme_padding"),
+            args: vec![ArgSpec::required("padding", ArgType::Number)],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(theme_padding_command),
+        },
+    );
+
+    register_command(
+        debug_state,
+        "Restore the default overlay theme",
+        Command {
+            namespace: String::from("ui"),
+            name: String::from("theme_reset"),
+            args: vec![],
+            protection: PermissionLevel::Normal,
+            executor: Box::new(theme_reset_command),
+        },
+    );
+}
+
+fn help_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let command = arguments[0].as_str()?;
+    let help = help_text(&debug_state.commands.registry, command)?;
+
+    Ok(CommandOutput::Text(help))
+}
+
+fn help_text(registry: &[CommandRegistryEntry], command: &str) -> Result<String, String> {
+    let entries: Vec<&CommandRegistryEntry> = if command.is_empty() {
+        registry.iter().collect()
+    } else {
+        registry
+            .iter()
+            .filter(|entry| format!("{}::{}", entry.namespace, entry.name) == command)
+            .collect()
+    };
+
+    require(
+        !entries.is_empty(),
+        &format!("Command '{}' not found", command),
+    )?;
+
+    let names: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("{}::{} {}", entry.namespace, entry.name, entry.args))
+        .collect();
+
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut help = String::new();
+
+    for (entry, name) in entries.iter().zip(names.iter()) {
+        help.push_str(&format::align_left(name, width));
+        help.push_str("  ");
+        help.push_str(entry.desc);
+        help.push('\n');
+    }
+
+    help.pop();
+    Ok(help)
+}
+
+fn exec_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+    let script =
+        fs::read_to_string(path).map_err(|err| format!("Failed to read '{}': {}", path, err))?;
+
+    let errors: Vec<String> = execute_script(debug_state, &script)
+        .iter()
+        .map(|(line, err)| format!("{}:{}: {}", path, line, err))
+        .collect();
+
+    if errors.is_empty() {
+        Ok(CommandOutput::None)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn if_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let condition = arguments[0].as_str()?;
+    let command = arguments[1].as_str()?;
+
+    if !conditions::evaluate(debug_state, condition)? {
+        return Ok(CommandOutput::None);
+    }
+
+    execute_sequence(debug_state, command).map_err(|err| err.to_string())
+}
+
+fn schedule_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+    repeat: bool,
+) -> Result<CommandOutput, String> {
+    let seconds = arguments[0].as_number()?;
+    let command = arguments[1].as_str()?;
+
+    // Checked before the conversion, which panics on overflow.
+    let max_seconds = scheduler::MAX_SCHEDULE_DELAY.as_secs_f64();
+    require(
+        (0. ..=max_seconds).contains(&seconds),
+        &format!("Seconds should be in 0..{}", max_seconds),
+    )?;
+
+    let delay = Duration::from_secs_f64(seconds);
+    let id = scheduler::schedule(
+        &mut debug_state.scheduler,
+        command,
+        delay,
+        if repeat { Some(delay) } else { None },
+        Instant::now(),
+    )?;
+
+    Ok(CommandOutput::Text(format!("Scheduled command {}", id)))
+}
+
+fn defer_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    schedule_command(debug_state, arguments, false)
+}
+
+fn every_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    schedule_command(debug_state, arguments, true)
+}
+
+fn cancel_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let id = arguments[0].as_number()?;
+    scheduler::cancel(&mut debug_state.scheduler, id as u64)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn env_set_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let value = match &arguments[1] {
+        CommandArgument::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+
+    require(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_'),
+        "Name should consist of letters, digits and '_'",
+    )?;
+
+    set_substitution(debug_state, name, &value);
+    Ok(CommandOutput::None)
+}
+
+fn env_unset_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+
+    match debug_state.commands.substitutions.remove(name) {
+        Some(_) => Ok(CommandOutput::None),
+        None => Err(format!("Substitution '{}' not found", name)),
+    }
+}
+
+fn env_list_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let mut rows: Vec<Vec<String>> = debug_state
+        .commands
+        .substitutions
+        .iter()
+        .map(|(name, value)| vec![name.clone(), value.clone()])
+        .collect();
+    rows.sort();
+
+    Ok(CommandOutput::Table {
+        header: vec![String::from("name"), String::from("value")],
+        rows,
+    })
+}
+
+fn scheduled_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let now = Instant::now();
+
+    let rows = debug_state
+        .scheduler
+        .commands
+        .iter()
+        .map(|scheduled| {
+            vec![
+                scheduled.id.to_string(),
+                format::duration(scheduled.at.saturating_duration_since(now)),
+                scheduled.repeat.map_or_else(String::new, format::duration),
+                scheduled.command.clone(),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("id"),
+            String::from("in"),
+            String::from("every"),
+            String::from("command"),
+        ],
+        rows,
+    })
+}
+
+fn set_snapshot_interval_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let interval = arguments[0].as_number()?;
+    require(
+        interval >= 1.,
+        "Snapshot interval should be at least 1 frame",
+    )?;
+
+    let mut profile_state = debug_state.profile_state();
+    profiler::update_snapshot_interval(&mut profile_state, interval as usize)?;
+
+    Ok(CommandOutput::Text(format!(
+        "Snapshot interval is {} frames",
+        profile_state.snapshot_interval
+    )))
+}
+
+fn configure_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let option = arguments[0].as_str()?;
+    let value = arguments[1].as_number()?;
+    require(value >= 0., "Value should not be negative")?;
+
+    let mut profile_state = debug_state.profile_state();
+    let mut config = profile_state.config;
+
+    *config
+        .option_mut(option)
+        .ok_or_else(|| format!("Unknown option '{}'", option))? = value as usize;
+
+    profiler::configure(&mut profile_state, config)?;
+
+    Ok(CommandOutput::Text(format!(
+        "records_capacity = {}, log_size = {}, state_size = {}",
+        config.records_capacity, config.log_size, config.state_size
+    )))
+}
+
+fn set_spike_threshold_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let ms = arguments[0].as_number()?;
+
+    if !ms.is_finite() || ms <= 0. {
+        return Err(format!("Spike threshold should be positive, found {}", ms));
+    }
+
+    let max_ms = profiler::MAX_SPIKE_THRESHOLD.as_secs_f64() * 1000.;
+
+    if ms > max_ms {
+        return Err(format!(
+            "Spike threshold should be at most {} ms, found {}",
+            max_ms, ms
+        ));
+    }
+
+    let mut profile_state = debug_state.profile_state();
+    profiler::update_spike_threshold(&mut profile_state, Duration::from_secs_f64(ms / 1000.));
+
+    Ok(CommandOutput::None)
+}
+
+fn set_budget_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let ms = arguments[0].as_number()?;
+    let log_offenders = arguments[1].as_bool()?;
+
+    if !ms.is_finite() || ms < 0. {
+        return Err(format!("Frame budget should be positive, found {}", ms));
+    }
+
+    let max_ms = profiler::MAX_BUDGET.as_secs_f64() * 1000.;
+
+    if ms > max_ms {
+        return Err(format!(
+            "Frame budget should be at most {} ms, found {}",
+            max_ms, ms
+        ));
+    }
+
+    let budget = if ms == 0. {
+        None
+    } else {
+        Some(Duration::from_secs_f64(ms / 1000.))
+    };
+
+    let mut profile_state = debug_state.profile_state();
+    profiler::set_frame_budget(&mut profile_state, budget, log_offenders);
+
+    Ok(CommandOutput::None)
+}
+
+fn assert_budget_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let counter = arguments[0].as_str()?;
+    let ms = arguments[1].as_number()?;
+
+    if !ms.is_finite() || ms < 0. {
+        return Err(format!("Counter budget should be positive, found {}", ms));
+    }
+
+    let max_ms = profiler::MAX_BUDGET.as_secs_f64() * 1000.;
+
+    if ms > max_ms {
+        return Err(format!(
+            "Counter budget should be at most {} ms, found {}",
+            max_ms, ms
+        ));
+    }
+
+    let budget = if ms == 0. {
+        None
+    } else {
+        Some(Duration::from_secs_f64(ms / 1000.))
+    };
+
+    let mut profile_state = debug_state.profile_state();
+    profiler::assert_budget(&mut profile_state, counter, budget);
+
+    Ok(CommandOutput::None)
+}
+
+fn budget_violations_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let profile_state = debug_state.profile_state();
+
+    let rows = profile_state
+        .budget_violations
+        .iter()
+        .map(|violation| {
+            vec![
+                violation.snapshot.to_string(),
+                format::duration(violation.taken_at),
+                String::from(violation.counter),
+                format::duration(violation.budget),
+                format::duration(violation.actual),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("snapshot"),
+            String::from("at"),
+            String::from("counter"),
+            String::from("budget"),
+            String::from("actual"),
+        ],
+        rows,
+    })
+}
+
+fn overhead_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let overhead = debug_state.profile_state().last_overhead;
+
+    let rows = [
+        ("step", overhead.step),
+        ("render", overhead.render),
+        ("snapshot", overhead.snapshot),
+        ("lock wait", overhead.lock_wait),
+        ("total", overhead.total()),
+    ]
+    .iter()
+    .map(|(name, elapsed)| vec![String::from(*name), format::duration(*elapsed)])
+    .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![String::from("stage"), String::from("time")],
+        rows,
+    })
+}
+
+fn spikes_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let profile_state = debug_state.profile_state();
+
+    let rows = profile_state
+        .spike_log
+        .iter()
+        .map(|spike| {
+            let slowest = spike
+                .records
+                .iter()
+                .filter(|record| record.parent.is_none())
+                .max_by_key(|record| record.elapsed);
+
+            vec![
+                format::duration(spike.taken_at),
+                format::duration(spike.frame_elapsed),
+                slowest.map_or_else(String::new, |record| String::from(record.name)),
+                slowest.map_or_else(String::new, |record| format::duration(record.elapsed)),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("at"),
+            String::from("frame"),
+            String::from("slowest"),
+            String::from("elapsed"),
+        ],
+        rows,
+    })
+}
+
+fn diff_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let profile_state = debug_state.profile_state();
+    let log_size = profile_state.performance_counter_log.len();
+
+    let snapshot_index = |argument: &CommandArgument| -> Result<usize, String> {
+        let index = argument.as_number()?;
+
+        if index < 0. || index as usize >= log_size {
+            Err(format!(
+                "Snapshot index should be in range 0..{}, found {}",
+                log_size, index
+            ))
+        } else {
+            Ok(index as usize)
+        }
+    };
+
+    let a = snapshot_index(&arguments[0])?;
+    let b = snapshot_index(&arguments[1])?;
+
+    let diff = profiler::diff_snapshots(
+        &profile_state.performance_counter_log[a],
+        &profile_state.performance_counter_log[b],
+    );
+
+    let rows = diff
+        .iter()
+        .map(|record| {
+            vec![
+                String::from(record.name),
+                format::duration(record.elapsed_a),
+                format::duration(record.elapsed_b),
+                signed_duration(record.elapsed_delta()),
+                format!("{:+}", record.hits_b as i64 - record.hits_a as i64),
+                format!("{:+.1}%", record.percent_b - record.percent_a),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("block"),
+            String::from("a"),
+            String::from("b"),
+            String::from("delta"),
+            String::from("hits"),
+            String::from("percent"),
+        ],
+        rows,
+    })
+}
+
+fn signed_duration(nanos: i128) -> String {
+    let sign = if nanos < 0 { "-" } else { "+" };
+    let duration = Duration::from_nanos(nanos.unsigned_abs() as u64);
+
+    format!("{}{}", sign, format::duration(duration))
+}
+
+fn top_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let count = arguments[0].as_number()?;
+    require(count >= 1., "Count should be at least 1")?;
+
+    let profile_state = debug_state.profile_state();
+    let records = profiler::top_records(
+        profiler::last_snapshot(&profile_state),
+        profiler::ProfilerSortKey::Percent,
+        "",
+        None,
+        count as usize,
+    );
+
+    let rows = records
+        .iter()
+        .map(|record| {
+            vec![
+                String::from(record.name),
+                format!("{}:{}", record.file_name, record.line),
+                format!("{:.2}", record.sum_elapsed.as_secs_f64() * 1000.),
+                format::count(record.sum_hits as u64),
+                format!("{:.1}", record.percent),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("name"),
+            String::from("location"),
+            String::from("ms"),
+            String::from("hits"),
+            String::from("%"),
+        ],
+        rows,
+    })
+}
+
+fn category_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let category = arguments[0].as_str()?;
+    let widget = debug_variables::find_profiler_mut(&mut debug_state.variables)
+        .ok_or_else(|| String::from("Profiler counters widget not found"))?;
+
+    widget.category = if category.is_empty() {
+        None
+    } else {
+        Some(String::from(category))
+    };
+
+    Ok(CommandOutput::None)
+}
+
+fn filter_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let filter = arguments[0].as_str()?;
+    let widget = debug_variables::find_profiler_mut(&mut debug_state.variables)
+        .ok_or_else(|| String::from("Profiler counters widget not found"))?;
+
+    widget.filter = String::from(filter);
+
+    Ok(CommandOutput::None)
+}
+
+fn pin_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    profiler_pins::pin(&mut debug_state.pins, name)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn unpin_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    profiler_pins::unpin(&mut debug_state.pins, name)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn set_profiler_enabled(
+    debug_state: &mut DebugState,
+    enabled: bool,
+) -> Result<CommandOutput, String> {
+    let mut profile_state = debug_state.profile_state();
+    profiler::set_enabled(&mut profile_state, enabled);
+
+    Ok(CommandOutput::None)
+}
+
+fn export_trace_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+
+    profiler::export_chrome_trace(&debug_state.profile_state(), path)
+        .map_err(|err| format!("Failed to export trace to '{}': {}", path, err))?;
+
+    Ok(CommandOutput::Text(format!("Trace exported to '{}'", path)))
+}
+
+fn detail_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let detail = arguments[0].as_str()? == "on";
+
+    let mut profile_state = debug_state.profile_state();
+    profiler::set_detail(&mut profile_state, detail);
+
+    Ok(CommandOutput::None)
+}
+
+fn sampling_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    if arguments[0].as_str()? == "off" {
+        sampling::stop();
+        return Ok(CommandOutput::None);
+    }
+
+    let ms = arguments[1].as_number()?;
+    let max_ms = sampling::MAX_SAMPLING_INTERVAL.as_secs_f64() * 1000.;
+    require(
+        ms.is_finite() && ms > 0. && ms <= max_ms,
+        &format!("Sampling interval should be in 0..{} ms", max_ms),
+    )?;
+
+    profiler::reset_samples(&mut debug_state.profile_state());
+    sampling::start(Duration::from_secs_f64(ms / 1000.))?;
+
+    Ok(CommandOutput::None)
+}
+
+fn samples_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let count = arguments[0].as_number()?;
+    require(count >= 1., "Count should be at least 1")?;
+
+    let profile_state = debug_state.profile_state();
+    let sampling = &profile_state.sampling;
+
+    let rows = profiler::top_sampled_blocks(&profile_state, count as usize)
+        .iter()
+        .map(|(thread_id, location, samples)| {
+            let thread_samples = sampling.threads.get(thread_id).copied().unwrap_or(0);
+            let percent = samples.inclusive as f64 / thread_samples.max(1) as f64 * 100.;
+
+            vec![
+                profiler::thread_name(*thread_id),
+                String::from(location.name),
+                samples.inclusive.to_string(),
+                samples.exclusive.to_string(),
+                format::duration(sampling.estimated_time(samples.inclusive)),
+                format!("{:.1}", percent),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("thread"),
+            String::from("name"),
+            String::from("samples"),
+            String::from("self"),
+            String::from("estimated"),
+            String::from("%"),
+        ],
+        rows,
+    })
+}
+
+fn export_detail_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+
+    profiler::export_frame_timelines(&debug_state.profile_state(), path)
+        .map_err(|err| format!("Failed to export frames to '{}': {}", path, err))?;
+
+    Ok(CommandOutput::Text(format!(
+        "Frames exported to '{}'",
+        path
+    )))
+}
+
+fn export_csv_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+
+    profiler::export_csv(&debug_state.profile_state(), path)
+        .map_err(|err| format!("Failed to export CSV to '{}': {}", path, err))?;
+
+    Ok(CommandOutput::Text(format!("CSV exported to '{}'", path)))
+}
+
+fn export_flamegraph_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+
+    profiler::export_flamegraph(&debug_state.profile_state(), path)
+        .map_err(|err| format!("Failed to export flamegraph to '{}': {}", path, err))?;
+
+    Ok(CommandOutput::Text(format!(
+        "Flamegraph exported to '{}'",
+        path
+    )))
+}
+
+fn log_filter_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let level = arguments[0].as_str()?;
+    let filter = level
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("Unknown log level '{}'", level))?;
+
+    logging::set_filter(debug_state, filter);
+
+    Ok(CommandOutput::None)
+}
+
+fn log_targets_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    debug_state.log.targets = arguments
+        .iter()
+        .map(|argument| argument.as_str().map(String::from))
+        .collect::<Result<Vec<String>, String>>()?;
+
+    Ok(CommandOutput::None)
+}
+
+fn vars_set_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+    let variable = debug_variables::find_mut(&mut debug_state.variables, path)
+        .ok_or_else(|| format!("Variable '{}' not found", path))?;
+
+    debug_variables::set_from_argument(variable, &arguments[1])?;
+    debug_variables::notify_changed(&mut debug_state.on_change, variable);
+
+    Ok(CommandOutput::Text(format!(
+        "{} = {}",
+        path,
+        debug_variables::value_string(variable)
+    )))
+}
+
+fn vars_get_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+    let variable = debug_variables::find(&debug_state.variables, path)
+        .ok_or_else(|| format!("Variable '{}' not found", path))?;
+
+    Ok(CommandOutput::Text(debug_variables::value_string(variable)))
+}
+
+fn vars_list_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+    let group = if path.is_empty() {
+        &debug_state.variables
+    } else {
+        match debug_variables::find(&debug_state.variables, path) {
+            Some(DebugVariable::Group(_, group)) => group,
+            Some(_) => return Err(format!("'{}' is not a group", path)),
+            None => return Err(format!("Variable '{}' not found", path)),
+        }
+    };
+
+    let mut rows = Vec::new();
+
+    debug_variables::visit(group, path, &mut |path, variable| {
+        rows.push(vec![
+            String::from(path),
+            String::from(debug_variables::type_name(variable)),
+            debug_variables::value_string(variable),
+        ]);
+    });
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("path"),
+            String::from("type"),
+            String::from("value"),
+        ],
+        rows,
+    })
+}
+
+fn cvar_set_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    cvars::set_from_argument(&mut debug_state.cvars, name, &arguments[1])?;
+
+    Ok(CommandOutput::Text(format!(
+        "{} = {}",
+        name, debug_state.cvars.cvars[name].value
+    )))
+}
+
+fn cvar_get_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let cvar = debug_state
+        .cvars
+        .cvars
+        .get(name)
+        .ok_or_else(|| format!("Cvar '{}' not found", name))?;
+
+    Ok(CommandOutput::Text(cvar.value.to_string()))
+}
+
+fn cvar_reset_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    cvars::reset(&mut debug_state.cvars, name)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn cvar_list_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let filter = arguments[0].as_str()?;
+
+    let rows = debug_state
+        .cvars
+        .cvars
+        .values()
+        .filter(|cvar| cvar.name.contains(filter))
+        .map(|cvar| {
+            let mut flags = Vec::new();
+
+            for (flag, name) in &[
+                (cvars::CvarFlags::ARCHIVE, "archive"),
+                (cvars::CvarFlags::CHEAT, "cheat"),
+                (cvars::CvarFlags::READ_ONLY, "read-only"),
+            ] {
+                if cvar.flags.contains(*flag) {
+                    flags.push(*name);
+                }
+            }
+
+            vec![
+                String::from(cvar.name),
+                String::from(cvar.value.type_name()),
+                cvar.value.to_string(),
+                cvar.default.to_string(),
+                flags.join(", "),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("name"),
+            String::from("type"),
+            String::from("value"),
+            String::from("default"),
+            String::from("flags"),
+        ],
+        rows,
+    })
+}
+
+fn cvar_cheats_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let enabled = arguments[0].as_str()? == "on";
+    cvars::set_cheats(&mut debug_state.cvars, enabled);
+
+    Ok(CommandOutput::None)
+}
+
+fn bind_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let key = arguments[0].as_str()?;
+    let command = arguments[1].as_str()?;
+    key_bindings::bind(&mut debug_state.key_bindings, key, command)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn unbind_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let key = arguments[0].as_str()?;
+    key_bindings::unbind(&mut debug_state.key_bindings, key)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn bind_list_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let rows = debug_state
+        .key_bindings
+        .bindings
+        .iter()
+        .map(|(key, command)| vec![String::from(key.name()), command.clone()])
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![String::from("key"), String::from("command")],
+        rows,
+    })
+}
+
+fn watch_add_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    debug_watch::add(&mut debug_state.watch, name)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn watch_remove_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    debug_watch::remove(&mut debug_state.watch, name)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn watch_list_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let watch = &debug_state.watch;
+    let mut names: Vec<&String> = watch.values.keys().collect();
+
+    for name in watch.watches.iter() {
+        if !watch.values.contains_key(name) {
+            names.push(name);
+        }
+    }
+
+    let rows = names
+        .iter()
+        .map(|name| {
+            vec![
+                String::from(name.as_str()),
+                String::from(debug_watch::value(watch, name).unwrap_or("")),
+                watch.watches.contains(name).to_string(),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![
+            String::from("name"),
+            String::from("value"),
+            String::from("watched"),
+        ],
+        rows,
+    })
+}
+
+fn time_scale_command(
+    _: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let scale = arguments[0].as_number()?;
+    time_control::set_scale(&mut time_control::get_time_control_state(), scale as f32)?;
+
+    Ok(CommandOutput::None)
+}
+
+fn inspect_list_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let rows = debug_state
+        .inspector
+        .objects
+        .iter()
+        .map(|object| {
+            vec![
+                object.name.clone(),
+                object.provider.fields().len().to_string(),
+            ]
+        })
+        .collect();
+
+    Ok(CommandOutput::Table {
+        header: vec![String::from("name"), String::from("fields")],
+        rows,
+    })
+}
+
+fn inspect_show_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let object = inspector::find(&debug_state.inspector, name)?;
+    let rows = inspector::field_values(object)
+        .into_iter()
+        .map(|(field, value)| vec![String::from(field), value])
+        .collect();
+
+    debug_state.inspector.shown = Some(String::from(name));
+
+    Ok(CommandOutput::Table {
+        header: vec![String::from("field"), String::from("value")],
+        rows,
+    })
+}
+
+fn inspect_hide_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    debug_state.inspector.shown = None;
+
+    Ok(CommandOutput::None)
+}
+
+fn inspect_set_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let field = arguments[1].as_str()?;
+    let object = inspector::find_mut(&mut debug_state.inspector, name)?;
+
+    require(
+        object.provider.fields().contains(&field),
+        &format!("Object '{}' has no field '{}'", name, field),
+    )?;
+    object.provider.set(field, &arguments[2])?;
+
+    let value = object
+        .provider
+        .get(field)
+        .map_or_else(String::new, |value| value.to_string());
+
+    Ok(CommandOutput::Text(format!(
+        "{}.{} = {}",
+        name, field, value
+    )))
+}
+
+fn screenshot_command(
+    _: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let path = arguments[0].as_str()?;
+    let path = screenshot::request(path)?;
+
+    Ok(CommandOutput::Text(format!(
+        "Screenshot will be saved to '{}'",
+        path
+    )))
+}
+
+fn zoom_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let zoom = arguments[0].as_number()?;
+
+    require(zoom > 0., "Zoom should be positive")?;
+    debug_state.camera.set_zoom(zoom as f32);
+
+    Ok(CommandOutput::None)
+}
+
+fn pan_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let x = arguments[0].as_number()?;
+    let y = arguments[1].as_number()?;
+
+    debug_state.camera.position = Vec2f::new(x as f32, y as f32);
+
+    Ok(CommandOutput::None)
+}
+
+fn mouse_zoom_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let enabled = arguments[0].as_bool()?;
+
+    debug_state.camera.mouse_wheel_zoom = enabled;
+
+    Ok(CommandOutput::None)
+}
+
+fn anchor_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let anchor =
+        OverlayAnchor::from_name(name).ok_or_else(|| format!("Unknown corner '{}'", name))?;
+
+    debug_state.layout.anchor = anchor;
+
+    Ok(CommandOutput::None)
+}
+
+fn margin_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let x = arguments[0].as_number()?;
+    let y = arguments[1].as_number()?;
+
+    require(x >= 0. && y >= 0., "Margin should not be negative")?;
+    debug_state.layout.margin = Vec2f::new(x as f32, y as f32);
+
+    Ok(CommandOutput::None)
+}
+
+fn scale_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let scale = arguments[0].as_number()?;
+
+    require(scale > 0., "Scale should be positive")?;
+    debug_state.layout.set_scale(scale as f32);
+
+    Ok(CommandOutput::None)
+}
+
+fn theme_color_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let name = arguments[0].as_str()?;
+    let (r, g, b, a) = arguments[1].as_color()?;
+    let color = debug_state
+        .theme
+        .color_mut(name)
+        .ok_or_else(|| format!("Unknown theme color '{}'", name))?;
+
+    *color = Vec4f::new(
+        r as f32 / 255.,
+        g as f32 / 255.,
+        b as f32 / 255.,
+        a as f32 / 255.,
+    );
+
+    Ok(CommandOutput::None)
+}
+
+fn theme_font_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let font_id = arguments[0].as_number()?;
+    let size = arguments[1].as_number()?;
+
+    require(font_id >= 0., "Font id should not be negative")?;
+    require(size > 0., "Font size should be positive")?;
+    debug_state.theme.font_id = font_id as u32;
+    debug_state.theme.set_font_size(size as f32);
+
+    Ok(CommandOutput::None)
+}
+
+fn theme_padding_command(
+    debug_state: &mut DebugState,
+    arguments: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    let padding = arguments[0].as_number()?;
+
+    require(padding >= 0., "Padding should not be negative")?;
+    debug_state.theme.padding = padding as f32;
+
+    Ok(CommandOutput::None)
+}
 
-    let mut profile_state = profiler::get_profile_state();
-    profiler::update_snapshot_interval(&mut profile_state, interval as usize);
+fn theme_reset_command(
+    debug_state: &mut DebugState,
+    _: &[CommandArgument],
+) -> Result<CommandOutput, String> {
+    debug_state.theme = Theme::default();
 
-    Ok(())
+    Ok(CommandOutput::None)
 }
@@ -0,0 +1,312 @@
+//! Conditions of `debug::if`, e.g. `profiler/enabled && r_draw_distance > 500`.
+//!
+//! Operands are cvar names or debug variable paths without spaces,
+//! compared with `==`, `!=`, `<`, `<=`, `>`, `>=` to numbers, `true`/`false`
+//! or strings in single quotes, and combined with `!`, `&&`, `||` and parentheses.
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::cvars::CvarValue;
+use crate::debug_variables::{self, DebugVariable};
+use crate::state::DebugState;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Str(String),
+    Not,
+    And,
+    Or,
+    Compare(&'static str),
+    LParen,
+    RParen,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+const COMPARE_OPS: &[&str] = &["==", "!=", "<=", ">=", "<", ">"];
+
+/// Deeper `!` and parentheses are rejected, so long conditions can't overflow the stack.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+pub fn evaluate(debug_state: &DebugState, expression: &str) -> Result<bool, String> {
+    let mut parser = Parser {
+        debug_state,
+        tokens: tokenize(expression)?.into_iter().peekable(),
+        depth: 0,
+    };
+
+    let result = parser.or()?;
+
+    match parser.tokens.next() {
+        Some(token) => Err(format!("Unexpected {:?} in the condition", token)),
+        None => Ok(result),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+
+    while let Some(ch) = rest.chars().next() {
+        if let Some(op) = COMPARE_OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Compare(op));
+            rest = &rest[op.len()..];
+        } else if rest.starts_with("&&") || rest.starts_with("||") {
+            tokens.push(if ch == '&' { Token::And } else { Token::Or });
+            rest = &rest[2..];
+        } else if ch == '!' || ch == '(' || ch == ')' {
+            tokens.push(match ch {
+                '!' => Token::Not,
+                '(' => Token::LParen,
+                _ => Token::RParen,
+            });
+            rest = &rest[1..];
+        } else if ch == '\'' {
+            let end = rest[1..]
+                .find('\'')
+                .ok_or_else(|| String::from("Unterminated string in the condition"))?;
+            tokens.push(Token::Str(String::from(&rest[1..end + 1])));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|ch: char| ch.is_whitespace() || "!=<>&|()'".contains(ch))
+                .unwrap_or(rest.len());
+
+            if end == 0 {
+                return Err(format!("Unexpected '{}' in the condition", ch));
+            }
+
+            tokens.push(Token::Name(String::from(&rest[..end])));
+            rest = &rest[end..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    debug_state: &'a DebugState,
+    tokens: Peekable<IntoIter<Token>>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Skips the next token if it's the `expected` one.
+    fn accept(&mut self, expected: &Token) -> bool {
+        if self.tokens.peek() == Some(expected) {
+            self.tokens.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+
+        while self.accept(&Token::Or) {
+            // Both sides are evaluated, so errors are reported regardless of the values.
+            let right = self.and()?;
+            result = result || right;
+        }
+
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.unary()?;
+
+        while self.accept(&Token::And) {
+            let right = self.unary()?;
+            result = result && right;
+        }
+
+        Ok(result)
+    }
+
+    fn unary(&mut self) -> Result<bool, String> {
+        match self.tokens.next() {
+            Some(Token::Not) => Ok(!self.nested(Self::unary)?),
+            Some(Token::LParen) => {
+                let result = self.nested(Self::or)?;
+
+                match self.tokens.next() {
+                    Some(Token::RParen) => Ok(result),
+                    _ => Err(String::from("Expected ')' in the condition")),
+                }
+            }
+            Some(Token::Name(name)) => self.comparison(&name),
+            Some(token) => Err(format!("Unexpected {:?} in the condition", token)),
+            None => Err(String::from("Unexpected end of the condition")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<bool, String>) -> Result<bool, String> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Condition is nested deeper than {}",
+                MAX_NESTING_DEPTH
+            ));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn comparison(&mut self, name: &str) -> Result<bool, String> {
+        let value = lookup(self.debug_state, name)?;
+
+        let op = match self.tokens.peek() {
+            Some(Token::Compare(op)) => *op,
+            _ => {
+                return match value {
+                    Value::Bool(value) => Ok(value),
+                    _ => Err(format!("'{}' is not bool, compare it with a value", name)),
+                }
+            }
+        };
+
+        self.tokens.next();
+
+        let literal = match self.tokens.next() {
+            Some(Token::Name(literal)) => parse_literal(&literal),
+            Some(Token::Str(literal)) => Value::String(literal),
+            _ => return Err(format!("Expected value after '{}'", op)),
+        };
+
+        compare(&value, op, &literal)
+            .ok_or_else(|| format!("Can't compare '{}' with {:?} using '{}'", name, literal, op))
+    }
+}
+
+fn parse_literal(literal: &str) -> Value {
+    match literal {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match literal.parse() {
+            Ok(number) => Value::Number(number),
+            Err(_) => Value::String(String::from(literal)),
+        },
+    }
+}
+
+/// Cvars go first, then the debug variables.
+fn lookup(debug_state: &DebugState, name: &str) -> Result<Value, String> {
+    if let Some(cvar) = debug_state.cvars.cvars.get(name) {
+        return Ok(match &cvar.value {
+            CvarValue::Bool(value) => Value::Bool(*value),
+            CvarValue::Int(value) => Value::Number(*value as f64),
+            CvarValue::Float(value) => Value::Number(*value),
+            CvarValue::String(value) => Value::String(value.clone()),
+        });
+    }
+
+    match debug_variables::find(&debug_state.variables, name) {
+        Some(DebugVariable::Bool(_, variable)) => Ok(Value::Bool(variable.value)),
+        Some(DebugVariable::Float(_, variable)) => Ok(Value::Number(variable.value as f64)),
+        Some(DebugVariable::Int(_, variable)) => Ok(Value::Number(variable.value as f64)),
+        Some(DebugVariable::String(_, variable)) => Ok(Value::String(variable.value.clone())),
+        Some(DebugVariable::Enum(_, variable)) => {
+            Ok(Value::String(String::from(variable.value().unwrap_or(""))))
+        }
+        Some(variable) => Err(format!(
+            "'{}' is {}, only bool, number and string values can be checked",
+            name,
+            debug_variables::type_name(variable)
+        )),
+        None => Err(format!("'{}' is neither a cvar nor a variable", name)),
+    }
+}
+
+fn compare(value: &Value, op: &str, literal: &Value) -> Option<bool> {
+    match (value, literal) {
+        (Value::Number(a), Value::Number(b)) => Some(match op {
+            "==" => a == b,
+            "!=" => a != b,
+            "<" => a < b,
+            "<=" => a <= b,
+            ">" => a > b,
+            _ => a >= b,
+        }),
+        (a, b) if std::mem::discriminant(a) == std::mem::discriminant(b) => match op {
+            "==" => Some(a == b),
+            "!=" => Some(a != b),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conditions;
+    use crate::cvars::{self, CvarFlags, CvarValue};
+    use crate::debug_variables;
+    use crate::state::DebugState;
+
+    #[test]
+    fn evaluate() {
+        let mut debug_state = DebugState::default();
+
+        cvars::register_in(
+            &mut debug_state.cvars,
+            "r_draw_distance",
+            CvarValue::Float(1000.),
+            CvarFlags::NONE,
+        );
+        cvars::register_in(
+            &mut debug_state.cvars,
+            "map",
+            CvarValue::from("e1m1"),
+            CvarFlags::NONE,
+        );
+        debug_variables::register_in(
+            &mut debug_state.variables,
+            "profiler/enabled",
+            |group, id, name| group.add_bool(id, name, true),
+        );
+
+        let evaluate = |expression| conditions::evaluate(&debug_state, expression);
+
+        assert_eq!(Ok(true), evaluate("profiler/enabled"));
+        assert_eq!(Ok(false), evaluate("!profiler/enabled"));
+        assert_eq!(
+            Ok(true),
+            evaluate("r_draw_distance >= 1000 && map == 'e1m1'")
+        );
+        assert_eq!(
+            Ok(true),
+            evaluate("!(r_draw_distance < 500 || map != e1m1)")
+        );
+        assert_eq!(Ok(false), evaluate("profiler/enabled==false"));
+
+        assert!(evaluate("r_draw_distance").is_err());
+        assert!(evaluate("map > 3").is_err());
+        assert!(evaluate("fov > 3").is_err());
+        assert!(evaluate("profiler/enabled &&").is_err());
+        assert!(evaluate("(profiler/enabled").is_err());
+        assert!(evaluate("map == 'e1m1").is_err());
+
+        let nested = |depth| format!("{}profiler/enabled{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(
+            Ok(true),
+            conditions::evaluate(&debug_state, &nested(conditions::MAX_NESTING_DEPTH))
+        );
+        assert!(
+            conditions::evaluate(&debug_state, &nested(conditions::MAX_NESTING_DEPTH + 1)).is_err()
+        );
+        assert!(conditions::evaluate(&debug_state, &"!".repeat(100_000)).is_err());
+    }
+}
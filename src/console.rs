@@ -0,0 +1,182 @@
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::collections::RingBuffer;
+use crate::commands::{self, CommandError, Span};
+use crate::input::{InputEvent, Key};
+use crate::panel::PanelState;
+use crate::render::DrawList;
+use crate::state::DebugState;
+
+pub const CONSOLE_SCROLLBACK_CAPACITY: usize = 200;
+pub const CONSOLE_TOGGLE_KEY: Key = Key::F1;
+
+const CONSOLE_LINE_HEIGHT: f32 = 18.;
+
+pub struct ConsoleState {
+    pub visible: bool,
+    pub input: String,
+    pub scrollback: RingBuffer<String>,
+    pub history_cursor: Option<usize>,
+    pub panel: PanelState,
+}
+
+impl Default for ConsoleState {
+    fn default() -> Self {
+        ConsoleState {
+            visible: false,
+            input: String::new(),
+            scrollback: RingBuffer::new(CONSOLE_SCROLLBACK_CAPACITY),
+            history_cursor: None,
+            panel: PanelState::following_end(),
+        }
+    }
+}
+
+impl ConsoleState {
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            self.scrollback.push(String::from(line));
+        }
+    }
+}
+
+/// Handles console input, returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    if let InputEvent::KeyDown(key) = event {
+        if *key == CONSOLE_TOGGLE_KEY {
+            debug_state.console.visible = !debug_state.console.visible;
+            return true;
+        }
+    }
+
+    if !debug_state.console.visible {
+        return false;
+    }
+
+    match event {
+        InputEvent::Char(ch) if !ch.is_control() => {
+            debug_state.console.input.push(*ch);
+        }
+        InputEvent::KeyDown(Key::Backspace) => {
+            debug_state.console.input.pop();
+        }
+        InputEvent::KeyDown(Key::Escape) => {
+            debug_state.console.visible = false;
+        }
+        InputEvent::KeyDown(Key::Enter) => submit(debug_state),
+        InputEvent::KeyDown(Key::Up) => recall_history(debug_state, true),
+        InputEvent::KeyDown(Key::Down) => recall_history(debug_state, false),
+        InputEvent::KeyDown(Key::Tab) => {
+            let completions =
+                commands::complete_in(&debug_state.commands, &debug_state.console.input);
+
+            if let Some(completion) = completions.into_iter().next() {
+                debug_state.console.input = completion;
+            }
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn submit(debug_state: &mut DebugState) {
+    let input = std::mem::take(&mut debug_state.console.input);
+    debug_state.console.history_cursor = None;
+
+    if input.trim().is_empty() {
+        return;
+    }
+
+    debug_state.console.print(&format!("> {}", input));
+
+    match commands::execute(debug_state, &input) {
+        Ok(output) => {
+            let text = output.to_string();
+            debug_state.console.print(&text);
+        }
+        Err(err) => {
+            if let CommandError::ParseError { span, .. } = &err {
+                let underline = underline(&input, span);
+                debug_state.console.print(&underline);
+            }
+
+            debug_state.console.print(&format!("error: {}", err));
+        }
+    }
+}
+
+/// Marks the `span` of the trimmed `input` with `^` under the `> input` line.
+fn underline(input: &str, span: &Span) -> String {
+    let command = input.trim();
+    let indent = input.len() - input.trim_start().len();
+    let start = span.start.min(command.len());
+    let end = span.end.min(command.len()).max(start);
+
+    let offset = input[..indent].chars().count() + command[..start].chars().count();
+    let width = command[start..end].chars().count().max(1);
+
+    format!("  {}{}", " ".repeat(offset), "^".repeat(width))
+}
+
+fn recall_history(debug_state: &mut DebugState, backward: bool) {
+    let cursor = debug_state.console.history_cursor;
+    let cursor = if backward {
+        commands::history_prev(&debug_state.commands, cursor)
+    } else {
+        commands::history_next(&debug_state.commands, cursor)
+    };
+
+    debug_state.console.history_cursor = cursor;
+    debug_state.console.input = match cursor {
+        Some(cursor) => debug_state.commands.history[cursor].clone(),
+        None => String::new(),
+    };
+}
+
+/// Renders the input line at the bottom of the area and the scrollback above it,
+/// `position` is the top left corner of the area in screen space.
+pub fn update(
+    debug_state: &mut DebugState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+    size: Vec2f,
+) {
+    let padding = debug_state.theme.padding;
+    let console = &mut debug_state.console;
+
+    if !console.visible {
+        return;
+    }
+
+    let bottom = viewport_size.y - position.y - size.y;
+
+    draw_list.text(
+        camera,
+        Vec2f::new(position.x + padding, bottom + padding),
+        format!("> {}_", console.input),
+    );
+
+    // Scrollback sits right above the input line, older lines are scrolled to.
+    let content_height = console.scrollback.len() as f32 * CONSOLE_LINE_HEIGHT;
+    let lines_height = content_height.min((size.y - padding * 2. - CONSOLE_LINE_HEIGHT).max(0.));
+    let lines_bottom = bottom + padding + CONSOLE_LINE_HEIGHT;
+
+    let content_top = console.panel.begin(
+        draw_list,
+        viewport_size,
+        Vec2f::new(position.x, viewport_size.y - lines_bottom - lines_height),
+        Vec2f::new(size.x, lines_height),
+        content_height,
+    );
+    let mut y = viewport_size.y - content_top;
+
+    for line in console.scrollback.iter() {
+        y -= CONSOLE_LINE_HEIGHT;
+        draw_list.text(camera, Vec2f::new(position.x + padding, y), line.clone());
+    }
+
+    console.panel.end(draw_list, camera, viewport_size);
+}
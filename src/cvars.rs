@@ -0,0 +1,530 @@
+//! Console variables, named typed values set from the console with `cvar::set`,
+//! e.g. `cvars::register("r_draw_distance", 1000.0, CvarFlags::ARCHIVE)`.
+//!
+//! Unlike the debug menu variables, cvars have no widgets and can be protected:
+//! `CvarFlags::CHEAT` cvars can be changed only while cheats are enabled
+//! and `CvarFlags::READ_ONLY` cvars can be changed only from the code.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::BitOr;
+use std::sync::{Mutex, PoisonError};
+
+use crate::commands::CommandArgument;
+use crate::format;
+use crate::json::Json;
+use crate::state::{read_debug_state, write_debug_state};
+
+/// Values of the cvars with `CvarFlags::ARCHIVE`.
+pub const CVARS_PATH: &str = "cvars.cfg";
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct CvarFlags(u32);
+
+impl CvarFlags {
+    pub const NONE: CvarFlags = CvarFlags(0);
+    /// Value is saved to `CVARS_PATH` and restored on the next run.
+    pub const ARCHIVE: CvarFlags = CvarFlags(1);
+    /// Can be changed from the console only while cheats are enabled,
+    /// reset to the default when cheats get disabled.
+    pub const CHEAT: CvarFlags = CvarFlags(1 << 1);
+    /// Can't be changed from the console.
+    pub const READ_ONLY: CvarFlags = CvarFlags(1 << 2);
+
+    pub fn contains(self, flags: CvarFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl BitOr for CvarFlags {
+    type Output = CvarFlags;
+
+    fn bitor(self, other: CvarFlags) -> CvarFlags {
+        CvarFlags(self.0 | other.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl CvarValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CvarValue::Bool(_) => "bool",
+            CvarValue::Int(_) => "int",
+            CvarValue::Float(_) => "float",
+            CvarValue::String(_) => "string",
+        }
+    }
+
+    /// Converts `value` to the type of `self`, integers and floats are interchangeable
+    /// as long as the value is whole.
+    fn coerce(&self, value: CvarValue) -> Result<CvarValue, String> {
+        match (self, value) {
+            (CvarValue::Bool(_), CvarValue::Bool(value)) => Ok(CvarValue::Bool(value)),
+            (CvarValue::Int(_), CvarValue::Int(value)) => Ok(CvarValue::Int(value)),
+            (CvarValue::Int(_), CvarValue::Float(value)) if value.fract() == 0. => {
+                Ok(CvarValue::Int(value as i64))
+            }
+            (CvarValue::Float(_), CvarValue::Float(value)) if value.is_finite() => {
+                Ok(CvarValue::Float(value))
+            }
+            (CvarValue::Float(_), CvarValue::Int(value)) => Ok(CvarValue::Float(value as f64)),
+            (CvarValue::String(_), CvarValue::String(value)) => Ok(CvarValue::String(value)),
+            (_, value) => Err(format!(
+                "value should be {}, found {}",
+                self.type_name(),
+                value
+            )),
+        }
+    }
+
+    fn from_argument(argument: &CommandArgument) -> Result<CvarValue, String> {
+        match argument {
+            CommandArgument::Bool(value) => Ok(CvarValue::Bool(*value)),
+            CommandArgument::Number(value) => Ok(CvarValue::Float(*value)),
+            CommandArgument::String(value) => Ok(CvarValue::String(value.clone())),
+            _ => Err(String::from("value should be bool, number or string")),
+        }
+    }
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CvarValue::Bool(value) => write!(f, "{}", value),
+            CvarValue::Int(value) => write!(f, "{}", value),
+            CvarValue::Float(value) => write!(f, "{:?}", value),
+            CvarValue::String(value) => write!(f, "{}", format::json_string(value)),
+        }
+    }
+}
+
+impl From<bool> for CvarValue {
+    fn from(value: bool) -> Self {
+        CvarValue::Bool(value)
+    }
+}
+
+impl From<i32> for CvarValue {
+    fn from(value: i32) -> Self {
+        CvarValue::Int(value as i64)
+    }
+}
+
+impl From<i64> for CvarValue {
+    fn from(value: i64) -> Self {
+        CvarValue::Int(value)
+    }
+}
+
+impl From<f32> for CvarValue {
+    fn from(value: f32) -> Self {
+        CvarValue::Float(value as f64)
+    }
+}
+
+impl From<f64> for CvarValue {
+    fn from(value: f64) -> Self {
+        CvarValue::Float(value)
+    }
+}
+
+impl From<&str> for CvarValue {
+    fn from(value: &str) -> Self {
+        CvarValue::String(String::from(value))
+    }
+}
+
+impl From<String> for CvarValue {
+    fn from(value: String) -> Self {
+        CvarValue::String(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cvar {
+    pub name: &'static str,
+    pub value: CvarValue,
+    pub default: CvarValue,
+    pub flags: CvarFlags,
+}
+
+/// Called with the cvar after its value is changed.
+/// The debug state is locked during the call, so `get_*` functions can't be used inside,
+/// read the value from the passed cvar instead.
+/// Callbacks are kept in a `Mutex`, so they don't have to be `Sync`.
+pub type OnCvarChange = Box<dyn FnMut(&Cvar) + Send>;
+
+#[derive(Default)]
+pub struct CvarsState {
+    pub cvars: BTreeMap<&'static str, Cvar>,
+    /// Allows changing `CvarFlags::CHEAT` cvars from the console.
+    pub cheats: bool,
+    /// Values restored from disk, applied to the cvars when they're registered.
+    pub saved: HashMap<String, CvarValue>,
+    pub on_change: HashMap<&'static str, Mutex<OnCvarChange>>,
+}
+
+/// Registers the cvar with the default `value`, already registered cvars are kept untouched.
+pub fn register<V: Into<CvarValue>>(name: &'static str, value: V, flags: CvarFlags) {
+    register_in(&mut write_debug_state().cvars, name, value.into(), flags);
+}
+
+pub(crate) fn register_in(
+    cvars: &mut CvarsState,
+    name: &'static str,
+    value: CvarValue,
+    flags: CvarFlags,
+) {
+    if cvars.cvars.contains_key(name) {
+        return;
+    }
+
+    let mut cvar = Cvar {
+        name,
+        value: value.clone(),
+        default: value,
+        flags,
+    };
+
+    if let Some(saved) = cvars.saved.get(name) {
+        match cvar.default.coerce(saved.clone()) {
+            Ok(value) if flags.contains(CvarFlags::ARCHIVE) => cvar.value = value,
+            Ok(_) => {}
+            Err(err) => log::warn!("Saved value of '{}' is ignored: {}", name, err),
+        }
+    }
+
+    cvars.cvars.insert(name, cvar);
+}
+
+/// Sets the change callback of the cvar, e.g. `on_change("r_vsync", |cvar| ...)`.
+pub fn on_change<F>(name: &'static str, callback: F)
+where
+    F: FnMut(&Cvar) + Send + 'static,
+{
+    write_debug_state()
+        .cvars
+        .on_change
+        .insert(name, Mutex::new(Box::new(callback)));
+}
+
+/// Sets the value from the code, the protection flags are ignored.
+pub fn set<V: Into<CvarValue>>(name: &str, value: V) -> Result<(), String> {
+    set_value(&mut write_debug_state().cvars, name, value.into())
+}
+
+fn set_value(cvars: &mut CvarsState, name: &str, value: CvarValue) -> Result<(), String> {
+    let cvar = cvars
+        .cvars
+        .get_mut(name)
+        .ok_or_else(|| format!("Cvar '{}' not found", name))?;
+    let value = cvar
+        .value
+        .coerce(value)
+        .map_err(|err| format!("Bad value of '{}': {}", name, err))?;
+
+    if cvar.value == value {
+        return Ok(());
+    }
+
+    cvar.value = value;
+
+    if let Some(callback) = cvars.on_change.get_mut(cvar.name) {
+        let callback = callback.get_mut().unwrap_or_else(PoisonError::into_inner);
+        callback(cvar);
+    }
+
+    Ok(())
+}
+
+/// Sets the value from the console, fails if the cvar is protected.
+pub fn set_from_argument(
+    cvars: &mut CvarsState,
+    name: &str,
+    argument: &CommandArgument,
+) -> Result<(), String> {
+    let flags = cvars
+        .cvars
+        .get(name)
+        .ok_or_else(|| format!("Cvar '{}' not found", name))?
+        .flags;
+
+    if flags.contains(CvarFlags::READ_ONLY) {
+        return Err(format!("Cvar '{}' is read-only", name));
+    }
+
+    if flags.contains(CvarFlags::CHEAT) && !cvars.cheats {
+        return Err(format!(
+            "Cvar '{}' is cheat protected, enable cheats with cvar::cheats on",
+            name
+        ));
+    }
+
+    set_value(cvars, name, CvarValue::from_argument(argument)?)
+}
+
+pub fn reset(cvars: &mut CvarsState, name: &str) -> Result<(), String> {
+    let default = cvars
+        .cvars
+        .get(name)
+        .ok_or_else(|| format!("Cvar '{}' not found", name))?
+        .default
+        .clone();
+
+    set_value(cvars, name, default)
+}
+
+/// Disabling cheats resets the cheat protected cvars to their defaults.
+pub fn set_cheats(cvars: &mut CvarsState, enabled: bool) {
+    cvars.cheats = enabled;
+
+    if enabled {
+        return;
+    }
+
+    let names: Vec<&'static str> = cvars
+        .cvars
+        .values()
+        .filter(|cvar| cvar.flags.contains(CvarFlags::CHEAT))
+        .map(|cvar| cvar.name)
+        .collect();
+
+    for name in names {
+        // The default has the type of the cvar, so the reset can't fail.
+        let _ = reset(cvars, name);
+    }
+}
+
+pub fn get(name: &str) -> Option<CvarValue> {
+    let debug_state = read_debug_state();
+    debug_state
+        .cvars
+        .cvars
+        .get(name)
+        .map(|cvar| cvar.value.clone())
+}
+
+pub fn get_bool(name: &str) -> Option<bool> {
+    match get(name)? {
+        CvarValue::Bool(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_int(name: &str) -> Option<i64> {
+    match get(name)? {
+        CvarValue::Int(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_float(name: &str) -> Option<f64> {
+    match get(name)? {
+        CvarValue::Float(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_string(name: &str) -> Option<String> {
+    match get(name)? {
+        CvarValue::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Writes the archived cvars to the `path`, a `name value` line per cvar,
+/// saved values of the cvars not registered in this run are kept.
+pub fn save(cvars: &CvarsState, path: &str) -> io::Result<()> {
+    fs::write(path, serialize(cvars))
+}
+
+fn serialize(cvars: &CvarsState) -> String {
+    let mut values: BTreeMap<&str, &CvarValue> = cvars
+        .saved
+        .iter()
+        .map(|(name, value)| (name.as_str(), value))
+        .collect();
+
+    for cvar in cvars.cvars.values() {
+        if cvar.flags.contains(CvarFlags::ARCHIVE) {
+            values.insert(cvar.name, &cvar.value);
+        }
+    }
+
+    values
+        .iter()
+        .map(|(name, value)| format!("{} {}\n", name, value))
+        .collect()
+}
+
+/// Reads the values from the `path` and applies them to the registered archived cvars,
+/// the rest is applied when the cvars get registered.
+pub fn load(cvars: &mut CvarsState, path: &str) -> io::Result<()> {
+    let content = fs::read_to_string(path)?;
+    let values = parse(&content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    for (name, value) in values.iter() {
+        let archived = cvars
+            .cvars
+            .get(name.as_str())
+            .map_or(false, |cvar| cvar.flags.contains(CvarFlags::ARCHIVE));
+
+        if archived {
+            if let Err(err) = set_value(cvars, name, value.clone()) {
+                log::warn!("Saved value of '{}' is ignored: {}", name, err);
+            }
+        }
+    }
+
+    cvars.saved = values;
+    Ok(())
+}
+
+/// Values are JSON literals, so strings are quoted and escaped.
+fn parse(content: &str) -> Result<HashMap<String, CvarValue>, String> {
+    let mut values = HashMap::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let bad_line = || format!("bad line {}: {}", i + 1, line);
+        let separator = line.find(' ').ok_or_else(bad_line)?;
+        let (name, value) = (&line[..separator], line[separator..].trim());
+
+        // Floats are written with the fraction, e.g. `1000.0`.
+        if let Ok(value) = value.parse::<i64>() {
+            values.insert(String::from(name), CvarValue::Int(value));
+            continue;
+        }
+
+        let value = match Json::parse(value).map_err(|_| bad_line())? {
+            Json::Bool(value) => CvarValue::Bool(value),
+            Json::Number(value) => CvarValue::Float(value),
+            Json::String(value) => CvarValue::String(value),
+            _ => return Err(bad_line()),
+        };
+
+        values.insert(String::from(name), value);
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::commands::CommandArgument;
+    use crate::cvars::{self, Cvar, CvarFlags, CvarValue, CvarsState};
+
+    #[test]
+    fn set_protected() {
+        let mut cvars = CvarsState::default();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let callback_changes = changes.clone();
+
+        cvars::register_in(
+            &mut cvars,
+            "r_draw_distance",
+            CvarValue::Float(1000.),
+            CvarFlags::NONE,
+        );
+        cvars::register_in(&mut cvars, "god", CvarValue::Bool(false), CvarFlags::CHEAT);
+        cvars::register_in(
+            &mut cvars,
+            "version",
+            CvarValue::Int(3),
+            CvarFlags::READ_ONLY,
+        );
+        cvars.on_change.insert(
+            "r_draw_distance",
+            Mutex::new(Box::new(move |cvar: &Cvar| {
+                callback_changes.lock().unwrap().push(cvar.value.clone())
+            })),
+        );
+
+        let set = |cvars: &mut CvarsState, name, argument| {
+            cvars::set_from_argument(cvars, name, &argument)
+        };
+
+        assert_eq!(
+            Ok(()),
+            set(&mut cvars, "r_draw_distance", CommandArgument::Number(500.))
+        );
+        assert_eq!(
+            Ok(()),
+            set(&mut cvars, "r_draw_distance", CommandArgument::Number(500.))
+        );
+        assert!(set(&mut cvars, "r_draw_distance", CommandArgument::Bool(true)).is_err());
+        assert!(set(&mut cvars, "version", CommandArgument::Number(4.)).is_err());
+        assert!(set(&mut cvars, "god", CommandArgument::Bool(true)).is_err());
+        assert!(set(&mut cvars, "fov", CommandArgument::Number(90.)).is_err());
+
+        cvars::set_cheats(&mut cvars, true);
+        assert_eq!(Ok(()), set(&mut cvars, "god", CommandArgument::Bool(true)));
+
+        cvars::set_cheats(&mut cvars, false);
+        assert_eq!(CvarValue::Bool(false), cvars.cvars["god"].value);
+        assert_eq!(vec![CvarValue::Float(500.)], *changes.lock().unwrap());
+    }
+
+    #[test]
+    fn archive() {
+        let mut cvars = CvarsState::default();
+
+        cvars::register_in(
+            &mut cvars,
+            "name",
+            CvarValue::from("player"),
+            CvarFlags::ARCHIVE,
+        );
+        cvars::register_in(&mut cvars, "fov", CvarValue::Int(90), CvarFlags::ARCHIVE);
+        cvars::register_in(&mut cvars, "god", CvarValue::Bool(false), CvarFlags::CHEAT);
+        cvars.saved = cvars::parse("volume 0.5\n").unwrap();
+        cvars.cvars.get_mut("name").unwrap().value = CvarValue::from("say \"hi\"");
+
+        let content = cvars::serialize(&cvars);
+        assert_eq!("fov 90\nname \"say \\\"hi\\\"\"\nvolume 0.5\n", content);
+
+        let mut restored = CvarsState {
+            saved: cvars::parse(&content).unwrap(),
+            ..Default::default()
+        };
+        cvars::register_in(
+            &mut restored,
+            "name",
+            CvarValue::from("player"),
+            CvarFlags::ARCHIVE,
+        );
+        cvars::register_in(
+            &mut restored,
+            "fov",
+            CvarValue::Float(60.),
+            CvarFlags::ARCHIVE,
+        );
+        cvars::register_in(
+            &mut restored,
+            "volume",
+            CvarValue::Float(1.),
+            CvarFlags::NONE,
+        );
+
+        assert_eq!(CvarValue::from("say \"hi\""), restored.cvars["name"].value);
+        assert_eq!(CvarValue::Float(90.), restored.cvars["fov"].value);
+        assert_eq!(CvarValue::Float(1.), restored.cvars["volume"].value);
+        assert!(cvars::parse("fov").is_err());
+    }
+}
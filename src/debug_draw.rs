@@ -0,0 +1,251 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use vm_math::{CameraMatrices, Vec2f, Vec4f};
+
+use crate::render::DrawList;
+use crate::state;
+
+pub const DEBUG_DRAW_LINE_WIDTH: f32 = 1.;
+pub const DEBUG_DRAW_CIRCLE_SEGMENTS: usize = 32;
+
+lazy_static! {
+    static ref DEBUG_DRAW_STATE: Mutex<DebugDrawState> = Mutex::new(DebugDrawState::default());
+}
+
+/// World space shape, outlines are drawn for rects and circles.
+#[derive(Clone)]
+pub enum Shape {
+    Line {
+        a: Vec2f,
+        b: Vec2f,
+        color: Vec4f,
+    },
+    Rect {
+        position: Vec2f,
+        size: Vec2f,
+        color: Vec4f,
+    },
+    Circle {
+        center: Vec2f,
+        radius: f32,
+        color: Vec4f,
+    },
+    Text {
+        position: Vec2f,
+        text: String,
+    },
+}
+
+/// Shape drawn every frame until it expires.
+#[derive(Clone)]
+pub struct PersistentShape {
+    pub shape: Shape,
+    pub expires_at: Instant,
+}
+
+#[derive(Default)]
+pub struct DebugDrawState {
+    /// Shapes submitted during the current frame, drawn and cleared on render.
+    pub shapes: Vec<Shape>,
+    /// Shapes submitted with a lifetime, removed in `step` once expired.
+    pub persistent_shapes: Vec<PersistentShape>,
+}
+
+pub fn line(a: Vec2f, b: Vec2f, color: Vec4f) {
+    push(Shape::Line { a, b, color });
+}
+
+pub fn rect(position: Vec2f, size: Vec2f, color: Vec4f) {
+    push(Shape::Rect {
+        position,
+        size,
+        color,
+    });
+}
+
+pub fn circle(center: Vec2f, radius: f32, color: Vec4f) {
+    push(Shape::Circle {
+        center,
+        radius,
+        color,
+    });
+}
+
+pub fn text_3d(position: Vec2f, text: &str) {
+    push(Shape::Text {
+        position,
+        text: String::from(text),
+    });
+}
+
+pub fn line_for(a: Vec2f, b: Vec2f, color: Vec4f, duration: Duration) {
+    push_for(Shape::Line { a, b, color }, duration);
+}
+
+pub fn rect_for(position: Vec2f, size: Vec2f, color: Vec4f, duration: Duration) {
+    push_for(
+        Shape::Rect {
+            position,
+            size,
+            color,
+        },
+        duration,
+    );
+}
+
+pub fn circle_for(center: Vec2f, radius: f32, color: Vec4f, duration: Duration) {
+    push_for(
+        Shape::Circle {
+            center,
+            radius,
+            color,
+        },
+        duration,
+    );
+}
+
+fn get_debug_draw_state<'a>() -> MutexGuard<'a, DebugDrawState> {
+    state::recover(DEBUG_DRAW_STATE.lock(), "debug draw state")
+}
+
+fn push_for(shape: Shape, duration: Duration) {
+    get_debug_draw_state()
+        .persistent_shapes
+        .push(PersistentShape {
+            shape,
+            expires_at: Instant::now() + duration,
+        });
+}
+
+fn push(shape: Shape) {
+    get_debug_draw_state().shapes.push(shape);
+}
+
+/// Drops shapes of the frame without drawing them, persistent shapes are kept.
+pub(crate) fn clear() {
+    get_debug_draw_state().shapes.clear();
+}
+
+/// Removes persistent shapes that have expired by now.
+pub(crate) fn remove_expired() {
+    let state = &mut get_debug_draw_state();
+
+    remove_expired_in(state, Instant::now());
+}
+
+fn remove_expired_in(state: &mut DebugDrawState, now: Instant) {
+    state
+        .persistent_shapes
+        .retain(|shape| shape.expires_at > now);
+}
+
+/// Moves shapes of the frame and copies persistent shapes into the `draw_list`.
+pub(crate) fn flush(draw_list: &mut DrawList, camera: &CameraMatrices) {
+    let state = &mut get_debug_draw_state();
+
+    for shape in state.persistent_shapes.iter() {
+        draw_shape(draw_list, camera, shape.shape.clone());
+    }
+
+    for shape in state.shapes.drain(..) {
+        draw_shape(draw_list, camera, shape);
+    }
+}
+
+fn draw_shape(draw_list: &mut DrawList, camera: &CameraMatrices, shape: Shape) {
+    match shape {
+        Shape::Line { a, b, color } => {
+            draw_list.line(camera, a, b, DEBUG_DRAW_LINE_WIDTH, color);
+        }
+        Shape::Rect {
+            position,
+            size,
+            color,
+        } => {
+            let corners = [
+                position,
+                Vec2f::new(position.x + size.x, position.y),
+                Vec2f::new(position.x + size.x, position.y + size.y),
+                Vec2f::new(position.x, position.y + size.y),
+            ];
+
+            draw_polygon(draw_list, camera, &corners, color);
+        }
+        Shape::Circle {
+            center,
+            radius,
+            color,
+        } => {
+            let points = circle_points(center, radius, DEBUG_DRAW_CIRCLE_SEGMENTS);
+            draw_polygon(draw_list, camera, &points, color);
+        }
+        Shape::Text { position, text } => {
+            draw_list.text(camera, position, text);
+        }
+    }
+}
+
+fn draw_polygon(draw_list: &mut DrawList, camera: &CameraMatrices, points: &[Vec2f], color: Vec4f) {
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        draw_list.line(camera, *a, b, DEBUG_DRAW_LINE_WIDTH, color);
+    }
+}
+
+fn circle_points(center: Vec2f, radius: f32, segments: usize) -> Vec<Vec2f> {
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::PI * 2.;
+            Vec2f::new(
+                center.x + angle.cos() * radius,
+                center.y + angle.sin() * radius,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debug_draw;
+    use std::time::{Duration, Instant};
+    use vm_math::{Vec2f, Vec4f};
+
+    #[test]
+    fn remove_expired() {
+        let now = Instant::now();
+        let shape = |expires_at| debug_draw::PersistentShape {
+            shape: debug_draw::Shape::Line {
+                a: Vec2f::ZERO,
+                b: Vec2f::new(1., 1.),
+                color: Vec4f::new(1., 0., 0., 1.),
+            },
+            expires_at,
+        };
+
+        let mut state = debug_draw::DebugDrawState {
+            persistent_shapes: vec![
+                shape(now - Duration::from_millis(1)),
+                shape(now + Duration::from_secs(1)),
+            ],
+            ..Default::default()
+        };
+
+        debug_draw::remove_expired_in(&mut state, now);
+
+        assert_eq!(1, state.persistent_shapes.len());
+        assert!(state.persistent_shapes[0].expires_at > now);
+    }
+
+    #[test]
+    fn circle_points() {
+        let points = debug_draw::circle_points(Vec2f::new(10., 0.), 2., 4);
+
+        assert_eq!(4, points.len());
+        assert!((points[0].x - 12.).abs() < 1e-5);
+        assert!((points[1].y - 2.).abs() < 1e-5);
+        assert!((points[2].x - 8.).abs() < 1e-5);
+        assert!((points[3].y + 2.).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,326 @@
+use vm_math::{CameraMatrices, Rect, Vec2f, Vec4f};
+
+use crate::debug_variables;
+use crate::profiler::ProfileState;
+use crate::profiler_widget;
+use crate::render::{self, DrawList};
+use crate::state::{
+    ColorVariable, DebugState, DebugVariable, GroupVariable, COLOR_CHANNEL_FIELD_WIDTH,
+    COLOR_CHANNEL_NAMES, VECTOR_COMPONENT_FIELD_WIDTH, VECTOR_COMPONENT_NAMES,
+};
+use crate::theme::Theme;
+
+const MENU_LINE_HEIGHT: f32 = 18.;
+const MENU_INDENT: f32 = 16.;
+const MENU_WIDTH: f32 = 300.;
+const MENU_CHECKBOX_SIZE: f32 = 10.;
+const MENU_CHECKBOX_MARGIN: f32 = 6.;
+/// Offset of the color channel and vector component fields from the name.
+const MENU_FIELDS_OFFSET: f32 = 120.;
+
+struct MenuLayout<'a> {
+    draw_list: &'a mut DrawList,
+    camera: &'a CameraMatrices,
+    profile_state: &'a ProfileState,
+    theme: Theme,
+    viewport_size: Vec2f,
+    snapshot_offset: usize,
+    /// Top of the next row in screen space.
+    y: f32,
+    /// Right edge of the rows in screen space, widened by widgets.
+    right: f32,
+}
+
+/// Renders the variables tree, children of collapsed groups are skipped,
+/// bounds of the visible variables are updated for hit-testing.
+/// `position` is the top left corner in screen space, returns the size of the menu.
+pub fn update(
+    debug_state: &mut DebugState,
+    profile_state: &ProfileState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    let snapshot_offset = debug_variables::find_profiler_log_slider_mut(&mut debug_state.variables)
+        .map_or(0, |slider| slider.offset);
+
+    let mut layout = MenuLayout {
+        draw_list,
+        camera,
+        profile_state,
+        theme: debug_state.theme.clone(),
+        viewport_size,
+        snapshot_offset,
+        y: position.y,
+        right: position.x + MENU_WIDTH,
+    };
+
+    let root = &mut debug_state.variables;
+    let bottom = layout.next_row_bottom();
+
+    layout.draw_list.text(
+        camera,
+        Vec2f::new(position.x, bottom),
+        String::from(root.name),
+    );
+    layout.y += MENU_LINE_HEIGHT;
+    layout.group(root, position.x + MENU_INDENT);
+
+    Vec2f::new(layout.right - position.x, layout.y - position.y)
+}
+
+impl<'a> MenuLayout<'a> {
+    /// Bottom of the next row in the camera space, y axis goes up there.
+    fn next_row_bottom(&self) -> f32 {
+        self.viewport_size.y - self.y - MENU_LINE_HEIGHT
+    }
+
+    fn group(&mut self, group: &mut GroupVariable, x: f32) {
+        for variable in group.variables.iter_mut() {
+            match variable {
+                DebugVariable::Group(_, child) => {
+                    let arrow = if child.is_expanded { "v" } else { ">" };
+
+                    child.bounds = self.row(x, child.is_hot, false);
+                    self.label(x, format!("{} {}", arrow, child.name));
+
+                    if child.is_expanded {
+                        self.group(child, x + MENU_INDENT);
+                    }
+                }
+                DebugVariable::Bool(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, false);
+                    self.checkbox(x, variable.value);
+                    self.label(
+                        x + MENU_CHECKBOX_SIZE + MENU_CHECKBOX_MARGIN,
+                        String::from(variable.name),
+                    );
+                }
+                DebugVariable::Float(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, variable.is_active);
+                    self.label(x, format!("{}: {:.3}", variable.name, variable.value));
+                }
+                DebugVariable::Int(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, variable.is_active);
+                    self.label(x, format!("{}: {}", variable.name, variable.value));
+                }
+                DebugVariable::Enum(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, false);
+                    self.label(
+                        x,
+                        format!("{}: {}", variable.name, variable.value().unwrap_or("")),
+                    );
+                }
+                DebugVariable::String(_, variable) => {
+                    let caret = if variable.is_focused { "_" } else { "" };
+
+                    variable.bounds = self.row(x, variable.is_hot, variable.is_focused);
+                    self.label(
+                        x,
+                        format!("{}: \"{}{}\"", variable.name, variable.value, caret),
+                    );
+                }
+                DebugVariable::Color(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, false);
+                    self.color(x, variable);
+                }
+                DebugVariable::Vec2(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, false);
+
+                    let components: Vec<String> = variable
+                        .components()
+                        .iter()
+                        .map(|component| format!("{:.3}", component))
+                        .collect();
+
+                    variable.components_x = self.fields(
+                        x + MENU_FIELDS_OFFSET,
+                        VECTOR_COMPONENT_FIELD_WIDTH,
+                        &VECTOR_COMPONENT_NAMES,
+                        &components,
+                        variable.active_component.filter(|_| variable.is_active),
+                    );
+                    self.label(x, String::from(variable.name));
+                }
+                DebugVariable::Vec3(_, variable) => {
+                    variable.bounds = self.row(x, variable.is_hot, false);
+
+                    let components: Vec<String> = variable
+                        .components()
+                        .iter()
+                        .map(|component| format!("{:.3}", component))
+                        .collect();
+
+                    variable.components_x = self.fields(
+                        x + MENU_FIELDS_OFFSET,
+                        VECTOR_COMPONENT_FIELD_WIDTH,
+                        &VECTOR_COMPONENT_NAMES,
+                        &components,
+                        variable.active_component.filter(|_| variable.is_active),
+                    );
+                    self.label(x, String::from(variable.name));
+                }
+                DebugVariable::Display(_, variable) => {
+                    variable.bounds = self.row(x, false, false);
+                    self.label(x, format!("{}: {}", variable.name, variable.value));
+                }
+                DebugVariable::ProfilerLogSlider(_, slider) => {
+                    let position = Vec2f::new(x, self.y);
+                    let size = profiler_widget::update_log_slider(
+                        slider,
+                        self.draw_list,
+                        self.camera,
+                        self.viewport_size,
+                        position,
+                        self.profile_state.performance_counter_log.len(),
+                    );
+
+                    slider.bounds = self.widget(position, size);
+                }
+                DebugVariable::Profiler(_, widget) => {
+                    let position = Vec2f::new(x, self.y);
+                    let size = profiler_widget::update(
+                        widget,
+                        self.profile_state,
+                        self.snapshot_offset,
+                        self.draw_list,
+                        self.camera,
+                        self.viewport_size,
+                        position,
+                    );
+
+                    widget.bounds = self.widget(position, size);
+                }
+            }
+        }
+    }
+
+    /// Draws the row highlight, returns bounds of the row.
+    fn row(&mut self, x: f32, is_hot: bool, is_active: bool) -> Rect {
+        let color = if is_active {
+            Some(render::color(1., 1., 1., 0.3))
+        } else if is_hot {
+            Some(render::color(1., 1., 1., 0.15))
+        } else {
+            None
+        };
+
+        let width = (self.right - x).max(0.);
+
+        if let Some(color) = color {
+            self.highlight(x, width, color);
+        }
+
+        Rect {
+            position: Vec2f::new(x, self.y),
+            size: Vec2f::new(width, MENU_LINE_HEIGHT),
+        }
+    }
+
+    fn highlight(&mut self, x: f32, width: f32, color: Vec4f) {
+        let bottom = self.next_row_bottom();
+
+        self.draw_list.rect(
+            self.camera,
+            Vec2f::new(x, bottom),
+            Vec2f::new(width, MENU_LINE_HEIGHT),
+            color,
+        );
+    }
+
+    /// Draws the row text and moves to the next row.
+    fn label(&mut self, x: f32, text: String) {
+        let bottom = self.next_row_bottom();
+
+        self.draw_list
+            .text(self.camera, Vec2f::new(x, bottom), text);
+        self.y += MENU_LINE_HEIGHT;
+    }
+
+    fn checkbox(&mut self, x: f32, checked: bool) {
+        let margin = (MENU_LINE_HEIGHT - MENU_CHECKBOX_SIZE) / 2.;
+        let bottom = self.next_row_bottom() + margin;
+        let color = if checked {
+            self.theme.normal
+        } else {
+            render::color(0.4, 0.4, 0.4, 1.)
+        };
+
+        self.draw_list.rect(
+            self.camera,
+            Vec2f::new(x, bottom),
+            Vec2f::new(MENU_CHECKBOX_SIZE, MENU_CHECKBOX_SIZE),
+            color,
+        );
+    }
+
+    /// Draws the swatch, the name and the channel fields, the dragged channel is highlighted.
+    fn color(&mut self, x: f32, variable: &mut ColorVariable) {
+        let margin = (MENU_LINE_HEIGHT - MENU_CHECKBOX_SIZE) / 2.;
+        let bottom = self.next_row_bottom();
+        let [r, g, b, a] = variable.value;
+
+        self.draw_list.rect(
+            self.camera,
+            Vec2f::new(x, bottom + margin),
+            Vec2f::new(MENU_CHECKBOX_SIZE, MENU_CHECKBOX_SIZE),
+            render::color(
+                r as f32 / 255.,
+                g as f32 / 255.,
+                b as f32 / 255.,
+                a as f32 / 255.,
+            ),
+        );
+
+        let name_x = x + MENU_CHECKBOX_SIZE + MENU_CHECKBOX_MARGIN;
+        let channels: Vec<String> = variable.value.iter().map(u8::to_string).collect();
+
+        variable.channels_x = self.fields(
+            name_x + MENU_FIELDS_OFFSET,
+            COLOR_CHANNEL_FIELD_WIDTH,
+            &COLOR_CHANNEL_NAMES,
+            &channels,
+            variable.active_channel.filter(|_| variable.is_active),
+        );
+        self.label(name_x, String::from(variable.name));
+    }
+
+    /// Draws the named fields of the current row starting from `fields_x`,
+    /// the dragged field is highlighted, returns `fields_x` for hit-testing.
+    fn fields(
+        &mut self,
+        fields_x: f32,
+        field_width: f32,
+        names: &[&str],
+        values: &[String],
+        active_field: Option<usize>,
+    ) -> f32 {
+        let bottom = self.next_row_bottom();
+
+        for (i, (name, value)) in names.iter().zip(values).enumerate() {
+            let field_x = fields_x + i as f32 * field_width;
+
+            if active_field == Some(i) {
+                self.highlight(field_x, field_width, render::color(1., 1., 1., 0.3));
+            }
+
+            self.draw_list.text(
+                self.camera,
+                Vec2f::new(field_x, bottom),
+                format!("{}:{}", name, value),
+            );
+        }
+
+        self.right = self.right.max(fields_x + values.len() as f32 * field_width);
+        fields_x
+    }
+
+    /// Moves past the widget rendered at `position`, returns its bounds.
+    fn widget(&mut self, position: Vec2f, size: Vec2f) -> Rect {
+        self.y += size.y;
+        self.right = self.right.max(position.x + size.x);
+
+        Rect { position, size }
+    }
+}
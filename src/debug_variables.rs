@@ -0,0 +1,773 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+
+use vm_math::{Vec2f, Vec3f};
+
+use crate::commands::{self, require, CommandArgument};
+use crate::format;
+
+use crate::state::{read_debug_state, write_debug_state, DebugState};
+
+pub use crate::state::{
+    BoolVariable, ColorVariable, DebugVariable, DisplayVariable, EnumVariable, FloatVariable,
+    GroupVariable, IntVariable, ProfilerLogSliderVariable, ProfilerVariable, StringVariable,
+    Vec2Variable, Vec3Variable,
+};
+
+pub const PATH_SEPARATOR: char = '/';
+pub const DEBUG_VARIABLES_PATH: &str = "debug_variables.toml";
+pub const PROFILER_LOG_PATH: &str = "Profiler/Log";
+pub const PROFILER_COUNTERS_PATH: &str = "Profiler/Counters";
+
+/// Called with the variable after its value is changed from the debug menu or `vars::set`.
+/// The debug state is locked during the call, so `get_*` functions can't be used inside,
+/// read the value from the passed variable instead.
+/// Callbacks are kept in a `Mutex`, so they don't have to be `Sync`.
+pub type OnChange = Box<dyn FnMut(&DebugVariable) + Send>;
+
+/// Value restored from disk, applied to the variable when it's registered.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SavedValue {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Vector(Vec<f64>),
+}
+
+pub fn init(debug_state: &mut DebugState) {
+    let group = ensure_group(&mut debug_state.variables, "Profiler");
+
+    if group.variables.is_empty() {
+        group.variables.push(DebugVariable::ProfilerLogSlider(
+            variable_id(PROFILER_LOG_PATH),
+            ProfilerLogSliderVariable::default(),
+        ));
+        group.variables.push(DebugVariable::Profiler(
+            variable_id(PROFILER_COUNTERS_PATH),
+            ProfilerVariable::default(),
+        ));
+    }
+}
+
+/// Stable id of the variable, the same path gives the same id between runs.
+pub fn variable_id(path: &str) -> usize {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in path.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash as usize
+}
+
+pub fn register_group(path: &'static str) -> usize {
+    let debug_state = &mut write_debug_state();
+    ensure_group(&mut debug_state.variables, path);
+    apply_saved_value(debug_state, path);
+    variable_id(path)
+}
+
+pub fn register_bool(path: &'static str, value: bool) -> usize {
+    register(path, |group, id, name| group.add_bool(id, name, value))
+}
+
+pub fn register_float(path: &'static str, value: f32, min: f32, max: f32, step: f32) -> usize {
+    register(path, |group, id, name| {
+        group.add_float(id, name, value, min, max, step)
+    })
+}
+
+pub fn register_int(path: &'static str, value: i64, min: i64, max: i64, step: f32) -> usize {
+    register(path, |group, id, name| {
+        group.add_int(id, name, value, min, max, step)
+    })
+}
+
+pub fn register_string(path: &'static str, value: &str, max_length: usize) -> usize {
+    register(path, |group, id, name| {
+        group.add_string(id, name, value, max_length)
+    })
+}
+
+pub fn register_color(path: &'static str, value: [u8; 4], step: f32) -> usize {
+    register(path, |group, id, name| {
+        group.add_color(id, name, value, step)
+    })
+}
+
+pub fn register_vec2(path: &'static str, value: Vec2f, step: f32) -> usize {
+    register(path, |group, id, name| {
+        group.add_vec2(id, name, value, step)
+    })
+}
+
+pub fn register_vec3(path: &'static str, value: Vec3f, step: f32) -> usize {
+    register(path, |group, id, name| {
+        group.add_vec3(id, name, value, step)
+    })
+}
+
+pub fn register_display(path: &'static str) -> usize {
+    register(path, |group, id, name| group.add_display(id, name))
+}
+
+/// Updates the value of the display variable, usually called each frame.
+pub fn set_display<T: Display>(path: &str, value: T) {
+    let debug_state = &mut write_debug_state();
+
+    if let Some(DebugVariable::Display(_, variable)) = find_mut(&mut debug_state.variables, path) {
+        variable.value = value.to_string();
+    }
+}
+
+pub fn register_enum(path: &'static str, values: Vec<&'static str>, selected: usize) -> usize {
+    register(path, move |group, id, name| {
+        group.add_enum(id, name, values, selected)
+    })
+}
+
+/// Sets the change callback of the variable registered with the `id`,
+/// e.g. `on_change(register_bool("rendering/vsync", true), |variable| ...)`.
+pub fn on_change<F>(id: usize, callback: F)
+where
+    F: FnMut(&DebugVariable) + Send + 'static,
+{
+    let debug_state = &mut write_debug_state();
+    debug_state
+        .on_change
+        .insert(id, Mutex::new(Box::new(callback)));
+}
+
+/// Calls the change callback of the variable if it's set.
+pub fn notify_changed(callbacks: &mut HashMap<usize, Mutex<OnChange>>, variable: &DebugVariable) {
+    if let Some(callback) = callbacks.get_mut(&variable.id()) {
+        // Exclusive access doesn't lock, so a panicking callback can't poison it.
+        let callback = callback.get_mut().unwrap_or_else(PoisonError::into_inner);
+        callback(variable);
+    }
+}
+
+fn register<F>(path: &'static str, add: F) -> usize
+where
+    F: FnOnce(&mut GroupVariable, usize, &'static str),
+{
+    let debug_state = &mut write_debug_state();
+    let id = register_in(&mut debug_state.variables, path, add);
+    apply_saved_value(debug_state, path);
+    id
+}
+
+fn apply_saved_value(debug_state: &mut DebugState, path: &str) {
+    let value = match debug_state.saved_variables.get(path) {
+        Some(value) => value.clone(),
+        None => return,
+    };
+
+    if let Some(variable) = find_mut(&mut debug_state.variables, path) {
+        apply_value(variable, &value);
+    }
+}
+
+fn apply_value(variable: &mut DebugVariable, value: &SavedValue) {
+    match (variable, value) {
+        (DebugVariable::Bool(_, variable), SavedValue::Bool(value)) => variable.value = *value,
+        (DebugVariable::Group(_, group), SavedValue::Bool(value)) => group.is_expanded = *value,
+        (DebugVariable::Float(_, variable), SavedValue::Number(value)) => {
+            variable.set(*value as f32)
+        }
+        (DebugVariable::Int(_, variable), SavedValue::Number(value)) => variable.set(*value as i64),
+        (DebugVariable::Enum(_, variable), SavedValue::String(value)) => {
+            variable.select(value);
+        }
+        (DebugVariable::String(_, variable), SavedValue::String(value)) => variable.set(value),
+        (DebugVariable::Color(_, variable), SavedValue::String(value)) => {
+            // Malformed saved colors are ignored like other mismatched values.
+            if let Some(color) = commands::parse_color(value) {
+                variable.value = color;
+            }
+        }
+        (DebugVariable::Vec2(_, variable), SavedValue::Vector(value)) if value.len() == 2 => {
+            variable.value = Vec2f::new(value[0] as f32, value[1] as f32)
+        }
+        (DebugVariable::Vec3(_, variable), SavedValue::Vector(value)) if value.len() == 3 => {
+            variable.value = Vec3f::new(value[0] as f32, value[1] as f32, value[2] as f32)
+        }
+        _ => {}
+    }
+}
+
+/// Writes values and expanded state of the variables tree to the `path`.
+pub fn save(debug_state: &DebugState, path: &str) -> io::Result<()> {
+    let mut values = debug_state.saved_variables.clone();
+    collect_values(&debug_state.variables, "", &mut values);
+    debug_state.windows.collect_values(&mut values);
+    debug_state.key_bindings.collect_values(&mut values);
+
+    fs::write(path, serialize(&values))
+}
+
+/// Reads values from the `path` and applies them to registered variables,
+/// the rest is applied when variables get registered.
+pub fn load(debug_state: &mut DebugState, path: &str) -> io::Result<()> {
+    let content = fs::read_to_string(path)?;
+    let values = parse(&content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    for (path, value) in values.iter() {
+        if let Some(variable) = find_mut(&mut debug_state.variables, path) {
+            apply_value(variable, value);
+        }
+    }
+
+    debug_state.windows.apply_values(&values);
+    debug_state.key_bindings.apply_values(&values);
+    debug_state.saved_variables = values;
+    Ok(())
+}
+
+/// Calls `f` for each variable in the tree with its full path, parents go first.
+pub fn visit<F>(group: &GroupVariable, prefix: &str, f: &mut F)
+where
+    F: FnMut(&str, &DebugVariable),
+{
+    for variable in group.variables.iter() {
+        let path = if prefix.is_empty() {
+            String::from(variable.name())
+        } else {
+            format!("{}{}{}", prefix, PATH_SEPARATOR, variable.name())
+        };
+
+        f(&path, variable);
+
+        if let DebugVariable::Group(_, child) = variable {
+            visit(child, &path, f);
+        }
+    }
+}
+
+fn collect_values(group: &GroupVariable, prefix: &str, values: &mut HashMap<String, SavedValue>) {
+    visit(group, prefix, &mut |path, variable| {
+        let value = match variable {
+            DebugVariable::Bool(_, variable) => SavedValue::Bool(variable.value),
+            DebugVariable::Float(_, variable) => SavedValue::Number(variable.value as f64),
+            DebugVariable::Int(_, variable) => SavedValue::Number(variable.value as f64),
+            DebugVariable::Enum(_, variable) => match variable.value() {
+                Some(value) => SavedValue::String(String::from(value)),
+                None => return,
+            },
+            DebugVariable::String(_, variable) => SavedValue::String(variable.value.clone()),
+            DebugVariable::Color(_, variable) => SavedValue::String(variable.hex()),
+            DebugVariable::Vec2(_, variable) => {
+                SavedValue::Vector(variable.components().iter().map(|c| *c as f64).collect())
+            }
+            DebugVariable::Vec3(_, variable) => {
+                SavedValue::Vector(variable.components().iter().map(|c| *c as f64).collect())
+            }
+            DebugVariable::Group(_, group) => SavedValue::Bool(group.is_expanded),
+            _ => return,
+        };
+
+        values.insert(String::from(path), value);
+    });
+}
+
+pub fn type_name(variable: &DebugVariable) -> &'static str {
+    match variable {
+        DebugVariable::Bool(_, _) => "bool",
+        DebugVariable::Float(_, _) => "float",
+        DebugVariable::Int(_, _) => "int",
+        DebugVariable::Enum(_, _) => "enum",
+        DebugVariable::String(_, _) => "string",
+        DebugVariable::Color(_, _) => "color",
+        DebugVariable::Vec2(_, _) => "vec2",
+        DebugVariable::Vec3(_, _) => "vec3",
+        DebugVariable::Display(_, _) => "display",
+        DebugVariable::Group(_, _) => "group",
+        DebugVariable::Profiler(_, _) | DebugVariable::ProfilerLogSlider(_, _) => "widget",
+    }
+}
+
+pub fn value_string(variable: &DebugVariable) -> String {
+    match variable {
+        DebugVariable::Bool(_, variable) => variable.value.to_string(),
+        DebugVariable::Float(_, variable) => variable.value.to_string(),
+        DebugVariable::Int(_, variable) => variable.value.to_string(),
+        DebugVariable::Enum(_, variable) => {
+            format!(
+                "{} ({})",
+                variable.value().unwrap_or(""),
+                variable.values.join("|")
+            )
+        }
+        DebugVariable::String(_, variable) => format::json_string(&variable.value),
+        DebugVariable::Color(_, variable) => variable.hex(),
+        DebugVariable::Vec2(_, variable) => format!("({}, {})", variable.value.x, variable.value.y),
+        DebugVariable::Vec3(_, variable) => format!(
+            "({}, {}, {})",
+            variable.value.x, variable.value.y, variable.value.z
+        ),
+        DebugVariable::Display(_, variable) => variable.value.clone(),
+        DebugVariable::Group(_, group) => format!("{} variables", group.variables.len()),
+        DebugVariable::Profiler(_, _) | DebugVariable::ProfilerLogSlider(_, _) => String::new(),
+    }
+}
+
+/// Sets variable value from the command argument, numbers are clamped to the variable range.
+pub fn set_from_argument(
+    variable: &mut DebugVariable,
+    argument: &CommandArgument,
+) -> Result<(), String> {
+    match (variable, argument) {
+        (DebugVariable::Bool(_, variable), CommandArgument::Bool(value)) => {
+            variable.value = *value;
+            Ok(())
+        }
+        (DebugVariable::Float(_, variable), CommandArgument::Number(value)) => {
+            variable.set(*value as f32);
+            Ok(())
+        }
+        (DebugVariable::Int(_, variable), CommandArgument::Number(value)) => {
+            require(value.fract() == 0., "value should be int")?;
+            variable.set(*value as i64);
+            Ok(())
+        }
+        (DebugVariable::Enum(_, variable), CommandArgument::String(value)) => require(
+            variable.select(value),
+            &format!("value should be one of: {}", variable.values.join(", ")),
+        ),
+        (DebugVariable::String(_, variable), CommandArgument::String(value)) => {
+            require(
+                value.chars().count() <= variable.max_length,
+                &format!("value should be at most {} characters", variable.max_length),
+            )?;
+            variable.set(value);
+            Ok(())
+        }
+        (DebugVariable::Color(_, variable), CommandArgument::Color(r, g, b, a)) => {
+            variable.value = [*r, *g, *b, *a];
+            Ok(())
+        }
+        (DebugVariable::Vec2(_, variable), CommandArgument::Vec2(x, y)) => {
+            variable.value = Vec2f::new(*x as f32, *y as f32);
+            Ok(())
+        }
+        (DebugVariable::Vec3(_, variable), CommandArgument::Vec3(x, y, z)) => {
+            variable.value = Vec3f::new(*x as f32, *y as f32, *z as f32);
+            Ok(())
+        }
+        (DebugVariable::Display(_, _), _) => Err(String::from("variable is read-only")),
+        (variable, _) => Err(format!("value should be {}", type_name(variable))),
+    }
+}
+
+fn serialize(values: &HashMap<String, SavedValue>) -> String {
+    let mut keys: Vec<&String> = values.keys().collect();
+    keys.sort();
+
+    let mut result = String::from("[variables]\n");
+
+    for key in keys {
+        let value = match &values[key] {
+            SavedValue::Bool(value) => value.to_string(),
+            SavedValue::Number(value) => format!("{:?}", value),
+            SavedValue::String(value) => format::json_string(value),
+            SavedValue::Vector(value) => {
+                let components: Vec<String> = value.iter().map(|c| format!("{:?}", c)).collect();
+                format!("[{}]", components.join(", "))
+            }
+        };
+
+        result.push_str(&format!("{} = {}\n", format::json_string(key), value));
+    }
+
+    result
+}
+
+/// Parses flat TOML table written by `serialize`.
+fn parse(content: &str) -> Result<HashMap<String, SavedValue>, String> {
+    let mut values = HashMap::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+
+        let (key, value) =
+            parse_line(line).ok_or_else(|| format!("bad line {}: {}", i + 1, line))?;
+        values.insert(key, value);
+    }
+
+    Ok(values)
+}
+
+fn parse_line(line: &str) -> Option<(String, SavedValue)> {
+    let (key, rest) = parse_string(line)?;
+    let value = rest.trim_start().strip_prefix('=')?.trim();
+
+    let value = match value {
+        "true" => SavedValue::Bool(true),
+        "false" => SavedValue::Bool(false),
+        _ if value.starts_with('"') => SavedValue::String(parse_string(value)?.0),
+        _ if value.starts_with('[') => {
+            let components = value.strip_prefix('[')?.strip_suffix(']')?;
+            let components: Result<Vec<f64>, _> = components
+                .split(',')
+                .map(|component| component.trim().parse())
+                .collect();
+
+            SavedValue::Vector(components.ok()?)
+        }
+        _ => SavedValue::Number(value.parse().ok()?),
+    };
+
+    Some((key, value))
+}
+
+/// Parses quoted string with escapes, returns the string and the rest of the input.
+fn parse_string(input: &str) -> Option<(String, &str)> {
+    let input = input.strip_prefix('"')?;
+    let mut result = String::new();
+    let mut chars = input.char_indices();
+
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => return Some((result, &input[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                't' => result.push('\t'),
+                ch => result.push(ch),
+            },
+            ch => result.push(ch),
+        }
+    }
+
+    None
+}
+
+/// Adds variable to the tree creating missing parent groups,
+/// already registered variables are kept untouched.
+pub(crate) fn register_in<F>(root: &mut GroupVariable, path: &'static str, add: F) -> usize
+where
+    F: FnOnce(&mut GroupVariable, usize, &'static str),
+{
+    let id = variable_id(path);
+    let (parent, name) = split_path(path);
+    let group = ensure_group(root, parent);
+
+    if !group
+        .variables
+        .iter()
+        .any(|variable| variable.name() == name)
+    {
+        add(group, id, name);
+    }
+
+    id
+}
+
+fn split_path(path: &'static str) -> (&'static str, &'static str) {
+    match path.rfind(PATH_SEPARATOR) {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    }
+}
+
+pub(crate) fn ensure_group<'a>(
+    root: &'a mut GroupVariable,
+    path: &'static str,
+) -> &'a mut GroupVariable {
+    let mut group = root;
+    let mut prefix_end = 0;
+
+    for segment in path.split(PATH_SEPARATOR) {
+        prefix_end += segment.len();
+
+        if segment.is_empty() {
+            prefix_end += 1;
+            continue;
+        }
+
+        let idx = group.variables.iter().position(|variable| match variable {
+            DebugVariable::Group(_, child) => child.name == segment,
+            _ => false,
+        });
+
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                let id = variable_id(&path[..prefix_end]);
+                group.variables.push(DebugVariable::Group(
+                    id,
+                    GroupVariable::new(segment, Vec::new()),
+                ));
+                group.variables.len() - 1
+            }
+        };
+
+        group = match &mut group.variables[idx] {
+            DebugVariable::Group(_, child) => child,
+            _ => unreachable!(),
+        };
+
+        prefix_end += 1;
+    }
+
+    group
+}
+
+/// Finds variable by path like "rendering/shadows/enabled".
+pub fn find<'a>(root: &'a GroupVariable, path: &str) -> Option<&'a DebugVariable> {
+    let mut segments = path.split(PATH_SEPARATOR).filter(|s| !s.is_empty());
+    let first = segments.next()?;
+    let mut variable = root.variables.iter().find(|v| v.name() == first)?;
+
+    for segment in segments {
+        variable = match variable {
+            DebugVariable::Group(_, group) => {
+                group.variables.iter().find(|v| v.name() == segment)?
+            }
+            _ => return None,
+        };
+    }
+
+    Some(variable)
+}
+
+pub fn find_mut<'a>(root: &'a mut GroupVariable, path: &str) -> Option<&'a mut DebugVariable> {
+    let mut segments = path.split(PATH_SEPARATOR).filter(|s| !s.is_empty());
+    let first = segments.next()?;
+    let mut variable = root.variables.iter_mut().find(|v| v.name() == first)?;
+
+    for segment in segments {
+        variable = match variable {
+            DebugVariable::Group(_, group) => {
+                group.variables.iter_mut().find(|v| v.name() == segment)?
+            }
+            _ => return None,
+        };
+    }
+
+    Some(variable)
+}
+
+/// Profiler log slider registered by `init`.
+pub fn find_profiler_log_slider_mut(
+    root: &mut GroupVariable,
+) -> Option<&mut ProfilerLogSliderVariable> {
+    match find_mut(root, PROFILER_LOG_PATH)? {
+        DebugVariable::ProfilerLogSlider(_, variable) => Some(variable),
+        _ => None,
+    }
+}
+
+/// Profiler counters widget registered by `init`.
+pub fn find_profiler_mut(root: &mut GroupVariable) -> Option<&mut ProfilerVariable> {
+    match find_mut(root, PROFILER_COUNTERS_PATH)? {
+        DebugVariable::Profiler(_, variable) => Some(variable),
+        _ => None,
+    }
+}
+
+pub fn get_bool(path: &str) -> Option<bool> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Bool(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+pub fn get_float(path: &str) -> Option<f32> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Float(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+pub fn get_int(path: &str) -> Option<i64> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Int(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+pub fn get_enum(path: &str) -> Option<&'static str> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Enum(_, variable) => variable.value(),
+        _ => None,
+    }
+}
+
+pub fn get_string(path: &str) -> Option<String> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::String(_, variable) => Some(variable.value.clone()),
+        _ => None,
+    }
+}
+
+pub fn get_color(path: &str) -> Option<[u8; 4]> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Color(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+pub fn get_vec2(path: &str) -> Option<Vec2f> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Vec2(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+pub fn get_vec3(path: &str) -> Option<Vec3f> {
+    let debug_state = read_debug_state();
+
+    match find(&debug_state.variables, path)? {
+        DebugVariable::Vec3(_, variable) => Some(variable.value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::CommandArgument;
+    use crate::debug_variables;
+    use crate::state::{DebugVariable, GroupVariable};
+    use std::collections::HashMap;
+    use vm_math::Vec3f;
+
+    #[test]
+    fn serialize_and_parse() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+
+        debug_variables::register_in(&mut root, "rendering/vsync", |g, id, name| {
+            g.add_bool(id, name, true)
+        });
+        debug_variables::register_in(&mut root, "rendering/mode", |g, id, name| {
+            g.add_enum(id, name, vec!["fill", "wire \"frame\""], 1)
+        });
+        debug_variables::register_in(&mut root, "physics/gravity", |g, id, name| {
+            g.add_float(id, name, 9.5, 0.0, 20.0, 0.1)
+        });
+        debug_variables::register_in(&mut root, "camera/offset", |g, id, name| {
+            g.add_vec3(id, name, Vec3f::new(0.5, -1.0, 2.0), 0.1)
+        });
+
+        let mut values = HashMap::new();
+        debug_variables::collect_values(&root, "", &mut values);
+
+        let content = debug_variables::serialize(&values);
+        let parsed = debug_variables::parse(&content).unwrap();
+
+        assert_eq!(values, parsed);
+        assert_eq!(
+            Some(&debug_variables::SavedValue::String(String::from(
+                "wire \"frame\""
+            ))),
+            parsed.get("rendering/mode")
+        );
+        assert_eq!(
+            Some(&debug_variables::SavedValue::Bool(false)),
+            parsed.get("rendering")
+        );
+        assert_eq!(
+            Some(&debug_variables::SavedValue::Vector(vec![0.5, -1.0, 2.0])),
+            parsed.get("camera/offset")
+        );
+    }
+
+    #[test]
+    fn display_variable_is_read_only() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+
+        debug_variables::register_in(&mut root, "stats/fps", |g, id, name| {
+            g.add_display(id, name)
+        });
+
+        let variable = debug_variables::find_mut(&mut root, "stats/fps").unwrap();
+
+        assert_eq!(
+            Err(String::from("variable is read-only")),
+            debug_variables::set_from_argument(variable, &CommandArgument::Number(60.))
+        );
+        assert_eq!("display", debug_variables::type_name(variable));
+    }
+
+    #[test]
+    fn register_and_find() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+
+        let id =
+            debug_variables::register_in(&mut root, "rendering/shadows/enabled", |g, id, name| {
+                g.add_bool(id, name, true)
+            });
+
+        assert_eq!(
+            debug_variables::variable_id("rendering/shadows/enabled"),
+            id
+        );
+
+        match debug_variables::find(&root, "rendering/shadows/enabled") {
+            Some(DebugVariable::Bool(found_id, variable)) => {
+                assert_eq!(id, *found_id);
+                assert!(variable.value);
+            }
+            _ => panic!("variable not found"),
+        }
+
+        match debug_variables::find(&root, "rendering/shadows") {
+            Some(DebugVariable::Group(found_id, group)) => {
+                assert_eq!(debug_variables::variable_id("rendering/shadows"), *found_id);
+                assert_eq!(1, group.variables.len());
+            }
+            _ => panic!("group not found"),
+        }
+
+        assert!(debug_variables::find(&root, "rendering/missing").is_none());
+    }
+
+    #[test]
+    fn register_twice_keeps_value() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+
+        debug_variables::register_in(&mut root, "physics/gravity", |g, id, name| {
+            g.add_float(id, name, 9.8, 0.0, 20.0, 0.1)
+        });
+
+        if let Some(DebugVariable::Float(_, variable)) =
+            debug_variables::find_mut(&mut root, "physics/gravity")
+        {
+            variable.set(3.0);
+        }
+
+        debug_variables::register_in(&mut root, "physics/gravity", |g, id, name| {
+            g.add_float(id, name, 9.8, 0.0, 20.0, 0.1)
+        });
+
+        match debug_variables::find(&root, "physics/gravity") {
+            Some(DebugVariable::Float(_, variable)) => assert_eq!(3.0, variable.value),
+            _ => panic!("variable not found"),
+        }
+
+        assert_eq!(1, root.variables.len());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::render::DrawList;
+use crate::state::DebugState;
+
+const WATCH_LINE_HEIGHT: f32 = 18.;
+
+#[derive(Default)]
+pub struct WatchState {
+    /// Last published value of each name, published values are kept
+    /// even if they aren't watched so they can be listed.
+    pub values: BTreeMap<String, String>,
+    /// Watched names in the order they were added.
+    pub watches: Vec<String>,
+}
+
+/// Publishes the current value of `name`, usually called every frame.
+pub fn publish<T: Display>(name: &str, value: T) {
+    let value = value.to_string();
+
+    DebugState::with(|debug_state| {
+        debug_state.watch.values.insert(String::from(name), value);
+    });
+}
+
+pub fn add(watch: &mut WatchState, name: &str) -> Result<(), String> {
+    if watch.watches.iter().any(|watched| watched == name) {
+        return Err(format!("'{}' is already watched", name));
+    }
+
+    watch.watches.push(String::from(name));
+    Ok(())
+}
+
+pub fn remove(watch: &mut WatchState, name: &str) -> Result<(), String> {
+    match watch.watches.iter().position(|watched| watched == name) {
+        Some(idx) => {
+            watch.watches.remove(idx);
+            Ok(())
+        }
+        None => Err(format!("'{}' is not watched", name)),
+    }
+}
+
+/// Value of the watched name, `None` if nothing has been published yet.
+pub fn value<'a>(watch: &'a WatchState, name: &str) -> Option<&'a str> {
+    watch.values.get(name).map(|value| value.as_str())
+}
+
+/// Renders watched values, `position` is the top left corner in screen space,
+/// returns the size of the panel.
+pub fn update(
+    debug_state: &DebugState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    let watch = &debug_state.watch;
+    let mut y = viewport_size.y - position.y;
+
+    for name in watch.watches.iter() {
+        y -= WATCH_LINE_HEIGHT;
+
+        let text = format!("{} = {}", name, value(watch, name).unwrap_or("<none>"));
+        draw_list.text(camera, Vec2f::new(position.x, y), text);
+    }
+
+    Vec2f::new(0., watch.watches.len() as f32 * WATCH_LINE_HEIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debug_watch;
+
+    #[test]
+    fn add_and_remove() {
+        let mut watch = debug_watch::WatchState::default();
+
+        watch
+            .values
+            .insert(String::from("player_speed"), String::from("4.5"));
+
+        assert_eq!(Ok(()), debug_watch::add(&mut watch, "player_speed"));
+        assert_eq!(Ok(()), debug_watch::add(&mut watch, "enemies"));
+        assert!(debug_watch::add(&mut watch, "enemies").is_err());
+
+        assert_eq!(Some("4.5"), debug_watch::value(&watch, "player_speed"));
+        assert_eq!(None, debug_watch::value(&watch, "enemies"));
+
+        assert_eq!(Ok(()), debug_watch::remove(&mut watch, "player_speed"));
+        assert!(debug_watch::remove(&mut watch, "player_speed").is_err());
+        assert_eq!(vec![String::from("enemies")], watch.watches);
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+pub const DURATION_WIDTH: usize = 9;
+pub const BYTES_WIDTH: usize = 9;
+
+pub fn duration(value: Duration) -> String {
+    let nanos = value.as_nanos();
+
+    if nanos < 1_000 {
+        format!("{}ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{:.2}µs", nanos as f64 / 1_000.)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.2}ms", nanos as f64 / 1_000_000.)
+    } else {
+        format!("{:.2}s", nanos as f64 / 1_000_000_000.)
+    }
+}
+
+pub fn duration_aligned(value: Duration) -> String {
+    align_right(&duration(value), DURATION_WIDTH)
+}
+
+pub fn bytes(value: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+
+    if value < KB {
+        format!("{}B", value)
+    } else if value < MB {
+        format!("{:.2}KB", value as f64 / KB as f64)
+    } else if value < GB {
+        format!("{:.2}MB", value as f64 / MB as f64)
+    } else {
+        format!("{:.2}GB", value as f64 / GB as f64)
+    }
+}
+
+pub fn bytes_aligned(value: u64) -> String {
+    align_right(&bytes(value), BYTES_WIDTH)
+}
+
+/// Formats count with thousands separators, e.g. 1234567 -> "1,234,567".
+pub fn count(value: u64) -> String {
+    let digits = value.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+
+        result.push(ch);
+    }
+
+    result
+}
+
+pub fn count_aligned(value: u64, width: usize) -> String {
+    align_right(&count(value), width)
+}
+
+pub fn align_right(value: &str, width: usize) -> String {
+    format!("{:>width$}", value, width = width)
+}
+
+pub fn align_left(value: &str, width: usize) -> String {
+    format!("{:<width$}", value, width = width)
+}
+
+/// Quotes and escapes `value` as JSON string.
+pub fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+
+    for ch in value.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => result.push(ch),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Quotes `value` as CSV field if it contains separators, quotes or new lines.
+pub fn csv_field(value: &str) -> String {
+    if value.contains(|ch| ch == ',' || ch == '"' || ch == '\n' || ch == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+/// Quotes and escapes `value` as Prometheus label value.
+pub fn prometheus_label(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+
+    for ch in value.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            ch => result.push(ch),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format;
+    use std::time::Duration;
+
+    #[test]
+    fn duration() {
+        assert_eq!("999ns", format::duration(Duration::from_nanos(999)));
+        assert_eq!("1.50µs", format::duration(Duration::from_nanos(1_500)));
+        assert_eq!("16.67ms", format::duration(Duration::from_micros(16_666)));
+        assert_eq!("2.00s", format::duration(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn duration_aligned() {
+        assert_eq!(
+            "   1.00ms",
+            format::duration_aligned(Duration::from_millis(1))
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!("512B", format::bytes(512));
+        assert_eq!("1.50KB", format::bytes(1536));
+        assert_eq!("2.00MB", format::bytes(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn count() {
+        assert_eq!("0", format::count(0));
+        assert_eq!("999", format::count(999));
+        assert_eq!("1,000", format::count(1000));
+        assert_eq!("1,234,567", format::count(1_234_567));
+    }
+
+    #[test]
+    fn json_string() {
+        assert_eq!(r#""say \"hi\"\n\\""#, format::json_string("say \"hi\"\n\\"));
+    }
+
+    #[test]
+    fn csv_field() {
+        assert_eq!("update", format::csv_field("update"));
+        assert_eq!(r#""a, ""b""""#, format::csv_field(r#"a, "b""#));
+    }
+
+    #[test]
+    fn prometheus_label() {
+        assert_eq!(r#""a\"b\\c\n""#, format::prometheus_label("a\"b\\c\n"));
+    }
+}
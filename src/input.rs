@@ -0,0 +1,138 @@
+use vm::module::{ClientEvent, KeyCode};
+use vm_math::Vec2f;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Enter,
+    Backspace,
+    Delete,
+    Escape,
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+    Grave,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+}
+
+const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::Enter, "Enter"),
+    (Key::Backspace, "Backspace"),
+    (Key::Delete, "Delete"),
+    (Key::Escape, "Escape"),
+    (Key::Tab, "Tab"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::Grave, "Grave"),
+    (Key::F1, "F1"),
+    (Key::F2, "F2"),
+    (Key::F3, "F3"),
+    (Key::F4, "F4"),
+    (Key::F5, "F5"),
+    (Key::F6, "F6"),
+    (Key::F7, "F7"),
+    (Key::F8, "F8"),
+    (Key::F9, "F9"),
+    (Key::F10, "F10"),
+    (Key::F11, "F11"),
+    (Key::F12, "F12"),
+];
+
+impl Key {
+    /// Parses the key name, e.g. `F3`, case insensitive.
+    pub fn from_name(name: &str) -> Option<Key> {
+        KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| *key)
+    }
+
+    pub fn name(self) -> &'static str {
+        KEY_NAMES
+            .iter()
+            .find(|(key, _)| *key == self)
+            .map_or("", |(_, name)| name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputEvent {
+    Char(char),
+    KeyDown(Key),
+    KeyUp(Key),
+    MouseMove(Vec2f),
+    MouseDown(Vec2f),
+    MouseUp(Vec2f),
+    MouseWheel(f32),
+}
+
+impl InputEvent {
+    /// Converts mouse positions from physical to logical pixels.
+    pub fn scaled(self, scale: f32) -> Self {
+        let unscale = |point: Vec2f| Vec2f::new(point.x / scale, point.y / scale);
+
+        match self {
+            InputEvent::MouseMove(point) => InputEvent::MouseMove(unscale(point)),
+            InputEvent::MouseDown(point) => InputEvent::MouseDown(unscale(point)),
+            InputEvent::MouseUp(point) => InputEvent::MouseUp(unscale(point)),
+            event => event,
+        }
+    }
+}
+
+/// Translates client event into the debug layer input event,
+/// returns `None` for events that are not related to input.
+pub fn translate(event: &ClientEvent) -> Option<InputEvent> {
+    match event {
+        ClientEvent::CharInput { ch } => Some(InputEvent::Char(*ch)),
+        ClientEvent::KeyPressed { key } => translate_key(*key).map(InputEvent::KeyDown),
+        ClientEvent::KeyReleased { key } => translate_key(*key).map(InputEvent::KeyUp),
+        ClientEvent::MouseMove { x, y } => Some(InputEvent::MouseMove(Vec2f::new(*x, *y))),
+        ClientEvent::MouseDown { x, y, .. } => Some(InputEvent::MouseDown(Vec2f::new(*x, *y))),
+        ClientEvent::MouseUp { x, y, .. } => Some(InputEvent::MouseUp(Vec2f::new(*x, *y))),
+        ClientEvent::MouseWheel { dy, .. } => Some(InputEvent::MouseWheel(*dy)),
+        _ => None,
+    }
+}
+
+fn translate_key(key: KeyCode) -> Option<Key> {
+    match key {
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Backspace => Some(Key::Backspace),
+        KeyCode::Delete => Some(Key::Delete),
+        KeyCode::Escape => Some(Key::Escape),
+        KeyCode::Tab => Some(Key::Tab),
+        KeyCode::Up => Some(Key::Up),
+        KeyCode::Down => Some(Key::Down),
+        KeyCode::Left => Some(Key::Left),
+        KeyCode::Right => Some(Key::Right),
+        KeyCode::Grave => Some(Key::Grave),
+        KeyCode::F1 => Some(Key::F1),
+        KeyCode::F2 => Some(Key::F2),
+        KeyCode::F3 => Some(Key::F3),
+        KeyCode::F4 => Some(Key::F4),
+        KeyCode::F5 => Some(Key::F5),
+        KeyCode::F6 => Some(Key::F6),
+        KeyCode::F7 => Some(Key::F7),
+        KeyCode::F8 => Some(Key::F8),
+        KeyCode::F9 => Some(Key::F9),
+        KeyCode::F10 => Some(Key::F10),
+        KeyCode::F11 => Some(Key::F11),
+        KeyCode::F12 => Some(Key::F12),
+        _ => None,
+    }
+}
@@ -0,0 +1,194 @@
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::commands::CommandArgument;
+use crate::render::DrawList;
+use crate::state::{write_debug_state, DebugState};
+
+const INSPECTOR_LINE_HEIGHT: f32 = 18.;
+
+/// Exposes named fields of the game object, values use the command argument
+/// types so they can be edited from the console.
+pub trait InspectorProvider: Send + Sync {
+    fn fields(&self) -> Vec<&'static str>;
+
+    fn get(&self, field: &str) -> Option<CommandArgument>;
+
+    fn set(&mut self, field: &str, value: &CommandArgument) -> Result<(), String>;
+}
+
+pub struct InspectedObject {
+    pub name: String,
+    pub provider: Box<dyn InspectorProvider>,
+}
+
+#[derive(Default)]
+pub struct InspectorState {
+    pub objects: Vec<InspectedObject>,
+    /// Object shown in the overlay.
+    pub shown: Option<String>,
+}
+
+/// Registers the object, provider of the object with the same name is replaced.
+pub fn register(name: &str, provider: Box<dyn InspectorProvider>) {
+    let debug_state = &mut write_debug_state();
+    register_in(&mut debug_state.inspector, name, provider);
+}
+
+pub fn unregister(name: &str) {
+    let debug_state = &mut write_debug_state();
+    let inspector = &mut debug_state.inspector;
+
+    inspector.objects.retain(|object| object.name != name);
+
+    if inspector.shown.as_deref() == Some(name) {
+        inspector.shown = None;
+    }
+}
+
+pub(crate) fn register_in(
+    inspector: &mut InspectorState,
+    name: &str,
+    provider: Box<dyn InspectorProvider>,
+) {
+    match inspector
+        .objects
+        .iter_mut()
+        .find(|object| object.name == name)
+    {
+        Some(object) => object.provider = provider,
+        None => inspector.objects.push(InspectedObject {
+            name: String::from(name),
+            provider,
+        }),
+    }
+}
+
+pub fn find<'a>(inspector: &'a InspectorState, name: &str) -> Result<&'a InspectedObject, String> {
+    inspector
+        .objects
+        .iter()
+        .find(|object| object.name == name)
+        .ok_or_else(|| format!("Object '{}' not found", name))
+}
+
+pub fn find_mut<'a>(
+    inspector: &'a mut InspectorState,
+    name: &str,
+) -> Result<&'a mut InspectedObject, String> {
+    inspector
+        .objects
+        .iter_mut()
+        .find(|object| object.name == name)
+        .ok_or_else(|| format!("Object '{}' not found", name))
+}
+
+/// Field names with their current values, fields without a value are shown empty.
+pub fn field_values(object: &InspectedObject) -> Vec<(&'static str, String)> {
+    object
+        .provider
+        .fields()
+        .into_iter()
+        .map(|field| {
+            let value = object
+                .provider
+                .get(field)
+                .map_or_else(String::new, |value| value.to_string());
+            (field, value)
+        })
+        .collect()
+}
+
+/// Renders fields of the shown object, `position` is the top left corner
+/// in screen space, returns the size of the panel.
+pub fn update(
+    debug_state: &DebugState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    let inspector = &debug_state.inspector;
+    let object = match inspector
+        .shown
+        .as_deref()
+        .and_then(|name| find(inspector, name).ok())
+    {
+        Some(object) => object,
+        None => return Vec2f::ZERO,
+    };
+
+    let mut y = viewport_size.y - position.y - INSPECTOR_LINE_HEIGHT;
+    draw_list.text(camera, Vec2f::new(position.x, y), object.name.clone());
+
+    let fields = field_values(object);
+
+    for (field, value) in fields.iter() {
+        y -= INSPECTOR_LINE_HEIGHT;
+
+        draw_list.text(
+            camera,
+            Vec2f::new(position.x, y),
+            format!("  {}: {}", field, value),
+        );
+    }
+
+    Vec2f::new(0., (fields.len() + 1) as f32 * INSPECTOR_LINE_HEIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::CommandArgument;
+    use crate::inspector;
+
+    struct Player {
+        speed: f64,
+    }
+
+    impl inspector::InspectorProvider for Player {
+        fn fields(&self) -> Vec<&'static str> {
+            vec!["speed", "name"]
+        }
+
+        fn get(&self, field: &str) -> Option<CommandArgument> {
+            match field {
+                "speed" => Some(CommandArgument::Number(self.speed)),
+                _ => None,
+            }
+        }
+
+        fn set(&mut self, field: &str, value: &CommandArgument) -> Result<(), String> {
+            match field {
+                "speed" => self.speed = value.as_number()?,
+                _ => return Err(format!("Field '{}' is read only", field)),
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn register_and_set() {
+        let mut state = inspector::InspectorState::default();
+
+        inspector::register_in(&mut state, "player", Box::new(Player { speed: 1. }));
+        inspector::register_in(&mut state, "player", Box::new(Player { speed: 2. }));
+
+        assert_eq!(1, state.objects.len());
+        assert!(inspector::find(&state, "enemy").is_err());
+
+        let object = inspector::find_mut(&mut state, "player").unwrap();
+
+        assert!(object
+            .provider
+            .set("speed", &CommandArgument::String(String::from("fast")))
+            .is_err());
+        assert_eq!(
+            Ok(()),
+            object.provider.set("speed", &CommandArgument::Number(4.5))
+        );
+        assert_eq!(
+            vec![("speed", String::from("4.5")), ("name", String::new())],
+            inspector::field_values(object)
+        );
+    }
+}
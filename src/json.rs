@@ -0,0 +1,256 @@
+//! Minimal JSON parser for the remote requests and the saved reports,
+//! output is written with `format::json_string` instead.
+
+/// Deeper arrays and objects are rejected, so untrusted input can't overflow the stack.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Parsed JSON value.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            chars: text.chars().peekable(),
+            depth: 0,
+        };
+
+        let value = parser.value()?;
+        parser.skip_whitespace();
+
+        match parser.chars.next() {
+            Some(ch) => Err(format!("unexpected '{}' after the value", ch)),
+            None => Ok(value),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// Number of the arrays and objects being parsed.
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.chars.peek() {
+            if !ch.is_whitespace() {
+                break;
+            }
+
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => Err(format!("expected '{}', found '{}'", expected, ch)),
+            None => Err(format!("expected '{}', found end of input", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(_) => self.number(),
+            None => Err(String::from("unexpected end of input")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(format!("nesting is deeper than {}", MAX_NESTING_DEPTH));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        value
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut text = String::new();
+
+        while let Some(ch) = self.chars.peek() {
+            if !(ch.is_ascii_digit() || "+-.eE".contains(*ch)) {
+                break;
+            }
+
+            text.push(*ch);
+            self.chars.next();
+        }
+
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("bad number '{}'", text))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+
+        let mut result = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(result),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some('b') => result.push('\u{8}'),
+                    Some('f') => result.push('\u{c}'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let ch = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(std::char::from_u32)
+                            .ok_or_else(|| format!("bad escape '\\u{}'", code))?;
+
+                        result.push(ch);
+                    }
+                    Some(ch) => result.push(ch),
+                    None => return Err(String::from("unterminated string")),
+                },
+                Some(ch) => result.push(ch),
+                None => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(values)),
+                _ => return Err(String::from("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return Err(String::from("expected ',' or '}' in object")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{Json, MAX_NESTING_DEPTH};
+
+    #[test]
+    fn parse() {
+        let value =
+            Json::parse(r#" {"name": "a\"b", "values": [1, -2.5e1], "ok": true} "#).unwrap();
+
+        assert_eq!(Some("a\"b"), value.get("name").and_then(Json::as_str));
+        assert_eq!(
+            Some(&[Json::Number(1.), Json::Number(-25.)][..]),
+            value.get("values").and_then(Json::as_array)
+        );
+        assert_eq!(Some(&Json::Bool(true)), value.get("ok"));
+        assert_eq!(None, value.get("missing").and_then(Json::as_number));
+        assert!(Json::parse(r#"{"name": }"#).is_err());
+        assert!(Json::parse("[1] 2").is_err());
+    }
+
+    #[test]
+    fn parse_deeply_nested() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(Json::parse(&nested(MAX_NESTING_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_NESTING_DEPTH + 1)).is_err());
+        assert!(Json::parse(&"[{\"a\":".repeat(100_000)).is_err());
+    }
+}
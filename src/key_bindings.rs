@@ -0,0 +1,160 @@
+//! Console commands bound to keys, e.g. `input::bind "F3" "profile::pause"`.
+
+use std::collections::HashMap;
+
+use crate::commands;
+use crate::debug_variables::SavedValue;
+use crate::input::{InputEvent, Key};
+use crate::state::DebugState;
+
+const BINDINGS_SAVED_PREFIX: &str = "Bindings/";
+
+#[derive(Default)]
+pub struct KeyBindingsState {
+    /// Bound keys with their commands in the order they were bound.
+    pub bindings: Vec<(Key, String)>,
+}
+
+impl KeyBindingsState {
+    pub fn command(&self, key: Key) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|(bound_key, _)| *bound_key == key)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// Adds bindings to the values saved with the debug variables.
+    pub fn collect_values(&self, values: &mut HashMap<String, SavedValue>) {
+        values.retain(|key, _| !key.starts_with(BINDINGS_SAVED_PREFIX));
+
+        for (key, command) in self.bindings.iter() {
+            values.insert(
+                format!("{}{}", BINDINGS_SAVED_PREFIX, key.name()),
+                SavedValue::String(command.clone()),
+            );
+        }
+    }
+
+    /// Restores bindings from the values loaded with the debug variables.
+    pub fn apply_values(&mut self, values: &HashMap<String, SavedValue>) {
+        let mut saved: Vec<(&String, &SavedValue)> = values.iter().collect();
+        saved.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, value) in saved {
+            let key = name
+                .strip_prefix(BINDINGS_SAVED_PREFIX)
+                .and_then(Key::from_name);
+
+            if let (Some(key), SavedValue::String(command)) = (key, value) {
+                bind_key(self, key, command);
+            }
+        }
+    }
+}
+
+/// Binds the command to the key, replaces the previous command of the key.
+pub fn bind(state: &mut KeyBindingsState, key_name: &str, command: &str) -> Result<(), String> {
+    let key = Key::from_name(key_name).ok_or_else(|| format!("Unknown key '{}'", key_name))?;
+
+    if command.trim().is_empty() {
+        return Err(String::from("Command should not be empty"));
+    }
+
+    bind_key(state, key, command);
+    Ok(())
+}
+
+fn bind_key(state: &mut KeyBindingsState, key: Key, command: &str) {
+    match state
+        .bindings
+        .iter_mut()
+        .find(|(bound_key, _)| *bound_key == key)
+    {
+        Some(binding) => binding.1 = String::from(command),
+        None => state.bindings.push((key, String::from(command))),
+    }
+}
+
+pub fn unbind(state: &mut KeyBindingsState, key_name: &str) -> Result<(), String> {
+    let key = Key::from_name(key_name).ok_or_else(|| format!("Unknown key '{}'", key_name))?;
+
+    match state
+        .bindings
+        .iter()
+        .position(|(bound_key, _)| *bound_key == key)
+    {
+        Some(idx) => {
+            state.bindings.remove(idx);
+            Ok(())
+        }
+        None => Err(format!("'{}' is not bound", key.name())),
+    }
+}
+
+/// Executes the command bound to the pressed key, returns true if the event was consumed.
+/// Bindings are skipped while the console, the command palette or a text field takes the input.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    let key = match event {
+        InputEvent::KeyDown(key) => *key,
+        _ => return false,
+    };
+
+    let typing = debug_state.console.visible
+        || debug_state.palette.visible
+        || debug_state.ui.focused.is_some();
+
+    if debug_state.visible && typing {
+        return false;
+    }
+
+    let command = match debug_state.key_bindings.command(key) {
+        Some(command) => String::from(command),
+        None => return false,
+    };
+
+    if let Err(err) = commands::execute(debug_state, &command) {
+        log::warn!("Command bound to {} failed: {}", key.name(), err);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::input::Key;
+    use crate::key_bindings::{self, KeyBindingsState};
+
+    #[test]
+    fn bind_and_persist() {
+        let mut state = KeyBindingsState::default();
+
+        assert_eq!(
+            Ok(()),
+            key_bindings::bind(&mut state, "F3", "profile::pause")
+        );
+        assert_eq!(Ok(()), key_bindings::bind(&mut state, "f5", "time::step"));
+        assert_eq!(
+            Ok(()),
+            key_bindings::bind(&mut state, "F3", "profile::resume")
+        );
+        assert!(key_bindings::bind(&mut state, "Q", "time::step").is_err());
+        assert!(key_bindings::bind(&mut state, "F6", " ").is_err());
+
+        assert_eq!(Some("profile::resume"), state.command(Key::F3));
+        assert_eq!(2, state.bindings.len());
+
+        let mut values = HashMap::new();
+        state.collect_values(&mut values);
+
+        assert_eq!(Ok(()), key_bindings::unbind(&mut state, "F5"));
+        assert!(key_bindings::unbind(&mut state, "F5").is_err());
+
+        let mut restored = KeyBindingsState::default();
+        restored.apply_values(&values);
+
+        assert_eq!(Some("profile::resume"), restored.command(Key::F3));
+        assert_eq!(Some("time::step"), restored.command(Key::F5));
+    }
+}
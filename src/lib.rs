@@ -1,131 +1,1116 @@
+pub mod collections;
 pub mod commands;
+pub mod cvars;
+pub mod debug_draw;
+pub mod debug_variables;
+pub mod debug_watch;
+pub mod format;
+pub mod inspector;
+pub mod logging;
+#[cfg(feature = "metrics-http")]
+pub mod metrics_http;
+pub mod profile_report;
 pub mod profiler;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod sampling;
+pub mod screenshot;
+#[cfg(feature = "tcp-console")]
+pub mod tcp_console;
+pub mod time_control;
 
+mod command_palette;
 mod commands_registry;
+mod conditions;
+mod console;
+mod debug_menu;
+mod input;
+mod json;
+mod key_bindings;
+mod panel;
+mod profiler_pins;
+mod profiler_widget;
+mod render;
+mod scheduler;
+mod services;
 mod state;
+mod theme;
+#[cfg(feature = "tracy")]
+mod tracy;
+mod ui;
+mod windows;
 
-use profiler::{ProfileState, PROFILE_STATE};
-use state::DEBUG_STATE;
+pub use input::Key;
+pub use services::DebugServices;
+
+#[cfg(feature = "macros")]
+pub use debug_services_macros::profile;
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use commands::PermissionLevel;
+use input::InputEvent;
+use profiler::{ProfileState, FRAME_HISTORY_SIZE};
+use render::DrawList;
+use state::{DebugState, CAMERA_WHEEL_ZOOM_STEP};
+use theme::Theme;
 use vm::{
     gapi,
     module::{Module, ModuleState, StepState, CLIENT_ID},
 };
-use vm_math::{
-    create_2d_model_matrix, create_ortho_camera_matrices, CameraMatrices, Mat4f,
-    OthroCameraTransforms, Transforms2D, Vec2f,
-};
+use vm_math::{create_ortho_camera_matrices, CameraMatrices, OthroCameraTransforms, Vec2f, Vec4f};
+use windows::WindowId;
+
+const FRAME_TIME_TEXT_HEIGHT: f32 = 24.;
+const FRAME_TIME_TEXT_WIDTH: f32 = 300.;
+const FRAME_GRAPH_BAR_WIDTH: f32 = 2.;
+const FRAME_GRAPH_HEIGHT: f32 = 60.;
+const FRAME_GRAPH_TARGET_MS: f32 = 1000. / 60.;
+const USER_COUNTER_LINE_HEIGHT: f32 = 18.;
+const MODULE_BREAKDOWN_BAR_HEIGHT: f32 = 10.;
+const MODULE_BREAKDOWN_LINE_HEIGHT: f32 = 18.;
+const MODULE_BREAKDOWN_MARKER_SIZE: f32 = 8.;
+const MODULE_BREAKDOWN_MARKER_MARGIN: f32 = 6.;
+const TOP_BARS_COUNT: usize = 8;
+const TOP_BARS_WIDTH: f32 = 300.;
+const TOP_BARS_LINE_HEIGHT: f32 = 18.;
+const TOP_BARS_MARGIN: f32 = 2.;
+const SPANS_TIMELINE_DURATION: Duration = Duration::from_secs(10);
+const SPANS_TIMELINE_WIDTH: f32 = 300.;
+const SPANS_TIMELINE_ROWS: usize = 6;
+const SPANS_TIMELINE_LINE_HEIGHT: f32 = 18.;
+const FRAME_TIMELINE_WIDTH: f32 = 480.;
+const FRAME_TIMELINE_LINE_HEIGHT: f32 = 18.;
+const FRAME_TIMELINE_ROW_HEIGHT: f32 = 16.;
+const FRAME_TIMELINE_MAX_ROWS: usize = 12;
+
+#[derive(Clone, Debug)]
+pub struct DebugServicesConfig {
+    /// Script with commands executed on module init.
+    pub autoexec_path: Option<String>,
+    /// Shows and hides the whole overlay, hidden overlay doesn't handle input.
+    pub overlay_toggle_key: Key,
+    /// Pauses the game time or advances the paused game time by a single frame.
+    pub time_step_key: Key,
+    /// Window scale factor, e.g. 2 on high-DPI displays,
+    /// see `DebugServicesModule::set_scale_factor` for the later changes.
+    pub scale_factor: f32,
+    /// Profiler buffer sizes, see `profile::configure` for the later changes.
+    pub profiler: profiler::ProfilerConfig,
+    /// File the `profile_report::ProfileReport` is written to on module shutdown.
+    pub report_path: Option<String>,
+    /// Highest protection of the commands allowed to execute,
+    /// e.g. `PermissionLevel::Normal` in shipping builds.
+    pub permission_level: PermissionLevel,
+}
+
+impl Default for DebugServicesConfig {
+    fn default() -> Self {
+        DebugServicesConfig {
+            autoexec_path: None,
+            overlay_toggle_key: Key::Grave,
+            time_step_key: Key::F10,
+            scale_factor: 1.,
+            profiler: profiler::ProfilerConfig::default(),
+            report_path: None,
+            permission_level: PermissionLevel::Developer,
+        }
+    }
+}
 
 pub struct DebugServicesModule {
-    frametime_text_mvp_matrix: Mat4f,
+    config: DebugServicesConfig,
+    services: DebugServices,
+    /// Size of the widgets stack in the previous frame, used to anchor it.
+    overlay_size: Vec2f,
     screen_camera_matrices: CameraMatrices,
     screen_camera_transform: OthroCameraTransforms,
+    /// Camera of the game world the `debug_draw` shapes are in, see `set_world_camera`.
+    world_camera_matrices: Option<CameraMatrices>,
+    draw_list: DrawList,
+    /// Drawn after `draw_list`, e.g. frame time text on top of the budget flash.
+    top_draw_list: DrawList,
+    world_draw_list: DrawList,
 }
 
 struct DebugContext<'a> {
     pos: Vec2f,
-    _profile_state: &'a ProfileState,
+    /// Viewport size in logical pixels.
+    viewport_size: Vec2f,
+    theme: Theme,
+    profile_state: &'a ProfileState,
 }
 
 impl Default for DebugServicesModule {
     fn default() -> Self {
-        Self::new()
+        Self::new(DebugServicesConfig::default())
     }
 }
 
 impl DebugServicesModule {
-    pub fn new() -> Self {
+    /// Creates the module working with the global `DebugServices`.
+    pub fn new(config: DebugServicesConfig) -> Self {
+        Self::with_services(config, DebugServices::global())
+    }
+
+    /// Creates the module owning the given states, e.g. `DebugServices::new()`
+    /// for each of the VM instances hosted in the same process.
+    pub fn with_services(config: DebugServicesConfig, services: DebugServices) -> Self {
         Self {
-            frametime_text_mvp_matrix: Mat4f::IDENT,
+            config,
+            services,
+            overlay_size: Vec2f::ZERO,
             screen_camera_matrices: CameraMatrices::default(),
             screen_camera_transform: OthroCameraTransforms {
                 viewport_size: Vec2f::new(1024., 768.),
                 position: Vec2f::ZERO,
                 zoom: 1.,
             },
+            world_camera_matrices: None,
+            draw_list: DrawList::default(),
+            top_draw_list: DrawList::default(),
+            world_draw_list: DrawList::default(),
         }
     }
 
+    pub fn services(&self) -> &DebugServices {
+        &self.services
+    }
+
+    /// Updates the window scale factor, e.g. when the window is moved to another display.
+    pub fn set_scale_factor(&self, scale_factor: f32) {
+        self.services
+            .debug_state()
+            .layout
+            .set_window_scale(scale_factor);
+    }
+
+    /// Changes the highest protection of the commands allowed to execute,
+    /// e.g. to unlock cheats after the developer mode is entered.
+    pub fn set_permission_level(&self, permission_level: PermissionLevel) {
+        self.services.debug_state().permission_level = permission_level;
+    }
+
+    /// Updates the camera `debug_draw` shapes are projected with, should be called
+    /// each frame the game camera moves. Shapes are drawn in screen space until it's set.
+    pub fn set_world_camera(&mut self, camera_matrices: CameraMatrices) {
+        self.world_camera_matrices = Some(camera_matrices);
+    }
+
     fn update_camera(&mut self) {
         self.screen_camera_matrices = create_ortho_camera_matrices(self.screen_camera_transform);
     }
 
-    fn update_frametime_text(&mut self) -> Vec2f {
-        let transforms = Transforms2D {
-            position: Vec2f::new(10.0, self.screen_camera_transform.viewport_size.y - 24.),
-            scaling: Vec2f::new(1., 1.),
-            rotation: 0.,
+    fn update_frametime_text(&mut self, context: &DebugContext) -> Vec2f {
+        let top = context.viewport_size.y - context.pos.y;
+        let profile_state = context.profile_state;
+
+        self.top_draw_list.text(
+            &self.screen_camera_matrices,
+            Vec2f::new(context.pos.x, top - FRAME_TIME_TEXT_HEIGHT),
+            format!(
+                "FPS {:.1} ({:.1} ms avg, {:.1} ms max)",
+                profile_state.fps,
+                profile_state.smoothed_frame_time.as_secs_f64() * 1000.,
+                profile_state.fps_window_max.as_secs_f64() * 1000.,
+            ),
+        );
+
+        if let Some(intensity) = profiler::budget_flash_intensity(context.profile_state) {
+            let error = context.theme.error;
+
+            self.draw_list.rect(
+                &self.screen_camera_matrices,
+                Vec2f::new(context.pos.x - 6., top - FRAME_TIME_TEXT_HEIGHT - 4.),
+                Vec2f::new(FRAME_TIME_TEXT_WIDTH, FRAME_TIME_TEXT_HEIGHT),
+                Vec4f::new(error.x, error.y, error.z, error.w * 0.8 * intensity),
+            );
+        }
+
+        Vec2f::new(FRAME_TIME_TEXT_WIDTH, FRAME_TIME_TEXT_HEIGHT)
+    }
+
+    fn update_frametime_graph(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let width = FRAME_HISTORY_SIZE as f32 * FRAME_GRAPH_BAR_WIDTH;
+        let top = context.viewport_size.y - context.pos.y;
+        let bottom = top - FRAME_GRAPH_HEIGHT;
+
+        self.draw_list.rect(
+            camera,
+            Vec2f::new(context.pos.x, bottom),
+            Vec2f::new(width, FRAME_GRAPH_HEIGHT),
+            context.theme.background,
+        );
+
+        let stats = profiler::frame_time_stats(context.profile_state);
+        let scale_ms = (stats.max.as_secs_f32() * 1000.).max(FRAME_GRAPH_TARGET_MS * 2.);
+
+        for (i, frame_time) in context.profile_state.frame_history.iter().enumerate() {
+            let ms = frame_time.as_secs_f32() * 1000.;
+            let height = (ms / scale_ms).min(1.) * FRAME_GRAPH_HEIGHT;
+            let color = if ms <= FRAME_GRAPH_TARGET_MS {
+                context.theme.normal
+            } else if ms <= FRAME_GRAPH_TARGET_MS * 2. {
+                context.theme.warning
+            } else {
+                context.theme.error
+            };
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(context.pos.x + i as f32 * FRAME_GRAPH_BAR_WIDTH, bottom),
+                Vec2f::new(FRAME_GRAPH_BAR_WIDTH, height),
+                color,
+            );
+        }
+
+        let mut marker_labels = Vec::new();
+
+        for marker in context.profile_state.event_markers.iter() {
+            let index = match profiler::marker_frame_index(context.profile_state, marker) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(
+                    context.pos.x + (index as f32 + 0.5) * FRAME_GRAPH_BAR_WIDTH,
+                    bottom,
+                ),
+                Vec2f::new(1., FRAME_GRAPH_HEIGHT),
+                render::color(0.3, 0.6, 1., 1.),
+            );
+            marker_labels.push(marker.label.as_str());
+        }
+
+        let target_y = bottom + FRAME_GRAPH_TARGET_MS / scale_ms * FRAME_GRAPH_HEIGHT;
+
+        self.draw_list.rect(
+            camera,
+            Vec2f::new(context.pos.x, target_y),
+            Vec2f::new(width, 1.),
+            render::color(1., 1., 1., 0.5),
+        );
+
+        let labels = format!(
+            "min {} avg {} max {} p99 {}",
+            format::duration(stats.min),
+            format::duration(stats.avg),
+            format::duration(stats.max),
+            format::duration(stats.p99),
+        );
+
+        self.draw_list
+            .text(camera, Vec2f::new(context.pos.x, bottom - 20.), labels);
+
+        if marker_labels.is_empty() {
+            return Vec2f::new(width, FRAME_GRAPH_HEIGHT + 24.);
+        }
+
+        self.draw_list.text(
+            camera,
+            Vec2f::new(context.pos.x, bottom - 38.),
+            format!("markers: {}", marker_labels.join(", ")),
+        );
+
+        Vec2f::new(width, FRAME_GRAPH_HEIGHT + 42.)
+    }
+
+    fn update_module_breakdown(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let module_times = &context.profile_state.last_module_frame_times;
+
+        if module_times.is_empty() {
+            return Vec2f::new(0., 0.);
+        }
+
+        let width = FRAME_HISTORY_SIZE as f32 * FRAME_GRAPH_BAR_WIDTH;
+        let top = context.viewport_size.y - context.pos.y;
+        let bottom = top - MODULE_BREAKDOWN_BAR_HEIGHT;
+        let total = context
+            .profile_state
+            .frame_elapsed
+            .max(module_times.iter().map(|time| time.elapsed).sum());
+
+        self.draw_list.rect(
+            camera,
+            Vec2f::new(context.pos.x, bottom),
+            Vec2f::new(width, MODULE_BREAKDOWN_BAR_HEIGHT),
+            context.theme.background,
+        );
+
+        let mut x = context.pos.x;
+        let mut y = bottom;
+
+        for (i, module_time) in module_times.iter().enumerate() {
+            let color = palette_color(i);
+            let segment_width = if total.as_nanos() == 0 {
+                0.
+            } else {
+                module_time.elapsed.as_secs_f32() / total.as_secs_f32() * width
+            };
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(x, bottom),
+                Vec2f::new(segment_width, MODULE_BREAKDOWN_BAR_HEIGHT),
+                color,
+            );
+            x += segment_width;
+
+            y -= MODULE_BREAKDOWN_LINE_HEIGHT;
+
+            let margin = (MODULE_BREAKDOWN_LINE_HEIGHT - MODULE_BREAKDOWN_MARKER_SIZE) / 2.;
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(context.pos.x, y + margin),
+                Vec2f::new(MODULE_BREAKDOWN_MARKER_SIZE, MODULE_BREAKDOWN_MARKER_SIZE),
+                color,
+            );
+
+            self.draw_list.text(
+                camera,
+                Vec2f::new(
+                    context.pos.x + MODULE_BREAKDOWN_MARKER_SIZE + MODULE_BREAKDOWN_MARKER_MARGIN,
+                    y,
+                ),
+                format!(
+                    "{} {}",
+                    module_time.id,
+                    format::duration(module_time.elapsed)
+                ),
+            );
+        }
+
+        Vec2f::new(
+            width,
+            MODULE_BREAKDOWN_BAR_HEIGHT + module_times.len() as f32 * MODULE_BREAKDOWN_LINE_HEIGHT,
+        )
+    }
+
+    /// Timed blocks of the last frame recorded in the detail mode,
+    /// a row per nesting level of each thread.
+    fn update_frame_timeline(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let timeline = match context.profile_state.frame_timelines.last() {
+            Some(timeline) => timeline,
+            None => return Vec2f::new(0., 0.),
         };
 
-        let model_matrix = create_2d_model_matrix(transforms);
-        self.frametime_text_mvp_matrix = self.screen_camera_matrices.mvp_matrix * model_matrix;
+        // First row of each thread, threads are placed in order of appearance.
+        let mut threads: Vec<(std::thread::ThreadId, usize)> = Vec::new();
+        let mut rows = 0;
+
+        for event in timeline.events.iter() {
+            if !threads.iter().any(|(id, _)| *id == event.thread_id) {
+                let depth = timeline
+                    .events
+                    .iter()
+                    .filter(|other| other.thread_id == event.thread_id)
+                    .map(|other| other.depth as usize + 1)
+                    .max()
+                    .unwrap_or(1);
 
-        Vec2f::new(0., 24.)
+                threads.push((event.thread_id, rows));
+                rows += depth;
+            }
+        }
+
+        let rows = rows.clamp(1, FRAME_TIMELINE_MAX_ROWS);
+        let height = FRAME_TIMELINE_LINE_HEIGHT + rows as f32 * FRAME_TIMELINE_ROW_HEIGHT;
+        let top = context.viewport_size.y - context.pos.y;
+        let frame = timeline
+            .end
+            .saturating_sub(timeline.begin)
+            .as_secs_f32()
+            .max(f32::EPSILON);
+        let x = |time: Duration| {
+            let t = time.saturating_sub(timeline.begin).as_secs_f32() / frame;
+            context.pos.x + t.min(1.) * FRAME_TIMELINE_WIDTH
+        };
+
+        self.draw_list.rect(
+            camera,
+            Vec2f::new(context.pos.x, top - height),
+            Vec2f::new(FRAME_TIMELINE_WIDTH, height),
+            context.theme.background,
+        );
+
+        self.draw_list.text(
+            camera,
+            Vec2f::new(context.pos.x, top - FRAME_TIMELINE_LINE_HEIGHT),
+            format!(
+                "frame {} {}",
+                timeline.frame_number,
+                format::duration(timeline.end.saturating_sub(timeline.begin))
+            ),
+        );
+
+        for event in timeline.events.iter() {
+            let first_row = threads
+                .iter()
+                .find(|(id, _)| *id == event.thread_id)
+                .map_or(0, |(_, row)| *row);
+            let row = first_row + event.depth as usize;
+
+            if row >= rows {
+                continue;
+            }
+
+            let y = top - FRAME_TIMELINE_LINE_HEIGHT - (row + 1) as f32 * FRAME_TIMELINE_ROW_HEIGHT;
+            let left = x(event.begin);
+            let width = (x(event.end) - left).max(1.);
+            let color = palette_color(row);
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(left, y + 1.),
+                Vec2f::new(width, FRAME_TIMELINE_ROW_HEIGHT - 2.),
+                Vec4f::new(color.x, color.y, color.z, 0.6),
+            );
+
+            // Rough text width, names of the narrow blocks are skipped.
+            if event.name.len() as f32 * context.theme.font_size * 0.6 < width {
+                self.draw_list
+                    .text(camera, Vec2f::new(left, y), String::from(event.name));
+            }
+        }
+
+        Vec2f::new(FRAME_TIMELINE_WIDTH, height)
     }
-}
 
-impl Module for DebugServicesModule {
-    fn id(&self) -> &'static str {
-        "tech.paws.debug_services"
+    /// Spans within the last `SPANS_TIMELINE_DURATION` as bars on a timeline,
+    /// the right edge is now.
+    fn update_spans_timeline(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let now = context.profile_state.start_time.elapsed();
+        let since = now.saturating_sub(SPANS_TIMELINE_DURATION);
+        let spans = profiler::timeline_spans(context.profile_state, since);
+        let spans = &spans[spans.len().saturating_sub(SPANS_TIMELINE_ROWS)..];
+
+        if spans.is_empty() {
+            return Vec2f::new(0., 0.);
+        }
+
+        let height = spans.len() as f32 * SPANS_TIMELINE_LINE_HEIGHT;
+        let top = context.viewport_size.y - context.pos.y;
+        let x = |time: Duration| {
+            let t =
+                time.saturating_sub(since).as_secs_f32() / SPANS_TIMELINE_DURATION.as_secs_f32();
+            context.pos.x + t * SPANS_TIMELINE_WIDTH
+        };
+
+        self.draw_list.rect(
+            camera,
+            Vec2f::new(context.pos.x, top - height),
+            Vec2f::new(SPANS_TIMELINE_WIDTH, height),
+            context.theme.background,
+        );
+
+        for (i, span) in spans.iter().enumerate() {
+            let y = top - (i + 1) as f32 * SPANS_TIMELINE_LINE_HEIGHT;
+            let end = span.end.unwrap_or(now);
+            let left = x(span.begin);
+            let color = palette_color(i);
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(left, y + TOP_BARS_MARGIN),
+                Vec2f::new(
+                    (x(end) - left).max(1.),
+                    SPANS_TIMELINE_LINE_HEIGHT - TOP_BARS_MARGIN * 2.,
+                ),
+                Vec4f::new(color.x, color.y, color.z, 0.6),
+            );
+
+            let elapsed = format::duration(end.saturating_sub(span.begin));
+            let label = match span.end {
+                Some(_) => format!("{} {}", span.name, elapsed),
+                None => format!("{} {}...", span.name, elapsed),
+            };
+
+            self.draw_list
+                .text(camera, Vec2f::new(context.pos.x, y), label);
+        }
+
+        Vec2f::new(SPANS_TIMELINE_WIDTH, height)
     }
 
-    fn init(&mut self, _: &mut ModuleState) {
-        let debug_state = &mut DEBUG_STATE.lock().expect("failed to get debug state");
-        commands_registry::init(debug_state);
+    /// Category totals of the latest snapshot, a line per category.
+    fn update_category_totals(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let categories = &profiler::last_snapshot(context.profile_state).categories;
+        let mut y = context.viewport_size.y - context.pos.y;
+
+        for category in categories.iter() {
+            y -= TOP_BARS_LINE_HEIGHT;
+
+            self.draw_list.text(
+                camera,
+                Vec2f::new(context.pos.x, y),
+                format!(
+                    "[{}] {} {:.1}%",
+                    category.category,
+                    format::duration(category.sum_elapsed),
+                    category.percent
+                ),
+            );
+        }
+
+        Vec2f::new(
+            TOP_BARS_WIDTH,
+            categories.len() as f32 * TOP_BARS_LINE_HEIGHT,
+        )
     }
 
-    fn shutdown(&mut self, _: &mut ModuleState) {}
+    /// Counters of the latest snapshot over their budgets, see `profiler::assert_budget`.
+    fn update_budget_violations(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let violations = profiler::last_budget_violations(context.profile_state);
+        let mut y = context.viewport_size.y - context.pos.y;
+
+        for violation in violations.iter() {
+            y -= TOP_BARS_LINE_HEIGHT;
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(context.pos.x, y + TOP_BARS_MARGIN),
+                Vec2f::new(TOP_BARS_WIDTH, TOP_BARS_LINE_HEIGHT - TOP_BARS_MARGIN * 2.),
+                context.theme.error,
+            );
+            self.draw_list.text(
+                camera,
+                Vec2f::new(context.pos.x, y),
+                format!(
+                    "{} {} > {}",
+                    violation.counter,
+                    format::duration(violation.actual),
+                    format::duration(violation.budget)
+                ),
+            );
+        }
+
+        Vec2f::new(
+            TOP_BARS_WIDTH,
+            violations.len() as f32 * TOP_BARS_LINE_HEIGHT,
+        )
+    }
+
+    /// Top records of the latest snapshot as bars scaled by their percent,
+    /// colored by the thread.
+    fn update_top_bars(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let snapshot = profiler::last_snapshot(context.profile_state);
+        let records = profiler::top_records(
+            snapshot,
+            profiler::ProfilerSortKey::Percent,
+            "",
+            None,
+            TOP_BARS_COUNT,
+        );
+        let mut y = context.viewport_size.y - context.pos.y;
+
+        for record in records.iter() {
+            y -= TOP_BARS_LINE_HEIGHT;
+
+            let thread_index = snapshot
+                .threads
+                .iter()
+                .position(|thread| thread.thread_id == record.thread_id)
+                .unwrap_or(0);
+            let width = (record.percent / 100.).clamp(0., 1.) * TOP_BARS_WIDTH;
+            let color = palette_color(thread_index);
+
+            self.draw_list.rect(
+                camera,
+                Vec2f::new(context.pos.x, y + TOP_BARS_MARGIN),
+                Vec2f::new(width, TOP_BARS_LINE_HEIGHT - TOP_BARS_MARGIN * 2.),
+                Vec4f::new(color.x, color.y, color.z, 0.6),
+            );
+
+            self.draw_list.text(
+                camera,
+                Vec2f::new(context.pos.x, y),
+                format!("{} {:.1}%", record.name, record.percent),
+            );
+        }
+
+        Vec2f::new(TOP_BARS_WIDTH, records.len() as f32 * TOP_BARS_LINE_HEIGHT)
+    }
+
+    fn update_user_counters(&mut self, context: &DebugContext) -> Vec2f {
+        let camera = &self.screen_camera_matrices;
+        let snapshot = profiler::last_snapshot(context.profile_state);
+        let mut y = context.viewport_size.y - context.pos.y;
+
+        for counter in snapshot.user_counters.iter() {
+            y -= USER_COUNTER_LINE_HEIGHT;
+
+            let text = format!(
+                "{} {} (min {} avg {} max {})",
+                counter.name,
+                format_counter_value(counter.last),
+                format_counter_value(counter.min),
+                format_counter_value(counter.mean),
+                format_counter_value(counter.max),
+            );
+
+            self.draw_list
+                .text(camera, Vec2f::new(context.pos.x, y), text);
+        }
+
+        Vec2f::new(
+            0.,
+            snapshot.user_counters.len() as f32 * USER_COUNTER_LINE_HEIGHT,
+        )
+    }
+
+    /// Renders the window frame and its widgets on top of the windows rendered before.
+    fn update_window(
+        &mut self,
+        debug_state: &mut DebugState,
+        context: &mut DebugContext,
+        id: WindowId,
+    ) {
+        let visible = match id {
+            WindowId::Console => debug_state.console.visible,
+            WindowId::Profiler | WindowId::Menu => true,
+        };
+
+        let window = windows::begin(
+            &mut debug_state.windows,
+            id,
+            visible,
+            &context.theme,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+        );
+
+        let (position, size) = match window {
+            Some(window) => (window.content_position(), window.content_size()),
+            None => return,
+        };
+
+        let padding = context.theme.padding;
+        context.pos = Vec2f::new(position.x + padding, position.y + padding);
+
+        match id {
+            WindowId::Profiler => {
+                let size = self.update_frametime_text(context);
+                context.pos.y += size.y;
+
+                let size = self.update_frametime_graph(context);
+                context.pos.y += size.y;
+
+                let size = self.update_module_breakdown(context);
+                context.pos.y += size.y;
+
+                let size = self.update_budget_violations(context);
+                context.pos.y += size.y;
+
+                let size = self.update_category_totals(context);
+                context.pos.y += size.y;
+
+                let size = self.update_top_bars(context);
+                context.pos.y += size.y;
+
+                let size = self.update_spans_timeline(context);
+                context.pos.y += size.y;
+
+                let size = self.update_frame_timeline(context);
+                context.pos.y += size.y;
+
+                self.update_user_counters(context);
+            }
+            WindowId::Menu => {
+                debug_menu::update(
+                    debug_state,
+                    context.profile_state,
+                    &mut self.draw_list,
+                    &self.screen_camera_matrices,
+                    context.viewport_size,
+                    context.pos,
+                );
+            }
+            WindowId::Console => {
+                console::update(
+                    debug_state,
+                    &mut self.draw_list,
+                    &self.screen_camera_matrices,
+                    context.viewport_size,
+                    position,
+                    size,
+                );
+            }
+        }
+
+        windows::end(&mut self.draw_list);
+    }
+
+    fn render_overlay(&mut self, state: &mut ModuleState) {
+        // Framebuffer still holds the previous frame, overlay included if it was visible.
+        screenshot::process_requests();
+
+        if !self.services.read_debug_state().visible {
+            debug_draw::clear();
+            return;
+        }
+
+        let context = gapi::GApiContext {
+            from: self.id(),
+            address: CLIENT_ID,
+            commands_bus: &mut state.commands_bus,
+        };
+
+        let world_camera = self
+            .world_camera_matrices
+            .as_ref()
+            .unwrap_or(&self.screen_camera_matrices);
+        debug_draw::flush(&mut self.world_draw_list, world_camera);
+        self.world_draw_list.flush(&context);
+
+        // Frame time text goes on top of the budget flash.
+        self.draw_list.flush(&context);
+        self.top_draw_list.flush(&context);
+    }
+
+    fn step_overlay(&mut self, state: &mut ModuleState) {
+        let services = self.services.clone();
+        let debug_state = &mut services.debug_state();
+        logging::drain_pending_records(debug_state);
+        debug_draw::remove_expired();
+        time_control::begin_frame(&mut time_control::get_time_control_state());
+        let scale = debug_state.layout.pixel_scale();
 
-    fn step(&mut self, state: &mut ModuleState) -> StepState {
         for event in state.client_info.events.iter() {
-            #[allow(clippy::single_match)]
-            match event {
-                vm::module::ClientEvent::WindowResize { w, h } => {
-                    self.screen_camera_transform.viewport_size = Vec2f::new(*w, *h);
+            if let vm::module::ClientEvent::WindowResize { w, h } = event {
+                self.screen_camera_transform.viewport_size = Vec2f::new(*w, *h);
+            }
+
+            let event = match input::translate(event) {
+                Some(event) => event.scaled(scale),
+                None => continue,
+            };
+
+            if event == InputEvent::KeyDown(self.config.overlay_toggle_key) {
+                debug_state.visible = !debug_state.visible;
+                continue;
+            }
+
+            if event == InputEvent::KeyDown(self.config.time_step_key) {
+                step_time();
+                continue;
+            }
+
+            if key_bindings::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if !debug_state.visible {
+                continue;
+            }
+
+            if ui::handle_text_input(debug_state, &event) {
+                continue;
+            }
+
+            if command_palette::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if console::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if windows::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if profiler_widget::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if ui::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if panel::handle_input(debug_state, &event) {
+                continue;
+            }
+
+            if let InputEvent::MouseWheel(dy) = event {
+                if debug_state.camera.mouse_wheel_zoom {
+                    let zoom = debug_state.camera.zoom + dy * CAMERA_WHEEL_ZOOM_STEP;
+                    debug_state.camera.set_zoom(zoom);
                 }
-                _ => {}
             }
         }
 
-        let _debug_state = &mut DEBUG_STATE.lock().expect("failed to get debug state");
-        let profile_state = &mut PROFILE_STATE.lock().expect("failed to get profile state");
+        scheduler::tick(debug_state);
+
+        self.draw_list.clear();
+        self.top_draw_list.clear();
+
+        if !debug_state.visible {
+            return;
+        }
+
+        self.screen_camera_transform.zoom = debug_state.camera.zoom;
+        self.screen_camera_transform.position = debug_state.camera.position;
+        let profile_state = &mut services.profile_state();
+
+        // Scale might be changed by the commands executed above.
+        let scale = debug_state.layout.pixel_scale();
+        self.draw_list.scale = scale;
+        self.draw_list.set_theme(&debug_state.theme);
+        self.top_draw_list.scale = scale;
+        self.top_draw_list.set_theme(&debug_state.theme);
+
+        let viewport_size = Vec2f::new(
+            self.screen_camera_transform.viewport_size.x / scale,
+            self.screen_camera_transform.viewport_size.y / scale,
+        );
+        let origin = debug_state.layout.origin(viewport_size, self.overlay_size);
+        let mut width: f32 = 0.;
 
         let mut context = DebugContext {
-            pos: Vec2f::new(10.0, 10.0),
-            _profile_state: profile_state,
+            pos: origin,
+            viewport_size,
+            theme: debug_state.theme.clone(),
+            profile_state,
         };
 
         self.update_camera();
-        let size = self.update_frametime_text();
+
+        let size = profiler_pins::update(
+            &debug_state.pins,
+            context.profile_state,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+            context.pos,
+        );
 
         context.pos.y += size.y;
-        context.pos.x = 5.;
+        context.pos.x = origin.x;
+        width = width.max(size.x);
 
-        StepState::None
+        let size = debug_watch::update(
+            debug_state,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+            context.pos,
+        );
+
+        context.pos.y += size.y;
+        context.pos.x = origin.x;
+        width = width.max(size.x);
+
+        let size = inspector::update(
+            debug_state,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+            context.pos,
+        );
+
+        context.pos.y += size.y;
+        context.pos.x = origin.x;
+        width = width.max(size.x);
+
+        let size = logging::update(
+            debug_state,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+            context.pos,
+        );
+
+        context.pos.y += size.y;
+        width = width.max(size.x);
+        self.overlay_size = Vec2f::new(width, context.pos.y - origin.y);
+
+        for id in debug_state.windows.order() {
+            self.update_window(debug_state, &mut context, id);
+        }
+
+        command_palette::update(
+            debug_state,
+            &mut self.draw_list,
+            &self.screen_camera_matrices,
+            context.viewport_size,
+        );
+    }
+}
+
+fn format_counter_value(value: f64) -> String {
+    if value.fract() != 0. || value.abs() >= u64::MAX as f64 {
+        format!("{:.2}", value)
+    } else if value < 0. {
+        format!("-{}", format::count(-value as u64))
+    } else {
+        format::count(value as u64)
+    }
+}
+
+fn palette_color(index: usize) -> Vec4f {
+    const PALETTE: [(f32, f32, f32); 6] = [
+        (0.3, 0.6, 0.9),
+        (0.9, 0.5, 0.2),
+        (0.4, 0.8, 0.4),
+        (0.8, 0.3, 0.7),
+        (0.9, 0.8, 0.2),
+        (0.3, 0.8, 0.8),
+    ];
+
+    let (r, g, b) = PALETTE[index % PALETTE.len()];
+    render::color(r, g, b, 1.)
+}
+
+impl Module for DebugServicesModule {
+    fn id(&self) -> &'static str {
+        "tech.paws.debug_services"
+    }
+
+    fn init(&mut self, _: &mut ModuleState) {
+        if let Err(err) =
+            profiler::configure(&mut self.services.profile_state(), self.config.profiler)
+        {
+            log::warn!("Invalid profiler config: {}", err);
+        }
+
+        let debug_state = &mut self.services.debug_state();
+        debug_state.permission_level = self.config.permission_level;
+        debug_state
+            .layout
+            .set_window_scale(self.config.scale_factor);
+        commands_registry::init(debug_state);
+        debug_variables::init(debug_state);
+
+        if let Err(err) = debug_variables::load(debug_state, debug_variables::DEBUG_VARIABLES_PATH)
+        {
+            log::info!("Debug variables weren't restored: {}", err);
+        }
+
+        if let Err(err) = cvars::load(&mut debug_state.cvars, cvars::CVARS_PATH) {
+            log::info!("Cvars weren't restored: {}", err);
+        }
+
+        if let Some(path) = &self.config.autoexec_path {
+            run_autoexec(debug_state, path);
+        }
+    }
+
+    fn shutdown(&mut self, _: &mut ModuleState) {
+        sampling::stop();
+
+        let debug_state = &self.services.read_debug_state();
+
+        if let Err(err) = debug_variables::save(debug_state, debug_variables::DEBUG_VARIABLES_PATH)
+        {
+            log::warn!("Failed to save debug variables: {}", err);
+        }
+
+        if let Err(err) = cvars::save(&debug_state.cvars, cvars::CVARS_PATH) {
+            log::warn!("Failed to save cvars: {}", err);
+        }
+
+        if let Some(path) = &self.config.report_path {
+            let report = profile_report::report(&self.services.profile_state());
+
+            match profile_report::save(&report, path) {
+                Ok(()) => log::info!("Profile report is written to '{}'", path),
+                Err(err) => log::warn!("Failed to write profile report '{}': {}", path, err),
+            }
+        }
+    }
+
+    fn step(&mut self, state: &mut ModuleState) -> StepState {
+        // Debug and profile states are unlocked by the time the module ends.
+        profiler::module_begin(self.id());
+        let timer = Instant::now();
+        self.step_overlay(state);
+        self.services.profile_state().overhead.step += timer.elapsed();
+        profiler::module_end(self.id());
+
+        time_step_state(&time_control::time_control())
     }
 
     fn render(&mut self, state: &mut ModuleState) {
-        let context = gapi::GApiContext {
-            from: self.id(),
-            address: CLIENT_ID,
-            commands_bus: &mut state.commands_bus,
-        };
+        let timer = Instant::now();
+        self.render_overlay(state);
+        self.services.profile_state().overhead.render += timer.elapsed();
+    }
+}
 
-        let frame_time = format!("Frame Time: {:?}", state.last_time.elapsed());
-        let text = gapi::TextData {
-            font_id: 0,
-            font_size: 14,
-            mvp_matrix: self.frametime_text_mvp_matrix,
-            text: frame_time,
-        };
+fn step_time() {
+    let time_control_state = &mut time_control::get_time_control_state();
+
+    if time_control_state.current.paused {
+        // Can't fail, the time is paused.
+        let _ = time_control::request_step(time_control_state);
+    } else {
+        time_control::pause(time_control_state);
+    }
+}
+
+/// Paused game time halts the modules stepped after this one, unless a single step is requested.
+fn time_step_state(time_control: &time_control::TimeControl) -> StepState {
+    if time_control.is_running() {
+        StepState::None
+    } else {
+        StepState::Break
+    }
+}
 
-        gapi::draw_texts(&context, &[text]);
+fn run_autoexec(debug_state: &mut DebugState, path: &str) {
+    let script = match fs::read_to_string(path) {
+        Ok(script) => script,
+        Err(err) => {
+            log::warn!("Failed to read autoexec '{}': {}", path, err);
+            return;
+        }
+    };
+
+    for (line, err) in commands::execute_script(debug_state, &script) {
+        log::warn!("{}:{}: {}", path, line, err);
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::time_control::TimeControl;
+    use vm::module::StepState;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn time_step_state() {
+        let mut time_control = TimeControl::default();
+        assert!(matches!(
+            crate::time_step_state(&time_control),
+            StepState::None
+        ));
+
+        time_control.paused = true;
+        assert!(matches!(
+            crate::time_step_state(&time_control),
+            StepState::Break
+        ));
+
+        time_control.is_stepping = true;
+        assert!(matches!(
+            crate::time_step_state(&time_control),
+            StepState::None
+        ));
+    }
 }
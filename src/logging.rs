@@ -0,0 +1,210 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use vm_math::{CameraMatrices, Vec2f, Vec4f};
+
+use crate::collections::RingBuffer;
+use crate::panel::PanelState;
+use crate::render::{self, DrawList};
+use crate::state::{self, DebugState};
+use crate::theme::Theme;
+
+pub const LOG_CAPACITY: usize = 256;
+pub const LOG_VISIBLE_LINES: usize = 8;
+pub const DEFAULT_LOG_FILTER: LevelFilter = LevelFilter::Info;
+
+const LOG_LINE_HEIGHT: f32 = 18.;
+const LOG_PANEL_WIDTH: f32 = 600.;
+const LOG_LEVEL_MARKER_SIZE: f32 = 8.;
+const LOG_LEVEL_MARKER_MARGIN: f32 = 6.;
+
+lazy_static! {
+    // Records logged since the last `step`, the logger can't take the debug state lock
+    // because records are also logged while the debug state is locked.
+    static ref PENDING_RECORDS: Mutex<RingBuffer<LogRecord>> =
+        Mutex::new(RingBuffer::new(LOG_CAPACITY));
+}
+
+// Mirrors `LogState.filter`, so the logger doesn't lock the debug state.
+static LOG_FILTER: AtomicUsize = AtomicUsize::new(DEFAULT_LOG_FILTER as usize);
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+pub struct LogState {
+    /// Use `set_filter` to change, records above the filter are neither captured nor shown.
+    pub filter: LevelFilter,
+    /// Only records with targets containing one of these are shown, all if it's empty.
+    pub targets: Vec<String>,
+    pub records: RingBuffer<LogRecord>,
+    /// Number of records captured since the start, including the ones dropped from `records`.
+    pub total_records: u64,
+    pub panel: PanelState,
+}
+
+impl Default for LogState {
+    fn default() -> Self {
+        LogState {
+            filter: DEFAULT_LOG_FILTER,
+            targets: Vec::new(),
+            records: RingBuffer::new(LOG_CAPACITY),
+            total_records: 0,
+            panel: PanelState::following_end(),
+        }
+    }
+}
+
+impl LogState {
+    pub fn shows_target(&self, target: &str) -> bool {
+        self.targets.is_empty()
+            || self
+                .targets
+                .iter()
+                .any(|shown| target.contains(shown.as_str()))
+    }
+}
+
+/// Captures records for the overlay and forwards them to `env_logger`.
+struct DebugLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for DebugLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= filter() || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        if record.level() > filter() {
+            return;
+        }
+
+        // Not `state::recover`, it logs the poisoning and would reenter the logger
+        // while the records are locked.
+        PENDING_RECORDS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(LogRecord {
+                level: record.level(),
+                target: String::from(record.target()),
+                message: record.args().to_string(),
+            });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger, use instead of `env_logger::init`,
+/// the stderr output is still configured by `RUST_LOG`.
+pub fn init() -> Result<(), log::SetLoggerError> {
+    let inner = env_logger::Builder::from_default_env().build();
+
+    log::set_boxed_logger(Box::new(DebugLogger { inner }))?;
+    // The filter might be changed at runtime, so everything reaches the logger.
+    log::set_max_level(LevelFilter::Trace);
+
+    Ok(())
+}
+
+fn filter() -> LevelFilter {
+    match LOG_FILTER.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+pub fn set_filter(debug_state: &mut DebugState, filter: LevelFilter) {
+    debug_state.log.filter = filter;
+    LOG_FILTER.store(filter as usize, Ordering::Relaxed);
+}
+
+/// Moves records logged since the last call into the debug state.
+pub fn drain_pending_records(debug_state: &mut DebugState) {
+    let mut pending = state::recover(PENDING_RECORDS.lock(), "pending log records");
+
+    for record in pending.iter() {
+        debug_state.log.records.push(record.clone());
+        debug_state.log.total_records += 1;
+    }
+
+    pending.clear();
+}
+
+fn level_color(theme: &Theme, level: Level) -> Vec4f {
+    match level {
+        Level::Error => theme.error,
+        Level::Warn => theme.warning,
+        Level::Info => theme.normal,
+        Level::Debug => render::color(0.5, 0.5, 0.5, 1.),
+        Level::Trace => render::color(0.3, 0.3, 0.3, 1.),
+    }
+}
+
+/// Renders the records passing the filter scrolled to the latest ones,
+/// `position` is the top left corner in screen space, returns the size of the log.
+pub fn update(
+    debug_state: &mut DebugState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    let log_state = &mut debug_state.log;
+    let filter = log_state.filter;
+    let records: Vec<&LogRecord> = log_state
+        .records
+        .iter()
+        .filter(|record| record.level <= filter && log_state.shows_target(&record.target))
+        .collect();
+
+    let content_height = records.len() as f32 * LOG_LINE_HEIGHT;
+    let height = content_height.min(LOG_VISIBLE_LINES as f32 * LOG_LINE_HEIGHT);
+    let content_top = log_state.panel.begin(
+        draw_list,
+        viewport_size,
+        position,
+        Vec2f::new(LOG_PANEL_WIDTH, height),
+        content_height,
+    );
+    let mut y = viewport_size.y - content_top;
+
+    for record in records.iter() {
+        y -= LOG_LINE_HEIGHT;
+
+        let margin = (LOG_LINE_HEIGHT - LOG_LEVEL_MARKER_SIZE) / 2.;
+
+        draw_list.rect(
+            camera,
+            Vec2f::new(position.x, y + margin),
+            Vec2f::new(LOG_LEVEL_MARKER_SIZE, LOG_LEVEL_MARKER_SIZE),
+            level_color(&debug_state.theme, record.level),
+        );
+
+        draw_list.text(
+            camera,
+            Vec2f::new(
+                position.x + LOG_LEVEL_MARKER_SIZE + LOG_LEVEL_MARKER_MARGIN,
+                y,
+            ),
+            format!("[{}] {}: {}", record.level, record.target, record.message),
+        );
+    }
+
+    log_state.panel.end(draw_list, camera, viewport_size);
+
+    Vec2f::new(0., height)
+}
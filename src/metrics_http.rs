@@ -0,0 +1,166 @@
+//! Minimal HTTP endpoint serving `profiler::metrics_text` for Prometheus scraping.
+//!
+//! ```text
+//! scrape_configs:
+//!   - job_name: game
+//!     static_configs:
+//!       - targets: ["localhost:9184"]
+//! ```
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::profiler;
+
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_SIZE: usize = 8192;
+const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub struct MetricsHttpHandle {
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MetricsHttpHandle {
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::warn!("Metrics HTTP thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for MetricsHttpHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts serving metrics on `addr`, the listener stops when the handle is dropped.
+pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<MetricsHttpHandle> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+
+    log::info!(
+        "Metrics are served on http://{}{}",
+        listener.local_addr()?,
+        METRICS_PATH
+    );
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    let thread = thread::Builder::new()
+        .name(String::from("debug-metrics-http"))
+        .spawn(move || accept_loop(listener, thread_running))?;
+
+    Ok(MetricsHttpHandle {
+        running,
+        thread: Some(thread),
+    })
+}
+
+/// Scrapes are short and infrequent, so requests are served one by one.
+fn accept_loop(listener: TcpListener, running: Arc<AtomicBool>) {
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                if let Err(err) = serve_request(stream) {
+                    log::warn!("Metrics HTTP request from {} failed: {}", addr, err);
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+            Err(err) => {
+                log::warn!("Metrics HTTP accept failed: {}", err);
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn serve_request(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let request = read_request_head(&mut stream)?;
+    let (status, body) = match request_target(&request) {
+        Some(("GET", METRICS_PATH)) => ("200 OK", profiler::metrics_text()),
+        Some(("GET", _)) => ("404 Not Found", String::from("not found\n")),
+        Some(_) => (
+            "405 Method Not Allowed",
+            String::from("method not allowed\n"),
+        ),
+        None => ("400 Bad Request", String::from("bad request\n")),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        METRICS_CONTENT_TYPE,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/// Reads the request line and headers, the body is ignored.
+fn read_request_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head is too large",
+            ));
+        }
+
+        let read = stream.read(&mut buffer)?;
+
+        if read == 0 {
+            break;
+        }
+
+        head.extend_from_slice(&buffer[..read]);
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Method and path of the request, query is stripped from the path.
+fn request_target(request: &str) -> Option<(&str, &str)> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let path = target.split('?').next()?;
+
+    Some((method, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics_http;
+
+    #[test]
+    fn request_target() {
+        assert_eq!(
+            Some(("GET", "/metrics")),
+            metrics_http::request_target("GET /metrics?name[]=x HTTP/1.1\r\nHost: a\r\n\r\n")
+        );
+        assert_eq!(None, metrics_http::request_target(""));
+    }
+}
@@ -0,0 +1,173 @@
+//! Scrollable regions of the widgets, e.g. log or profiler table rows.
+//! Content outside of the visible part is clipped by the draw list.
+
+use vm_math::{CameraMatrices, Rect, Vec2f};
+
+use crate::debug_variables;
+use crate::input::InputEvent;
+use crate::render::{self, DrawList};
+use crate::state::DebugState;
+use crate::ui;
+
+/// Scroll distance of a single mouse wheel step.
+pub const PANEL_SCROLL_STEP: f32 = 54.;
+
+const PANEL_SCROLLBAR_WIDTH: f32 = 3.;
+
+pub struct PanelState {
+    /// Distance from the top of the content to the top of the visible part.
+    pub scroll: f32,
+    /// Content height in the last frame.
+    pub content_height: f32,
+    /// Visible part in screen space, set on render.
+    pub bounds: Rect,
+    /// Keeps the end of the content visible while it grows, unless scrolled up,
+    /// e.g. for logs.
+    pub follow_end: bool,
+}
+
+impl Default for PanelState {
+    fn default() -> Self {
+        PanelState {
+            scroll: 0.,
+            content_height: 0.,
+            bounds: Rect::ZERO,
+            follow_end: false,
+        }
+    }
+}
+
+impl PanelState {
+    pub fn following_end() -> Self {
+        PanelState {
+            follow_end: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn max_scroll(&self) -> f32 {
+        (self.content_height - self.bounds.size.y).max(0.)
+    }
+
+    pub fn scroll_by(&mut self, delta: f32) {
+        self.scroll = (self.scroll + delta).max(0.).min(self.max_scroll());
+    }
+
+    /// Scrolls the panel under the `point` by the mouse wheel `dy`,
+    /// returns true if the panel was hit.
+    pub fn wheel(&mut self, point: Vec2f, dy: f32) -> bool {
+        if !ui::rect_contains(&self.bounds, point) {
+            return false;
+        }
+
+        self.scroll_by(-dy * PANEL_SCROLL_STEP);
+        true
+    }
+
+    /// Sets the visible part, `position` is the top left corner in screen space,
+    /// and clips the draw list to it. Returns the top of the content in screen space.
+    pub fn begin(
+        &mut self,
+        draw_list: &mut DrawList,
+        viewport_size: Vec2f,
+        position: Vec2f,
+        size: Vec2f,
+        content_height: f32,
+    ) -> f32 {
+        let at_end = self.scroll >= self.max_scroll();
+
+        self.content_height = content_height;
+        self.bounds = Rect { position, size };
+
+        if self.follow_end && at_end {
+            self.scroll = self.max_scroll();
+        }
+
+        self.scroll_by(0.);
+
+        draw_list.push_clip(Rect {
+            position: Vec2f::new(position.x, viewport_size.y - position.y - size.y),
+            size,
+        });
+
+        position.y - self.scroll
+    }
+
+    /// Pops the clip and draws the scrollbar if the content doesn't fit.
+    pub fn end(&self, draw_list: &mut DrawList, camera: &CameraMatrices, viewport_size: Vec2f) {
+        draw_list.pop_clip();
+
+        if self.max_scroll() <= 0. {
+            return;
+        }
+
+        let bounds = &self.bounds;
+        let height = bounds.size.y * bounds.size.y / self.content_height;
+        let offset = self.scroll / self.content_height * bounds.size.y;
+
+        draw_list.rect(
+            camera,
+            Vec2f::new(
+                bounds.position.x + bounds.size.x - PANEL_SCROLLBAR_WIDTH,
+                viewport_size.y - bounds.position.y - offset - height,
+            ),
+            Vec2f::new(PANEL_SCROLLBAR_WIDTH, height),
+            render::color(1., 1., 1., 0.4),
+        );
+    }
+}
+
+/// Scrolls the panel under the mouse cursor, returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    let dy = match event {
+        InputEvent::MouseWheel(dy) => *dy,
+        _ => return false,
+    };
+
+    let point = debug_state.ui.mouse_position;
+
+    if debug_state.console.visible && debug_state.console.panel.wheel(point, dy) {
+        return true;
+    }
+
+    if debug_state.log.panel.wheel(point, dy) {
+        return true;
+    }
+
+    match debug_variables::find_profiler_mut(&mut debug_state.variables) {
+        Some(widget) => widget.panel.wheel(point, dy),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::panel::{self, PanelState};
+    use crate::render::DrawList;
+    use vm_math::Vec2f;
+
+    #[test]
+    fn follow_end() {
+        let mut state = PanelState::following_end();
+        let mut draw_list = DrawList::default();
+        let viewport_size = Vec2f::new(800., 600.);
+        let position = Vec2f::new(10., 10.);
+        let size = Vec2f::new(200., 100.);
+
+        let top = state.begin(&mut draw_list, viewport_size, position, size, 300.);
+        assert_eq!(-190., top);
+
+        state.scroll_by(-50.);
+        state.begin(&mut draw_list, viewport_size, position, size, 400.);
+        assert_eq!(150., state.scroll);
+
+        state.scroll_by(1000.);
+        state.begin(&mut draw_list, viewport_size, position, size, 500.);
+        assert_eq!(400., state.scroll);
+        assert_eq!(3, draw_list.clips.len());
+
+        assert!(state.wheel(Vec2f::new(50., 50.), 1.));
+        assert!(!state.wheel(Vec2f::new(250., 50.), 1.));
+        assert_eq!(400. - panel::PANEL_SCROLL_STEP, state.scroll);
+    }
+}
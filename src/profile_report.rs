@@ -0,0 +1,385 @@
+//! Summary of the profiler run, written on the module shutdown
+//! (see `DebugServicesConfig::report_path`) and compared against a baseline
+//! report in automated performance tests:
+//!
+//! ```text
+//! let report = profile_report::report(&profiler::get_profile_state());
+//! let comparison = profile_report::compare_with_baseline(
+//!     &report,
+//!     "perf/baseline.json",
+//!     &ReportThresholds::default(),
+//! )?;
+//!
+//! assert!(comparison.passed(), "{}", comparison.failures().join("\n"));
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use crate::format;
+use crate::json::Json;
+use crate::profiler::{self, ProfileState};
+
+pub const REPORT_TOP_COUNTERS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportCounter {
+    pub name: String,
+    /// Time per frame within the last snapshot, records of the same name are summed.
+    pub mean: Duration,
+    pub percent: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProfileReport {
+    /// Number of frames since the profiler start.
+    pub frames: u64,
+    /// Frame time statistics of the frames in the profiler history.
+    pub frame_mean: Duration,
+    pub frame_p50: Duration,
+    pub frame_p95: Duration,
+    pub frame_p99: Duration,
+    pub frame_max: Duration,
+    /// Frames over the spike threshold in the spike log.
+    pub spikes_count: usize,
+    pub top_counters: Vec<ReportCounter>,
+}
+
+/// Allowed regressions against the baseline.
+#[derive(Clone, Copy, Debug)]
+pub struct ReportThresholds {
+    /// Relative increase of the frame time mean and percentiles, e.g. 0.1 for 10%.
+    pub frame_time: f64,
+    /// Relative increase of the counter time.
+    pub counter: f64,
+    /// Counter changes below are ignored as noise.
+    pub counter_min_delta: Duration,
+    /// Additional spikes over the baseline.
+    pub spikes: usize,
+}
+
+impl Default for ReportThresholds {
+    fn default() -> Self {
+        ReportThresholds {
+            frame_time: 0.1,
+            counter: 0.2,
+            counter_min_delta: Duration::from_micros(100),
+            spikes: 0,
+        }
+    }
+}
+
+/// Single compared value, times are in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportCheck {
+    pub name: String,
+    pub baseline: f64,
+    pub actual: f64,
+    pub passed: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportComparison {
+    pub checks: Vec<ReportCheck>,
+}
+
+impl ReportComparison {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn failures(&self) -> Vec<String> {
+        self.checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| {
+                format!(
+                    "{}: {:.3} over the baseline {:.3}",
+                    check.name, check.actual, check.baseline
+                )
+            })
+            .collect()
+    }
+}
+
+pub fn report(profile_state: &ProfileState) -> ProfileReport {
+    let mut frame_times = profile_state.frame_history.to_vec();
+    frame_times.sort();
+
+    let frame_mean = if frame_times.is_empty() {
+        Duration::from_nanos(0)
+    } else {
+        frame_times.iter().sum::<Duration>() / frame_times.len() as u32
+    };
+
+    ProfileReport {
+        frames: profile_state.frame_number,
+        frame_mean,
+        frame_p50: profiler::percentile(&frame_times, 0.5),
+        frame_p95: profiler::percentile(&frame_times, 0.95),
+        frame_p99: profiler::percentile(&frame_times, 0.99),
+        frame_max: frame_times.last().copied().unwrap_or_default(),
+        spikes_count: profile_state.spike_log.len(),
+        top_counters: top_counters(profile_state),
+    }
+}
+
+fn top_counters(profile_state: &ProfileState) -> Vec<ReportCounter> {
+    let frames = profile_state.snapshot_interval.max(1) as u32;
+    let mut counters: Vec<ReportCounter> = Vec::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+
+    for record in profiler::last_snapshot(profile_state).records.iter() {
+        let index = *indices.entry(record.name).or_insert_with(|| {
+            counters.push(ReportCounter {
+                name: String::from(record.name),
+                mean: Duration::from_nanos(0),
+                percent: 0.,
+            });
+            counters.len() - 1
+        });
+
+        counters[index].mean += record.sum_elapsed / frames;
+        counters[index].percent += record.percent;
+    }
+
+    counters.sort_by(|a, b| b.mean.cmp(&a.mean));
+    counters.truncate(REPORT_TOP_COUNTERS);
+    counters
+}
+
+impl ProfileReport {
+    pub fn to_json(&self) -> String {
+        let counters: Vec<String> = self
+            .top_counters
+            .iter()
+            .map(|counter| {
+                format!(
+                    r#"{{"name":{},"mean_ms":{},"percent":{}}}"#,
+                    format::json_string(&counter.name),
+                    millis(counter.mean),
+                    counter.percent
+                )
+            })
+            .collect();
+
+        format!(
+            r#"{{"frames":{},"frame_mean_ms":{},"frame_p50_ms":{},"frame_p95_ms":{},"frame_p99_ms":{},"frame_max_ms":{},"spikes_count":{},"top_counters":[{}]}}"#,
+            self.frames,
+            millis(self.frame_mean),
+            millis(self.frame_p50),
+            millis(self.frame_p95),
+            millis(self.frame_p99),
+            millis(self.frame_max),
+            self.spikes_count,
+            counters.join(",")
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<ProfileReport, String> {
+        let json = Json::parse(text)?;
+        let mut top_counters = Vec::new();
+
+        for counter in json_field(&json, "top_counters", Json::as_array)?.iter() {
+            top_counters.push(ReportCounter {
+                name: String::from(json_field(counter, "name", Json::as_str)?),
+                mean: json_duration(counter, "mean_ms")?,
+                percent: json_field(counter, "percent", Json::as_number)? as f32,
+            });
+        }
+
+        Ok(ProfileReport {
+            frames: json_field(&json, "frames", Json::as_number)? as u64,
+            frame_mean: json_duration(&json, "frame_mean_ms")?,
+            frame_p50: json_duration(&json, "frame_p50_ms")?,
+            frame_p95: json_duration(&json, "frame_p95_ms")?,
+            frame_p99: json_duration(&json, "frame_p99_ms")?,
+            frame_max: json_duration(&json, "frame_max_ms")?,
+            spikes_count: json_field(&json, "spikes_count", Json::as_number)? as usize,
+            top_counters,
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+fn json_field<'a, T, F>(json: &'a Json, name: &str, convert: F) -> Result<T, String>
+where
+    F: Fn(&'a Json) -> Option<T>,
+{
+    json.get(name)
+        .and_then(convert)
+        .ok_or_else(|| format!("Report field '{}' is missing or invalid", name))
+}
+
+fn json_duration(json: &Json, name: &str) -> Result<Duration, String> {
+    let ms = json_field(json, name, Json::as_number)?;
+
+    if !ms.is_finite() || ms < 0. {
+        return Err(format!(
+            "Report field '{}' should be positive, found {}",
+            name, ms
+        ));
+    }
+
+    Ok(Duration::from_nanos((ms * 1_000_000.).round() as u64))
+}
+
+pub fn save(report: &ProfileReport, path: &str) -> io::Result<()> {
+    fs::write(path, report.to_json())
+}
+
+pub fn load(path: &str) -> Result<ProfileReport, String> {
+    let text =
+        fs::read_to_string(path).map_err(|err| format!("Failed to read '{}': {}", path, err))?;
+
+    ProfileReport::from_json(&text).map_err(|err| format!("Bad report '{}': {}", path, err))
+}
+
+/// Checks the frame times, spikes and counters present in both reports,
+/// counters missing in one of them are skipped.
+pub fn compare(
+    report: &ProfileReport,
+    baseline: &ProfileReport,
+    thresholds: &ReportThresholds,
+) -> ReportComparison {
+    let mut checks = Vec::new();
+
+    let frame_times = [
+        ("frame_mean", report.frame_mean, baseline.frame_mean),
+        ("frame_p50", report.frame_p50, baseline.frame_p50),
+        ("frame_p95", report.frame_p95, baseline.frame_p95),
+        ("frame_p99", report.frame_p99, baseline.frame_p99),
+    ];
+
+    for (name, actual, expected) in frame_times.iter() {
+        checks.push(ReportCheck {
+            name: String::from(*name),
+            baseline: millis(*expected),
+            actual: millis(*actual),
+            passed: millis(*actual) <= millis(*expected) * (1. + thresholds.frame_time),
+        });
+    }
+
+    checks.push(ReportCheck {
+        name: String::from("spikes_count"),
+        baseline: baseline.spikes_count as f64,
+        actual: report.spikes_count as f64,
+        passed: report.spikes_count <= baseline.spikes_count + thresholds.spikes,
+    });
+
+    for expected in baseline.top_counters.iter() {
+        let actual = match report
+            .top_counters
+            .iter()
+            .find(|counter| counter.name == expected.name)
+        {
+            Some(actual) => actual,
+            None => continue,
+        };
+
+        let limit = millis(expected.mean) * (1. + thresholds.counter);
+        let delta = actual.mean.checked_sub(expected.mean).unwrap_or_default();
+
+        checks.push(ReportCheck {
+            name: expected.name.clone(),
+            baseline: millis(expected.mean),
+            actual: millis(actual.mean),
+            passed: millis(actual.mean) <= limit || delta < thresholds.counter_min_delta,
+        });
+    }
+
+    ReportComparison { checks }
+}
+
+pub fn compare_with_baseline(
+    report: &ProfileReport,
+    baseline_path: &str,
+    thresholds: &ReportThresholds,
+) -> Result<ReportComparison, String> {
+    let baseline = load(baseline_path)?;
+
+    Ok(compare(report, &baseline, thresholds))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile_report::{self, ProfileReport, ReportCounter, ReportThresholds};
+    use std::time::Duration;
+
+    fn counter(name: &str, micros: u64) -> ReportCounter {
+        ReportCounter {
+            name: String::from(name),
+            mean: Duration::from_micros(micros),
+            percent: 25.,
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let report = ProfileReport {
+            frames: 600,
+            frame_mean: Duration::from_micros(16_500),
+            frame_p50: Duration::from_micros(16_250),
+            frame_p95: Duration::from_micros(18_000),
+            frame_p99: Duration::from_micros(21_000),
+            frame_max: Duration::from_micros(30_000),
+            spikes_count: 2,
+            top_counters: vec![counter("ai \"update\"", 4_000)],
+        };
+
+        let parsed = ProfileReport::from_json(&report.to_json()).unwrap();
+
+        assert_eq!(report, parsed);
+        assert!(ProfileReport::from_json(r#"{"frames":1}"#).is_err());
+    }
+
+    #[test]
+    fn compare_with_baseline() {
+        let baseline = ProfileReport {
+            frame_mean: Duration::from_millis(16),
+            frame_p50: Duration::from_millis(16),
+            frame_p95: Duration::from_millis(18),
+            frame_p99: Duration::from_millis(20),
+            spikes_count: 1,
+            top_counters: vec![
+                counter("ai", 4_000),
+                counter("audio", 50),
+                counter("ui", 500),
+            ],
+            ..Default::default()
+        };
+        let mut report = ProfileReport {
+            top_counters: vec![
+                counter("ai", 4_500),
+                counter("audio", 120),
+                counter("net", 900),
+            ],
+            ..baseline.clone()
+        };
+        let thresholds = ReportThresholds::default();
+
+        let comparison = profile_report::compare(&report, &baseline, &thresholds);
+        assert!(comparison.passed());
+        assert_eq!(7, comparison.checks.len());
+
+        report.frame_p95 = Duration::from_millis(20);
+        report.spikes_count = 2;
+        report.top_counters[0].mean = Duration::from_millis(5);
+
+        let comparison = profile_report::compare(&report, &baseline, &thresholds);
+        let failed: Vec<&str> = comparison
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.name.as_str())
+            .collect();
+
+        assert_eq!(vec!["frame_p95", "spikes_count", "ai"], failed);
+        assert_eq!(3, comparison.failures().len());
+    }
+}
@@ -1,93 +1,609 @@
 use std::time::{Duration, Instant};
 
-use std::collections::HashMap;
-use std::sync::{Mutex, MutexGuard};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, RwLock, Weak};
 use std::thread;
 
 use lazy_static::lazy_static;
 
+use crate::collections::{RingBuffer, SlotMap};
+use crate::format;
+use crate::state;
+#[cfg(feature = "tracy")]
+use crate::tracy;
+
+/// Defaults of the `ProfilerConfig`.
 pub const PERFORMANCE_RECORDS_CAPACITY: usize = 512;
 pub const PERFORMANCE_COUNTER_LOG_SIZE: usize = 120; // max entires
 pub const PERFORMANCE_COUNTER_STATE_SIZE: usize = 60; // max entires
+pub const TIMED_BLOCK_EVENTS_CAPACITY: usize = 4096;
+pub const FRAME_HISTORY_SIZE: usize = 120; // max entires
+pub const EVENT_MARKERS_CAPACITY: usize = 256; // max entires
+pub const CLOSED_BLOCKS_CHANNEL_CAPACITY: usize = 65536;
+pub const SPIKE_LOG_SIZE: usize = 32; // max entires
+pub const SPAN_LOG_SIZE: usize = 64; // max entires
+/// Spans which are never ended don't pile up, the oldest open span is dropped past the limit.
+pub const MAX_OPEN_SPANS: usize = 256;
+pub const BUDGET_VIOLATIONS_LOG_SIZE: usize = 64; // max entires
+pub const FRAME_TIMELINES_SIZE: usize = 16; // max entires
+pub const DEFAULT_SPIKE_THRESHOLD: Duration = Duration::from_micros(16_667);
+pub const MAX_SPIKE_THRESHOLD: Duration = Duration::from_secs(60);
+/// Weight of the last frame in `smoothed_frame_time`.
+pub const FRAME_TIME_SMOOTHING: f64 = 0.1;
+pub const FPS_WINDOW: Duration = Duration::from_secs(1);
+pub const BUDGET_FLASH_DURATION: Duration = Duration::from_millis(500);
+/// Longest frame and counter budget accepted by `set_budget_ms` and `assert_budget`.
+pub const MAX_BUDGET: Duration = Duration::from_secs(60);
+pub const BUDGET_OFFENDERS_COUNT: usize = 3;
+/// Offenders are logged at most once per interval, so slow stretches don't flood the log.
+pub const BUDGET_OFFENDERS_LOG_INTERVAL: Duration = Duration::from_secs(1);
+/// Returned by `push_timed_block` while the profiler is disabled.
+pub const DISABLED_TIMED_BLOCK_ID: u64 = u64::MAX;
+/// Distinct dynamic block names kept by `intern_name`, the rest share `INTERNED_NAMES_OVERFLOW`.
+pub const INTERNED_NAMES_CAPACITY: usize = 4096;
+pub const INTERNED_NAMES_OVERFLOW: &str = "<too many names>";
+/// Gauge with the debug overhead of the last frame in milliseconds, see `DebugOverhead`.
+pub const DEBUG_OVERHEAD_COUNTER: &str = "debug_overhead_ms";
+/// Frames after which blocks opened with `push_timed_block` and never dropped expire.
+pub const DEFAULT_TIMED_BLOCK_MAX_AGE: u64 = 600;
 
 lazy_static! {
-    pub static ref PROFILE_STATE: Mutex<ProfileState> = Mutex::new(ProfileState::default());
+    /// Profile state of the global `DebugServices`, see `DebugServices::global`.
+    pub static ref PROFILE_STATE: Arc<Mutex<ProfileState>> =
+        Arc::new(Mutex::new(ProfileState::default()));
+    /// Dynamic block names, see `intern_name`.
+    static ref INTERNED_NAMES: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+    /// Names registered with `register_thread_name`.
+    static ref THREAD_NAMES: RwLock<HashMap<thread::ThreadId, String>> =
+        RwLock::new(HashMap::new());
+    /// Stacks of the threads that opened blocks while sampling, see `set_sampling`.
+    static ref SAMPLED_STACKS: Mutex<Vec<(thread::ThreadId, Weak<SampledStack>)>> =
+        Mutex::new(Vec::new());
+    /// Samples not yet merged into a profile state.
+    static ref PENDING_SAMPLES: Mutex<SamplingStatistics> =
+        Mutex::new(SamplingStatistics::default());
+    static ref CLOSED_BLOCKS_CHANNEL: ClosedBlocksChannel = {
+        let (sender, receiver) = mpsc::sync_channel(CLOSED_BLOCKS_CHANNEL_CAPACITY);
+        (Mutex::new(sender), Mutex::new(receiver))
+    };
+}
+
+static DROPPED_TIMED_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+// Waits for the debug and profile state locks since the last `frame_end`, in nanoseconds.
+static LOCK_WAIT_NANOS: AtomicU64 = AtomicU64::new(0);
+
+// Mirrors `ProfileState.enabled`, so opening a block doesn't lock the profile state.
+static PROFILER_ENABLED: AtomicBool = AtomicBool::new(true);
+
+// Threads copy their blocks stacks into `SAMPLED_STACKS` only while sampling.
+static SAMPLING_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Set when `PENDING_SAMPLES` has samples, so frames without them don't lock.
+static SAMPLES_PENDING: AtomicBool = AtomicBool::new(false);
+
+type SampledStack = Mutex<Vec<BlockLocation>>;
+
+type ClosedBlocksChannel = (
+    Mutex<mpsc::SyncSender<ClosedTimedBlock>>,
+    Mutex<mpsc::Receiver<ClosedTimedBlock>>,
+);
+
+thread_local! {
+    // Currently opened blocks of the thread, used to find parent of the new block.
+    static BLOCKS_STACK: RefCell<Vec<BlockLocation>> = RefCell::new(Vec::new());
+
+    // Copy of `BLOCKS_STACK` read by the sampling thread.
+    static SAMPLED_STACK: Arc<SampledStack> = register_sampled_stack();
+
+    // Each thread clones the sender once, so submitting blocks doesn't lock.
+    static CLOSED_BLOCKS_SENDER: mpsc::SyncSender<ClosedTimedBlock> =
+        state::recover(CLOSED_BLOCKS_CHANNEL.0.lock(), "closed blocks sender").clone();
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct BlockLocation {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub line: u32,
+}
+
+/// Buffer sizes of the profile state, e.g. smaller for memory constrained targets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfilerConfig {
+    /// Records reserved per frame and per snapshot.
+    pub records_capacity: usize,
+    /// Snapshots kept in `performance_counter_log`.
+    pub log_size: usize,
+    /// Frames kept between snapshots, upper limit of the snapshot interval.
+    pub state_size: usize,
+}
+
+impl Default for ProfilerConfig {
+    fn default() -> Self {
+        ProfilerConfig {
+            records_capacity: PERFORMANCE_RECORDS_CAPACITY,
+            log_size: PERFORMANCE_COUNTER_LOG_SIZE,
+            state_size: PERFORMANCE_COUNTER_STATE_SIZE,
+        }
+    }
+}
+
+impl ProfilerConfig {
+    pub const OPTION_NAMES: &'static [&'static str] =
+        &["records_capacity", "log_size", "state_size"];
+
+    pub fn option_mut(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "records_capacity" => Some(&mut self.records_capacity),
+            "log_size" => Some(&mut self.log_size),
+            "state_size" => Some(&mut self.state_size),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.log_size == 0 {
+            return Err(String::from("Log size should be at least 1"));
+        }
+
+        if self.state_size == 0 {
+            return Err(String::from("State size should be at least 1"));
+        }
+
+        Ok(())
+    }
 }
 
 pub struct ProfileState {
+    /// Use `configure` to change.
+    pub config: ProfilerConfig,
+    /// Use `set_enabled` to change, disabled profiler doesn't record timed blocks.
+    pub enabled: bool,
     pub snapshot_interval: usize,
+    pub start_time: Instant,
     pub frame_timer: Instant,
     pub frame_elapsed: Duration,
+    pub frame_history: RingBuffer<Duration>,
+    /// Exponential moving average of the frame time.
+    pub smoothed_frame_time: Duration,
+    /// Frames per second and the longest frame within the last finished `FPS_WINDOW`.
+    pub fps: f32,
+    pub fps_window_max: Duration,
+    pub fps_window_start: Instant,
+    pub fps_window_frames: u32,
+    pub fps_window_current_max: Duration,
+    /// Number of frames pushed into `frame_history` since the start.
+    pub frame_number: u64,
+    pub event_markers: RingBuffer<EventMarker>,
     pub frame_counter: usize,
     pub snapshot_counter: usize,
+    /// Number of snapshots taken since the start.
+    pub snapshots_taken: u64,
     pub performance_counter_states: Vec<PerformanceCounterState>,
     pub performance_counter_log: Vec<PerformanceCounterStatistics>,
-    pub timed_blocks: HashMap<u64, TimedBlock>,
-    // TODO: Make proper id managment
-    pub last_timed_block_id: u64,
+    pub timed_block_events: RingBuffer<TimedBlockEvent>,
+    /// Records `frame_timelines`, see `set_detail`.
+    pub detail: bool,
+    /// Timed block events of the last frames, recorded only in the detail mode.
+    pub frame_timelines: RingBuffer<FrameTimeline>,
+    /// Events of the current frame in the detail mode.
+    pub frame_timeline_events: Vec<TimedBlockEvent>,
+    /// Frames longer than the threshold are captured into `spike_log`.
+    pub spike_threshold: Duration,
+    pub spike_log: Vec<FrameSpike>,
+    /// Frames longer than the budget flash the frame time text, see `set_frame_budget`.
+    pub frame_budget: Option<Duration>,
+    /// Logs the slowest blocks of the frames over the budget.
+    pub log_budget_offenders: bool,
+    pub budget_exceeded_at: Option<Instant>,
+    pub budget_offenders_logged_at: Option<Instant>,
+    /// Upper bounds of the counters checked after each snapshot, see `assert_budget`.
+    pub counter_budgets: Vec<CounterBudget>,
+    /// Counters over their budgets, oldest first.
+    pub budget_violations: RingBuffer<BudgetViolation>,
+    /// Start times of the modules opened with `module_begin`.
+    pub module_timers: Vec<(&'static str, Instant)>,
+    /// Time of each module within the current frame.
+    pub module_frame_times: Vec<ModuleFrameTime>,
+    /// Time of each module within the last finished frame.
+    pub last_module_frame_times: Vec<ModuleFrameTime>,
+    /// Ids of the recorded block locations, see `counter_id`.
+    pub counter_ids: HashMap<BlockLocation, CounterId>,
+    /// Blocks opened with `push_timed_block` by id.
+    pub timed_blocks: SlotMap<ManualTimedBlock>,
+    /// Frames after which the blocks in `timed_blocks` expire.
+    pub timed_block_max_age: u64,
+    /// Time the debug services spend on themselves within the current frame.
+    pub overhead: DebugOverhead,
+    /// Overhead of the last finished frame.
+    pub last_overhead: DebugOverhead,
+    /// Spans opened with `span_begin` by id.
+    pub open_spans: SlotMap<Span>,
+    /// Spans closed with `span_end`, oldest first.
+    pub span_log: RingBuffer<Span>,
+    /// Open blocks sampled since the last `reset_samples`, see `sampling::start`.
+    pub sampling: SamplingStatistics,
 }
 
 impl Default for ProfileState {
     fn default() -> Self {
-        let snapshot_interval = 3;
+        Self::new(ProfilerConfig::default())
+    }
+}
+
+impl ProfileState {
+    /// Creates the state with buffers of the `config` sizes,
+    /// invalid sizes are raised to 1.
+    pub fn new(config: ProfilerConfig) -> Self {
+        let config = ProfilerConfig {
+            log_size: config.log_size.max(1),
+            state_size: config.state_size.max(1),
+            ..config
+        };
+        let snapshot_interval = 3.min(config.state_size);
 
         ProfileState {
+            config,
+            enabled: true,
             frame_counter: 0,
             snapshot_counter: 0,
+            snapshots_taken: 0,
             snapshot_interval,
-            performance_counter_states: vec![
-                PerformanceCounterState::default();
-                PERFORMANCE_COUNTER_STATE_SIZE
-            ],
-            performance_counter_log: vec![
-                PerformanceCounterStatistics::default();
-                PERFORMANCE_COUNTER_LOG_SIZE
-            ],
+            performance_counter_states: counter_states(&config),
+            performance_counter_log: counter_log(&config),
+            start_time: Instant::now(),
             frame_timer: Instant::now(),
             frame_elapsed: Duration::from_nanos(0),
-            timed_blocks: HashMap::new(),
-            last_timed_block_id: 0,
+            frame_history: RingBuffer::new(FRAME_HISTORY_SIZE),
+            smoothed_frame_time: Duration::from_nanos(0),
+            fps: 0.,
+            fps_window_max: Duration::from_nanos(0),
+            fps_window_start: Instant::now(),
+            fps_window_frames: 0,
+            fps_window_current_max: Duration::from_nanos(0),
+            frame_number: 0,
+            event_markers: RingBuffer::new(EVENT_MARKERS_CAPACITY),
+            timed_block_events: RingBuffer::new(TIMED_BLOCK_EVENTS_CAPACITY),
+            detail: false,
+            frame_timelines: RingBuffer::new(FRAME_TIMELINES_SIZE),
+            frame_timeline_events: Vec::new(),
+            spike_threshold: DEFAULT_SPIKE_THRESHOLD,
+            spike_log: Vec::with_capacity(SPIKE_LOG_SIZE),
+            frame_budget: None,
+            log_budget_offenders: false,
+            budget_exceeded_at: None,
+            budget_offenders_logged_at: None,
+            counter_budgets: Vec::new(),
+            budget_violations: RingBuffer::new(BUDGET_VIOLATIONS_LOG_SIZE),
+            module_timers: Vec::new(),
+            module_frame_times: Vec::new(),
+            last_module_frame_times: Vec::new(),
+            counter_ids: HashMap::new(),
+            timed_blocks: SlotMap::new(),
+            timed_block_max_age: DEFAULT_TIMED_BLOCK_MAX_AGE,
+            overhead: DebugOverhead::default(),
+            last_overhead: DebugOverhead::default(),
+            open_spans: SlotMap::new(),
+            span_log: RingBuffer::new(SPAN_LOG_SIZE),
+            sampling: SamplingStatistics::default(),
         }
     }
 }
 
+/// Compact id of the block location, interned by `counter_id` on the first record of the block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CounterId(u32);
+
+/// Block, its parent and thread, records of the frame are unique by the key.
+type CounterKey = (CounterId, Option<CounterId>, thread::ThreadId);
+
 #[derive(Clone)]
 pub struct PerformanceCounterState {
     pub records: Vec<ClocsDebugRecord>,
+    pub user_counters: Vec<UserCounterRecord>,
+    /// Index in `records` by the record key.
+    record_index: HashMap<CounterKey, usize>,
+}
+
+impl PerformanceCounterState {
+    pub fn with_capacity(records_capacity: usize) -> Self {
+        PerformanceCounterState {
+            records: Vec::with_capacity(records_capacity),
+            user_counters: Vec::new(),
+            record_index: HashMap::new(),
+        }
+    }
+
+    /// Removes the records keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.user_counters.clear();
+        self.record_index.clear();
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UserCounterKind {
+    /// Accumulated within the frame, see `counter_add`.
+    Count,
+    /// Last value set within the frame, see `gauge_set`.
+    Gauge,
+}
+
+#[derive(Clone, Debug)]
+pub struct UserCounterRecord {
+    pub name: &'static str,
+    pub kind: UserCounterKind,
+    pub value: f64,
+}
+
+/// Per frame values of the user counter within the snapshot window.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Debug)]
+pub struct UserCounterStatistics {
+    pub name: &'static str,
+    pub kind: UserCounterKind,
+    pub last: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub frames: u32,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Clone, Default, Debug)]
 pub struct PerformanceCounterStatisticsRecord {
     pub name: &'static str,
     pub file_name: &'static str,
     pub line: u32,
+    pub category: Option<&'static str>,
     pub sum_elapsed: Duration,
     pub sum_hits: u32,
     pub sum_hits_over_elapsed: u128,
     pub hits: u32,
     pub percent: f32,
     pub thread_id: String,
+    pub parent: Option<BlockLocation>,
+    pub depth: u32,
+    /// Per frame elapsed statistics within the snapshot window,
+    /// frames where the block wasn't hit are not counted.
+    pub min_elapsed: Duration,
+    pub max_elapsed: Duration,
+    pub mean_elapsed: Duration,
+    pub p95_elapsed: Duration,
+    pub p99_elapsed: Duration,
+    pub std_dev_elapsed: Duration,
+}
+
+impl PerformanceCounterStatisticsRecord {
+    pub fn location(&self) -> BlockLocation {
+        BlockLocation {
+            name: self.name,
+            file_name: self.file_name,
+            line: self.line,
+        }
+    }
 }
 
 impl Default for PerformanceCounterState {
     fn default() -> Self {
-        PerformanceCounterState {
-            records: Vec::with_capacity(PERFORMANCE_RECORDS_CAPACITY),
-        }
+        Self::with_capacity(PERFORMANCE_RECORDS_CAPACITY)
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Clone, Debug)]
 pub struct PerformanceCounterStatistics {
+    /// Time since profiler start when the snapshot was taken.
+    pub taken_at: Duration,
     pub records: Vec<PerformanceCounterStatisticsRecord>,
+    pub threads: Vec<PerformanceCounterThreadStatistics>,
+    pub categories: Vec<CategoryStatistics>,
+    pub user_counters: Vec<UserCounterStatistics>,
+}
+
+/// Total time of the blocks tagged with the category within the snapshot window,
+/// blocks nested into blocks of the same category are counted once.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Default, Debug)]
+pub struct CategoryStatistics {
+    pub category: &'static str,
+    pub sum_elapsed: Duration,
+    /// Share of the top level blocks time.
+    pub percent: f32,
 }
 
 impl Default for PerformanceCounterStatistics {
     fn default() -> Self {
+        Self::with_capacity(PERFORMANCE_RECORDS_CAPACITY)
+    }
+}
+
+impl PerformanceCounterStatistics {
+    pub fn with_capacity(records_capacity: usize) -> Self {
         PerformanceCounterStatistics {
-            records: Vec::with_capacity(PERFORMANCE_RECORDS_CAPACITY),
+            taken_at: Duration::from_nanos(0),
+            records: Vec::with_capacity(records_capacity),
+            threads: Vec::new(),
+            categories: Vec::new(),
+            user_counters: Vec::new(),
+        }
+    }
+
+    pub fn thread_records<'a>(
+        &'a self,
+        thread_id: &'a str,
+    ) -> impl Iterator<Item = &'a PerformanceCounterStatisticsRecord> {
+        self.records
+            .iter()
+            .filter(move |record| record.thread_id == thread_id)
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Default, Debug)]
+pub struct PerformanceCounterThreadStatistics {
+    pub thread_id: String,
+    pub sum_elapsed: Duration,
+    pub records_count: u32,
+    pub percent: f32,
+}
+
+/// Instant event recorded with `mark`.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventMarker {
+    pub label: String,
+    /// Time since the profiler start.
+    pub timestamp: Duration,
+    /// `frame_number` of the frame the marker was recorded in.
+    pub frame_number: u64,
+}
+
+/// Time the debug services spend on themselves, so the observer effect can be checked.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct DebugOverhead {
+    /// Overlay update and input handling.
+    pub step: Duration,
+    pub render: Duration,
+    pub snapshot: Duration,
+    /// Waits for the debug and profile state locks.
+    pub lock_wait: Duration,
+}
+
+impl DebugOverhead {
+    pub fn total(&self) -> Duration {
+        self.step + self.render + self.snapshot + self.lock_wait
+    }
+}
+
+/// Operation spanning many frames, e.g. asset load or network request.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub name: String,
+    /// Time since the profiler start.
+    pub begin: Duration,
+    /// `None` while the span is open.
+    pub end: Option<Duration>,
+}
+
+/// Returned by `span_begin`, closes the span with `span_end`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanId(u64);
+
+/// Time spent by the engine module within the frame.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleFrameTime {
+    pub id: &'static str,
+    pub elapsed: Duration,
+}
+
+/// Number of samples a block was open in, see `sampling::start`.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct BlockSamples {
+    /// Samples with the block anywhere in the stack.
+    pub inclusive: u64,
+    /// Samples with the block on top of the stack.
+    pub exclusive: u64,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct SamplingStatistics {
+    pub interval: Duration,
+    /// Samples per thread, including the ones outside of the instrumented blocks.
+    pub threads: HashMap<thread::ThreadId, u64>,
+    pub blocks: HashMap<(thread::ThreadId, BlockLocation), BlockSamples>,
+}
+
+impl SamplingStatistics {
+    pub fn merge(&mut self, other: &SamplingStatistics) {
+        if other.interval > Duration::from_nanos(0) {
+            self.interval = other.interval;
+        }
+
+        for (thread_id, samples) in other.threads.iter() {
+            *self.threads.entry(*thread_id).or_default() += samples;
+        }
+
+        for (key, samples) in other.blocks.iter() {
+            let element = self.blocks.entry(*key).or_default();
+            element.inclusive += samples.inclusive;
+            element.exclusive += samples.exclusive;
         }
     }
+
+    pub fn clear(&mut self) {
+        self.threads.clear();
+        self.blocks.clear();
+    }
+
+    /// Estimated time of the block, samples multiplied by the interval.
+    pub fn estimated_time(&self, samples: u64) -> Duration {
+        self.interval * u32::try_from(samples).unwrap_or(u32::MAX)
+    }
+}
+
+/// Over budget frame with all its records.
+#[derive(Clone, Debug)]
+pub struct FrameSpike {
+    /// Time since profiler start when the frame has ended.
+    pub taken_at: Duration,
+    pub frame_elapsed: Duration,
+    pub records: Vec<ClocsDebugRecord>,
+}
+
+/// Expected upper bound of the counter time per frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CounterBudget {
+    pub name: &'static str,
+    pub budget: Duration,
+}
+
+/// Counter over its budget within the snapshot window.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BudgetViolation {
+    pub counter: &'static str,
+    pub budget: Duration,
+    /// Slowest frame of the counter within the snapshot window.
+    pub actual: Duration,
+    /// Value of `snapshots_taken` for the snapshot.
+    pub snapshot: u64,
+    /// Time since profiler start when the snapshot was taken.
+    pub taken_at: Duration,
+}
+
+/// Raw begin/end of the single timed block,
+/// times are relative to profiler start.
+#[derive(Clone, Debug)]
+pub struct TimedBlockEvent {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub line: u32,
+    pub category: Option<&'static str>,
+    pub thread_id: thread::ThreadId,
+    pub depth: u32,
+    pub begin: Duration,
+    pub end: Duration,
+}
+
+/// All timed block events of the single frame, times are relative to profiler start.
+#[derive(Clone, Debug)]
+pub struct FrameTimeline {
+    pub frame_number: u64,
+    pub begin: Duration,
+    pub end: Duration,
+    pub events: Vec<TimedBlockEvent>,
 }
 
 #[derive(Debug, Clone)]
@@ -95,9 +611,12 @@ pub struct ClocsDebugRecord {
     pub name: &'static str,
     pub file_name: &'static str,
     pub line: u32,
+    pub category: Option<&'static str>,
     pub elapsed: Duration,
     pub hits: u32,
     pub thread_id: thread::ThreadId,
+    pub parent: Option<BlockLocation>,
+    pub depth: u32,
 }
 
 impl Default for ClocsDebugRecord {
@@ -106,202 +625,2824 @@ impl Default for ClocsDebugRecord {
             name: "",
             file_name: "",
             line: 0,
+            category: None,
             elapsed: Duration::from_nanos(0),
             hits: 0,
             thread_id: thread::current().id(),
+            parent: None,
+            depth: 0,
         }
     }
 }
 
+/// Name of the timed block, e.g. `"update"` or `format!("load texture: {}", path)`,
+/// dynamic names are interned with `intern_name`.
+pub trait IntoBlockName {
+    fn into_block_name(self) -> &'static str;
+}
+
+impl IntoBlockName for &'static str {
+    fn into_block_name(self) -> &'static str {
+        self
+    }
+}
+
+impl IntoBlockName for String {
+    fn into_block_name(self) -> &'static str {
+        intern_name(&self)
+    }
+}
+
+impl IntoBlockName for &String {
+    fn into_block_name(self) -> &'static str {
+        intern_name(self)
+    }
+}
+
+/// Returns the static copy of the `name`, copies are made once per distinct name
+/// and live until the program exits. After `INTERNED_NAMES_CAPACITY` distinct names
+/// new names are replaced with `INTERNED_NAMES_OVERFLOW`.
+pub fn intern_name(name: &str) -> &'static str {
+    let mut names = state::recover(INTERNED_NAMES.lock(), "interned names");
+
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+
+    if names.len() >= INTERNED_NAMES_CAPACITY {
+        return INTERNED_NAMES_OVERFLOW;
+    }
+
+    let interned: &'static str = Box::leak(String::from(name).into_boxed_str());
+    names.insert(interned);
+
+    if names.len() == INTERNED_NAMES_CAPACITY {
+        log::warn!(
+            "{} distinct block names were interned, new names are shown as '{}'",
+            INTERNED_NAMES_CAPACITY,
+            INTERNED_NAMES_OVERFLOW
+        );
+    }
+
+    interned
+}
+
 #[derive(Clone)]
 pub struct TimedBlock {
     manual_drop: bool,
+    enabled: bool,
     pub thread_id: thread::ThreadId,
     pub name: &'static str,
     pub file_name: &'static str,
     pub line: u32,
+    /// Groups blocks in snapshots and exports, e.g. `"render"` or `"physics"`.
+    pub category: Option<&'static str>,
+    pub parent: Option<BlockLocation>,
+    pub depth: u32,
     pub timer: Instant,
 }
 
 impl TimedBlock {
-    pub fn new(name: &'static str, file_name: &'static str, line: u32) -> TimedBlock {
+    pub fn new<N: IntoBlockName>(name: N, file_name: &'static str, line: u32) -> TimedBlock {
+        // Disabled blocks aren't recorded, so dynamic names aren't interned for them.
+        let name = if is_enabled() {
+            name.into_block_name()
+        } else {
+            ""
+        };
+
+        TimedBlock::open(name, file_name, line, false)
+    }
+
+    fn open(name: &'static str, file_name: &'static str, line: u32, manual_drop: bool) -> Self {
+        let enabled = is_enabled();
+        let location = BlockLocation {
+            name,
+            file_name,
+            line,
+        };
+
+        let (parent, depth) = if enabled {
+            BLOCKS_STACK.with(|stack| {
+                let mut stack = stack.borrow_mut();
+                let parent = stack.last().copied();
+                let depth = stack.len() as u32;
+                stack.push(location);
+                mirror_blocks_stack(&stack);
+                (parent, depth)
+            })
+        } else {
+            (None, 0)
+        };
+
+        #[cfg(feature = "tracy")]
+        if enabled && !manual_drop {
+            tracy::zone_begin(location);
+        }
+
         TimedBlock {
             name,
             file_name,
             line,
-            manual_drop: false,
+            category: None,
+            manual_drop,
+            enabled,
             thread_id: thread::current().id(),
+            parent,
+            depth,
             timer: Instant::now(),
         }
     }
+
+    pub fn location(&self) -> BlockLocation {
+        BlockLocation {
+            name: self.name,
+            file_name: self.file_name,
+            line: self.line,
+        }
+    }
+
+    /// Tags the block, e.g. `TimedBlock::new("solve", file!(), line!()).with_category("physics")`.
+    pub fn with_category(mut self, category: &'static str) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    fn close(&self) {
+        // Blocks stack is per thread, manual blocks might be dropped on another thread.
+        if self.thread_id != thread::current().id() {
+            return;
+        }
+
+        let location = self.location();
+
+        #[cfg(feature = "tracy")]
+        if !self.manual_drop {
+            tracy::zone_end(location);
+        }
+
+        BLOCKS_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+
+            if let Some(pos) = stack.iter().rposition(|item| *item == location) {
+                stack.remove(pos);
+            }
+
+            mirror_blocks_stack(&stack);
+        });
+    }
 }
 
 impl Drop for TimedBlock {
     fn drop(&mut self) {
-        if !self.manual_drop {
-            drop_timed_block(self, &mut get_profile_state());
+        if self.enabled && !self.manual_drop {
+            submit_timed_block(self);
         }
     }
 }
 
-#[macro_export]
-macro_rules! timed_block {
-    ($name:expr) => {
-        crate::debug_services::profile::TimedBlock::new($name, file!(), line!())
-    };
+/// Block opened with `push_timed_block`, waiting for `drop_timed_block_by_id`.
+#[derive(Clone)]
+pub struct ManualTimedBlock {
+    pub block: TimedBlock,
+    /// `frame_number` of the frame the block was opened in.
+    pub opened_at: u64,
 }
 
-pub fn get_profile_state<'a>() -> MutexGuard<'a, ProfileState> {
-    PROFILE_STATE.lock().expect("failed to get profile state")
+/// Timed block that has been finished, recorded into the profile state in `frame_end`.
+#[derive(Clone, Debug)]
+pub struct ClosedTimedBlock {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub line: u32,
+    pub category: Option<&'static str>,
+    pub thread_id: thread::ThreadId,
+    pub parent: Option<BlockLocation>,
+    pub depth: u32,
+    pub begin: Instant,
+    pub end: Instant,
 }
 
-pub fn push_timed_block(name: &'static str, file_name: &'static str, line: u32) -> u64 {
-    let profile = &mut get_profile_state();
+impl ClosedTimedBlock {
+    fn new(timed_block: &TimedBlock) -> Self {
+        ClosedTimedBlock {
+            name: timed_block.name,
+            file_name: timed_block.file_name,
+            line: timed_block.line,
+            category: timed_block.category,
+            thread_id: timed_block.thread_id,
+            parent: timed_block.parent,
+            depth: timed_block.depth,
+            begin: timed_block.timer,
+            end: Instant::now(),
+        }
+    }
+}
 
-    let block = TimedBlock {
-        name,
-        file_name,
-        line,
-        manual_drop: true,
-        thread_id: thread::current().id(),
-        timer: Instant::now(),
-    };
+/// Closes the block and sends it to the profiler without taking
+/// the profile state lock, dropped if the channel is full.
+fn submit_timed_block(timed_block: &TimedBlock) {
+    let block = ClosedTimedBlock::new(timed_block);
+    timed_block.close();
+
+    let sent = CLOSED_BLOCKS_SENDER.with(|sender| sender.try_send(block).is_ok());
+
+    if !sent {
+        DROPPED_TIMED_BLOCKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
 
-    let id = profile.last_timed_block_id;
+/// Records all blocks submitted since the last call into the current frame.
+fn drain_closed_blocks(profile_state: &mut MutexGuard<ProfileState>) {
+    let receiver = state::recover(CLOSED_BLOCKS_CHANNEL.1.lock(), "closed blocks receiver");
 
-    profile.last_timed_block_id += 1;
-    profile.timed_blocks.insert(id, block);
+    for block in receiver.try_iter() {
+        record_closed_block(&block, profile_state);
+    }
+
+    let dropped = DROPPED_TIMED_BLOCKS.swap(0, Ordering::Relaxed);
 
-    id
+    if dropped > 0 {
+        log::warn!("{} timed blocks were dropped, the channel is full", dropped);
+    }
 }
 
-pub fn drop_timed_block_by_id(id: u64) {
-    let profile = &mut get_profile_state();
-    let block = match profile.timed_blocks.get(&id) {
-        Some(value) => value.clone(),
-        None => {
-            log::warn!("Couldn't drop block by id: {}", id);
-            return;
-        }
+/// Opens the timed block, closed when the returned value is dropped:
+/// `let _block = timed_block!("update");`.
+#[macro_export]
+macro_rules! timed_block {
+    ($name:expr) => {
+        $crate::profiler::TimedBlock::new($name, file!(), line!())
     };
+    ($name:expr, $category:expr) => {
+        $crate::profiler::TimedBlock::new($name, file!(), line!()).with_category($category)
+    };
+}
 
-    drop_timed_block(&block, profile);
-    profile.timed_blocks.remove(&id);
+/// Times the block and evaluates to its value:
+/// `let path = timed_scope!("path_finding", { find_path(from, to) });`.
+#[macro_export]
+macro_rules! timed_scope {
+    ($name:expr, $body:block) => {{
+        let _timed_block = $crate::timed_block!($name);
+        $body
+    }};
 }
 
-pub fn drop_timed_block(timed_block: &TimedBlock, profile_state: &mut MutexGuard<ProfileState>) {
-    let mut hits = 1;
-    let mut elapsed = timed_block.timer.elapsed();
-    let mut to_modify = false;
-    let mut modify_idx: usize = 0;
+/// Opens the timed block named after the enclosing function:
+/// `let _block = timed_fn!();`.
+#[macro_export]
+macro_rules! timed_fn {
+    () => {{
+        fn f() {}
+        $crate::profiler::TimedBlock::new($crate::profiler::function_name(f), file!(), line!())
+    }};
+}
 
-    let frame_counter = profile_state.frame_counter;
-    let records = &mut profile_state.performance_counter_states[frame_counter].records;
+/// Path of the function enclosing `f` without the crate name,
+/// where `f` is an item declared inside of it, used by `timed_fn!`.
+#[doc(hidden)]
+pub fn function_name<T>(_: T) -> &'static str {
+    let mut name = std::any::type_name::<T>();
 
-    // NOTE(sysint64): Right now this method is faster than Map
-    for (i, c) in records.iter().enumerate() {
-        if c.name == timed_block.name
-            && c.file_name == timed_block.file_name
-            && c.line == timed_block.line
-        {
-            hits += c.hits;
-            elapsed += c.elapsed;
-            to_modify = true;
-            modify_idx = i;
-        }
-    }
+    name = name.strip_suffix("::f").unwrap_or(name);
 
-    if to_modify {
-        records[modify_idx] = ClocsDebugRecord {
-            name: timed_block.name,
-            file_name: timed_block.file_name,
-            line: timed_block.line,
-            thread_id: thread::current().id(),
-            elapsed,
-            hits,
-        };
+    while let Some(outer) = name.strip_suffix("::{{closure}}") {
+        name = outer;
     }
-    else {
-        records.push(ClocsDebugRecord {
-            name: timed_block.name,
-            file_name: timed_block.file_name,
-            line: timed_block.line,
-            thread_id: thread::current().id(),
-            elapsed,
-            hits,
-        });
+
+    match name.find("::") {
+        Some(pos) => &name[pos + 2..],
+        None => name,
     }
 }
 
-pub fn frame_start(profile_state: &mut MutexGuard<ProfileState>) {
-    profile_state.frame_timer = Instant::now();
+pub fn get_profile_state<'a>() -> MutexGuard<'a, ProfileState> {
+    timed_lock(|| state::recover(PROFILE_STATE.lock(), "profile state"))
 }
 
-pub fn frame_end(profile_state: &mut MutexGuard<ProfileState>) {
-    profile_state.frame_counter += 1;
-    profile_state.frame_elapsed = profile_state.frame_timer.elapsed();
+/// Takes the guard with `lock`, the wait is counted as the debug overhead.
+pub(crate) fn timed_lock<G, F: FnOnce() -> G>(lock: F) -> G {
+    let timer = Instant::now();
+    let guard = lock();
 
-    let snapshot_interval = profile_state.snapshot_interval;
+    LOCK_WAIT_NANOS.fetch_add(timer.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    guard
+}
 
-    if profile_state.frame_counter >= snapshot_interval {
-        take_snapshot(profile_state);
-        profile_state.frame_counter = 0;
+pub fn is_enabled() -> bool {
+    PROFILER_ENABLED.load(Ordering::Relaxed)
+}
 
-        for i in 0..snapshot_interval {
-            profile_state.performance_counter_states[i] = PerformanceCounterState::default();
-        }
-    }
+/// Names the current thread in snapshots, overlays and exports,
+/// e.g. `profiler::register_thread_name("worker-0")`.
+pub fn register_thread_name(name: &str) {
+    let mut names = state::recover(THREAD_NAMES.write(), "thread names");
+    names.insert(thread::current().id(), String::from(name));
 }
 
-fn take_snapshot(profile_state: &mut MutexGuard<ProfileState>) {
-    profile_state.snapshot_counter += 1;
+/// Registered name of the thread, `ThreadId(N)` if the thread isn't registered.
+pub fn thread_name(thread_id: thread::ThreadId) -> String {
+    let names = state::recover(THREAD_NAMES.read(), "thread names");
 
-    if profile_state.snapshot_counter >= PERFORMANCE_COUNTER_LOG_SIZE {
-        profile_state.snapshot_counter = 0;
+    match names.get(&thread_id) {
+        Some(name) => name.clone(),
+        None => format!("{:?}", thread_id),
     }
+}
 
-    let mut statistics: HashMap<String, PerformanceCounterStatisticsRecord> = HashMap::new();
+/// Pauses or resumes recording of timed blocks,
+/// the snapshot log is kept as is while the profiler is paused.
+pub fn set_enabled(profile_state: &mut MutexGuard<ProfileState>, enabled: bool) {
+    profile_state.enabled = enabled;
+    PROFILER_ENABLED.store(enabled, Ordering::Relaxed);
+}
 
-    for state in profile_state.performance_counter_states.iter() {
-        for record in state.records.iter() {
-            let key = String::from(record.name) + record.file_name + &record.line.to_string();
-            let element = statistics.entry(key).or_default();
+pub fn push_timed_block<N: IntoBlockName>(name: N, file_name: &'static str, line: u32) -> u64 {
+    if !is_enabled() {
+        return DISABLED_TIMED_BLOCK_ID;
+    }
 
-            element.name = record.name;
-            element.file_name = record.file_name;
-            element.line = record.line;
-            element.sum_elapsed += record.elapsed;
-            element.sum_hits += record.hits;
-            element.sum_hits_over_elapsed += record.elapsed.as_nanos() / record.hits as u128;
-            element.hits += 1;
-            element.thread_id = format!("{:?}", record.thread_id);
+    let block = TimedBlock::open(name.into_block_name(), file_name, line, true);
+    let profile = &mut get_profile_state();
+    let opened_at = profile.frame_number;
+
+    profile
+        .timed_blocks
+        .insert(ManualTimedBlock { block, opened_at })
+}
+
+/// Adds `value` to the counter of the current frame, e.g. number of draw calls.
+pub fn counter_add(name: &'static str, value: i64) {
+    let profile_state = &mut get_profile_state();
+
+    if profile_state.enabled {
+        user_counter_mut(profile_state, name, UserCounterKind::Count).value += value as f64;
+    }
+}
+
+/// Sets the gauge value of the current frame, e.g. number of alive entities.
+pub fn gauge_set(name: &'static str, value: f64) {
+    let profile_state = &mut get_profile_state();
+
+    if profile_state.enabled {
+        user_counter_mut(profile_state, name, UserCounterKind::Gauge).value = value;
+    }
+}
+
+fn user_counter_mut<'a>(
+    profile_state: &'a mut MutexGuard<ProfileState>,
+    name: &'static str,
+    kind: UserCounterKind,
+) -> &'a mut UserCounterRecord {
+    let frame_counter = profile_state.frame_counter;
+    let counters = &mut profile_state.performance_counter_states[frame_counter].user_counters;
+
+    match counters.iter().position(|counter| counter.name == name) {
+        Some(idx) => &mut counters[idx],
+        None => {
+            counters.push(UserCounterRecord {
+                name,
+                kind,
+                value: 0.,
+            });
+            counters.last_mut().unwrap()
         }
     }
+}
 
-    let total_elapsed: u128 = statistics
-        .values()
-        .map(|record| record.sum_elapsed.as_nanos())
-        .sum();
+/// Records the instant event, e.g. `profiler::mark("level_load_start")`,
+/// markers are shown on the frame time graph and included in trace exports.
+pub fn mark(label: &str) {
+    let profile_state = &mut get_profile_state();
 
-    for record in statistics.values_mut() {
-        record.percent =
-            (record.sum_elapsed.as_nanos() as f64 / total_elapsed as f64) as f32 * 100.0;
+    if profile_state.enabled {
+        push_event_marker(profile_state, label);
+
+        #[cfg(feature = "tracy")]
+        tracy::message(label);
     }
+}
 
-    let mut records: Vec<PerformanceCounterStatisticsRecord> =
-        statistics.values().cloned().collect();
+fn push_event_marker(profile_state: &mut ProfileState, label: &str) {
+    let marker = EventMarker {
+        label: String::from(label),
+        timestamp: profile_state.start_time.elapsed(),
+        frame_number: profile_state.frame_number,
+    };
 
-    records.sort_by(|a, b| b.percent.partial_cmp(&a.percent).unwrap());
+    profile_state.event_markers.push(marker);
+}
 
-    let counter = profile_state.snapshot_counter;
-    let snapshot = &mut profile_state.performance_counter_log[counter].records;
+/// Opens the span, e.g. `profiler::span_begin("load_level")`, spans aren't bound
+/// to frames or threads and are recorded even if the profiler is paused.
+pub fn span_begin(name: &str) -> SpanId {
+    let profile_state = &mut get_profile_state();
+    let begin = profile_state.start_time.elapsed();
+
+    open_span(profile_state, name, begin)
+}
+
+fn open_span(profile_state: &mut ProfileState, name: &str, begin: Duration) -> SpanId {
+    if profile_state.open_spans.len() >= MAX_OPEN_SPANS {
+        let oldest = profile_state.open_spans.iter().map(|span| span.begin).min();
+
+        for span in profile_state
+            .open_spans
+            .remove_where(|span| Some(span.begin) == oldest)
+        {
+            log::warn!(
+                "More than {} spans are open, dropped '{}'",
+                MAX_OPEN_SPANS,
+                span.name
+            );
+        }
+    }
+
+    SpanId(profile_state.open_spans.insert(Span {
+        name: String::from(name),
+        begin,
+        end: None,
+    }))
+}
+
+pub fn span_end(id: SpanId) {
+    let profile_state = &mut get_profile_state();
+    let end = profile_state.start_time.elapsed();
+
+    close_span(profile_state, id, end);
+}
+
+fn close_span(profile_state: &mut ProfileState, id: SpanId, end: Duration) {
+    match profile_state.open_spans.remove(id.0) {
+        Some(mut span) => {
+            span.end = Some(end);
+            profile_state.span_log.push(span);
+        }
+        None => log::warn!("Couldn't end span by id: {:?}", id),
+    }
+}
+
+/// Open spans and spans closed after `since`, sorted by the begin time.
+pub fn timeline_spans(profile_state: &ProfileState, since: Duration) -> Vec<&Span> {
+    let mut spans: Vec<&Span> = profile_state
+        .span_log
+        .iter()
+        .filter(|span| span.end.map_or(true, |end| end >= since))
+        .chain(profile_state.open_spans.iter())
+        .collect();
+
+    spans.sort_by_key(|span| span.begin);
+    spans
+}
+
+/// Index in `frame_history` of the frame the marker was recorded in,
+/// `None` if the frame isn't finished yet or has left the history.
+pub fn marker_frame_index(profile_state: &ProfileState, marker: &EventMarker) -> Option<usize> {
+    let first_frame_number = profile_state.frame_number - profile_state.frame_history.len() as u64;
+
+    if marker.frame_number >= first_frame_number && marker.frame_number < profile_state.frame_number
+    {
+        Some((marker.frame_number - first_frame_number) as usize)
+    } else {
+        None
+    }
+}
+
+/// Starts timing of the engine module, `id` is usually `Module::id`.
+pub fn module_begin(id: &'static str) {
+    let profile_state = &mut get_profile_state();
+
+    if !profile_state.enabled {
+        return;
+    }
+
+    match profile_state
+        .module_timers
+        .iter_mut()
+        .find(|(timer_id, _)| *timer_id == id)
+    {
+        Some(timer) => timer.1 = Instant::now(),
+        None => profile_state.module_timers.push((id, Instant::now())),
+    }
+}
+
+/// Adds time since the matching `module_begin` to the module time of the current frame.
+pub fn module_end(id: &'static str) {
+    let profile_state = &mut get_profile_state();
+
+    let idx = match profile_state
+        .module_timers
+        .iter()
+        .position(|(timer_id, _)| *timer_id == id)
+    {
+        Some(idx) => idx,
+        None => return,
+    };
+
+    let (_, begin) = profile_state.module_timers.remove(idx);
+    record_module_time(profile_state, id, begin.elapsed());
+}
+
+fn record_module_time(profile_state: &mut ProfileState, id: &'static str, elapsed: Duration) {
+    let times = &mut profile_state.module_frame_times;
+
+    match times.iter_mut().find(|time| time.id == id) {
+        Some(time) => time.elapsed += elapsed,
+        None => times.push(ModuleFrameTime { id, elapsed }),
+    }
+}
+
+pub fn drop_timed_block_by_id(id: u64) {
+    if id == DISABLED_TIMED_BLOCK_ID {
+        return;
+    }
+
+    let profile = &mut get_profile_state();
+    let manual = match profile.timed_blocks.remove(id) {
+        Some(value) => value,
+        None => {
+            log::warn!("Couldn't drop block by id: {}", id);
+            return;
+        }
+    };
+
+    drop_timed_block(&manual.block, profile);
+}
+
+/// Removes blocks opened with `push_timed_block` more than `timed_block_max_age`
+/// frames ago, they are never recorded.
+fn expire_timed_blocks(profile_state: &mut MutexGuard<ProfileState>) {
+    let frame_number = profile_state.frame_number;
+    let max_age = profile_state.timed_block_max_age;
+    let expired = profile_state
+        .timed_blocks
+        .remove_where(|manual| frame_number.saturating_sub(manual.opened_at) > max_age);
+
+    for manual in expired.iter() {
+        let block = &manual.block;
+
+        log::warn!(
+            "Timed block '{}' at {}:{} wasn't dropped for {} frames, expired",
+            block.name,
+            block.file_name,
+            block.line,
+            max_age
+        );
+        block.close();
+    }
+}
+
+pub fn drop_timed_block(timed_block: &TimedBlock, profile_state: &mut MutexGuard<ProfileState>) {
+    if !timed_block.enabled {
+        return;
+    }
+
+    let block = ClosedTimedBlock::new(timed_block);
+    timed_block.close();
+    record_closed_block(&block, profile_state);
+}
+
+/// Id of the block location, the same location always gets the same id.
+pub fn counter_id(profile_state: &mut ProfileState, location: BlockLocation) -> CounterId {
+    let next_id = CounterId(profile_state.counter_ids.len() as u32);
+    *profile_state.counter_ids.entry(location).or_insert(next_id)
+}
+
+fn record_closed_block(block: &ClosedTimedBlock, profile_state: &mut MutexGuard<ProfileState>) {
+    let elapsed = block.end.saturating_duration_since(block.begin);
+
+    push_timed_block_event(block, profile_state);
+
+    let location = BlockLocation {
+        name: block.name,
+        file_name: block.file_name,
+        line: block.line,
+    };
+    let id = counter_id(profile_state, location);
+    let parent = block.parent.map(|parent| counter_id(profile_state, parent));
+    let key = (id, parent, block.thread_id);
+
+    let frame_counter = profile_state.frame_counter;
+    let state = &mut profile_state.performance_counter_states[frame_counter];
+
+    match state.record_index.get(&key) {
+        Some(idx) => {
+            let record = &mut state.records[*idx];
+            record.elapsed += elapsed;
+            record.hits += 1;
+            record.category = block.category;
+        }
+        None => {
+            state.record_index.insert(key, state.records.len());
+            state.records.push(ClocsDebugRecord {
+                name: block.name,
+                file_name: block.file_name,
+                line: block.line,
+                category: block.category,
+                thread_id: block.thread_id,
+                parent: block.parent,
+                depth: block.depth,
+                elapsed,
+                hits: 1,
+            });
+        }
+    }
+}
+
+fn push_timed_block_event(block: &ClosedTimedBlock, profile_state: &mut MutexGuard<ProfileState>) {
+    let start_time = profile_state.start_time;
+    let event = TimedBlockEvent {
+        name: block.name,
+        file_name: block.file_name,
+        line: block.line,
+        category: block.category,
+        thread_id: block.thread_id,
+        depth: block.depth,
+        begin: block.begin.saturating_duration_since(start_time),
+        end: block.end.saturating_duration_since(start_time),
+    };
+
+    if profile_state.detail {
+        profile_state.frame_timeline_events.push(event.clone());
+    }
+
+    profile_state.timed_block_events.push(event);
+}
+
+/// Enables recording of every timed block event of the last `FRAME_TIMELINES_SIZE` frames,
+/// disabling drops the recorded frames.
+pub fn set_detail(profile_state: &mut MutexGuard<ProfileState>, detail: bool) {
+    profile_state.detail = detail;
+
+    if !detail {
+        profile_state.frame_timelines.clear();
+        profile_state.frame_timeline_events.clear();
+    }
+}
+
+fn push_frame_timeline(profile_state: &mut MutexGuard<ProfileState>) {
+    let start_time = profile_state.start_time;
+    let timeline = FrameTimeline {
+        frame_number: profile_state.frame_number,
+        begin: profile_state
+            .frame_timer
+            .saturating_duration_since(start_time),
+        end: start_time.elapsed(),
+        events: std::mem::take(&mut profile_state.frame_timeline_events),
+    };
+
+    profile_state.frame_timelines.push(timeline);
+}
+
+pub fn frame_start(profile_state: &mut MutexGuard<ProfileState>) {
+    profile_state.frame_timer = Instant::now();
+}
+
+pub fn frame_end(profile_state: &mut MutexGuard<ProfileState>) {
+    drain_closed_blocks(profile_state);
+    drain_samples(profile_state);
+
+    profile_state.frame_elapsed = profile_state.frame_timer.elapsed();
+
+    if profile_state.detail {
+        push_frame_timeline(profile_state);
+    }
+
+    push_frame_history(profile_state);
+    update_frame_rate(profile_state, Instant::now());
+    expire_timed_blocks(profile_state);
+
+    profile_state.last_module_frame_times = std::mem::take(&mut profile_state.module_frame_times);
+
+    if !profile_state.enabled {
+        finish_overhead_frame(profile_state);
+        return;
+    }
+
+    if profile_state.frame_elapsed > profile_state.spike_threshold {
+        capture_spike(profile_state);
+    }
+
+    if let Some(budget) = profile_state.frame_budget {
+        if profile_state.frame_elapsed > budget {
+            report_budget_exceeded(profile_state, budget);
+        }
+    }
+
+    #[cfg(feature = "tracy")]
+    forward_frame_to_tracy(profile_state);
+
+    profile_state.frame_counter += 1;
+
+    let snapshot_interval = profile_state.snapshot_interval;
+
+    if profile_state.frame_counter >= snapshot_interval {
+        let timer = Instant::now();
+
+        take_snapshot(profile_state);
+        profile_state.frame_counter = 0;
+
+        for state in profile_state.performance_counter_states[..snapshot_interval].iter_mut() {
+            state.clear();
+        }
+
+        profile_state.overhead.snapshot += timer.elapsed();
+    }
+
+    finish_overhead_frame(profile_state);
+}
+
+/// Moves the overhead of the finished frame to `last_overhead`
+/// and reports it as `DEBUG_OVERHEAD_COUNTER` of the next frame.
+fn finish_overhead_frame(profile_state: &mut MutexGuard<ProfileState>) {
+    let lock_wait = LOCK_WAIT_NANOS.swap(0, Ordering::Relaxed);
+    profile_state.overhead.lock_wait += Duration::from_nanos(lock_wait);
+    profile_state.last_overhead = std::mem::take(&mut profile_state.overhead);
+
+    if profile_state.enabled {
+        let total = profile_state.last_overhead.total().as_secs_f64() * 1000.;
+        user_counter_mut(
+            profile_state,
+            DEBUG_OVERHEAD_COUNTER,
+            UserCounterKind::Gauge,
+        )
+        .value = total;
+    }
+}
+
+#[cfg(feature = "tracy")]
+fn forward_frame_to_tracy(profile_state: &ProfileState) {
+    let frame_counter = profile_state.frame_counter;
+
+    for counter in profile_state.performance_counter_states[frame_counter]
+        .user_counters
+        .iter()
+    {
+        tracy::plot(counter.name, counter.value);
+    }
+
+    tracy::frame_mark();
+}
+
+fn capture_spike(profile_state: &mut MutexGuard<ProfileState>) {
+    let frame_counter = profile_state.frame_counter;
+    let spike = FrameSpike {
+        taken_at: profile_state.start_time.elapsed(),
+        frame_elapsed: profile_state.frame_elapsed,
+        records: profile_state.performance_counter_states[frame_counter]
+            .records
+            .clone(),
+    };
+
+    let spike_log = &mut profile_state.spike_log;
+
+    if spike_log.len() >= SPIKE_LOG_SIZE {
+        spike_log.remove(0);
+    }
+
+    spike_log.push(spike);
+}
+
+fn update_frame_rate(profile_state: &mut ProfileState, now: Instant) {
+    let elapsed = profile_state.frame_elapsed;
+
+    profile_state.smoothed_frame_time = if profile_state.frame_number <= 1 {
+        elapsed
+    } else {
+        let smoothed = profile_state.smoothed_frame_time.as_secs_f64();
+        let delta = elapsed.as_secs_f64() - smoothed;
+        Duration::from_secs_f64((smoothed + delta * FRAME_TIME_SMOOTHING).max(0.))
+    };
+
+    profile_state.fps_window_frames += 1;
+    profile_state.fps_window_current_max = profile_state.fps_window_current_max.max(elapsed);
+
+    let window = now.saturating_duration_since(profile_state.fps_window_start);
+
+    if window >= FPS_WINDOW {
+        profile_state.fps = profile_state.fps_window_frames as f32 / window.as_secs_f32();
+        profile_state.fps_window_max = profile_state.fps_window_current_max;
+        profile_state.fps_window_start = now;
+        profile_state.fps_window_frames = 0;
+        profile_state.fps_window_current_max = Duration::from_nanos(0);
+    }
+}
+
+/// Sets the frame time budget, `None` disables the budget warning.
+pub fn set_frame_budget(
+    profile_state: &mut MutexGuard<ProfileState>,
+    budget: Option<Duration>,
+    log_offenders: bool,
+) {
+    profile_state.frame_budget = budget;
+    profile_state.log_budget_offenders = log_offenders;
+    profile_state.budget_exceeded_at = None;
+    profile_state.budget_offenders_logged_at = None;
+}
+
+fn report_budget_exceeded(profile_state: &mut MutexGuard<ProfileState>, budget: Duration) {
+    let now = Instant::now();
+    profile_state.budget_exceeded_at = Some(now);
+
+    let recently_logged = profile_state
+        .budget_offenders_logged_at
+        .map_or(false, |logged_at| {
+            now - logged_at < BUDGET_OFFENDERS_LOG_INTERVAL
+        });
+
+    if !profile_state.log_budget_offenders || recently_logged {
+        return;
+    }
+
+    profile_state.budget_offenders_logged_at = Some(now);
+
+    let frame_counter = profile_state.frame_counter;
+    let mut records: Vec<&ClocsDebugRecord> = profile_state.performance_counter_states
+        [frame_counter]
+        .records
+        .iter()
+        .collect();
+
+    records.sort_by(|a, b| b.elapsed.cmp(&a.elapsed));
+
+    let offenders: Vec<String> = records
+        .iter()
+        .take(BUDGET_OFFENDERS_COUNT)
+        .map(|record| format!("{} {}", record.name, format::duration(record.elapsed)))
+        .collect();
+
+    log::warn!(
+        "Frame took {} over the {} budget, slowest blocks: {}",
+        format::duration(profile_state.frame_elapsed),
+        format::duration(budget),
+        offenders.join(", ")
+    );
+}
+
+/// Fades from 1 to 0 within `BUDGET_FLASH_DURATION` after the frame over the budget,
+/// `None` when there are no recent frames over the budget.
+pub fn budget_flash_intensity(profile_state: &ProfileState) -> Option<f32> {
+    let elapsed = profile_state.budget_exceeded_at?.elapsed();
+
+    if elapsed >= BUDGET_FLASH_DURATION {
+        return None;
+    }
+
+    Some(1. - elapsed.as_secs_f32() / BUDGET_FLASH_DURATION.as_secs_f32())
+}
+
+/// Sets the expected upper bound of the counter time per frame,
+/// `None` removes the budget.
+pub fn assert_budget(profile_state: &mut ProfileState, name: &str, budget: Option<Duration>) {
+    profile_state
+        .counter_budgets
+        .retain(|counter_budget| counter_budget.name != name);
+
+    if let Some(budget) = budget {
+        profile_state.counter_budgets.push(CounterBudget {
+            name: intern_name(name),
+            budget,
+        });
+    }
+}
+
+/// Records the counters of the latest snapshot over their budgets.
+fn check_counter_budgets(profile_state: &mut ProfileState) {
+    if profile_state.counter_budgets.is_empty() {
+        return;
+    }
+
+    let snapshot = last_snapshot(profile_state);
+    let mut violations = Vec::new();
+
+    for counter_budget in profile_state.counter_budgets.iter() {
+        let actual = snapshot
+            .records
+            .iter()
+            .filter(|record| record.name == counter_budget.name)
+            .map(|record| record.max_elapsed)
+            .max();
+
+        match actual {
+            Some(actual) if actual > counter_budget.budget => violations.push(BudgetViolation {
+                counter: counter_budget.name,
+                budget: counter_budget.budget,
+                actual,
+                snapshot: profile_state.snapshots_taken,
+                taken_at: snapshot.taken_at,
+            }),
+            _ => {}
+        }
+    }
+
+    for violation in violations {
+        profile_state.budget_violations.push(violation);
+    }
+}
+
+/// Violations found in the latest snapshot.
+pub fn last_budget_violations(profile_state: &ProfileState) -> Vec<&BudgetViolation> {
+    profile_state
+        .budget_violations
+        .iter()
+        .filter(|violation| violation.snapshot == profile_state.snapshots_taken)
+        .collect()
+}
+
+pub fn update_spike_threshold(profile_state: &mut MutexGuard<ProfileState>, threshold: Duration) {
+    profile_state.spike_threshold = threshold;
+}
+
+fn push_frame_history(profile_state: &mut MutexGuard<ProfileState>) {
+    let frame_elapsed = profile_state.frame_elapsed;
+    profile_state.frame_history.push(frame_elapsed);
+    profile_state.frame_number += 1;
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct FrameTimeStats {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub p99: Duration,
+}
+
+pub fn frame_time_stats(profile_state: &ProfileState) -> FrameTimeStats {
+    let history = &profile_state.frame_history;
+
+    if history.is_empty() {
+        return FrameTimeStats::default();
+    }
+
+    let mut sorted = history.to_vec();
+    sorted.sort();
+
+    let sum: Duration = sorted.iter().sum();
+
+    FrameTimeStats {
+        min: sorted[0],
+        avg: sum / sorted.len() as u32,
+        max: sorted[sorted.len() - 1],
+        p99: percentile(&sorted, 0.99),
+    }
+}
+
+/// Current profiler state with the latest snapshot, for external tooling.
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Clone, Debug)]
+pub struct ProfileSummary<'a> {
+    pub enabled: bool,
+    pub frame_elapsed: Duration,
+    pub frame_time: FrameTimeStats,
+    pub spike_threshold: Duration,
+    pub spikes_count: usize,
+    pub module_frame_times: &'a [ModuleFrameTime],
+    pub snapshot: &'a PerformanceCounterStatistics,
+}
+
+pub fn summary(profile_state: &ProfileState) -> ProfileSummary {
+    ProfileSummary {
+        enabled: profile_state.enabled,
+        frame_elapsed: profile_state.frame_elapsed,
+        frame_time: frame_time_stats(profile_state),
+        spike_threshold: profile_state.spike_threshold,
+        spikes_count: profile_state.spike_log.len(),
+        module_frame_times: &profile_state.last_module_frame_times,
+        snapshot: last_snapshot(profile_state),
+    }
+}
+
+/// Profiler summary with the latest snapshot as JSON.
+#[cfg(feature = "serialize")]
+pub fn snapshot_json() -> String {
+    let profile_state = get_profile_state();
+
+    serde_json::to_string(&summary(&profile_state)).expect("failed to serialize profiler summary")
+}
+
+/// Nearest-rank percentile of the sorted samples.
+pub(crate) fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::from_nanos(0);
+    }
 
-    snapshot.clear();
-    snapshot.append(&mut records);
+    let idx = ((sorted.len() as f64 * p).ceil() as usize).max(1) - 1;
+    sorted[idx.min(sorted.len() - 1)]
 }
 
-pub fn update_snapshot_interval(profile_state: &mut MutexGuard<ProfileState>, new_interval: usize) {
-    if new_interval <= PERFORMANCE_COUNTER_STATE_SIZE {
-        profile_state.snapshot_interval = new_interval;
+fn std_dev(samples: &[Duration], mean: Duration) -> Duration {
+    if samples.is_empty() {
+        return Duration::from_nanos(0);
+    }
+
+    let mean = mean.as_secs_f64();
+    let variance = samples
+        .iter()
+        .map(|sample| (sample.as_secs_f64() - mean).powi(2))
+        .sum::<f64>()
+        / samples.len() as f64;
+
+    Duration::from_secs_f64(variance.sqrt())
+}
+
+fn apply_samples_statistics(
+    record: &mut PerformanceCounterStatisticsRecord,
+    samples: &mut [Duration],
+) {
+    if samples.is_empty() {
+        return;
+    }
+
+    samples.sort();
+
+    let sum: Duration = samples.iter().sum();
+    let mean = sum / samples.len() as u32;
+
+    record.min_elapsed = samples[0];
+    record.max_elapsed = samples[samples.len() - 1];
+    record.mean_elapsed = mean;
+    record.p95_elapsed = percentile(samples, 0.95);
+    record.p99_elapsed = percentile(samples, 0.99);
+    record.std_dev_elapsed = std_dev(samples, mean);
+}
+
+fn take_snapshot(profile_state: &mut MutexGuard<ProfileState>) {
+    profile_state.snapshot_counter += 1;
+
+    if profile_state.snapshot_counter >= profile_state.performance_counter_log.len() {
+        profile_state.snapshot_counter = 0;
+    }
+
+    type RecordKey = (BlockLocation, Option<BlockLocation>, thread::ThreadId);
+
+    let mut statistics: HashMap<RecordKey, PerformanceCounterStatisticsRecord> = HashMap::new();
+    let mut samples: HashMap<RecordKey, Vec<Duration>> = HashMap::new();
+
+    for state in profile_state.performance_counter_states.iter() {
+        for record in state.records.iter() {
+            let location = BlockLocation {
+                name: record.name,
+                file_name: record.file_name,
+                line: record.line,
+            };
+            let key = (location, record.parent, record.thread_id);
+            samples.entry(key).or_default().push(record.elapsed);
+
+            let element = statistics.entry(key).or_default();
+
+            element.name = record.name;
+            element.file_name = record.file_name;
+            element.line = record.line;
+            element.category = record.category;
+            element.parent = record.parent;
+            element.depth = record.depth;
+            element.sum_elapsed += record.elapsed;
+            element.sum_hits += record.hits;
+            element.sum_hits_over_elapsed += record.elapsed.as_nanos() / record.hits as u128;
+            element.hits += 1;
+            element.thread_id = thread_name(record.thread_id);
+        }
+    }
+
+    let total_elapsed: u128 = statistics
+        .values()
+        .map(|record| record.sum_elapsed.as_nanos())
+        .sum();
+
+    for (key, record) in statistics.iter_mut() {
+        record.percent =
+            (record.sum_elapsed.as_nanos() as f64 / total_elapsed as f64) as f32 * 100.0;
+
+        if let Some(samples) = samples.get_mut(key) {
+            apply_samples_statistics(record, samples);
+        }
+    }
+
+    let mut records: Vec<PerformanceCounterStatisticsRecord> =
+        statistics.values().cloned().collect();
+
+    records.sort_by(|a, b| b.percent.partial_cmp(&a.percent).unwrap());
+
+    let mut threads: Vec<PerformanceCounterThreadStatistics> = Vec::new();
+
+    // Only top level blocks are counted, nested blocks are already
+    // included in the elapsed time of their parents.
+    for record in records.iter().filter(|record| record.parent.is_none()) {
+        let thread = match threads.iter().position(|t| t.thread_id == record.thread_id) {
+            Some(idx) => &mut threads[idx],
+            None => {
+                threads.push(PerformanceCounterThreadStatistics {
+                    thread_id: record.thread_id.clone(),
+                    ..Default::default()
+                });
+                threads.last_mut().unwrap()
+            }
+        };
+
+        thread.sum_elapsed += record.sum_elapsed;
+        thread.records_count += 1;
+    }
+
+    let threads_elapsed: u128 = threads.iter().map(|t| t.sum_elapsed.as_nanos()).sum();
+
+    for thread in threads.iter_mut() {
+        thread.percent =
+            (thread.sum_elapsed.as_nanos() as f64 / threads_elapsed as f64) as f32 * 100.0;
+    }
+
+    threads.sort_by(|a, b| b.percent.partial_cmp(&a.percent).unwrap());
+
+    let user_counters = user_counters_statistics(&profile_state.performance_counter_states);
+
+    let counter = profile_state.snapshot_counter;
+    let taken_at = profile_state.start_time.elapsed();
+    let snapshot = &mut profile_state.performance_counter_log[counter];
+
+    snapshot.taken_at = taken_at;
+    snapshot.categories = category_statistics(&records);
+    snapshot.threads = threads;
+    snapshot.user_counters = user_counters;
+    snapshot.records.clear();
+    snapshot.records.append(&mut records);
+
+    profile_state.snapshots_taken += 1;
+    check_counter_budgets(profile_state);
+}
+
+fn category_statistics(records: &[PerformanceCounterStatisticsRecord]) -> Vec<CategoryStatistics> {
+    let mut statistics: Vec<CategoryStatistics> = Vec::new();
+    let total: Duration = records
+        .iter()
+        .filter(|record| record.parent.is_none())
+        .map(|record| record.sum_elapsed)
+        .sum();
+
+    for record in records.iter() {
+        let category = match record.category {
+            Some(category) => category,
+            None => continue,
+        };
+
+        if nested_into_category(records, record, category) {
+            continue;
+        }
+
+        match statistics.iter_mut().find(|item| item.category == category) {
+            Some(item) => item.sum_elapsed += record.sum_elapsed,
+            None => statistics.push(CategoryStatistics {
+                category,
+                sum_elapsed: record.sum_elapsed,
+                percent: 0.,
+            }),
+        }
+    }
+
+    for item in statistics.iter_mut() {
+        item.percent = if total.as_nanos() > 0 {
+            (item.sum_elapsed.as_secs_f64() / total.as_secs_f64() * 100.) as f32
+        } else {
+            0.
+        };
+    }
+
+    statistics.sort_by(|a, b| b.sum_elapsed.cmp(&a.sum_elapsed));
+    statistics
+}
+
+/// Checks if any ancestor of the record has the same category,
+/// the time of such records is already counted in the ancestor.
+fn nested_into_category(
+    records: &[PerformanceCounterStatisticsRecord],
+    record: &PerformanceCounterStatisticsRecord,
+    category: &str,
+) -> bool {
+    let mut parent = record.parent;
+
+    // Bounded, since recursive blocks are parents of themselves.
+    for _ in 0..records.len() {
+        let parent_record = parent.and_then(|location| {
+            records.iter().find(|parent_record| {
+                parent_record.thread_id == record.thread_id && parent_record.location() == location
+            })
+        });
+
+        match parent_record {
+            Some(parent_record) if parent_record.category == Some(category) => return true,
+            Some(parent_record) => parent = parent_record.parent,
+            None => return false,
+        }
+    }
+
+    false
+}
+
+fn user_counters_statistics(states: &[PerformanceCounterState]) -> Vec<UserCounterStatistics> {
+    let mut statistics: Vec<UserCounterStatistics> = Vec::new();
+
+    for state in states.iter() {
+        for counter in state.user_counters.iter() {
+            let value = counter.value;
+
+            match statistics.iter_mut().find(|s| s.name == counter.name) {
+                Some(element) => {
+                    element.last = value;
+                    element.min = element.min.min(value);
+                    element.max = element.max.max(value);
+                    element.mean += value;
+                    element.frames += 1;
+                }
+                None => statistics.push(UserCounterStatistics {
+                    name: counter.name,
+                    kind: counter.kind,
+                    last: value,
+                    min: value,
+                    max: value,
+                    mean: value,
+                    frames: 1,
+                }),
+            }
+        }
+    }
+
+    for element in statistics.iter_mut() {
+        element.mean /= element.frames as f64;
+    }
+
+    statistics.sort_by(|a, b| a.name.cmp(b.name));
+    statistics
+}
+
+/// Same block in two snapshots, zero values if the block is missing in one of them.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct SnapshotDiffRecord {
+    pub name: &'static str,
+    pub thread_id: String,
+    pub parent: Option<BlockLocation>,
+    pub elapsed_a: Duration,
+    pub elapsed_b: Duration,
+    pub hits_a: u32,
+    pub hits_b: u32,
+    pub percent_a: f32,
+    pub percent_b: f32,
+}
+
+impl SnapshotDiffRecord {
+    /// Change of elapsed time from `a` to `b` in nanoseconds.
+    pub fn elapsed_delta(&self) -> i128 {
+        self.elapsed_b.as_nanos() as i128 - self.elapsed_a.as_nanos() as i128
+    }
+}
+
+/// Compares blocks of two snapshots, sorted by the largest elapsed time change first.
+pub fn diff_snapshots(
+    a: &PerformanceCounterStatistics,
+    b: &PerformanceCounterStatistics,
+) -> Vec<SnapshotDiffRecord> {
+    let mut diff: HashMap<(BlockLocation, Option<BlockLocation>, &str), SnapshotDiffRecord> =
+        HashMap::new();
+
+    for (records, is_a) in [(&a.records, true), (&b.records, false)].iter() {
+        for record in records.iter() {
+            let location = BlockLocation {
+                name: record.name,
+                file_name: record.file_name,
+                line: record.line,
+            };
+            let element = diff
+                .entry((location, record.parent, record.thread_id.as_str()))
+                .or_insert_with(|| SnapshotDiffRecord {
+                    name: record.name,
+                    thread_id: record.thread_id.clone(),
+                    parent: record.parent,
+                    ..Default::default()
+                });
+
+            if *is_a {
+                element.elapsed_a = record.sum_elapsed;
+                element.hits_a = record.sum_hits;
+                element.percent_a = record.percent;
+            } else {
+                element.elapsed_b = record.sum_elapsed;
+                element.hits_b = record.sum_hits;
+                element.percent_b = record.percent;
+            }
+        }
+    }
+
+    let mut diff: Vec<SnapshotDiffRecord> = diff.into_values().collect();
+    diff.sort_by(|a, b| {
+        b.elapsed_delta()
+            .abs()
+            .cmp(&a.elapsed_delta().abs())
+            .then_with(|| a.name.cmp(b.name))
+    });
+
+    diff
+}
+
+/// Column the profiler widget rows are sorted by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProfilerSortKey {
+    Name,
+    #[default]
+    Percent,
+    Elapsed,
+    Hits,
+}
+
+/// Up to `limit` records of the snapshot with names containing `filter`
+/// and the `category` if it's set,
+/// names are sorted ascending, numbers descending.
+pub fn top_records<'a>(
+    snapshot: &'a PerformanceCounterStatistics,
+    sort_key: ProfilerSortKey,
+    filter: &str,
+    category: Option<&str>,
+    limit: usize,
+) -> Vec<&'a PerformanceCounterStatisticsRecord> {
+    let mut records: Vec<&PerformanceCounterStatisticsRecord> = snapshot
+        .records
+        .iter()
+        .filter(|record| record.name.contains(filter))
+        .filter(|record| category.map_or(true, |category| record.category == Some(category)))
+        .collect();
+
+    match sort_key {
+        ProfilerSortKey::Name => records.sort_by(|a, b| a.name.cmp(b.name)),
+        ProfilerSortKey::Percent => records.sort_by(|a, b| {
+            b.percent
+                .partial_cmp(&a.percent)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        ProfilerSortKey::Elapsed => records.sort_by(|a, b| b.sum_elapsed.cmp(&a.sum_elapsed)),
+        ProfilerSortKey::Hits => records.sort_by(|a, b| b.sum_hits.cmp(&a.sum_hits)),
+    }
+
+    records.truncate(limit);
+    records
+}
+
+/// Latest snapshot from the log.
+pub fn last_snapshot(profile_state: &ProfileState) -> &PerformanceCounterStatistics {
+    snapshot_at(profile_state, 0)
+}
+
+/// Snapshot taken `offset` snapshots before the latest one,
+/// offsets past the log size wrap around.
+pub fn snapshot_at(profile_state: &ProfileState, offset: usize) -> &PerformanceCounterStatistics {
+    let log_size = profile_state.performance_counter_log.len();
+    let offset = offset % log_size;
+    let idx = (profile_state.snapshot_counter + log_size - offset) % log_size;
+
+    &profile_state.performance_counter_log[idx]
+}
+
+/// Elapsed time of the counter with the `name` in each snapshot of the log,
+/// oldest first, summed over threads and call sites.
+pub fn counter_history(profile_state: &ProfileState, name: &str) -> Vec<Duration> {
+    (0..profile_state.performance_counter_log.len())
+        .rev()
+        .map(|offset| {
+            snapshot_at(profile_state, offset)
+                .records
+                .iter()
+                .filter(|record| record.name == name)
+                .map(|record| record.sum_elapsed)
+                .sum()
+        })
+        .collect()
+}
+
+fn register_sampled_stack() -> Arc<SampledStack> {
+    let stack = Arc::new(Mutex::new(Vec::new()));
+    let mut stacks = state::recover(SAMPLED_STACKS.lock(), "sampled stacks");
+
+    stacks.push((thread::current().id(), Arc::downgrade(&stack)));
+    stack
+}
+
+fn mirror_blocks_stack(stack: &[BlockLocation]) {
+    if !SAMPLING_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    // The thread local may be already destroyed if the block is closed on the thread exit.
+    let _ = SAMPLED_STACK.try_with(|sampled| {
+        let mut sampled = state::recover(sampled.lock(), "sampled stack");
+        sampled.clear();
+        sampled.extend_from_slice(stack);
+    });
+}
+
+/// Starts or stops copying the blocks stacks for the sampling thread,
+/// blocks opened before the start are seen after the next block of the thread is opened or closed.
+pub(crate) fn set_sampling(enabled: bool) {
+    SAMPLING_ENABLED.store(enabled, Ordering::SeqCst);
+
+    if enabled {
+        return;
+    }
+
+    for (_, stack) in state::recover(SAMPLED_STACKS.lock(), "sampled stacks").iter() {
+        if let Some(stack) = stack.upgrade() {
+            state::recover(stack.lock(), "sampled stack").clear();
+        }
+    }
+}
+
+/// Records the currently open blocks of each thread into `samples`.
+pub(crate) fn sample_open_blocks(samples: &mut SamplingStatistics) {
+    let mut stacks = state::recover(SAMPLED_STACKS.lock(), "sampled stacks");
+    stacks.retain(|(_, stack)| stack.strong_count() > 0);
+
+    for (thread_id, stack) in stacks.iter() {
+        let stack = match stack.upgrade() {
+            Some(stack) => stack,
+            None => continue,
+        };
+        let stack = state::recover(stack.lock(), "sampled stack");
+
+        *samples.threads.entry(*thread_id).or_default() += 1;
+
+        for (idx, location) in stack.iter().enumerate() {
+            // Recursive blocks are counted once.
+            if !stack[..idx].contains(location) {
+                samples
+                    .blocks
+                    .entry((*thread_id, *location))
+                    .or_default()
+                    .inclusive += 1;
+            }
+        }
+
+        if let Some(location) = stack.last() {
+            samples
+                .blocks
+                .entry((*thread_id, *location))
+                .or_default()
+                .exclusive += 1;
+        }
+    }
+}
+
+/// Hands the samples over to whichever profile state ends the frame first.
+pub(crate) fn submit_samples(samples: &mut SamplingStatistics) {
+    state::recover(PENDING_SAMPLES.lock(), "pending samples").merge(samples);
+    samples.clear();
+    SAMPLES_PENDING.store(true, Ordering::Release);
+}
+
+fn drain_samples(profile_state: &mut ProfileState) {
+    if !SAMPLES_PENDING.swap(false, Ordering::Acquire) {
+        return;
+    }
+
+    let mut pending = state::recover(PENDING_SAMPLES.lock(), "pending samples");
+    profile_state.sampling.merge(&pending);
+    pending.clear();
+}
+
+pub fn reset_samples(profile_state: &mut ProfileState) {
+    profile_state.sampling.clear();
+}
+
+/// Sampled blocks sorted by the inclusive samples, most sampled first.
+pub fn top_sampled_blocks(
+    profile_state: &ProfileState,
+    limit: usize,
+) -> Vec<(thread::ThreadId, BlockLocation, BlockSamples)> {
+    let mut blocks: Vec<(thread::ThreadId, BlockLocation, BlockSamples)> = profile_state
+        .sampling
+        .blocks
+        .iter()
+        .map(|((thread_id, location), samples)| (*thread_id, *location, *samples))
+        .collect();
+
+    blocks.sort_by(|a, b| b.2.inclusive.cmp(&a.2.inclusive));
+    blocks.truncate(limit);
+    blocks
+}
+
+/// Sets the number of frames per snapshot, frames collected since
+/// the last snapshot are dropped so the next one covers whole interval.
+pub fn update_snapshot_interval(
+    profile_state: &mut MutexGuard<ProfileState>,
+    new_interval: usize,
+) -> Result<(), String> {
+    let state_size = profile_state.performance_counter_states.len();
+
+    if new_interval == 0 || new_interval > state_size {
+        return Err(format!(
+            "Snapshot interval should be in range 1..={}, found {}",
+            state_size, new_interval
+        ));
+    }
+
+    profile_state.snapshot_interval = new_interval;
+    profile_state.frame_counter = 0;
+
+    for state in profile_state.performance_counter_states.iter_mut() {
+        state.clear();
+    }
+
+    Ok(())
+}
+
+/// Resizes the buffers, collected frames and snapshots are dropped.
+/// The snapshot interval is limited by the new state size.
+pub fn configure(
+    profile_state: &mut MutexGuard<ProfileState>,
+    config: ProfilerConfig,
+) -> Result<(), String> {
+    config.validate()?;
+
+    profile_state.config = config;
+    profile_state.performance_counter_states = counter_states(&config);
+    profile_state.performance_counter_log = counter_log(&config);
+    profile_state.snapshot_interval = profile_state.snapshot_interval.min(config.state_size);
+    profile_state.frame_counter = 0;
+    profile_state.snapshot_counter = 0;
+
+    Ok(())
+}
+
+fn counter_states(config: &ProfilerConfig) -> Vec<PerformanceCounterState> {
+    (0..config.state_size)
+        .map(|_| PerformanceCounterState::with_capacity(config.records_capacity))
+        .collect()
+}
+
+fn counter_log(config: &ProfilerConfig) -> Vec<PerformanceCounterStatistics> {
+    (0..config.log_size)
+        .map(|_| PerformanceCounterStatistics::with_capacity(config.records_capacity))
+        .collect()
+}
+
+/// Writes timed block events and snapshots from the log
+/// in the Trace Event format, viewable in chrome://tracing.
+pub fn export_chrome_trace(profile_state: &ProfileState, path: &str) -> io::Result<()> {
+    let trace = chrome_trace(profile_state);
+
+    fs::write(path, trace)
+}
+
+fn chrome_trace(profile_state: &ProfileState) -> String {
+    let mut events = chrome_trace_blocks(profile_state.timed_block_events.iter());
+
+    for marker in profile_state.event_markers.iter() {
+        events.push(format!(
+            r#"{{"name":{},"cat":"marker","ph":"i","s":"g","ts":{},"pid":0,"tid":0}}"#,
+            format::json_string(&marker.label),
+            marker.timestamp.as_micros(),
+        ));
+    }
+
+    let mut snapshots: Vec<&PerformanceCounterStatistics> = profile_state
+        .performance_counter_log
+        .iter()
+        .filter(|snapshot| !snapshot.records.is_empty())
+        .collect();
+
+    snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+
+    for snapshot in snapshots {
+        let args: Vec<String> = snapshot
+            .records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{}:{}",
+                    format::json_string(record.name),
+                    record.sum_elapsed.as_secs_f64() * 1000.
+                )
+            })
+            .collect();
+
+        events.push(format!(
+            r#"{{"name":"snapshot_elapsed_ms","ph":"C","ts":{},"pid":0,"tid":0,"args":{{{}}}}}"#,
+            snapshot.taken_at.as_micros(),
+            args.join(","),
+        ));
+    }
+
+    format!("{{\"traceEvents\":[{}]}}", events.join(",\n"))
+}
+
+/// Timed block events and thread names in chrome://tracing format.
+fn chrome_trace_blocks<'a>(blocks: impl Iterator<Item = &'a TimedBlockEvent>) -> Vec<String> {
+    let mut threads: Vec<thread::ThreadId> = Vec::new();
+    let mut events: Vec<String> = Vec::new();
+
+    for event in blocks {
+        let tid = match threads.iter().position(|id| *id == event.thread_id) {
+            Some(tid) => tid,
+            None => {
+                threads.push(event.thread_id);
+                threads.len() - 1
+            }
+        };
+
+        events.push(format!(
+            r#"{{"name":{},"cat":{},"ph":"X","ts":{},"dur":{},"pid":0,"tid":{},"args":{{"file":{},"line":{}}}}}"#,
+            format::json_string(event.name),
+            format::json_string(event.category.unwrap_or("timed_block")),
+            event.begin.as_micros(),
+            (event.end - event.begin).as_micros(),
+            tid,
+            format::json_string(event.file_name),
+            event.line,
+        ));
+    }
+
+    for (i, thread_id) in threads.iter().enumerate() {
+        events.push(format!(
+            r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{},"args":{{"name":{}}}}}"#,
+            i,
+            format::json_string(&thread_name(*thread_id)),
+        ));
+    }
+
+    events
+}
+
+/// Writes frames recorded in the detail mode in chrome://tracing format,
+/// each frame is an event on its own track.
+pub fn export_frame_timelines(profile_state: &ProfileState, path: &str) -> io::Result<()> {
+    if profile_state.frame_timelines.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "No frames recorded, enable profile::detail first",
+        ));
+    }
+
+    fs::write(path, frame_timelines_trace(profile_state))
+}
+
+fn frame_timelines_trace(profile_state: &ProfileState) -> String {
+    let timelines = &profile_state.frame_timelines;
+    let mut events =
+        chrome_trace_blocks(timelines.iter().flat_map(|timeline| timeline.events.iter()));
+
+    for timeline in timelines.iter() {
+        events.push(format!(
+            r#"{{"name":"frame {}","cat":"frame","ph":"X","ts":{},"dur":{},"pid":1,"tid":0}}"#,
+            timeline.frame_number,
+            timeline.begin.as_micros(),
+            timeline.end.saturating_sub(timeline.begin).as_micros(),
+        ));
+    }
+
+    format!("{{\"traceEvents\":[{}]}}", events.join(",\n"))
+}
+
+/// Writes recorded timed block events in the collapsed stack format
+/// compatible with inferno and speedscope, weights are self times in microseconds.
+pub fn export_flamegraph(profile_state: &ProfileState, path: &str) -> io::Result<()> {
+    let folded = folded_stacks(&profile_state.timed_block_events.to_vec());
+
+    fs::write(path, folded)
+}
+
+fn folded_stacks(events: &[TimedBlockEvent]) -> String {
+    struct Frame {
+        name: &'static str,
+        begin: Duration,
+        end: Duration,
+        children: Duration,
+    }
+
+    fn close_frame(stack: &mut Vec<Frame>, root: &str, stacks: &mut BTreeMap<String, u128>) {
+        let frame = match stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        let total = frame.end - frame.begin;
+        let self_time = total.checked_sub(frame.children).unwrap_or_default();
+
+        let mut path = String::from(root);
+
+        for parent in stack.iter() {
+            path.push(';');
+            path.push_str(parent.name);
+        }
+
+        path.push(';');
+        path.push_str(frame.name);
+
+        *stacks.entry(path).or_default() += self_time.as_micros();
+
+        if let Some(parent) = stack.last_mut() {
+            parent.children += total;
+        }
+    }
+
+    let mut threads: Vec<thread::ThreadId> = Vec::new();
+
+    for event in events.iter() {
+        if !threads.contains(&event.thread_id) {
+            threads.push(event.thread_id);
+        }
+    }
+
+    let mut stacks: BTreeMap<String, u128> = BTreeMap::new();
+
+    for thread_id in threads {
+        let root = thread_name(thread_id);
+        let mut thread_events: Vec<&TimedBlockEvent> = events
+            .iter()
+            .filter(|event| event.thread_id == thread_id)
+            .collect();
+
+        thread_events.sort_by(|a, b| a.begin.cmp(&b.begin).then(b.end.cmp(&a.end)));
+
+        let mut stack: Vec<Frame> = Vec::new();
+
+        for event in thread_events {
+            while stack.last().map_or(false, |top| top.end <= event.begin) {
+                close_frame(&mut stack, &root, &mut stacks);
+            }
+
+            stack.push(Frame {
+                name: event.name,
+                begin: event.begin,
+                end: event.end,
+                children: Duration::from_nanos(0),
+            });
+        }
+
+        while !stack.is_empty() {
+            close_frame(&mut stack, &root, &mut stacks);
+        }
+    }
+
+    stacks
+        .iter()
+        .map(|(path, weight)| format!("{} {}\n", path, weight))
+        .collect()
+}
+
+/// Writes all records of the snapshot log as CSV, one row per record.
+pub fn export_csv(profile_state: &ProfileState, path: &str) -> io::Result<()> {
+    let csv = counter_log_csv(&profile_state.performance_counter_log);
+
+    fs::write(path, csv)
+}
+
+fn counter_log_csv(log: &[PerformanceCounterStatistics]) -> String {
+    let mut csv = String::from(
+        "snapshot,taken_at_us,thread_id,name,file_name,line,parent,depth,\
+         sum_elapsed_ns,sum_hits,hits,percent,min_elapsed_ns,max_elapsed_ns,\
+         mean_elapsed_ns,p95_elapsed_ns,p99_elapsed_ns,std_dev_elapsed_ns,category\n",
+    );
+
+    for (snapshot_index, snapshot) in log.iter().enumerate() {
+        for record in snapshot.records.iter() {
+            let row = [
+                snapshot_index.to_string(),
+                snapshot.taken_at.as_micros().to_string(),
+                format::csv_field(&record.thread_id),
+                format::csv_field(record.name),
+                format::csv_field(record.file_name),
+                record.line.to_string(),
+                format::csv_field(record.parent.map_or("", |parent| parent.name)),
+                record.depth.to_string(),
+                record.sum_elapsed.as_nanos().to_string(),
+                record.sum_hits.to_string(),
+                record.hits.to_string(),
+                record.percent.to_string(),
+                record.min_elapsed.as_nanos().to_string(),
+                record.max_elapsed.as_nanos().to_string(),
+                record.mean_elapsed.as_nanos().to_string(),
+                record.p95_elapsed.as_nanos().to_string(),
+                record.p99_elapsed.as_nanos().to_string(),
+                record.std_dev_elapsed.as_nanos().to_string(),
+                format::csv_field(record.category.unwrap_or("")),
+            ];
+
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+    }
+
+    csv
+}
+
+/// Frame time, timed blocks of the last snapshot and user counters
+/// in the Prometheus text exposition format, e.g. to serve on `/metrics`.
+pub fn metrics_text() -> String {
+    let profile_state = get_profile_state();
+    prometheus_metrics(&profile_state)
+}
+
+fn prometheus_metrics(profile_state: &ProfileState) -> String {
+    let mut text = String::new();
+    let frame_time = frame_time_stats(profile_state);
+    let snapshot = last_snapshot(profile_state);
+
+    metric_family(
+        &mut text,
+        "debug_frames_total",
+        "counter",
+        "Frames since profiler start.",
+    );
+    text.push_str(&format!(
+        "debug_frames_total {}\n",
+        profile_state.frame_number
+    ));
+
+    metric_family(
+        &mut text,
+        "debug_frame_time_seconds",
+        "gauge",
+        "Last frame time and statistics over the frame history.",
+    );
+
+    let frame_times = [
+        ("last", profile_state.frame_elapsed),
+        ("min", frame_time.min),
+        ("avg", frame_time.avg),
+        ("max", frame_time.max),
+        ("p99", frame_time.p99),
+    ];
+
+    for (stat, value) in frame_times.iter() {
+        text.push_str(&format!(
+            "debug_frame_time_seconds{{stat=\"{}\"}} {}\n",
+            stat,
+            value.as_secs_f64()
+        ));
+    }
+
+    if !profile_state.last_module_frame_times.is_empty() {
+        metric_family(
+            &mut text,
+            "debug_module_frame_time_seconds",
+            "gauge",
+            "Time spent in the module within the last frame.",
+        );
+
+        for module in profile_state.last_module_frame_times.iter() {
+            text.push_str(&format!(
+                "debug_module_frame_time_seconds{{module={}}} {}\n",
+                format::prometheus_label(module.id),
+                module.elapsed.as_secs_f64()
+            ));
+        }
+    }
+
+    if !snapshot.records.is_empty() {
+        metric_family(
+            &mut text,
+            "debug_block_elapsed_seconds",
+            "gauge",
+            "Timed block elapsed time within the last snapshot.",
+        );
+
+        for record in snapshot.records.iter() {
+            text.push_str(&format!(
+                "debug_block_elapsed_seconds{{{}}} {}\n",
+                block_labels(record),
+                record.sum_elapsed.as_secs_f64()
+            ));
+        }
+
+        metric_family(
+            &mut text,
+            "debug_block_hits",
+            "gauge",
+            "Timed block hits within the last snapshot.",
+        );
+
+        for record in snapshot.records.iter() {
+            text.push_str(&format!(
+                "debug_block_hits{{{}}} {}\n",
+                block_labels(record),
+                record.sum_hits
+            ));
+        }
+    }
+
+    if !snapshot.user_counters.is_empty() {
+        metric_family(
+            &mut text,
+            "debug_user_counter",
+            "gauge",
+            "Last frame value of the user counter.",
+        );
+
+        for counter in snapshot.user_counters.iter() {
+            let kind = match counter.kind {
+                UserCounterKind::Count => "count",
+                UserCounterKind::Gauge => "gauge",
+            };
+
+            text.push_str(&format!(
+                "debug_user_counter{{name={},kind=\"{}\"}} {}\n",
+                format::prometheus_label(counter.name),
+                kind,
+                counter.last
+            ));
+        }
+    }
+
+    text
+}
+
+fn metric_family(text: &mut String, name: &str, kind: &str, help: &str) {
+    text.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} {}\n",
+        name, help, name, kind
+    ));
+}
+
+fn block_labels(record: &PerformanceCounterStatisticsRecord) -> String {
+    format!(
+        "thread={},name={},file={},line=\"{}\"",
+        format::prometheus_label(&record.thread_id),
+        format::prometheus_label(record.name),
+        format::prometheus_label(record.file_name),
+        record.line
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profiler::{self, IntoBlockName};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn event(name: &'static str, begin: u64, end: u64) -> profiler::TimedBlockEvent {
+        profiler::TimedBlockEvent {
+            name,
+            file_name: file!(),
+            line: 0,
+            category: None,
+            thread_id: thread::current().id(),
+            depth: 0,
+            begin: Duration::from_micros(begin),
+            end: Duration::from_micros(end),
+        }
+    }
+
+    #[test]
+    fn debug_overhead() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profile_state.overhead.step = Duration::from_millis(2);
+        profile_state.overhead.render = Duration::from_millis(1);
+        profiler::frame_end(profile_state);
+
+        assert_eq!(Duration::default(), profile_state.overhead.step);
+        assert_eq!(Duration::from_millis(2), profile_state.last_overhead.step);
+        assert!(profile_state.last_overhead.total() >= Duration::from_millis(3));
+
+        let counters =
+            &profile_state.performance_counter_states[profile_state.frame_counter].user_counters;
+        let counter = counters
+            .iter()
+            .find(|counter| counter.name == profiler::DEBUG_OVERHEAD_COUNTER)
+            .unwrap();
+
+        assert!(counter.value >= 3.);
+    }
+
+    #[test]
+    fn sample_open_blocks() {
+        let location = |name| profiler::BlockLocation {
+            name,
+            file_name: file!(),
+            line: 0,
+        };
+        let thread_id = thread::current().id();
+        let mut samples = profiler::SamplingStatistics::default();
+
+        profiler::set_sampling(true);
+
+        {
+            let _outer = profiler::TimedBlock::new("sampled_outer", file!(), 0);
+            let _inner = profiler::TimedBlock::new("sampled_inner", file!(), 0);
+
+            profiler::sample_open_blocks(&mut samples);
+            profiler::sample_open_blocks(&mut samples);
+        }
+
+        profiler::sample_open_blocks(&mut samples);
+        profiler::set_sampling(false);
+
+        let outer = samples.blocks[&(thread_id, location("sampled_outer"))];
+        let inner = samples.blocks[&(thread_id, location("sampled_inner"))];
+
+        assert_eq!(3, samples.threads[&thread_id]);
+        assert_eq!(
+            profiler::BlockSamples {
+                inclusive: 2,
+                exclusive: 0
+            },
+            outer
+        );
+        assert_eq!(
+            profiler::BlockSamples {
+                inclusive: 2,
+                exclusive: 2
+            },
+            inner
+        );
+
+        let mut profile_state = profiler::ProfileState::default();
+        samples.interval = Duration::from_millis(1);
+        profile_state.sampling.merge(&samples);
+        profile_state.sampling.merge(&samples);
+
+        let top = profiler::top_sampled_blocks(&profile_state, 100);
+        let inner = top
+            .iter()
+            .find(|(_, location, _)| location.name == "sampled_inner")
+            .unwrap();
+
+        assert_eq!(4, inner.2.exclusive);
+        assert_eq!(
+            Duration::from_millis(4),
+            profile_state.sampling.estimated_time(inner.2.inclusive)
+        );
+    }
+
+    #[test]
+    fn thread_names() {
+        let (named, unnamed) = thread::spawn(|| {
+            profiler::register_thread_name("worker-0");
+            (
+                profiler::thread_name(thread::current().id()),
+                thread::spawn(|| thread::current().id()).join().unwrap(),
+            )
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!("worker-0", named);
+        assert_eq!(format!("{:?}", unnamed), profiler::thread_name(unnamed));
+    }
+
+    #[test]
+    fn frame_timelines() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+        let block = |profile_state: &mut std::sync::MutexGuard<profiler::ProfileState>| {
+            let block = profiler::TimedBlock::open("frame_timelines", file!(), line!(), true);
+            profiler::drop_timed_block(&block, profile_state);
+        };
+
+        block(profile_state);
+        profiler::frame_end(profile_state);
+        assert!(profile_state.frame_timelines.is_empty());
+
+        profiler::set_detail(profile_state, true);
+        block(profile_state);
+        block(profile_state);
+        profiler::frame_end(profile_state);
+        profiler::frame_end(profile_state);
+
+        assert_eq!(2, profile_state.frame_timelines.len());
+        assert_eq!(2, profile_state.frame_timelines[0].events.len());
+        assert_eq!(1, profile_state.frame_timelines[0].frame_number);
+        assert!(profile_state.frame_timelines[1].events.is_empty());
+
+        profiler::set_detail(profile_state, false);
+        assert!(profile_state.frame_timelines.is_empty());
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let events = vec![
+            event("update", 0, 100),
+            event("physics", 10, 40),
+            event("collisions", 20, 30),
+            event("ai", 50, 70),
+            event("render", 100, 150),
+        ];
+
+        let root = format!("{:?}", thread::current().id());
+        let expected = format!(
+            "{root};render 50\n{root};update 50\n{root};update;ai 20\n{root};update;physics 20\n{root};update;physics;collisions 10\n",
+            root = root
+        );
+
+        assert_eq!(expected, profiler::folded_stacks(&events));
+    }
+
+    #[test]
+    fn frame_time_stats() {
+        let mut profile_state = profiler::ProfileState::default();
+        for ms in 1..=100 {
+            profile_state.frame_history.push(Duration::from_millis(ms));
+        }
+
+        let stats = profiler::frame_time_stats(&profile_state);
+
+        assert_eq!(Duration::from_millis(1), stats.min);
+        assert_eq!(Duration::from_micros(50_500), stats.avg);
+        assert_eq!(Duration::from_millis(100), stats.max);
+        assert_eq!(Duration::from_millis(99), stats.p99);
+    }
+
+    #[test]
+    fn counter_samples_statistics() {
+        let mut record = profiler::PerformanceCounterStatisticsRecord::default();
+        let mut samples: Vec<Duration> = [4, 2, 8, 6, 100]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+
+        profiler::apply_samples_statistics(&mut record, &mut samples);
+
+        assert_eq!(Duration::from_millis(2), record.min_elapsed);
+        assert_eq!(Duration::from_millis(100), record.max_elapsed);
+        assert_eq!(Duration::from_millis(24), record.mean_elapsed);
+        assert_eq!(Duration::from_millis(100), record.p95_elapsed);
+        assert_eq!(Duration::from_millis(100), record.p99_elapsed);
+        assert_eq!(38, record.std_dev_elapsed.as_millis());
+    }
+
+    #[test]
+    fn dynamic_block_names() {
+        let texture = "grass.png";
+        let block =
+            profiler::TimedBlock::new(format!("load texture: {}", texture), file!(), line!());
+        let name = String::from("load texture: grass.png");
+
+        assert_eq!("load texture: grass.png", block.name);
+        assert!(std::ptr::eq(block.name, profiler::intern_name(&name)));
+        assert!(std::ptr::eq(block.name, (&name).into_block_name()));
+    }
+
+    #[test]
+    fn record_lookup() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+        let block = |profile_state: &mut std::sync::MutexGuard<profiler::ProfileState>, line| {
+            let block = profiler::TimedBlock::open("record_lookup", file!(), line, true);
+            profiler::drop_timed_block(&block, profile_state);
+        };
+
+        block(profile_state, 1);
+        block(profile_state, 2);
+        block(profile_state, 1);
+
+        let records = &profile_state.performance_counter_states[0].records;
+
+        assert_eq!(2, records.len());
+        assert_eq!((1, 2), (records[0].line, records[0].hits));
+        assert_eq!((2, 1), (records[1].line, records[1].hits));
+
+        let location = profiler::BlockLocation {
+            name: "record_lookup",
+            file_name: file!(),
+            line: 2,
+        };
+
+        assert_eq!(
+            profiler::counter_id(profile_state, location),
+            profiler::counter_id(profile_state, location)
+        );
+        assert_eq!(2, profile_state.counter_ids.len());
+    }
+
+    #[test]
+    fn frame_spike_capture() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profile_state.performance_counter_states[0]
+            .records
+            .push(profiler::ClocsDebugRecord {
+                name: "spike_block",
+                elapsed: Duration::from_millis(18),
+                hits: 1,
+                ..Default::default()
+            });
+
+        profile_state.frame_timer = Instant::now() - Duration::from_millis(20);
+        profiler::frame_end(profile_state);
+
+        profile_state.frame_timer = Instant::now();
+        profiler::frame_end(profile_state);
+
+        assert_eq!(1, profile_state.spike_log.len());
+
+        let spike = &profile_state.spike_log[0];
+        assert!(spike.frame_elapsed >= Duration::from_millis(20));
+        assert!(spike
+            .records
+            .iter()
+            .any(|record| record.name == "spike_block"));
+    }
+
+    #[test]
+    fn category_statistics() {
+        let root = profiler::BlockLocation {
+            name: "physics",
+            ..Default::default()
+        };
+        let record = |name, category, parent, ms| profiler::PerformanceCounterStatisticsRecord {
+            name,
+            category,
+            parent,
+            sum_elapsed: Duration::from_millis(ms),
+            ..Default::default()
+        };
+
+        let raycast = profiler::BlockLocation {
+            name: "raycast",
+            ..Default::default()
+        };
+
+        let records = vec![
+            record("physics", Some("physics"), None, 6),
+            record("solve", Some("physics"), Some(root), 4),
+            record("raycast", Some("ai"), Some(root), 1),
+            // Nested into physics through ai, already counted in physics.
+            record("narrow_phase", Some("physics"), Some(raycast), 1),
+            record("render", None, None, 4),
+        ];
+
+        let statistics = profiler::category_statistics(&records);
+
+        assert_eq!(2, statistics.len());
+        assert_eq!("physics", statistics[0].category);
+        assert_eq!(Duration::from_millis(6), statistics[0].sum_elapsed);
+        assert_eq!(60., statistics[0].percent);
+        assert_eq!("ai", statistics[1].category);
+        assert_eq!(Duration::from_millis(1), statistics[1].sum_elapsed);
+
+        let snapshot = profiler::PerformanceCounterStatistics {
+            records,
+            ..Default::default()
+        };
+        let physics = profiler::top_records(
+            &snapshot,
+            profiler::ProfilerSortKey::Name,
+            "",
+            Some("physics"),
+            10,
+        );
+
+        assert_eq!(3, physics.len());
+    }
+
+    #[test]
+    fn counter_budgets() {
+        let mut profile_state = profiler::ProfileState::default();
+        let record = |name, ms| profiler::PerformanceCounterStatisticsRecord {
+            name,
+            max_elapsed: Duration::from_millis(ms),
+            ..Default::default()
+        };
+
+        profiler::assert_budget(
+            &mut profile_state,
+            "ai_update",
+            Some(Duration::from_millis(2)),
+        );
+        profiler::assert_budget(&mut profile_state, "render", Some(Duration::from_millis(8)));
+        profiler::assert_budget(&mut profile_state, "render", Some(Duration::from_millis(4)));
+        assert_eq!(2, profile_state.counter_budgets.len());
+
+        profile_state.performance_counter_log[0].records = vec![
+            record("ai_update", 3),
+            record("ai_update", 1),
+            record("render", 4),
+        ];
+        profile_state.snapshots_taken = 7;
+        profiler::check_counter_budgets(&mut profile_state);
+
+        let violations = profiler::last_budget_violations(&profile_state);
+
+        assert_eq!(1, violations.len());
+        assert_eq!("ai_update", violations[0].counter);
+        assert_eq!(Duration::from_millis(2), violations[0].budget);
+        assert_eq!(Duration::from_millis(3), violations[0].actual);
+        assert_eq!(7, violations[0].snapshot);
+
+        profiler::assert_budget(&mut profile_state, "ai_update", None);
+        profile_state.snapshots_taken = 8;
+        profiler::check_counter_budgets(&mut profile_state);
+
+        assert!(profiler::last_budget_violations(&profile_state).is_empty());
+        assert_eq!(1, profile_state.budget_violations.len());
+    }
+
+    #[test]
+    fn user_counters_statistics() {
+        let counter = |name, kind, value| profiler::UserCounterRecord { name, kind, value };
+        let mut states = vec![profiler::PerformanceCounterState::default(); 3];
+
+        states[0].user_counters = vec![
+            counter("entities", profiler::UserCounterKind::Gauge, 10.),
+            counter("draw_calls", profiler::UserCounterKind::Count, 4.),
+        ];
+        states[1].user_counters = vec![counter("draw_calls", profiler::UserCounterKind::Count, 8.)];
+        states[2].user_counters = vec![counter("entities", profiler::UserCounterKind::Gauge, 30.)];
+
+        let statistics = profiler::user_counters_statistics(&states);
+
+        assert_eq!(2, statistics.len());
+        assert_eq!("draw_calls", statistics[0].name);
+        assert_eq!(8., statistics[0].last);
+        assert_eq!(6., statistics[0].mean);
+        assert_eq!(2, statistics[0].frames);
+        assert_eq!("entities", statistics[1].name);
+        assert_eq!(profiler::UserCounterKind::Gauge, statistics[1].kind);
+        assert_eq!(10., statistics[1].min);
+        assert_eq!(30., statistics[1].max);
+    }
+
+    #[test]
+    fn diff_snapshots() {
+        let record = |name, ms, hits| profiler::PerformanceCounterStatisticsRecord {
+            name,
+            sum_elapsed: Duration::from_millis(ms),
+            sum_hits: hits,
+            ..Default::default()
+        };
+
+        let a = profiler::PerformanceCounterStatistics {
+            records: vec![record("update", 10, 3), record("render", 4, 1)],
+            ..Default::default()
+        };
+        let b = profiler::PerformanceCounterStatistics {
+            records: vec![record("update", 6, 3), record("physics", 5, 2)],
+            ..Default::default()
+        };
+
+        let diff = profiler::diff_snapshots(&a, &b);
+
+        assert_eq!(3, diff.len());
+        assert_eq!("physics", diff[0].name);
+        assert_eq!(5_000_000, diff[0].elapsed_delta());
+        assert_eq!(0, diff[0].hits_a);
+        assert_eq!("render", diff[1].name);
+        assert_eq!(-4_000_000, diff[1].elapsed_delta());
+        assert_eq!("update", diff[2].name);
+        assert_eq!(3, diff[2].hits_b);
+    }
+
+    #[test]
+    fn top_records() {
+        let record = |name, percent, ms, hits| profiler::PerformanceCounterStatisticsRecord {
+            name,
+            percent,
+            sum_elapsed: Duration::from_millis(ms),
+            sum_hits: hits,
+            ..Default::default()
+        };
+
+        let snapshot = profiler::PerformanceCounterStatistics {
+            records: vec![
+                record("update", 50., 8, 1),
+                record("render_ui", 20., 3, 40),
+                record("render_world", 30., 5, 12),
+            ],
+            ..Default::default()
+        };
+
+        let names = |records: Vec<&profiler::PerformanceCounterStatisticsRecord>| {
+            records.iter().map(|record| record.name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec!["update", "render_world"],
+            names(profiler::top_records(
+                &snapshot,
+                profiler::ProfilerSortKey::Percent,
+                "",
+                None,
+                2
+            ))
+        );
+        assert_eq!(
+            vec!["render_ui", "render_world"],
+            names(profiler::top_records(
+                &snapshot,
+                profiler::ProfilerSortKey::Name,
+                "render",
+                None,
+                10
+            ))
+        );
+        assert_eq!(
+            vec!["render_ui", "render_world", "update"],
+            names(profiler::top_records(
+                &snapshot,
+                profiler::ProfilerSortKey::Hits,
+                "",
+                None,
+                10
+            ))
+        );
+        assert_eq!(
+            vec!["update", "render_world", "render_ui"],
+            names(profiler::top_records(
+                &snapshot,
+                profiler::ProfilerSortKey::Elapsed,
+                "",
+                None,
+                10
+            ))
+        );
+    }
+
+    #[test]
+    fn snapshot_at() {
+        let mut profile_state = profiler::ProfileState::default();
+
+        for (i, snapshot) in profile_state.performance_counter_log.iter_mut().enumerate() {
+            snapshot.taken_at = Duration::from_millis(i as u64);
+        }
+
+        profile_state.snapshot_counter = 1;
+
+        let taken_at = |offset| profiler::snapshot_at(&profile_state, offset).taken_at;
+
+        assert_eq!(Duration::from_millis(1), taken_at(0));
+        assert_eq!(Duration::from_millis(0), taken_at(1));
+        assert_eq!(
+            Duration::from_millis(profiler::PERFORMANCE_COUNTER_LOG_SIZE as u64 - 1),
+            taken_at(2)
+        );
+    }
+
+    #[test]
+    fn update_snapshot_interval() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profile_state.frame_counter = 2;
+
+        assert!(profiler::update_snapshot_interval(profile_state, 0).is_err());
+        assert!(profiler::update_snapshot_interval(
+            profile_state,
+            profiler::PERFORMANCE_COUNTER_STATE_SIZE + 1
+        )
+        .is_err());
+        assert_eq!(2, profile_state.frame_counter);
+
+        assert_eq!(
+            Ok(()),
+            profiler::update_snapshot_interval(profile_state, 10)
+        );
+        assert_eq!(10, profile_state.snapshot_interval);
+        assert_eq!(0, profile_state.frame_counter);
+    }
+
+    #[test]
+    fn configure() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        let config = profiler::ProfilerConfig {
+            records_capacity: 16,
+            log_size: 10,
+            state_size: 2,
+        };
+
+        assert_eq!(Ok(()), profiler::configure(profile_state, config));
+        assert_eq!(10, profile_state.performance_counter_log.len());
+        assert_eq!(2, profile_state.performance_counter_states.len());
+        assert_eq!(2, profile_state.snapshot_interval);
+        assert_eq!(10, profiler::counter_history(profile_state, "update").len());
+
+        for _ in 0..25 {
+            profiler::frame_end(profile_state);
+        }
+
+        assert_eq!(2, profile_state.snapshot_counter);
+        assert!(profiler::update_snapshot_interval(profile_state, 3).is_err());
+
+        let invalid = profiler::ProfilerConfig {
+            log_size: 0,
+            ..config
+        };
+
+        assert!(profiler::configure(profile_state, invalid).is_err());
+        assert_eq!(config, profile_state.config);
+    }
+
+    #[test]
+    fn counter_history() {
+        let mut profile_state = profiler::ProfileState::default();
+        let record = |name, elapsed| profiler::PerformanceCounterStatisticsRecord {
+            name,
+            sum_elapsed: Duration::from_millis(elapsed),
+            ..Default::default()
+        };
+
+        profile_state.performance_counter_log[0].records = vec![
+            record("update", 2),
+            record("update", 3),
+            record("render", 4),
+        ];
+        profile_state.performance_counter_log[1].records = vec![record("update", 1)];
+        profile_state.snapshot_counter = 1;
+
+        let history = profiler::counter_history(&profile_state, "update");
+
+        assert_eq!(profiler::PERFORMANCE_COUNTER_LOG_SIZE, history.len());
+        assert_eq!(Duration::from_millis(5), history[history.len() - 2]);
+        assert_eq!(Duration::from_millis(1), history[history.len() - 1]);
+        assert_eq!(Duration::from_millis(0), history[0]);
+    }
+
+    #[test]
+    fn module_frame_times() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profiler::record_module_time(profile_state, "render", Duration::from_millis(2));
+        profiler::record_module_time(profile_state, "physics", Duration::from_millis(3));
+        profiler::record_module_time(profile_state, "render", Duration::from_millis(4));
+
+        profiler::frame_end(profile_state);
+
+        assert!(profile_state.module_frame_times.is_empty());
+        assert_eq!(
+            vec![
+                profiler::ModuleFrameTime {
+                    id: "render",
+                    elapsed: Duration::from_millis(6),
+                },
+                profiler::ModuleFrameTime {
+                    id: "physics",
+                    elapsed: Duration::from_millis(3),
+                },
+            ],
+            profile_state.last_module_frame_times
+        );
+    }
+
+    #[test]
+    fn frame_rate() {
+        let mut profile_state = profiler::ProfileState::default();
+        let start = profile_state.fps_window_start;
+
+        for i in 1..=30 {
+            profile_state.frame_number = i;
+            profile_state.frame_elapsed = Duration::from_millis(if i == 10 { 50 } else { 20 });
+            profiler::update_frame_rate(&mut profile_state, start + Duration::from_millis(i * 20));
+
+            if i == 1 {
+                assert_eq!(Duration::from_millis(20), profile_state.smoothed_frame_time);
+            }
+        }
+
+        // 1 second window has finished on the 50th frame.
+        assert_eq!(0., profile_state.fps);
+
+        for i in 31..=50 {
+            profile_state.frame_number = i;
+            profile_state.frame_elapsed = Duration::from_millis(20);
+            profiler::update_frame_rate(&mut profile_state, start + Duration::from_millis(i * 20));
+        }
+
+        assert_eq!(50., profile_state.fps);
+        assert_eq!(Duration::from_millis(50), profile_state.fps_window_max);
+        assert_eq!(0, profile_state.fps_window_frames);
+
+        let smoothed_ms = profile_state.smoothed_frame_time.as_secs_f64() * 1000.;
+        assert!((smoothed_ms - 20.).abs() < 0.1);
+    }
+
+    #[test]
+    fn frame_budget() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profiler::set_frame_budget(profile_state, Some(Duration::from_secs(60)), true);
+        profiler::frame_start(profile_state);
+        profiler::frame_end(profile_state);
+
+        assert_eq!(None, profiler::budget_flash_intensity(profile_state));
+
+        profiler::set_frame_budget(profile_state, Some(Duration::from_nanos(1)), true);
+        profiler::frame_start(profile_state);
+        thread::sleep(Duration::from_millis(1));
+        profiler::frame_end(profile_state);
+
+        assert!(profile_state.budget_exceeded_at.is_some());
+        assert!(profiler::budget_flash_intensity(profile_state).unwrap() > 0.);
+
+        // The next frame over the budget isn't logged again right away.
+        let logged_at = profile_state.budget_offenders_logged_at;
+        assert!(logged_at.is_some());
+        profiler::frame_start(profile_state);
+        thread::sleep(Duration::from_millis(1));
+        profiler::frame_end(profile_state);
+        assert_eq!(logged_at, profile_state.budget_offenders_logged_at);
+
+        profiler::set_frame_budget(profile_state, None, false);
+        assert_eq!(None, profiler::budget_flash_intensity(profile_state));
+    }
+
+    #[test]
+    fn event_markers() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profiler::push_event_marker(profile_state, "level_load_start");
+        profiler::frame_end(profile_state);
+        profiler::frame_end(profile_state);
+        profiler::push_event_marker(profile_state, "level_load_end");
+
+        let markers = profile_state.event_markers.to_vec();
+
+        assert_eq!(
+            Some(0),
+            profiler::marker_frame_index(profile_state, &markers[0])
+        );
+        assert_eq!(
+            None,
+            profiler::marker_frame_index(profile_state, &markers[1])
+        );
+
+        profiler::frame_end(profile_state);
+
+        assert_eq!(
+            Some(2),
+            profiler::marker_frame_index(profile_state, &markers[1])
+        );
+
+        let trace = profiler::chrome_trace(profile_state);
+
+        assert!(trace.contains(r#""name":"level_load_start","cat":"marker","ph":"i""#));
+        assert!(trace.contains(r#""name":"level_load_end","cat":"marker","ph":"i""#));
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn summary_json() {
+        let profile_state = profiler::ProfileState::default();
+        let json = serde_json::to_string(&profiler::summary(&profile_state)).unwrap();
+
+        assert!(json.starts_with(r#"{"enabled":true,"frame_elapsed":{"secs":0,"nanos":0}"#));
+        assert!(json.contains(r#""snapshot":{"taken_at":"#));
+    }
+
+    #[test]
+    fn counter_log_csv() {
+        let mut log = vec![profiler::PerformanceCounterStatistics::default(); 2];
+
+        log[1].taken_at = Duration::from_micros(1500);
+        log[1]
+            .records
+            .push(profiler::PerformanceCounterStatisticsRecord {
+                name: "render, ui",
+                file_name: "lib.rs",
+                line: 12,
+                thread_id: String::from("ThreadId(1)"),
+                sum_elapsed: Duration::from_micros(3),
+                sum_hits: 2,
+                hits: 1,
+                percent: 50.,
+                category: Some("ui"),
+                ..Default::default()
+            });
+
+        let csv = profiler::counter_log_csv(&log);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("snapshot,taken_at_us,thread_id,name,"));
+        assert!(lines[0].ends_with(",std_dev_elapsed_ns,category"));
+        assert_eq!(
+            "1,1500,ThreadId(1),\"render, ui\",lib.rs,12,,0,3000,2,1,50,0,0,0,0,0,0,ui",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn prometheus_metrics() {
+        let mut profile_state = profiler::ProfileState {
+            frame_number: 3,
+            frame_elapsed: Duration::from_millis(20),
+            ..Default::default()
+        };
+
+        profile_state.performance_counter_log[0].records.push(
+            profiler::PerformanceCounterStatisticsRecord {
+                name: "update",
+                file_name: "lib.rs",
+                line: 7,
+                thread_id: String::from("ThreadId(1)"),
+                sum_elapsed: Duration::from_millis(5),
+                sum_hits: 4,
+                ..Default::default()
+            },
+        );
+
+        let text = profiler::prometheus_metrics(&profile_state);
+
+        assert!(text.contains("# TYPE debug_frames_total counter\ndebug_frames_total 3\n"));
+        assert!(text.contains("debug_frame_time_seconds{stat=\"last\"} 0.02\n"));
+        assert!(text.contains(
+            "debug_block_hits{thread=\"ThreadId(1)\",name=\"update\",file=\"lib.rs\",line=\"7\"} 4\n"
+        ));
+        assert!(!text.contains("debug_user_counter"));
+    }
+
+    #[test]
+    fn timed_fn_name() {
+        let block = crate::timed_fn!();
+        let closure = || crate::timed_fn!().name;
+        let closure_name = closure();
+
+        assert_eq!("profiler::tests::timed_fn_name", block.name);
+        assert_eq!("profiler::tests::timed_fn_name", closure_name);
+        assert_eq!(4, crate::timed_scope!("scope", { 2 + 2 }));
+    }
+
+    #[test]
+    fn timeline_spans() {
+        let mut profile_state = profiler::ProfileState::default();
+        let span = |name, begin| profiler::Span {
+            name: String::from(name),
+            begin: Duration::from_secs(begin),
+            end: None,
+        };
+
+        let asset = profiler::SpanId(profile_state.open_spans.insert(span("asset", 3)));
+        let level = profiler::SpanId(profile_state.open_spans.insert(span("level", 1)));
+        let request = profiler::SpanId(profile_state.open_spans.insert(span("request", 2)));
+
+        profiler::close_span(&mut profile_state, level, Duration::from_secs(4));
+        profiler::close_span(&mut profile_state, request, Duration::from_secs(6));
+        profiler::close_span(&mut profile_state, level, Duration::from_secs(7));
+
+        let names = |since| {
+            profiler::timeline_spans(&profile_state, Duration::from_secs(since))
+                .iter()
+                .map(|span| span.name.as_str())
+                .collect::<Vec<&str>>()
+        };
+
+        assert_eq!(vec!["level", "request", "asset"], names(0));
+        assert_eq!(vec!["request", "asset"], names(5));
+        assert_eq!(
+            Some(Duration::from_secs(4)),
+            profile_state.span_log.get(0).and_then(|span| span.end)
+        );
+        assert_eq!(1, profile_state.open_spans.len());
+        assert!(profile_state.open_spans.get(asset.0).is_some());
+
+        // Past the limit the oldest open span is dropped.
+        for i in 0..profiler::MAX_OPEN_SPANS {
+            profiler::open_span(
+                &mut profile_state,
+                "leak",
+                Duration::from_secs(10 + i as u64),
+            );
+        }
+
+        assert_eq!(profiler::MAX_OPEN_SPANS, profile_state.open_spans.len());
+        assert!(profile_state.open_spans.get(asset.0).is_none());
+    }
+
+    #[test]
+    fn expire_timed_blocks() {
+        let profile_state = Mutex::new(profiler::ProfileState::default());
+        let profile_state = &mut profile_state.lock().unwrap();
+
+        profile_state.timed_block_max_age = 2;
+        profile_state
+            .timed_blocks
+            .insert(profiler::ManualTimedBlock {
+                block: profiler::TimedBlock::open("expire_timed_blocks", file!(), line!(), true),
+                opened_at: 0,
+            });
+
+        profiler::frame_end(profile_state);
+        profiler::frame_end(profile_state);
+        assert_eq!(1, profile_state.timed_blocks.len());
+
+        profiler::frame_end(profile_state);
+        assert!(profile_state.timed_blocks.is_empty());
+    }
+
+    #[test]
+    fn nested_timed_blocks() {
+        {
+            let outer = profiler::TimedBlock::new("nested_outer", file!(), line!());
+            let inner = profiler::TimedBlock::new("nested_inner", file!(), line!());
+
+            assert_eq!(0, outer.depth);
+            assert_eq!(1, inner.depth);
+            assert_eq!(Some(outer.location()), inner.parent);
+        }
+
+        let sibling = profiler::TimedBlock::new("nested_sibling", file!(), line!());
+        assert_eq!(0, sibling.depth);
+        assert_eq!(None, sibling.parent);
     }
 }
@@ -0,0 +1,114 @@
+//! Counters pinned to the overlay, shown even if the profiler window is hidden.
+
+use std::time::Duration;
+
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::format;
+use crate::profiler::{self, ProfileState};
+use crate::render::DrawList;
+
+const PIN_LINE_HEIGHT: f32 = 18.;
+
+#[derive(Default)]
+pub struct PinsState {
+    /// Pinned counter names in the order they were pinned.
+    pub names: Vec<String>,
+}
+
+pub fn pin(pins: &mut PinsState, name: &str) -> Result<(), String> {
+    if pins.names.iter().any(|pinned| pinned == name) {
+        return Err(format!("'{}' is already pinned", name));
+    }
+
+    pins.names.push(String::from(name));
+    Ok(())
+}
+
+pub fn unpin(pins: &mut PinsState, name: &str) -> Result<(), String> {
+    match pins.names.iter().position(|pinned| pinned == name) {
+        Some(idx) => {
+            pins.names.remove(idx);
+            Ok(())
+        }
+        None => Err(format!("'{}' is not pinned", name)),
+    }
+}
+
+/// Elapsed time and hits of the counter in the latest snapshot,
+/// summed over threads and call sites, `None` if the counter wasn't hit.
+pub fn latest(profile_state: &ProfileState, name: &str) -> Option<(Duration, u32)> {
+    profiler::last_snapshot(profile_state)
+        .records
+        .iter()
+        .filter(|record| record.name == name)
+        .fold(None, |sum, record| {
+            let (elapsed, hits) = sum.unwrap_or_default();
+            Some((elapsed + record.sum_elapsed, hits + record.sum_hits))
+        })
+}
+
+/// Renders a line per pinned counter, `position` is the top left corner
+/// in screen space, returns the size of the lines.
+pub fn update(
+    pins: &PinsState,
+    profile_state: &ProfileState,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    let mut y = viewport_size.y - position.y;
+
+    for name in pins.names.iter() {
+        y -= PIN_LINE_HEIGHT;
+
+        let text = match latest(profile_state, name) {
+            Some((elapsed, hits)) => format!(
+                "{}: {} {} hits",
+                name,
+                format::duration(elapsed),
+                format::count(hits as u64)
+            ),
+            None => format!("{}: <none>", name),
+        };
+
+        draw_list.text(camera, Vec2f::new(position.x, y), text);
+    }
+
+    Vec2f::new(0., pins.names.len() as f32 * PIN_LINE_HEIGHT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::profiler::{self, PerformanceCounterStatisticsRecord};
+    use crate::profiler_pins::{self, PinsState};
+
+    #[test]
+    fn pin_and_latest() {
+        let mut pins = PinsState::default();
+
+        assert_eq!(Ok(()), profiler_pins::pin(&mut pins, "physics_step"));
+        assert!(profiler_pins::pin(&mut pins, "physics_step").is_err());
+        assert_eq!(Ok(()), profiler_pins::unpin(&mut pins, "physics_step"));
+        assert!(profiler_pins::unpin(&mut pins, "physics_step").is_err());
+
+        let mut profile_state = profiler::ProfileState::default();
+        let record = |elapsed, hits| PerformanceCounterStatisticsRecord {
+            name: "physics_step",
+            sum_elapsed: Duration::from_millis(elapsed),
+            sum_hits: hits,
+            ..Default::default()
+        };
+
+        profile_state.performance_counter_log[0].records = vec![record(2, 1), record(3, 4)];
+
+        assert_eq!(
+            Some((Duration::from_millis(5), 5)),
+            profiler_pins::latest(&profile_state, "physics_step")
+        );
+        assert_eq!(None, profiler_pins::latest(&profile_state, "render"));
+    }
+}
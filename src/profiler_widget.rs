@@ -0,0 +1,378 @@
+use vm_math::{CameraMatrices, Vec2f};
+
+use crate::debug_variables;
+use crate::format;
+use crate::input::InputEvent;
+use crate::profiler::{self, ProfileState, ProfilerSortKey};
+use crate::render::{self, DrawList};
+use crate::state::{DebugState, ProfilerLogSliderVariable, ProfilerVariable};
+use crate::ui;
+
+/// Visible rows, the rest are scrolled to.
+pub const PROFILER_WIDGET_ROWS: usize = 10;
+pub const PROFILER_WIDGET_MAX_ROWS: usize = 100;
+
+const PROFILER_WIDGET_LINE_HEIGHT: f32 = 18.;
+const LOG_SLIDER_THUMB_WIDTH: f32 = 4.;
+const LOG_SLIDER_TRACK_MARGIN: f32 = 4.;
+const HEATMAP_HEIGHT: f32 = 12.;
+const PROFILER_WIDGET_COLUMNS: [(ProfilerSortKey, &str, f32); 4] = [
+    (ProfilerSortKey::Name, "name", 240.),
+    (ProfilerSortKey::Percent, "%", 60.),
+    (ProfilerSortKey::Elapsed, "ms", 80.),
+    (ProfilerSortKey::Hits, "hits", 60.),
+];
+
+/// Handles log slider drags and clicks on the column headers and rows,
+/// returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    handle_log_slider_input(debug_state, event)
+        || handle_header_input(debug_state, event)
+        || handle_row_input(debug_state, event)
+}
+
+fn handle_log_slider_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    let slider = match debug_variables::find_profiler_log_slider_mut(&mut debug_state.variables) {
+        Some(slider) => slider,
+        None => return false,
+    };
+
+    match event {
+        InputEvent::MouseDown(point) if log_slider_contains(slider.position, *point) => {
+            slider.is_active = true;
+            slider.offset = log_slider_offset(slider, point.x);
+        }
+        InputEvent::MouseMove(point) if slider.is_active => {
+            slider.offset = log_slider_offset(slider, point.x);
+        }
+        InputEvent::MouseUp(_) if slider.is_active => {
+            slider.is_active = false;
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn handle_header_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    let point = match event {
+        InputEvent::MouseDown(point) => *point,
+        _ => return false,
+    };
+
+    let widget = match debug_variables::find_profiler_mut(&mut debug_state.variables) {
+        Some(widget) => widget,
+        None => return false,
+    };
+
+    match column_at(widget.header_position, point) {
+        Some(sort_key) => {
+            widget.sort_key = sort_key;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Selects the counter of the clicked row for the heatmap,
+/// a click on the selected row clears the selection.
+fn handle_row_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    let point = match event {
+        InputEvent::MouseDown(point) => *point,
+        _ => return false,
+    };
+
+    let widget = match debug_variables::find_profiler_mut(&mut debug_state.variables) {
+        Some(widget) => widget,
+        None => return false,
+    };
+
+    if !ui::rect_contains(&widget.panel.bounds, point) {
+        return false;
+    }
+
+    let offset = point.y - widget.panel.bounds.position.y + widget.panel.scroll;
+    let row = (offset / PROFILER_WIDGET_LINE_HEIGHT) as usize;
+    let name = match widget.rows.get(row) {
+        Some(name) => *name,
+        None => return false,
+    };
+
+    if widget.selected == Some(name) {
+        widget.selected = None;
+    } else {
+        widget.selected = Some(name);
+    }
+
+    true
+}
+
+fn table_width() -> f32 {
+    PROFILER_WIDGET_COLUMNS
+        .iter()
+        .map(|(_, _, width)| width)
+        .sum()
+}
+
+fn log_slider_contains(position: Vec2f, point: Vec2f) -> bool {
+    point.x >= position.x
+        && point.x < position.x + table_width()
+        && point.y >= position.y
+        && point.y < position.y + PROFILER_WIDGET_LINE_HEIGHT
+}
+
+/// Snapshot offset under the `x`, the left edge is the oldest snapshot
+/// and the right edge is the latest one.
+fn log_slider_offset(slider: &ProfilerLogSliderVariable, x: f32) -> usize {
+    let t = ((x - slider.position.x) / table_width()).clamp(0., 1.);
+    let last = slider.log_size.saturating_sub(1);
+
+    last - (t * last as f32).round() as usize
+}
+
+/// Column of the header under the `point`, both are in screen space.
+fn column_at(header_position: Vec2f, point: Vec2f) -> Option<ProfilerSortKey> {
+    if point.y < header_position.y || point.y >= header_position.y + PROFILER_WIDGET_LINE_HEIGHT {
+        return None;
+    }
+
+    let mut x = header_position.x;
+
+    for (sort_key, _, width) in PROFILER_WIDGET_COLUMNS.iter() {
+        if point.x >= x && point.x < x + width {
+            return Some(*sort_key);
+        }
+
+        x += width;
+    }
+
+    None
+}
+
+/// Renders the snapshot log slider, `position` is the top left corner
+/// in screen space, returns the size of the slider.
+pub fn update_log_slider(
+    slider: &mut ProfilerLogSliderVariable,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+    log_size: usize,
+) -> Vec2f {
+    slider.position = position;
+    slider.log_size = log_size;
+    slider.offset = slider.offset.min(log_size.saturating_sub(1));
+
+    let width = table_width();
+    let bottom = viewport_size.y - position.y - PROFILER_WIDGET_LINE_HEIGHT;
+    let last = log_size.saturating_sub(1).max(1) as f32;
+    let thumb_x =
+        position.x + (last - slider.offset as f32) / last * (width - LOG_SLIDER_THUMB_WIDTH);
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(position.x, bottom + LOG_SLIDER_TRACK_MARGIN),
+        Vec2f::new(
+            width,
+            PROFILER_WIDGET_LINE_HEIGHT - LOG_SLIDER_TRACK_MARGIN * 2.,
+        ),
+        render::color(0., 0., 0., 0.5),
+    );
+
+    let thumb_color = if slider.is_active {
+        render::color(1., 1., 1., 1.)
+    } else {
+        render::color(0.7, 0.7, 0.7, 1.)
+    };
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(thumb_x, bottom),
+        Vec2f::new(LOG_SLIDER_THUMB_WIDTH, PROFILER_WIDGET_LINE_HEIGHT),
+        thumb_color,
+    );
+
+    let label = if slider.offset == 0 {
+        String::from("latest")
+    } else {
+        format!("-{}", slider.offset)
+    };
+
+    draw_list.text(
+        camera,
+        Vec2f::new(position.x + width + LOG_SLIDER_THUMB_WIDTH * 2., bottom),
+        label,
+    );
+
+    Vec2f::new(width, PROFILER_WIDGET_LINE_HEIGHT)
+}
+
+/// Renders the top records of the snapshot `offset` snapshots before the latest one
+/// as a table followed by the heatmap of the selected counter,
+/// `position` is the top left corner in screen space, returns the size of the widget.
+pub fn update(
+    widget: &mut ProfilerVariable,
+    profile_state: &ProfileState,
+    offset: usize,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+    position: Vec2f,
+) -> Vec2f {
+    widget.header_position = position;
+
+    let records = profiler::top_records(
+        profiler::snapshot_at(profile_state, offset),
+        widget.sort_key,
+        &widget.filter,
+        widget.category.as_deref(),
+        PROFILER_WIDGET_MAX_ROWS,
+    );
+
+    let width = table_width();
+    let rows = records.len().min(PROFILER_WIDGET_ROWS);
+    let height = (rows + 1) as f32 * PROFILER_WIDGET_LINE_HEIGHT;
+    let top = viewport_size.y - position.y;
+
+    widget.rows = records.iter().map(|record| record.name).collect();
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(position.x, top - height),
+        Vec2f::new(width, height),
+        render::color(0., 0., 0., 0.5),
+    );
+
+    let header = PROFILER_WIDGET_COLUMNS
+        .iter()
+        .map(|(sort_key, title, _)| {
+            if *sort_key == widget.sort_key {
+                format!("[{}]", title)
+            } else {
+                String::from(*title)
+            }
+        })
+        .collect();
+
+    draw_row(
+        draw_list,
+        camera,
+        Vec2f::new(position.x, top - PROFILER_WIDGET_LINE_HEIGHT),
+        header,
+    );
+
+    // Header stays in place while the rows are scrolled.
+    let content_top = widget.panel.begin(
+        draw_list,
+        viewport_size,
+        Vec2f::new(position.x, position.y + PROFILER_WIDGET_LINE_HEIGHT),
+        Vec2f::new(width, rows as f32 * PROFILER_WIDGET_LINE_HEIGHT),
+        records.len() as f32 * PROFILER_WIDGET_LINE_HEIGHT,
+    );
+    let mut y = viewport_size.y - content_top;
+
+    for record in records.iter() {
+        y -= PROFILER_WIDGET_LINE_HEIGHT;
+
+        let row = vec![
+            String::from(record.name),
+            format!("{:.1}", record.percent),
+            format!("{:.2}", record.sum_elapsed.as_secs_f64() * 1000.),
+            format::count(record.sum_hits as u64),
+        ];
+
+        draw_row(draw_list, camera, Vec2f::new(position.x, y), row);
+    }
+
+    widget.panel.end(draw_list, camera, viewport_size);
+
+    let heatmap_height = match widget.selected {
+        Some(name) => update_heatmap(
+            profile_state,
+            name,
+            draw_list,
+            camera,
+            Vec2f::new(position.x, top - height),
+        ),
+        None => 0.,
+    };
+
+    Vec2f::new(width, height + heatmap_height)
+}
+
+/// Renders elapsed time of the counter in each snapshot of the log as a strip
+/// of columns, oldest on the left, colored from blue for the cheapest
+/// to red for the most expensive snapshot. `top` is in camera space,
+/// returns the height of the heatmap.
+fn update_heatmap(
+    profile_state: &ProfileState,
+    name: &str,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    top: Vec2f,
+) -> f32 {
+    let history = profiler::counter_history(profile_state, name);
+    let max = history.iter().max().copied().unwrap_or_default();
+    let min = history
+        .iter()
+        .filter(|elapsed| elapsed.as_nanos() > 0)
+        .min()
+        .copied()
+        .unwrap_or_default();
+    let range = (max.as_secs_f64() - min.as_secs_f64()).max(f64::EPSILON);
+
+    let width = table_width();
+    let column_width = width / history.len() as f32;
+    let label_bottom = top.y - PROFILER_WIDGET_LINE_HEIGHT;
+    let strip_bottom = label_bottom - HEATMAP_HEIGHT;
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(top.x, strip_bottom),
+        Vec2f::new(width, PROFILER_WIDGET_LINE_HEIGHT + HEATMAP_HEIGHT),
+        render::color(0., 0., 0., 0.5),
+    );
+
+    draw_list.text(
+        camera,
+        Vec2f::new(top.x, label_bottom),
+        format!(
+            "{} {:.2} - {:.2} ms",
+            name,
+            min.as_secs_f64() * 1000.,
+            max.as_secs_f64() * 1000.
+        ),
+    );
+
+    for (i, elapsed) in history.iter().enumerate() {
+        // Snapshots without the counter stay empty.
+        if elapsed.as_nanos() == 0 {
+            continue;
+        }
+
+        let t = ((elapsed.as_secs_f64() - min.as_secs_f64()) / range) as f32;
+
+        draw_list.rect(
+            camera,
+            Vec2f::new(top.x + i as f32 * column_width, strip_bottom),
+            Vec2f::new(column_width, HEATMAP_HEIGHT),
+            render::color(t, 0.2, 1. - t, 1.),
+        );
+    }
+
+    PROFILER_WIDGET_LINE_HEIGHT + HEATMAP_HEIGHT
+}
+
+fn draw_row(
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    position: Vec2f,
+    cells: Vec<String>,
+) {
+    let mut x = position.x;
+
+    for ((_, _, width), cell) in PROFILER_WIDGET_COLUMNS.iter().zip(cells) {
+        draw_list.text(camera, Vec2f::new(x, position.y), cell);
+        x += width;
+    }
+}
@@ -0,0 +1,675 @@
+//! Remote debug protocol over WebSocket, used by browser dashboards.
+//!
+//! Clients send JSON text messages, each request gets a response with the same `type`:
+//!
+//! ```text
+//! > {"type": "execute", "command": "time::pause"}
+//! < {"type":"execute","ok":true,"output":""}
+//! > {"type": "vars.list"}
+//! < {"type":"vars.list","variables":[{"path":"rendering/vsync","type":"bool","value":"true"}]}
+//! > {"type": "vars.set", "path": "ui/tint", "value": "#FF8800FF"}
+//! < {"type":"vars.set","ok":true,"value":"#FF8800FF"}
+//! > {"type": "subscribe", "topic": "profiler"}
+//! < {"type":"subscribe","ok":true}
+//! < {"type":"profiler","taken_at_ms":1520.4,"records":[...]}
+//! ```
+//!
+//! Topics are `profiler` for snapshots and `log` for captured log records.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use crate::commands::{self, CommandArgument};
+use crate::debug_variables::{self, DebugVariable};
+use crate::format;
+use crate::json::Json;
+use crate::profiler;
+use crate::state::{read_debug_state, write_debug_state};
+
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const SUBSCRIPTIONS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+const MAX_MESSAGE_SIZE: u64 = 1 << 20;
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+pub struct RemoteHandle {
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl RemoteHandle {
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::warn!("Remote debug thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for RemoteHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts listening on `addr`, the listener stops when the handle is dropped.
+pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<RemoteHandle> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+
+    log::info!(
+        "Remote debug is listening on ws://{}",
+        listener.local_addr()?
+    );
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    let thread = thread::Builder::new()
+        .name(String::from("debug-remote"))
+        .spawn(move || accept_loop(listener, thread_running))?;
+
+    Ok(RemoteHandle {
+        running,
+        thread: Some(thread),
+    })
+}
+
+fn accept_loop(listener: TcpListener, running: Arc<AtomicBool>) {
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                log::info!("Remote debug client connected: {}", addr);
+
+                let client_running = running.clone();
+                let result = thread::Builder::new()
+                    .name(format!("debug-remote-{}", addr))
+                    .spawn(move || {
+                        if let Err(err) = serve_client(stream, client_running) {
+                            log::warn!("Remote debug client {} error: {}", addr, err);
+                        }
+                    });
+
+                if let Err(err) = result {
+                    log::warn!("Failed to spawn remote debug client thread: {}", err);
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+            Err(err) => {
+                log::warn!("Remote debug accept failed: {}", err);
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Frame received by the reader thread.
+enum Incoming {
+    Text(String),
+    Ping(Vec<u8>),
+    Close,
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    profiler: bool,
+    /// Time of the last sent snapshot.
+    profiler_taken_at: Duration,
+    log: bool,
+    /// `LogState::total_records` when the last records were sent.
+    log_total_records: u64,
+}
+
+fn serve_client(mut stream: TcpStream, running: Arc<AtomicBool>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    handshake(&mut stream)?;
+
+    let mut reader = stream.try_clone()?;
+    let (sender, receiver) = mpsc::channel();
+
+    // Frames are read on a separate thread, so subscriptions can be pushed
+    // while the client is silent.
+    thread::Builder::new()
+        .name(String::from("debug-remote-reader"))
+        .spawn(move || loop {
+            let incoming = read_message(&mut reader);
+            let is_done = !matches!(incoming, Ok(Incoming::Text(_)) | Ok(Incoming::Ping(_)));
+
+            if sender.send(incoming).is_err() || is_done {
+                break;
+            }
+        })?;
+
+    let mut subscriptions = Subscriptions::default();
+
+    while running.load(Ordering::SeqCst) {
+        match receiver.recv_timeout(SUBSCRIPTIONS_POLL_INTERVAL) {
+            Ok(Ok(Incoming::Text(text))) => {
+                let response = handle_request(&text, &mut subscriptions);
+                write_frame(&mut stream, OPCODE_TEXT, response.as_bytes())?;
+            }
+            Ok(Ok(Incoming::Ping(payload))) => write_frame(&mut stream, OPCODE_PONG, &payload)?,
+            Ok(Ok(Incoming::Close)) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Ok(Err(err)) => return Err(err),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+
+        for message in poll_subscriptions(&mut subscriptions) {
+            write_frame(&mut stream, OPCODE_TEXT, message.as_bytes())?;
+        }
+    }
+
+    write_frame(&mut stream, OPCODE_CLOSE, &[])?;
+    // Unblocks the reader thread.
+    stream.shutdown(Shutdown::Both)
+}
+
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut byte = [0u8; 1];
+
+    while !request.ends_with(b"\r\n\r\n") {
+        if request.len() >= MAX_HANDSHAKE_SIZE {
+            return Err(invalid_data("handshake is too large"));
+        }
+
+        stream.read_exact(&mut byte)?;
+        request.push(byte[0]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let key = request
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim())
+        .ok_or_else(|| invalid_data("missing Sec-WebSocket-Key header"))?;
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+
+    stream.write_all(response.as_bytes())
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads frames until the message is complete, fragmented messages are joined.
+fn read_message<R: Read>(reader: &mut R) -> io::Result<Incoming> {
+    let mut message = Vec::new();
+
+    loop {
+        let (is_final, opcode, payload) = read_frame(reader)?;
+
+        match opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                message.extend_from_slice(&payload);
+
+                if message.len() as u64 > MAX_MESSAGE_SIZE {
+                    return Err(invalid_data("message is too large"));
+                }
+
+                if is_final {
+                    return String::from_utf8(message)
+                        .map(Incoming::Text)
+                        .map_err(|_| invalid_data("message isn't valid UTF-8"));
+                }
+            }
+            OPCODE_PING => return Ok(Incoming::Ping(payload)),
+            OPCODE_PONG => {}
+            OPCODE_CLOSE => return Ok(Incoming::Close),
+            _ => return Err(invalid_data("unsupported frame opcode")),
+        }
+    }
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+
+    let is_final = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let is_masked = header[1] & 0x80 != 0;
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+
+    if len > MAX_MESSAGE_SIZE {
+        return Err(invalid_data("frame is too large"));
+    }
+
+    let mut mask = [0u8; 4];
+
+    if is_masked {
+        reader.read_exact(&mut mask)?;
+    }
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+
+    if is_masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+
+    Ok((is_final, opcode, payload))
+}
+
+/// Writes unmasked final frame, servers never mask frames.
+fn write_frame<W: Write>(writer: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+
+    if payload.len() < 126 {
+        frame.push(payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+fn handle_request(text: &str, subscriptions: &mut Subscriptions) -> String {
+    let request = match Json::parse(text) {
+        Ok(request) => request,
+        Err(err) => return error_response("error", &format!("bad request: {}", err)),
+    };
+
+    let request_type = match request.get("type").and_then(Json::as_str) {
+        Some(request_type) => request_type,
+        None => return error_response("error", "request type is missing"),
+    };
+
+    let result = match request_type {
+        "execute" => execute_request(&request),
+        "vars.list" => Ok(vars_list_response()),
+        "vars.set" => vars_set_request(&request),
+        "subscribe" | "unsubscribe" => {
+            subscribe_request(&request, subscriptions, request_type == "subscribe")
+        }
+        _ => Err(format!("unknown request type '{}'", request_type)),
+    };
+
+    match result {
+        Ok(fields) => format!(
+            r#"{{"type":{},"ok":true{}}}"#,
+            format::json_string(request_type),
+            fields
+        ),
+        Err(err) => error_response(request_type, &err),
+    }
+}
+
+fn error_response(request_type: &str, message: &str) -> String {
+    format!(
+        r#"{{"type":{},"ok":false,"error":{}}}"#,
+        format::json_string(request_type),
+        format::json_string(message)
+    )
+}
+
+/// Returns additional response fields, each prefixed with a comma.
+fn execute_request(request: &Json) -> Result<String, String> {
+    let command = request
+        .get("command")
+        .and_then(Json::as_str)
+        .ok_or_else(|| String::from("command is missing"))?;
+
+    let output = commands::execute_command(command).map_err(|err| err.to_string())?;
+
+    Ok(format!(
+        r#","output":{}"#,
+        format::json_string(&output.to_string())
+    ))
+}
+
+fn vars_list_response() -> String {
+    let debug_state = read_debug_state();
+    let mut variables = Vec::new();
+
+    debug_variables::visit(&debug_state.variables, "", &mut |path, variable| {
+        match variable {
+            DebugVariable::Group(_, _)
+            | DebugVariable::Profiler(_, _)
+            | DebugVariable::ProfilerLogSlider(_, _) => return,
+            _ => {}
+        }
+
+        variables.push(format!(
+            r#"{{"path":{},"type":{},"value":{}}}"#,
+            format::json_string(path),
+            format::json_string(debug_variables::type_name(variable)),
+            format::json_string(&debug_variables::value_string(variable)),
+        ));
+    });
+
+    format!(r#","variables":[{}]"#, variables.join(","))
+}
+
+fn vars_set_request(request: &Json) -> Result<String, String> {
+    let path = request
+        .get("path")
+        .and_then(Json::as_str)
+        .ok_or_else(|| String::from("path is missing"))?;
+    let argument = request
+        .get("value")
+        .and_then(json_argument)
+        .ok_or_else(|| String::from("value should be bool, number, string or array of numbers"))?;
+
+    let debug_state = &mut *write_debug_state();
+    let variable = debug_variables::find_mut(&mut debug_state.variables, path)
+        .ok_or_else(|| format!("Variable '{}' not found", path))?;
+
+    debug_variables::set_from_argument(variable, &argument)?;
+    debug_variables::notify_changed(&mut debug_state.on_change, variable);
+
+    Ok(format!(
+        r#","value":{}"#,
+        format::json_string(&debug_variables::value_string(variable))
+    ))
+}
+
+fn subscribe_request(
+    request: &Json,
+    subscriptions: &mut Subscriptions,
+    subscribe: bool,
+) -> Result<String, String> {
+    match request.get("topic").and_then(Json::as_str) {
+        Some("profiler") => {
+            subscriptions.profiler = subscribe;
+            subscriptions.profiler_taken_at = Duration::from_nanos(0);
+        }
+        Some("log") => {
+            subscriptions.log = subscribe;
+            // Only records captured after subscribing are streamed.
+            subscriptions.log_total_records = read_debug_state().log.total_records;
+        }
+        Some(topic) => return Err(format!("unknown topic '{}'", topic)),
+        None => return Err(String::from("topic is missing")),
+    }
+
+    Ok(String::new())
+}
+
+/// Returns messages with the data changed since the last poll.
+fn poll_subscriptions(subscriptions: &mut Subscriptions) -> Vec<String> {
+    let mut messages = Vec::new();
+
+    if subscriptions.profiler {
+        let profile_state = profiler::get_profile_state();
+        let snapshot = profiler::last_snapshot(&profile_state);
+
+        if snapshot.taken_at != subscriptions.profiler_taken_at {
+            subscriptions.profiler_taken_at = snapshot.taken_at;
+
+            let records: Vec<String> = snapshot
+                .records
+                .iter()
+                .map(|record| {
+                    format!(
+                        r#"{{"name":{},"percent":{},"elapsed_ms":{},"hits":{}}}"#,
+                        format::json_string(record.name),
+                        record.percent,
+                        record.sum_elapsed.as_secs_f64() * 1000.,
+                        record.sum_hits,
+                    )
+                })
+                .collect();
+
+            messages.push(format!(
+                r#"{{"type":"profiler","taken_at_ms":{},"frame_ms":{},"records":[{}]}}"#,
+                snapshot.taken_at.as_secs_f64() * 1000.,
+                profile_state.frame_elapsed.as_secs_f64() * 1000.,
+                records.join(","),
+            ));
+        }
+    }
+
+    if subscriptions.log {
+        let debug_state = read_debug_state();
+        let log_state = &debug_state.log;
+        let new_records = (log_state.total_records - subscriptions.log_total_records)
+            .min(log_state.records.len() as u64) as usize;
+
+        subscriptions.log_total_records = log_state.total_records;
+
+        for record in log_state
+            .records
+            .iter()
+            .skip(log_state.records.len() - new_records)
+        {
+            messages.push(format!(
+                r#"{{"type":"log","level":{},"target":{},"message":{}}}"#,
+                format::json_string(&record.level.to_string()),
+                format::json_string(&record.target),
+                format::json_string(&record.message),
+            ));
+        }
+    }
+
+    messages
+}
+
+/// Converts the value into the command argument, strings like `#RRGGBBAA` become colors,
+/// arrays of two or three numbers become vectors and other arrays become lists.
+fn json_argument(value: &Json) -> Option<CommandArgument> {
+    match value {
+        Json::Bool(value) => Some(CommandArgument::Bool(*value)),
+        Json::Number(value) => Some(CommandArgument::Number(*value)),
+        Json::String(value) => match commands::parse_color(value) {
+            Some([r, g, b, a]) => Some(CommandArgument::Color(r, g, b, a)),
+            None => Some(CommandArgument::String(value.clone())),
+        },
+        Json::Array(values) => {
+            let components: Option<Vec<f64>> = values
+                .iter()
+                .map(|value| match value {
+                    Json::Number(value) => Some(*value),
+                    _ => None,
+                })
+                .collect();
+
+            match components.as_deref() {
+                Some([x, y]) => Some(CommandArgument::Vec2(*x, *y)),
+                Some([x, y, z]) => Some(CommandArgument::Vec3(*x, *y, *z)),
+                _ => values
+                    .iter()
+                    .map(json_argument)
+                    .collect::<Option<Vec<CommandArgument>>>()
+                    .map(CommandArgument::List),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+
+    for (i, value) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::with_capacity((data.len() + 2) / 3 * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(value >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::CommandArgument;
+    use crate::json::Json;
+    use crate::remote::{self, Incoming};
+
+    #[test]
+    fn accept_key() {
+        // Example from RFC 6455.
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            remote::accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+        assert_eq!("Zm9vYg==", remote::base64(b"foob"));
+    }
+
+    #[test]
+    fn read_masked_and_fragmented_message() {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut data = vec![0x01, 0x83];
+        data.extend_from_slice(&mask);
+        data.extend(b"Hel".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        data.extend_from_slice(&[0x80, 0x02]);
+        data.extend_from_slice(b"lo");
+
+        match remote::read_message(&mut data.as_slice()) {
+            Ok(Incoming::Text(text)) => assert_eq!("Hello", text),
+            _ => panic!("expected text message"),
+        }
+
+        let mut frame = Vec::new();
+        remote::write_frame(&mut frame, remote::OPCODE_TEXT, &[b'x'; 200]).unwrap();
+
+        assert_eq!(&[0x81, 126, 0, 200], &frame[..4]);
+        assert_eq!(204, frame.len());
+    }
+
+    #[test]
+    fn parse_json_request() {
+        let request =
+            Json::parse(r#" {"type": "vars.set", "path": "a\"b", "value": [1, -2.5e1]} "#).unwrap();
+
+        assert_eq!(Some("vars.set"), request.get("type").and_then(Json::as_str));
+        assert_eq!(Some("a\"b"), request.get("path").and_then(Json::as_str));
+        assert_eq!(
+            Some(CommandArgument::Vec2(1., -25.)),
+            request.get("value").and_then(remote::json_argument)
+        );
+        assert_eq!(
+            Some(CommandArgument::Color(255, 136, 0, 255)),
+            remote::json_argument(&Json::String(String::from("#FF8800FF")))
+        );
+        assert!(Json::parse(r#"{"type": }"#).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use vm::gapi;
+use vm_math::{create_2d_model_matrix, CameraMatrices, Mat4f, Rect, Transforms2D, Vec2f, Vec4f};
+
+use crate::theme::Theme;
+
+pub struct Quad {
+    pub mvp_matrix: Mat4f,
+    pub color: Vec4f,
+}
+
+pub const FONT_SIZE: f32 = 14.;
+
+/// Overlay primitives collected during `step` and submitted in `render`.
+pub struct DrawList {
+    pub quads: Vec<Quad>,
+    pub texts: Vec<gapi::TextData>,
+    /// Positions and sizes are given in logical pixels and multiplied by the scale,
+    /// e.g. 2 for high-DPI displays.
+    pub scale: f32,
+    pub font_id: u32,
+    /// Font size in logical pixels.
+    pub font_size: f32,
+    /// Rects and lines are cut to the last clip rect, texts are skipped unless their line
+    /// fits it vertically. Pushed by panels and windows in camera space.
+    /// Clipping is done on the CPU, so it doesn't depend on the gapi pipeline state,
+    /// lines are cut along the segment and their width may stick out by half a pixel.
+    pub clips: Vec<Rect>,
+}
+
+impl Default for DrawList {
+    fn default() -> Self {
+        DrawList {
+            quads: Vec::new(),
+            texts: Vec::new(),
+            scale: 1.,
+            font_id: 0,
+            font_size: FONT_SIZE,
+            clips: Vec::new(),
+        }
+    }
+}
+
+impl DrawList {
+    pub fn clear(&mut self) {
+        self.quads.clear();
+        self.texts.clear();
+        self.clips.clear();
+    }
+
+    /// Clips the following primitives to the `rect` in camera space,
+    /// nested clips are intersected.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let rect = match self.clips.last() {
+            Some(clip) => {
+                let (position, size) = clip_rect(clip, rect.position, rect.size)
+                    .unwrap_or((rect.position, Vec2f::ZERO));
+
+                Rect { position, size }
+            }
+            None => rect,
+        };
+
+        self.clips.push(rect);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    pub fn rect(&mut self, camera: &CameraMatrices, position: Vec2f, size: Vec2f, color: Vec4f) {
+        let (position, size) = match self.clips.last() {
+            Some(clip) => match clip_rect(clip, position, size) {
+                Some(clipped) => clipped,
+                None => return,
+            },
+            None => (position, size),
+        };
+
+        let transforms = Transforms2D {
+            position: self.scaled(position),
+            scaling: self.scaled(size),
+            rotation: 0.,
+        };
+
+        self.quads.push(Quad {
+            mvp_matrix: camera.mvp_matrix * create_2d_model_matrix(transforms),
+            color,
+        });
+    }
+
+    /// Quad from `a` to `b` rotated along the segment.
+    pub fn line(&mut self, camera: &CameraMatrices, a: Vec2f, b: Vec2f, width: f32, color: Vec4f) {
+        let (a, b) = match self.clips.last() {
+            Some(clip) => match clip_segment(clip, a, b) {
+                Some(clipped) => clipped,
+                None => return,
+            },
+            None => (a, b),
+        };
+
+        let a = self.scaled(a);
+        let b = self.scaled(b);
+        let dx = b.x - a.x;
+        let dy = b.y - a.y;
+        let transforms = Transforms2D {
+            position: a,
+            scaling: Vec2f::new((dx * dx + dy * dy).sqrt(), width * self.scale),
+            rotation: dy.atan2(dx),
+        };
+
+        self.quads.push(Quad {
+            mvp_matrix: camera.mvp_matrix * create_2d_model_matrix(transforms),
+            color,
+        });
+    }
+
+    pub fn text(&mut self, camera: &CameraMatrices, position: Vec2f, text: String) {
+        if let Some(clip) = self.clips.last() {
+            if position.y < clip.position.y
+                || position.y + self.font_size > clip.position.y + clip.size.y
+            {
+                return;
+            }
+        }
+
+        let transforms = Transforms2D {
+            position: self.scaled(position),
+            scaling: Vec2f::new(1., 1.),
+            rotation: 0.,
+        };
+
+        self.texts.push(gapi::TextData {
+            font_id: self.font_id as _,
+            font_size: (self.font_size * self.scale).round() as _,
+            mvp_matrix: camera.mvp_matrix * create_2d_model_matrix(transforms),
+            text,
+        });
+    }
+
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.font_id = theme.font_id;
+        self.font_size = theme.font_size;
+    }
+
+    pub fn flush(&mut self, context: &gapi::GApiContext) {
+        for quad in self.quads.iter() {
+            gapi::set_color_pipeline(context, quad.color);
+            gapi::draw_quads(context, &[quad.mvp_matrix]);
+        }
+
+        if !self.texts.is_empty() {
+            gapi::draw_texts(context, &self.texts);
+        }
+
+        self.clear();
+    }
+
+    fn scaled(&self, value: Vec2f) -> Vec2f {
+        Vec2f::new(value.x * self.scale, value.y * self.scale)
+    }
+}
+
+/// Part of the rect inside the clip, `None` if they don't overlap.
+fn clip_rect(clip: &Rect, position: Vec2f, size: Vec2f) -> Option<(Vec2f, Vec2f)> {
+    let left = position.x.max(clip.position.x);
+    let bottom = position.y.max(clip.position.y);
+    let right = (position.x + size.x).min(clip.position.x + clip.size.x);
+    let top = (position.y + size.y).min(clip.position.y + clip.size.y);
+
+    if right <= left || top <= bottom {
+        return None;
+    }
+
+    Some((
+        Vec2f::new(left, bottom),
+        Vec2f::new(right - left, top - bottom),
+    ))
+}
+
+/// Part of the segment from `a` to `b` inside the clip, `None` if it's outside.
+fn clip_segment(clip: &Rect, a: Vec2f, b: Vec2f) -> Option<(Vec2f, Vec2f)> {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let mut enter: f32 = 0.;
+    let mut exit: f32 = 1.;
+
+    // Liang-Barsky, each edge limits the parameter range of the segment inside the clip.
+    let edges = [
+        (-dx, a.x - clip.position.x),
+        (dx, clip.position.x + clip.size.x - a.x),
+        (-dy, a.y - clip.position.y),
+        (dy, clip.position.y + clip.size.y - a.y),
+    ];
+
+    for &(direction, distance) in edges.iter() {
+        if direction == 0. {
+            if distance < 0. {
+                return None;
+            }
+        } else if direction < 0. {
+            enter = enter.max(distance / direction);
+        } else {
+            exit = exit.min(distance / direction);
+        }
+    }
+
+    if enter > exit {
+        return None;
+    }
+
+    Some((
+        Vec2f::new(a.x + dx * enter, a.y + dy * enter),
+        Vec2f::new(a.x + dx * exit, a.y + dy * exit),
+    ))
+}
+
+pub fn color(r: f32, g: f32, b: f32, a: f32) -> Vec4f {
+    Vec4f::new(r, g, b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render;
+    use vm_math::{Rect, Vec2f};
+
+    #[test]
+    fn clip_segment() {
+        let clip = Rect {
+            position: Vec2f::new(0., 0.),
+            size: Vec2f::new(10., 10.),
+        };
+
+        let (a, b) = render::clip_segment(&clip, Vec2f::new(5., -5.), Vec2f::new(5., 15.)).unwrap();
+        assert_eq!((5., 0.), (a.x, a.y));
+        assert_eq!((5., 10.), (b.x, b.y));
+
+        let (a, b) = render::clip_segment(&clip, Vec2f::new(2., 2.), Vec2f::new(8., 4.)).unwrap();
+        assert_eq!((2., 2.), (a.x, a.y));
+        assert_eq!((8., 4.), (b.x, b.y));
+
+        assert!(render::clip_segment(&clip, Vec2f::new(-5., 5.), Vec2f::new(-1., 15.)).is_none());
+        assert!(render::clip_segment(&clip, Vec2f::new(12., 0.), Vec2f::new(12., 10.)).is_none());
+    }
+}
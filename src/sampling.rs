@@ -0,0 +1,144 @@
+//! Sampling mode of the profiler: a background thread periodically records
+//! which timed blocks are open on each thread, so the time of the blocks
+//! can be estimated even in code without fine grained instrumentation.
+//!
+//! Samples are collected into `ProfileState::sampling` on `profiler::frame_end`,
+//! see `profiler::top_sampled_blocks`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use crate::profiler::{self, SamplingStatistics};
+use crate::state;
+
+pub const DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_millis(1);
+pub const MIN_SAMPLING_INTERVAL: Duration = Duration::from_micros(100);
+pub const MAX_SAMPLING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Samples are handed over to the profile state in batches to keep the lock free.
+const SAMPLES_SUBMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    static ref SAMPLER: Mutex<Option<Sampler>> = Mutex::new(None);
+}
+
+struct Sampler {
+    running: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+pub fn is_running() -> bool {
+    state::recover(SAMPLER.lock(), "sampler").is_some()
+}
+
+/// Starts the sampling thread, restarts it if it's already running.
+pub fn start(interval: Duration) -> Result<(), String> {
+    if interval < MIN_SAMPLING_INTERVAL || interval > MAX_SAMPLING_INTERVAL {
+        return Err(format!(
+            "Sampling interval should be in {:?}..{:?}, found {:?}",
+            MIN_SAMPLING_INTERVAL, MAX_SAMPLING_INTERVAL, interval
+        ));
+    }
+
+    stop();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    profiler::set_sampling(true);
+
+    let thread = thread::Builder::new()
+        .name(String::from("debug-sampler"))
+        .spawn(move || sample_loop(interval, thread_running))
+        .map_err(|err| {
+            profiler::set_sampling(false);
+            format!("Failed to spawn sampling thread: {}", err)
+        })?;
+
+    *state::recover(SAMPLER.lock(), "sampler") = Some(Sampler { running, thread });
+    Ok(())
+}
+
+/// Stops the sampling thread, collected samples are kept until `profiler::reset_samples`.
+pub fn stop() {
+    let sampler = state::recover(SAMPLER.lock(), "sampler").take();
+
+    if let Some(sampler) = sampler {
+        sampler.running.store(false, Ordering::SeqCst);
+        sampler.thread.thread().unpark();
+
+        if sampler.thread.join().is_err() {
+            log::warn!("Sampling thread panicked");
+        }
+
+        profiler::set_sampling(false);
+    }
+}
+
+fn sample_loop(interval: Duration, running: Arc<AtomicBool>) {
+    let mut samples = SamplingStatistics {
+        interval,
+        ..Default::default()
+    };
+    let mut submitted_at = Instant::now();
+
+    while running.load(Ordering::SeqCst) {
+        park_until(Instant::now() + interval, &running);
+
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+
+        profiler::sample_open_blocks(&mut samples);
+
+        if submitted_at.elapsed() >= SAMPLES_SUBMIT_INTERVAL {
+            profiler::submit_samples(&mut samples);
+            submitted_at = Instant::now();
+        }
+    }
+
+    profiler::submit_samples(&mut samples);
+}
+
+/// Parks the sampling thread until `deadline`, `stop` unparks it early.
+fn park_until(deadline: Instant, running: &AtomicBool) {
+    while running.load(Ordering::SeqCst) {
+        let now = Instant::now();
+
+        if now >= deadline {
+            return;
+        }
+
+        thread::park_timeout(deadline - now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::sampling::{self, MAX_SAMPLING_INTERVAL};
+
+    #[test]
+    fn stop_wakes_sampling_thread() {
+        sampling::start(MAX_SAMPLING_INTERVAL).unwrap();
+        thread::sleep(Duration::from_millis(10));
+
+        let timer = Instant::now();
+        sampling::stop();
+
+        assert!(!sampling::is_running());
+        assert!(timer.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn interval_out_of_range() {
+        assert!(sampling::start(Duration::from_micros(1)).is_err());
+        assert!(sampling::start(MAX_SAMPLING_INTERVAL * 2).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Commands executed later or repeatedly, e.g. `debug::defer 5 "profile::export_csv \"a.csv\""`
+//! or `debug::every 1 "profile::top 5"`, ticked from the module step.
+
+use std::time::{Duration, Instant};
+
+use crate::commands;
+use crate::state::DebugState;
+
+/// Longest delay and repeat interval of the scheduled commands.
+pub const MAX_SCHEDULE_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Shortest repeat interval, the commands are executed once per frame at most anyway.
+pub const MIN_REPEAT_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct ScheduledCommand {
+    pub id: u64,
+    pub command: String,
+    pub at: Instant,
+    /// Interval of the repeated command, `None` for a single execution.
+    pub repeat: Option<Duration>,
+}
+
+#[derive(Default)]
+pub struct SchedulerState {
+    pub commands: Vec<ScheduledCommand>,
+    next_id: u64,
+}
+
+/// Schedules the command to run after the `delay` and then every `repeat` if it's set,
+/// returns the id for `cancel`.
+pub fn schedule(
+    state: &mut SchedulerState,
+    command: &str,
+    delay: Duration,
+    repeat: Option<Duration>,
+    now: Instant,
+) -> Result<u64, String> {
+    if command.trim().is_empty() {
+        return Err(String::from("Command should not be empty"));
+    }
+
+    if repeat.map_or(false, |repeat| repeat < MIN_REPEAT_INTERVAL) {
+        return Err(format!(
+            "Repeat interval should be at least {:?}",
+            MIN_REPEAT_INTERVAL
+        ));
+    }
+
+    let at = now
+        .checked_add(delay)
+        .filter(|_| delay <= MAX_SCHEDULE_DELAY)
+        .ok_or_else(|| format!("Delay should be at most {:?}", MAX_SCHEDULE_DELAY))?;
+
+    state.next_id += 1;
+    state.commands.push(ScheduledCommand {
+        id: state.next_id,
+        command: String::from(command),
+        at,
+        repeat,
+    });
+
+    Ok(state.next_id)
+}
+
+pub fn cancel(state: &mut SchedulerState, id: u64) -> Result<(), String> {
+    match state.commands.iter().position(|command| command.id == id) {
+        Some(idx) => {
+            state.commands.remove(idx);
+            Ok(())
+        }
+        None => Err(format!("Scheduled command {} not found", id)),
+    }
+}
+
+/// Takes the commands due at `now` in the order of their time,
+/// repeated commands are rescheduled. Missed repeats are skipped rather than executed at once.
+pub fn take_due(state: &mut SchedulerState, now: Instant) -> Vec<String> {
+    let mut due: Vec<(Instant, String)> = Vec::new();
+
+    for scheduled in state
+        .commands
+        .iter_mut()
+        .filter(|scheduled| scheduled.at <= now)
+    {
+        due.push((scheduled.at, scheduled.command.clone()));
+
+        if let Some(repeat) = scheduled.repeat {
+            let missed = (now - scheduled.at).as_nanos() / repeat.as_nanos();
+            scheduled.at += repeat * (missed.min(u128::from(u32::MAX - 1)) as u32 + 1);
+        }
+    }
+
+    state
+        .commands
+        .retain(|scheduled| scheduled.repeat.is_some() || scheduled.at > now);
+
+    due.sort_by_key(|(at, _)| *at);
+    due.into_iter().map(|(_, command)| command).collect()
+}
+
+/// Executes the due commands, failed commands are logged.
+pub fn tick(debug_state: &mut DebugState) {
+    for command in take_due(&mut debug_state.scheduler, Instant::now()) {
+        if let Err(err) = commands::execute(debug_state, &command) {
+            log::warn!("Scheduled command '{}' failed: {}", command, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::scheduler::{self, SchedulerState};
+
+    #[test]
+    fn defer_and_repeat() {
+        let mut state = SchedulerState::default();
+        let start = Instant::now();
+        let secs = |secs: f64| start + Duration::from_secs_f64(secs);
+
+        let every = scheduler::schedule(
+            &mut state,
+            "profile::top 5",
+            Duration::from_secs(1),
+            Some(Duration::from_secs(1)),
+            start,
+        )
+        .unwrap();
+        scheduler::schedule(
+            &mut state,
+            "time::pause",
+            Duration::from_millis(500),
+            None,
+            start,
+        )
+        .unwrap();
+        assert!(scheduler::schedule(&mut state, "", Duration::from_secs(1), None, start).is_err());
+        assert!(scheduler::schedule(
+            &mut state,
+            "time::pause",
+            Duration::from_secs(u64::MAX),
+            None,
+            start
+        )
+        .is_err());
+        assert!(scheduler::schedule(
+            &mut state,
+            "time::pause",
+            Duration::from_secs(1),
+            Some(Duration::from_nanos(1)),
+            start
+        )
+        .is_err());
+
+        assert!(scheduler::take_due(&mut state, secs(0.1)).is_empty());
+        assert_eq!(
+            vec!["time::pause", "profile::top 5"],
+            scheduler::take_due(&mut state, secs(1.))
+        );
+        assert_eq!(1, state.commands.len());
+
+        // Missed repeats are executed once.
+        assert_eq!(
+            vec!["profile::top 5"],
+            scheduler::take_due(&mut state, secs(3.5))
+        );
+        assert!(scheduler::take_due(&mut state, secs(3.9)).is_empty());
+
+        assert_eq!(Ok(()), scheduler::cancel(&mut state, every));
+        assert!(scheduler::cancel(&mut state, every).is_err());
+        assert!(scheduler::take_due(&mut state, secs(10.)).is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+
+use crate::state;
+
+lazy_static! {
+    static ref SCREENSHOT_STATE: Mutex<ScreenshotState> = Mutex::new(ScreenshotState::default());
+}
+
+/// RGBA8 pixels, rows go from top to bottom.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Implemented by the host that owns the framebuffer.
+pub trait FramebufferCapture: Send {
+    /// Reads back the last rendered frame, `None` if it isn't available.
+    fn capture(&mut self) -> Option<Image>;
+}
+
+#[derive(Default)]
+pub struct ScreenshotState {
+    /// Locked separately, so the state isn't locked while the host reads back the frame.
+    pub capture: Option<Arc<Mutex<Box<dyn FramebufferCapture>>>>,
+    /// Paths of the screenshots to write after the next frame is rendered.
+    pub requests: Vec<String>,
+}
+
+fn get_screenshot_state<'a>() -> MutexGuard<'a, ScreenshotState> {
+    state::recover(SCREENSHOT_STATE.lock(), "screenshot state")
+}
+
+pub fn register_capture(capture: Box<dyn FramebufferCapture>) {
+    get_screenshot_state().capture = Some(Arc::new(Mutex::new(capture)));
+}
+
+/// Queues the screenshot, the timestamp is appended to the file name
+/// so repeated requests don't overwrite each other, returns the final path.
+pub fn request(path: &str) -> Result<String, String> {
+    let state = &mut get_screenshot_state();
+
+    if state.capture.is_none() {
+        return Err(String::from("Framebuffer capture isn't registered"));
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or(0);
+    let path = timestamped_path(path, timestamp);
+
+    state.requests.push(path.clone());
+    Ok(path)
+}
+
+fn timestamped_path(path: &str, timestamp: u128) -> String {
+    let file_name_start = path
+        .rfind(|ch| ch == '/' || ch == '\\')
+        .map_or(0, |pos| pos + 1);
+
+    match path[file_name_start..].rfind('.') {
+        Some(pos) if pos > 0 => {
+            let (stem, extension) = path.split_at(file_name_start + pos);
+            format!("{}_{}{}", stem, timestamp, extension)
+        }
+        _ => format!("{}_{}", path, timestamp),
+    }
+}
+
+/// Captures the framebuffer for the queued requests, called after the frame is rendered.
+pub(crate) fn process_requests() {
+    let (requests, capture) = {
+        let state = &mut get_screenshot_state();
+
+        if state.requests.is_empty() {
+            return;
+        }
+
+        (std::mem::take(&mut state.requests), state.capture.clone())
+    };
+
+    let image = capture
+        .as_ref()
+        .and_then(|capture| state::recover(capture.lock(), "framebuffer capture").capture());
+
+    let image = match image {
+        Some(image) => image,
+        None => {
+            log::warn!(
+                "Failed to capture framebuffer for {} screenshots",
+                requests.len()
+            );
+            return;
+        }
+    };
+
+    for path in requests {
+        match write_png(&path, &image) {
+            Ok(()) => log::info!("Screenshot saved to '{}'", path),
+            Err(err) => log::warn!("Failed to save screenshot to '{}': {}", path, err),
+        }
+    }
+}
+
+pub fn write_png(path: &str, image: &Image) -> io::Result<()> {
+    fs::write(path, encode_png(image)?)
+}
+
+/// Encodes the image as PNG with uncompressed deflate blocks.
+fn encode_png(image: &Image) -> io::Result<Vec<u8>> {
+    let row_size = image.width as usize * 4;
+
+    if image.pixels.len() != row_size * image.height as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "expected {} bytes of {}x{} RGBA pixels, found {}",
+                row_size * image.height as usize,
+                image.width,
+                image.height,
+                image.pixels.len()
+            ),
+        ));
+    }
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    // 8 bit RGBA, default compression, filtering and no interlace.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    push_chunk(&mut png, b"IHDR", &header);
+
+    let mut raw = Vec::with_capacity((row_size + 1) * image.height as usize);
+
+    for row in image.pixels.chunks(row_size.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    push_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    push_chunk(&mut png, b"IEND", &[]);
+
+    Ok(png)
+}
+
+fn push_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK_SIZE: usize = 65535;
+
+    let mut result = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK_SIZE).peekable();
+
+    if blocks.peek().is_none() {
+        result.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        result.push(is_final as u8);
+        result.extend_from_slice(&len.to_le_bytes());
+        result.extend_from_slice(&(!len).to_le_bytes());
+        result.extend_from_slice(block);
+    }
+
+    result.extend_from_slice(&adler32(data).to_be_bytes());
+    result
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screenshot;
+
+    struct RequestingCapture;
+
+    impl screenshot::FramebufferCapture for RequestingCapture {
+        fn capture(&mut self) -> Option<screenshot::Image> {
+            screenshot::request("shot.png").unwrap();
+            None
+        }
+    }
+
+    #[test]
+    fn request_while_capturing() {
+        screenshot::register_capture(Box::new(RequestingCapture));
+        screenshot::request("shot.png").unwrap();
+        screenshot::process_requests();
+
+        // Queued for the next frame, the state isn't locked during the capture.
+        assert_eq!(1, screenshot::get_screenshot_state().requests.len());
+    }
+
+    #[test]
+    fn timestamped_path() {
+        assert_eq!(
+            "shots/shot_42.png",
+            screenshot::timestamped_path("shots/shot.png", 42)
+        );
+        assert_eq!("shot_42", screenshot::timestamped_path("shot", 42));
+        assert_eq!(
+            "my.dir/.hidden_42",
+            screenshot::timestamped_path("my.dir/.hidden", 42)
+        );
+    }
+
+    #[test]
+    fn encode_png() {
+        let image = screenshot::Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 255, 0, 255],
+        };
+
+        let png = screenshot::encode_png(&image).unwrap();
+
+        assert_eq!(&[0x89, b'P', b'N', b'G'], &png[..4]);
+        assert_eq!(
+            &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82],
+            &png[png.len() - 12..]
+        );
+        assert_eq!(0x062c_0215, screenshot::adler32(b"hello"));
+
+        let bad_image = screenshot::Image {
+            width: 2,
+            height: 2,
+            pixels: vec![0; 4],
+        };
+
+        assert!(screenshot::encode_png(&bad_image).is_err());
+    }
+}
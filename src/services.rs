@@ -0,0 +1,107 @@
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::commands::{self, CommandError, CommandOutput};
+use crate::profiler::{self, ProfileState, PROFILE_STATE};
+use crate::state::{self, DebugState, DEBUG_STATE};
+
+/// Handle to the debug and profile states of a `DebugServicesModule`,
+/// cloning the handle shares the states.
+///
+/// Command executors use the profile state of the same handle, see `DebugState::profile_state`.
+/// Free functions like `commands::execute_command` or `profiler::counter_add`
+/// keep working with the global instance, timed blocks are collected from all
+/// threads into whichever profile state ends the frame first.
+#[derive(Clone)]
+pub struct DebugServices {
+    debug_state: Arc<RwLock<DebugState>>,
+    profile_state: Arc<Mutex<ProfileState>>,
+}
+
+impl DebugServices {
+    /// Creates isolated states, e.g. for the second VM instance or tests.
+    pub fn new() -> Self {
+        let profile_state = Arc::new(Mutex::new(ProfileState::default()));
+        let debug_state = DebugState {
+            profile_state: profile_state.clone(),
+            ..DebugState::default()
+        };
+
+        DebugServices {
+            debug_state: Arc::new(RwLock::new(debug_state)),
+            profile_state,
+        }
+    }
+
+    /// Handle to the global states used by the free functions.
+    pub fn global() -> Self {
+        DebugServices {
+            debug_state: DEBUG_STATE.clone(),
+            profile_state: PROFILE_STATE.clone(),
+        }
+    }
+
+    pub fn debug_state(&self) -> RwLockWriteGuard<DebugState> {
+        profiler::timed_lock(|| state::recover(self.debug_state.write(), "debug state"))
+    }
+
+    /// Shared access for readers, e.g. rendering or variable getters.
+    pub fn read_debug_state(&self) -> RwLockReadGuard<DebugState> {
+        profiler::timed_lock(|| state::recover(self.debug_state.read(), "debug state"))
+    }
+
+    pub fn profile_state(&self) -> MutexGuard<ProfileState> {
+        profiler::timed_lock(|| state::recover(self.profile_state.lock(), "profile state"))
+    }
+
+    pub fn execute_command(&self, command: &str) -> Result<CommandOutput, CommandError> {
+        commands::execute(&mut self.debug_state(), command)
+    }
+}
+
+impl Default for DebugServices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands;
+    use crate::commands_registry;
+    use crate::services::DebugServices;
+
+    #[test]
+    fn isolated_instances() {
+        let first = DebugServices::new();
+        let second = DebugServices::new();
+
+        commands::register_command(
+            &mut first.debug_state(),
+            "Test command",
+            commands::Command::from_fn("isolated", "answer", &[], || 42.),
+        );
+
+        assert_eq!(
+            Ok(commands::CommandOutput::Number(42.)),
+            first.execute_command("isolated::answer")
+        );
+        assert!(matches!(
+            second.execute_command("isolated::answer"),
+            Err(commands::CommandError::UnknownCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn profile_commands_use_own_state() {
+        let services = DebugServices::new();
+        commands_registry::init(&mut services.debug_state());
+
+        assert_eq!(
+            Ok(commands::CommandOutput::Text(String::from(
+                "Snapshot interval is 7 frames"
+            ))),
+            services.execute_command("profile::set_snapshot_interval 7")
+        );
+        assert_eq!(7, services.profile_state().snapshot_interval);
+    }
+}
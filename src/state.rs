@@ -1,31 +1,194 @@
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use lazy_static::lazy_static;
-use vm_math::Rect;
+use vm_math::{Rect, Vec2f, Vec3f};
 
-use crate::commands::CommandsState;
+use crate::command_palette::CommandPaletteState;
+use crate::commands::{CommandsState, PermissionLevel};
+use crate::console::ConsoleState;
+use crate::cvars::CvarsState;
+use crate::debug_variables::{OnChange, SavedValue};
+use crate::debug_watch::WatchState;
+use crate::inspector::InspectorState;
+use crate::key_bindings::KeyBindingsState;
+use crate::logging::LogState;
+use crate::panel::PanelState;
+use crate::profiler::{
+    self, ProfileState, ProfilerSortKey, PERFORMANCE_COUNTER_LOG_SIZE, PROFILE_STATE,
+};
+use crate::profiler_pins::PinsState;
+use crate::scheduler::SchedulerState;
+use crate::theme::Theme;
+use crate::ui::UiState;
+use crate::windows::WindowsState;
 
 lazy_static! {
-    pub static ref DEBUG_STATE: Mutex<DebugState> = Mutex::new(DebugState::default());
+    /// Debug state of the global `DebugServices`, see `DebugServices::global`.
+    /// Readers like variable getters take the read lock, so they don't block each other.
+    pub static ref DEBUG_STATE: Arc<RwLock<DebugState>> =
+        Arc::new(RwLock::new(DebugState::default()));
 }
 
+// Poisoned locks stay poisoned, so the incident is logged only once.
+static POISONING_REPORTED: AtomicBool = AtomicBool::new(false);
+
 pub enum DebugVariable {
     Bool(usize, BoolVariable),
+    Float(usize, FloatVariable),
+    Int(usize, IntVariable),
+    Enum(usize, EnumVariable),
+    String(usize, StringVariable),
+    Color(usize, ColorVariable),
+    Vec2(usize, Vec2Variable),
+    Vec3(usize, Vec3Variable),
+    Display(usize, DisplayVariable),
     Group(usize, GroupVariable),
     Profiler(usize, ProfilerVariable),
     ProfilerLogSlider(usize, ProfilerLogSliderVariable),
 }
 
-#[derive(Default)]
+impl DebugVariable {
+    pub fn id(&self) -> usize {
+        match self {
+            DebugVariable::Bool(id, _) => *id,
+            DebugVariable::Float(id, _) => *id,
+            DebugVariable::Int(id, _) => *id,
+            DebugVariable::Enum(id, _) => *id,
+            DebugVariable::String(id, _) => *id,
+            DebugVariable::Color(id, _) => *id,
+            DebugVariable::Vec2(id, _) => *id,
+            DebugVariable::Vec3(id, _) => *id,
+            DebugVariable::Display(id, _) => *id,
+            DebugVariable::Group(id, _) => *id,
+            DebugVariable::Profiler(id, _) => *id,
+            DebugVariable::ProfilerLogSlider(id, _) => *id,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugVariable::Bool(_, variable) => variable.name,
+            DebugVariable::Float(_, variable) => variable.name,
+            DebugVariable::Int(_, variable) => variable.name,
+            DebugVariable::Enum(_, variable) => variable.name,
+            DebugVariable::String(_, variable) => variable.name,
+            DebugVariable::Color(_, variable) => variable.name,
+            DebugVariable::Vec2(_, variable) => variable.name,
+            DebugVariable::Vec3(_, variable) => variable.name,
+            DebugVariable::Display(_, variable) => variable.name,
+            DebugVariable::Group(_, variable) => variable.name,
+            DebugVariable::Profiler(_, _) => "Counters",
+            DebugVariable::ProfilerLogSlider(_, _) => "Log",
+        }
+    }
+
+    /// Screen space rect of the variable row, set on render.
+    pub fn bounds(&self) -> &Rect {
+        match self {
+            DebugVariable::Bool(_, variable) => &variable.bounds,
+            DebugVariable::Float(_, variable) => &variable.bounds,
+            DebugVariable::Int(_, variable) => &variable.bounds,
+            DebugVariable::Enum(_, variable) => &variable.bounds,
+            DebugVariable::String(_, variable) => &variable.bounds,
+            DebugVariable::Color(_, variable) => &variable.bounds,
+            DebugVariable::Vec2(_, variable) => &variable.bounds,
+            DebugVariable::Vec3(_, variable) => &variable.bounds,
+            DebugVariable::Display(_, variable) => &variable.bounds,
+            DebugVariable::Group(_, variable) => &variable.bounds,
+            DebugVariable::Profiler(_, variable) => &variable.bounds,
+            DebugVariable::ProfilerLogSlider(_, variable) => &variable.bounds,
+        }
+    }
+
+    pub fn set_hot(&mut self, is_hot: bool) {
+        match self {
+            DebugVariable::Bool(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Float(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Int(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Enum(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::String(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Color(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Vec2(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Vec3(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Display(_, _) => {}
+            DebugVariable::Group(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::Profiler(_, variable) => variable.is_hot = is_hot,
+            DebugVariable::ProfilerLogSlider(_, variable) => variable.is_hot = is_hot,
+        }
+    }
+
+    /// Only draggable variables can stay active, the rest just receive clicks.
+    pub fn set_active(&mut self, is_active: bool) {
+        match self {
+            DebugVariable::Float(_, variable) => variable.is_active = is_active,
+            DebugVariable::Int(_, variable) => variable.is_active = is_active,
+            DebugVariable::Color(_, variable) => variable.is_active = is_active,
+            DebugVariable::Vec2(_, variable) => variable.is_active = is_active,
+            DebugVariable::Vec3(_, variable) => variable.is_active = is_active,
+            _ => {}
+        }
+    }
+}
+
 pub struct ProfilerLogSliderVariable {
     pub is_hot: bool,
+    pub is_active: bool,
     pub bounds: Rect,
+    /// Number of snapshots before the latest one shown by the profiler widget,
+    /// zero follows the latest snapshot.
+    pub offset: usize,
+    /// Top left corner of the slider in screen space, set on render.
+    pub position: Vec2f,
+    /// Snapshots in the profiler log, set on render.
+    pub log_size: usize,
+}
+
+impl Default for ProfilerLogSliderVariable {
+    fn default() -> Self {
+        ProfilerLogSliderVariable {
+            is_hot: false,
+            is_active: false,
+            bounds: Rect::ZERO,
+            offset: 0,
+            position: Vec2f::ZERO,
+            log_size: PERFORMANCE_COUNTER_LOG_SIZE,
+        }
+    }
 }
 
-#[derive(Default)]
 pub struct ProfilerVariable {
     pub is_hot: bool,
     pub bounds: Rect,
+    pub sort_key: ProfilerSortKey,
+    /// Only records with names containing the filter are shown.
+    pub filter: String,
+    /// Only records of the category are shown if it's set.
+    pub category: Option<String>,
+    /// Top left corner of the header row in screen space, set on render.
+    pub header_position: Vec2f,
+    pub panel: PanelState,
+    /// Names of the rendered rows, top to bottom.
+    pub rows: Vec<&'static str>,
+    /// Counter shown in the history heatmap below the table, selected by a click on its row.
+    pub selected: Option<&'static str>,
+}
+
+impl Default for ProfilerVariable {
+    fn default() -> Self {
+        ProfilerVariable {
+            is_hot: false,
+            bounds: Rect::ZERO,
+            sort_key: ProfilerSortKey::default(),
+            filter: String::new(),
+            category: None,
+            header_position: Vec2f::ZERO,
+            panel: PanelState::default(),
+            rows: Vec::new(),
+            selected: None,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -36,6 +199,336 @@ pub struct BoolVariable {
     pub bounds: Rect,
 }
 
+#[derive(Default)]
+pub struct FloatVariable {
+    pub name: &'static str,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+    /// Value change per pixel of mouse drag.
+    pub step: f32,
+    pub is_hot: bool,
+    pub is_active: bool,
+    pub bounds: Rect,
+}
+
+impl FloatVariable {
+    pub fn new(name: &'static str, value: f32, min: f32, max: f32, step: f32) -> Self {
+        FloatVariable {
+            name,
+            value: value.max(min).min(max),
+            min,
+            max,
+            step,
+            ..Default::default()
+        }
+    }
+
+    pub fn set(&mut self, value: f32) {
+        self.value = value.max(self.min).min(self.max);
+    }
+
+    pub fn drag(&mut self, delta: f32) {
+        self.set(self.value + delta * self.step);
+    }
+}
+
+#[derive(Default)]
+pub struct IntVariable {
+    pub name: &'static str,
+    pub value: i64,
+    pub min: i64,
+    pub max: i64,
+    /// Value change per pixel of mouse drag.
+    pub step: f32,
+    pub is_hot: bool,
+    pub is_active: bool,
+    pub bounds: Rect,
+    drag_remainder: f32,
+}
+
+impl IntVariable {
+    pub fn new(name: &'static str, value: i64, min: i64, max: i64, step: f32) -> Self {
+        IntVariable {
+            name,
+            value: value.max(min).min(max),
+            min,
+            max,
+            step,
+            ..Default::default()
+        }
+    }
+
+    pub fn set(&mut self, value: i64) {
+        self.value = value.max(self.min).min(self.max);
+    }
+
+    pub fn drag(&mut self, delta: f32) {
+        // Accumulate fractional changes so slow drags still change the value.
+        self.drag_remainder += delta * self.step;
+        let change = self.drag_remainder.trunc();
+        self.drag_remainder -= change;
+        self.set(self.value + change as i64);
+    }
+}
+
+#[derive(Default)]
+pub struct EnumVariable {
+    pub name: &'static str,
+    pub values: Vec<&'static str>,
+    pub selected: usize,
+    pub is_hot: bool,
+    pub bounds: Rect,
+}
+
+impl EnumVariable {
+    pub fn new(name: &'static str, values: Vec<&'static str>, selected: usize) -> Self {
+        EnumVariable {
+            name,
+            selected: selected.min(values.len().saturating_sub(1)),
+            values,
+            ..Default::default()
+        }
+    }
+
+    pub fn value(&self) -> Option<&'static str> {
+        self.values.get(self.selected).copied()
+    }
+
+    pub fn select(&mut self, value: &str) -> bool {
+        match self.values.iter().position(|v| *v == value) {
+            Some(idx) => {
+                self.selected = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn next(&mut self) {
+        if !self.values.is_empty() {
+            self.selected = (self.selected + 1) % self.values.len();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StringVariable {
+    pub name: &'static str,
+    /// Use `set` to change, the value is never longer than `max_length` chars.
+    pub value: String,
+    pub max_length: usize,
+    pub is_hot: bool,
+    /// Focused variable receives the keyboard input.
+    pub is_focused: bool,
+    pub bounds: Rect,
+}
+
+impl StringVariable {
+    pub fn new(name: &'static str, value: &str, max_length: usize) -> Self {
+        let mut variable = StringVariable {
+            name,
+            max_length,
+            ..Default::default()
+        };
+
+        variable.set(value);
+        variable
+    }
+
+    /// Sets the value truncated to `max_length` chars.
+    pub fn set(&mut self, value: &str) {
+        self.value = value.chars().take(self.max_length).collect();
+    }
+
+    /// Appends the char, returns false if the value is already at the max length.
+    pub fn push(&mut self, ch: char) -> bool {
+        if self.value.chars().count() >= self.max_length {
+            return false;
+        }
+
+        self.value.push(ch);
+        true
+    }
+
+    pub fn pop(&mut self) {
+        self.value.pop();
+    }
+}
+
+/// Width of the channel fields of `ColorVariable` in screen space.
+pub const COLOR_CHANNEL_FIELD_WIDTH: f32 = 48.;
+pub const COLOR_CHANNEL_NAMES: [&str; 4] = ["r", "g", "b", "a"];
+
+#[derive(Default)]
+pub struct ColorVariable {
+    pub name: &'static str,
+    /// RGBA
+    pub value: [u8; 4],
+    /// Channel value change per pixel of mouse drag.
+    pub step: f32,
+    pub is_hot: bool,
+    pub is_active: bool,
+    pub bounds: Rect,
+    /// Channel pressed by the mouse, it receives the drags.
+    pub active_channel: Option<usize>,
+    /// Left edge of the first channel field in screen space, set on render.
+    pub channels_x: f32,
+    drag_remainder: f32,
+}
+
+impl ColorVariable {
+    pub fn new(name: &'static str, value: [u8; 4], step: f32) -> Self {
+        ColorVariable {
+            name,
+            value,
+            step,
+            ..Default::default()
+        }
+    }
+
+    /// Value formatted as `#RRGGBBAA`.
+    pub fn hex(&self) -> String {
+        let [r, g, b, a] = self.value;
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+
+    /// Selects the channel field under the screen space `x` for dragging.
+    pub fn press(&mut self, x: f32) {
+        self.active_channel = field_at(
+            self.channels_x,
+            COLOR_CHANNEL_FIELD_WIDTH,
+            self.value.len(),
+            x,
+        );
+        self.drag_remainder = 0.;
+    }
+
+    pub fn drag(&mut self, delta: f32) {
+        let channel = match self.active_channel {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        self.drag_remainder += delta * self.step;
+        let change = self.drag_remainder.trunc();
+        self.drag_remainder -= change;
+
+        let value = (self.value[channel] as f32 + change).clamp(0., 255.);
+        self.value[channel] = value as u8;
+    }
+}
+
+/// Informational value updated by the game, can't be edited from the debug menu.
+#[derive(Default)]
+pub struct DisplayVariable {
+    pub name: &'static str,
+    pub value: String,
+    pub bounds: Rect,
+}
+
+/// Width of the component fields of the vector variables in screen space.
+pub const VECTOR_COMPONENT_FIELD_WIDTH: f32 = 80.;
+pub const VECTOR_COMPONENT_NAMES: [&str; 3] = ["x", "y", "z"];
+
+#[derive(Default)]
+pub struct Vec2Variable {
+    pub name: &'static str,
+    pub value: Vec2f,
+    /// Component change per pixel of mouse drag.
+    pub step: f32,
+    pub is_hot: bool,
+    pub is_active: bool,
+    pub bounds: Rect,
+    /// Component pressed by the mouse, it receives the drags.
+    pub active_component: Option<usize>,
+    /// Left edge of the first component field in screen space, set on render.
+    pub components_x: f32,
+}
+
+impl Vec2Variable {
+    pub fn new(name: &'static str, value: Vec2f, step: f32) -> Self {
+        Vec2Variable {
+            name,
+            value,
+            step,
+            ..Default::default()
+        }
+    }
+
+    pub fn components(&self) -> [f32; 2] {
+        [self.value.x, self.value.y]
+    }
+
+    /// Selects the component field under the screen space `x` for dragging.
+    pub fn press(&mut self, x: f32) {
+        self.active_component = field_at(self.components_x, VECTOR_COMPONENT_FIELD_WIDTH, 2, x);
+    }
+
+    pub fn drag(&mut self, delta: f32) {
+        match self.active_component {
+            Some(0) => self.value.x += delta * self.step,
+            Some(1) => self.value.y += delta * self.step,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Vec3Variable {
+    pub name: &'static str,
+    pub value: Vec3f,
+    /// Component change per pixel of mouse drag.
+    pub step: f32,
+    pub is_hot: bool,
+    pub is_active: bool,
+    pub bounds: Rect,
+    /// Component pressed by the mouse, it receives the drags.
+    pub active_component: Option<usize>,
+    /// Left edge of the first component field in screen space, set on render.
+    pub components_x: f32,
+}
+
+impl Vec3Variable {
+    pub fn new(name: &'static str, value: Vec3f, step: f32) -> Self {
+        Vec3Variable {
+            name,
+            value,
+            step,
+            ..Default::default()
+        }
+    }
+
+    pub fn components(&self) -> [f32; 3] {
+        [self.value.x, self.value.y, self.value.z]
+    }
+
+    /// Selects the component field under the screen space `x` for dragging.
+    pub fn press(&mut self, x: f32) {
+        self.active_component = field_at(self.components_x, VECTOR_COMPONENT_FIELD_WIDTH, 3, x);
+    }
+
+    pub fn drag(&mut self, delta: f32) {
+        match self.active_component {
+            Some(0) => self.value.x += delta * self.step,
+            Some(1) => self.value.y += delta * self.step,
+            Some(2) => self.value.z += delta * self.step,
+            _ => {}
+        }
+    }
+}
+
+/// Index of the field under the screen space `x`, fields go one after another from `fields_x`.
+fn field_at(fields_x: f32, field_width: f32, count: usize, x: f32) -> Option<usize> {
+    let field = ((x - fields_x) / field_width).floor();
+
+    if field >= 0. && field < count as f32 {
+        Some(field as usize)
+    } else {
+        None
+    }
+}
+
 pub struct GroupVariable {
     pub name: &'static str,
     pub is_expanded: bool,
@@ -45,7 +538,7 @@ pub struct GroupVariable {
 }
 
 impl GroupVariable {
-    fn new(name: &'static str, variables: Vec<DebugVariable>) -> Self {
+    pub(crate) fn new(name: &'static str, variables: Vec<DebugVariable>) -> Self {
         GroupVariable {
             name,
             is_expanded: false,
@@ -54,27 +547,452 @@ impl GroupVariable {
             bounds: Rect::ZERO,
         }
     }
+
+    pub fn add_bool(&mut self, id: usize, name: &'static str, value: bool) {
+        self.variables.push(DebugVariable::Bool(
+            id,
+            BoolVariable {
+                name,
+                value,
+                ..Default::default()
+            },
+        ));
+    }
+
+    pub fn add_float(
+        &mut self,
+        id: usize,
+        name: &'static str,
+        value: f32,
+        min: f32,
+        max: f32,
+        step: f32,
+    ) {
+        self.variables.push(DebugVariable::Float(
+            id,
+            FloatVariable::new(name, value, min, max, step),
+        ));
+    }
+
+    pub fn add_int(
+        &mut self,
+        id: usize,
+        name: &'static str,
+        value: i64,
+        min: i64,
+        max: i64,
+        step: f32,
+    ) {
+        self.variables.push(DebugVariable::Int(
+            id,
+            IntVariable::new(name, value, min, max, step),
+        ));
+    }
+
+    pub fn add_string(&mut self, id: usize, name: &'static str, value: &str, max_length: usize) {
+        self.variables.push(DebugVariable::String(
+            id,
+            StringVariable::new(name, value, max_length),
+        ));
+    }
+
+    pub fn add_color(&mut self, id: usize, name: &'static str, value: [u8; 4], step: f32) {
+        self.variables.push(DebugVariable::Color(
+            id,
+            ColorVariable::new(name, value, step),
+        ));
+    }
+
+    pub fn add_vec2(&mut self, id: usize, name: &'static str, value: Vec2f, step: f32) {
+        self.variables.push(DebugVariable::Vec2(
+            id,
+            Vec2Variable::new(name, value, step),
+        ));
+    }
+
+    pub fn add_vec3(&mut self, id: usize, name: &'static str, value: Vec3f, step: f32) {
+        self.variables.push(DebugVariable::Vec3(
+            id,
+            Vec3Variable::new(name, value, step),
+        ));
+    }
+
+    pub fn add_display(&mut self, id: usize, name: &'static str) {
+        self.variables.push(DebugVariable::Display(
+            id,
+            DisplayVariable {
+                name,
+                ..Default::default()
+            },
+        ));
+    }
+
+    pub fn add_enum(
+        &mut self,
+        id: usize,
+        name: &'static str,
+        values: Vec<&'static str>,
+        selected: usize,
+    ) {
+        self.variables.push(DebugVariable::Enum(
+            id,
+            EnumVariable::new(name, values, selected),
+        ));
+    }
+}
+
+pub const CAMERA_MIN_ZOOM: f32 = 0.25;
+pub const CAMERA_MAX_ZOOM: f32 = 8.;
+pub const CAMERA_WHEEL_ZOOM_STEP: f32 = 0.1;
+
+pub struct CameraState {
+    pub zoom: f32,
+    pub position: Vec2f,
+    pub mouse_wheel_zoom: bool,
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        CameraState {
+            zoom: 1.,
+            position: Vec2f::ZERO,
+            mouse_wheel_zoom: false,
+        }
+    }
+}
+
+impl CameraState {
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM);
+    }
+}
+
+pub const LAYOUT_MIN_SCALE: f32 = 0.5;
+pub const LAYOUT_MAX_SCALE: f32 = 4.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlayAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayAnchor {
+    pub const NAMES: &'static [&'static str] =
+        &["top_left", "top_right", "bottom_left", "bottom_right"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "top_left" => Some(OverlayAnchor::TopLeft),
+            "top_right" => Some(OverlayAnchor::TopRight),
+            "bottom_left" => Some(OverlayAnchor::BottomLeft),
+            "bottom_right" => Some(OverlayAnchor::BottomRight),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OverlayAnchor::TopLeft => "top_left",
+            OverlayAnchor::TopRight => "top_right",
+            OverlayAnchor::BottomLeft => "bottom_left",
+            OverlayAnchor::BottomRight => "bottom_right",
+        }
+    }
+}
+
+/// Placement of the overlay widgets stack on the screen.
+pub struct LayoutState {
+    pub anchor: OverlayAnchor,
+    /// Distance from the anchored corner to the stack.
+    pub margin: Vec2f,
+    /// Overlay scale set by the user, applied on top of the window scale factor.
+    pub scale: f32,
+    /// Scale factor of the window, e.g. 2 on high-DPI displays.
+    pub window_scale: f32,
+}
+
+impl Default for LayoutState {
+    fn default() -> Self {
+        LayoutState {
+            anchor: OverlayAnchor::TopLeft,
+            margin: Vec2f::new(10., 10.),
+            scale: 1.,
+            window_scale: 1.,
+        }
+    }
+}
+
+impl LayoutState {
+    /// Logical pixel size in physical pixels.
+    pub fn pixel_scale(&self) -> f32 {
+        self.window_scale * self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.clamp(LAYOUT_MIN_SCALE, LAYOUT_MAX_SCALE);
+    }
+
+    /// Non-positive factors reported by some platforms on startup are ignored.
+    pub fn set_window_scale(&mut self, scale: f32) {
+        if scale > 0. && scale.is_finite() {
+            self.window_scale = scale;
+        }
+    }
+
+    /// Top left corner of the stack of `stack_size` in screen space,
+    /// the stack is measured in the previous frame. Sizes are in logical pixels.
+    pub fn origin(&self, viewport_size: Vec2f, stack_size: Vec2f) -> Vec2f {
+        let x = match self.anchor {
+            OverlayAnchor::TopLeft | OverlayAnchor::BottomLeft => self.margin.x,
+            OverlayAnchor::TopRight | OverlayAnchor::BottomRight => {
+                viewport_size.x - self.margin.x - stack_size.x
+            }
+        };
+
+        let y = match self.anchor {
+            OverlayAnchor::TopLeft | OverlayAnchor::TopRight => self.margin.y,
+            OverlayAnchor::BottomLeft | OverlayAnchor::BottomRight => {
+                viewport_size.y - self.margin.y - stack_size.y
+            }
+        };
+
+        Vec2f::new(x.max(0.), y.max(0.))
+    }
 }
 
 pub struct DebugState {
+    /// Hidden overlay isn't rendered and doesn't handle input.
+    pub visible: bool,
+    /// Commands protected with a higher level are rejected, see `PermissionLevel`.
+    pub permission_level: PermissionLevel,
     pub commands: CommandsState,
+    pub console: ConsoleState,
+    pub palette: CommandPaletteState,
+    pub log: LogState,
+    pub watch: WatchState,
+    pub cvars: CvarsState,
+    pub key_bindings: KeyBindingsState,
+    pub scheduler: SchedulerState,
+    pub pins: PinsState,
+    pub inspector: InspectorState,
+    pub camera: CameraState,
+    pub layout: LayoutState,
+    pub theme: Theme,
+    pub windows: WindowsState,
+    pub ui: UiState,
     pub variables: GroupVariable,
+    pub saved_variables: HashMap<String, SavedValue>,
+    /// Change callbacks of the variables by id, only called with the exclusive access.
+    pub on_change: HashMap<usize, Mutex<OnChange>>,
+    /// Profile state of the same `DebugServices`, see `DebugState::profile_state`.
+    pub(crate) profile_state: Arc<Mutex<ProfileState>>,
 }
 
 impl Default for DebugState {
     fn default() -> Self {
         DebugState {
+            visible: true,
+            permission_level: PermissionLevel::Developer,
             commands: CommandsState::default(),
-            variables: GroupVariable::new(
-                "Debug Menu",
-                vec![DebugVariable::Group(1, GroupVariable::new(
-                    "Profiler",
-                    vec![
-                        DebugVariable::ProfilerLogSlider(2, ProfilerLogSliderVariable::default()),
-                        DebugVariable::Profiler(3, ProfilerVariable::default()),
-                    ],
-                ))],
-            ),
+            console: ConsoleState::default(),
+            palette: CommandPaletteState::default(),
+            log: LogState::default(),
+            watch: WatchState::default(),
+            cvars: CvarsState::default(),
+            key_bindings: KeyBindingsState::default(),
+            scheduler: SchedulerState::default(),
+            pins: PinsState::default(),
+            inspector: InspectorState::default(),
+            camera: CameraState::default(),
+            layout: LayoutState::default(),
+            theme: Theme::default(),
+            windows: WindowsState::default(),
+            ui: UiState::default(),
+            variables: GroupVariable::new("Debug Menu", Vec::new()),
+            saved_variables: HashMap::new(),
+            on_change: HashMap::new(),
+            profile_state: PROFILE_STATE.clone(),
         }
     }
 }
+
+impl DebugState {
+    /// Runs `f` with the global debug state locked for writing.
+    pub fn with<R, F: FnOnce(&mut DebugState) -> R>(f: F) -> R {
+        f(&mut write_debug_state())
+    }
+
+    /// Runs `f` with the global debug state locked for reading.
+    pub fn with_read<R, F: FnOnce(&DebugState) -> R>(f: F) -> R {
+        f(&read_debug_state())
+    }
+
+    /// Profile state owned by the same `DebugServices` handle,
+    /// command executors use it instead of the global one.
+    pub fn profile_state(&self) -> MutexGuard<ProfileState> {
+        profiler::timed_lock(|| recover(self.profile_state.lock(), "profile state"))
+    }
+}
+
+pub fn write_debug_state() -> RwLockWriteGuard<'static, DebugState> {
+    profiler::timed_lock(|| recover(DEBUG_STATE.write(), "debug state"))
+}
+
+pub fn read_debug_state() -> RwLockReadGuard<'static, DebugState> {
+    profiler::timed_lock(|| recover(DEBUG_STATE.read(), "debug state"))
+}
+
+/// Takes the guard out of the poisoned lock result, so a panic inside
+/// e.g. a command executor doesn't fail every further access to the state.
+pub fn recover<G>(result: LockResult<G>, name: &str) -> G {
+    result.unwrap_or_else(|err| {
+        if !POISONING_REPORTED.swap(true, Ordering::Relaxed) {
+            log::error!("The {} was poisoned by a panic, recovering", name);
+        }
+
+        err.into_inner()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use vm_math::{Vec2f, Vec3f};
+
+    #[test]
+    fn string_variable_max_length() {
+        let mut variable = state::StringVariable::new("address", "127.0.0.1:8080", 9);
+        assert_eq!("127.0.0.1", variable.value);
+
+        variable.pop();
+        assert!(variable.push('2'));
+        assert!(!variable.push('3'));
+        assert_eq!("127.0.0.2", variable.value);
+    }
+
+    #[test]
+    fn color_variable_drag() {
+        let mut variable = state::ColorVariable::new("tint", [255, 136, 0, 255], 1.0);
+        variable.channels_x = 100.;
+
+        variable.press(150.);
+        assert_eq!(Some(1), variable.active_channel);
+
+        variable.drag(-6.5);
+        variable.drag(-0.5);
+        assert_eq!("#FF8100FF", variable.hex());
+
+        variable.press(50.);
+        variable.drag(10.);
+        assert_eq!(None, variable.active_channel);
+        assert_eq!([255, 129, 0, 255], variable.value);
+    }
+
+    #[test]
+    fn vec3_variable_drag() {
+        let mut variable = state::Vec3Variable::new("offset", Vec3f::new(1.0, 2.0, 3.0), 0.5);
+        variable.components_x = 100.;
+
+        variable.press(270.);
+        variable.drag(4.0);
+        assert_eq!([1.0, 2.0, 5.0], variable.components());
+
+        variable.press(99.);
+        variable.drag(4.0);
+        assert_eq!(None, variable.active_component);
+        assert_eq!([1.0, 2.0, 5.0], variable.components());
+    }
+
+    #[test]
+    fn float_variable_drag() {
+        let mut variable = state::FloatVariable::new("speed", 1.0, 0.0, 2.0, 0.1);
+
+        variable.drag(5.0);
+        assert!((variable.value - 1.5).abs() < 1e-5);
+
+        variable.drag(100.0);
+        assert_eq!(2.0, variable.value);
+    }
+
+    #[test]
+    fn int_variable_drag() {
+        let mut variable = state::IntVariable::new("count", 5, 0, 10, 0.5);
+
+        variable.drag(1.0);
+        assert_eq!(5, variable.value);
+
+        variable.drag(1.0);
+        assert_eq!(6, variable.value);
+
+        variable.drag(-100.0);
+        assert_eq!(0, variable.value);
+    }
+
+    #[test]
+    fn enum_variable() {
+        let mut variable = state::EnumVariable::new("mode", vec!["fill", "wireframe"], 0);
+
+        variable.next();
+        assert_eq!(Some("wireframe"), variable.value());
+
+        variable.next();
+        assert_eq!(Some("fill"), variable.value());
+
+        assert!(!variable.select("points"));
+        assert!(variable.select("wireframe"));
+        assert_eq!(1, variable.selected);
+    }
+
+    #[test]
+    fn recover_poisoned_lock() {
+        let lock = Arc::new(RwLock::new(1));
+        let poisoner = lock.clone();
+
+        let result = thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("executor panicked");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert!(lock.is_poisoned());
+        assert_eq!(1, *state::recover(lock.read(), "test state"));
+    }
+
+    #[test]
+    fn layout_origin() {
+        let mut layout = state::LayoutState::default();
+        let viewport_size = Vec2f::new(1024., 768.);
+        let stack_size = Vec2f::new(300., 200.);
+
+        let origin = layout.origin(viewport_size, stack_size);
+        assert_eq!((10., 10.), (origin.x, origin.y));
+
+        layout.anchor = state::OverlayAnchor::from_name("bottom_right").unwrap();
+        layout.margin = Vec2f::new(20., 30.);
+        let origin = layout.origin(viewport_size, stack_size);
+        assert_eq!((704., 538.), (origin.x, origin.y));
+
+        // Stack wider than the viewport stays at the left edge.
+        layout.anchor = state::OverlayAnchor::TopRight;
+        let origin = layout.origin(Vec2f::new(200., 100.), stack_size);
+        assert_eq!((0., 30.), (origin.x, origin.y));
+    }
+
+    #[test]
+    fn layout_pixel_scale() {
+        let mut layout = state::LayoutState::default();
+
+        layout.set_window_scale(2.);
+        layout.set_window_scale(0.);
+        layout.set_scale(1.5);
+        assert_eq!(3., layout.pixel_scale());
+
+        layout.set_scale(100.);
+        assert_eq!(2. * state::LAYOUT_MAX_SCALE, layout.pixel_scale());
+    }
+}
@@ -0,0 +1,230 @@
+//! Remote console, accepts newline-delimited commands over TCP.
+//!
+//! Each command gets a response with the command output or `error: <message>`,
+//! terminated by a line with a single `.`. Output lines starting with `.` get
+//! one more `.` in front, so the output can't end the response early:
+//!
+//! ```text
+//! > debug::help "ui::zoom"
+//! < ui::zoom <zoom: number>  Set overlay camera zoom
+//! < .
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::commands;
+
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Read timeout of the clients, so they notice the console is stopped.
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct TcpConsoleHandle {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl TcpConsoleHandle {
+    /// Address the console is bound to, e.g. to find the port picked for `127.0.0.1:0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::warn!("TCP console thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for TcpConsoleHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Starts listening on `addr`, the listener stops when the handle is dropped.
+pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<TcpConsoleHandle> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+
+    let addr = listener.local_addr()?;
+    log::info!("TCP console is listening on {}", addr);
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+
+    let thread = thread::Builder::new()
+        .name(String::from("debug-tcp-console"))
+        .spawn(move || accept_loop(listener, thread_running))?;
+
+    Ok(TcpConsoleHandle {
+        addr,
+        running,
+        thread: Some(thread),
+    })
+}
+
+fn accept_loop(listener: TcpListener, running: Arc<AtomicBool>) {
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                log::info!("TCP console client connected: {}", addr);
+
+                let client_running = running.clone();
+                let result = thread::Builder::new()
+                    .name(format!("debug-tcp-console-{}", addr))
+                    .spawn(move || {
+                        if let Err(err) = serve_client(stream, client_running) {
+                            log::warn!("TCP console client {} error: {}", addr, err);
+                        }
+                    });
+
+                if let Err(err) = result {
+                    log::warn!("Failed to spawn TCP console client thread: {}", err);
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+            Err(err) => {
+                log::warn!("TCP console accept failed: {}", err);
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn serve_client(stream: TcpStream, running: Arc<AtomicBool>) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CLIENT_POLL_INTERVAL))?;
+
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    // Bytes of the current line, kept between the timed out reads.
+    let mut line = Vec::new();
+
+    while running.load(Ordering::SeqCst) {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(ref err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue;
+            }
+            Err(err) => return Err(err),
+        }
+
+        let command = String::from_utf8_lossy(&line).trim().to_owned();
+        line.clear();
+
+        if command.is_empty() {
+            continue;
+        }
+
+        let response = match commands::execute_command(&command) {
+            Ok(output) => output.to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+
+        write_response(&mut writer, &response)?;
+    }
+
+    Ok(())
+}
+
+fn write_response(writer: &mut TcpStream, response: &str) -> io::Result<()> {
+    for line in response.lines() {
+        if line.starts_with('.') {
+            writer.write_all(b".")?;
+        }
+
+        writer.write_all(line.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.write_all(b".\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands;
+    use crate::state::write_debug_state;
+    use crate::tcp_console;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn execute_remote_commands() {
+        commands::register_command(
+            &mut write_debug_state(),
+            "Test command",
+            commands::Command::from_fn("tcp", "double", &["value"], |value: f64| value * 2.),
+        );
+        commands::register_command(
+            &mut write_debug_state(),
+            "Test command",
+            commands::Command::from_fn("tcp", "lines", &[], || String::from("first\n\n.second")),
+        );
+
+        let console = tcp_console::listen("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(console.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut response = |command: &str| {
+            writeln!(stream, "{}", command).unwrap();
+
+            let mut lines = Vec::new();
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+
+                let line = line.trim_end();
+
+                if line == "." {
+                    return lines;
+                }
+
+                lines.push(String::from(line.strip_prefix('.').unwrap_or(line)));
+            }
+        };
+
+        assert_eq!(vec![String::from("42")], response("tcp::double 21"));
+        assert!(response("tcp::triple 21")[0].starts_with("error: "));
+        assert_eq!(vec!["first", "", ".second"], response("tcp::lines"));
+    }
+
+    #[test]
+    fn stop_disconnects_clients() {
+        let mut console = tcp_console::listen("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(console.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // Waits for the response, so the client is served by the time the console is stopped.
+        writeln!(stream, "tcp::unknown").unwrap();
+        let mut line = String::new();
+
+        while line.trim_end() != "." {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        console.stop();
+
+        line.clear();
+        assert_eq!(0, reader.read_line(&mut line).unwrap());
+    }
+}
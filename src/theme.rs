@@ -0,0 +1,74 @@
+//! Fonts, colors and spacing shared by the overlay widgets.
+
+use vm_math::Vec4f;
+
+use crate::render::{self, FONT_SIZE};
+
+pub const THEME_MIN_FONT_SIZE: f32 = 6.;
+pub const THEME_MAX_FONT_SIZE: f32 = 64.;
+
+#[derive(Clone)]
+pub struct Theme {
+    /// Font registered by the client.
+    pub font_id: u32,
+    /// Font size in logical pixels.
+    pub font_size: f32,
+    /// Status colors, e.g. frames within the target time or info log records.
+    pub normal: Vec4f,
+    pub warning: Vec4f,
+    pub error: Vec4f,
+    /// Panels behind the widgets.
+    pub background: Vec4f,
+    /// Space between the panel edges and the text.
+    pub padding: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            font_id: 0,
+            font_size: FONT_SIZE,
+            normal: render::color(0.2, 0.8, 0.2, 1.),
+            warning: render::color(0.9, 0.8, 0.1, 1.),
+            error: render::color(0.9, 0.2, 0.2, 1.),
+            background: render::color(0., 0., 0., 0.5),
+            padding: 6.,
+        }
+    }
+}
+
+impl Theme {
+    pub const COLOR_NAMES: &'static [&'static str] = &["normal", "warning", "error", "background"];
+
+    pub fn color_mut(&mut self, name: &str) -> Option<&mut Vec4f> {
+        match name {
+            "normal" => Some(&mut self.normal),
+            "warning" => Some(&mut self.warning),
+            "error" => Some(&mut self.error),
+            "background" => Some(&mut self.background),
+            _ => None,
+        }
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size.clamp(THEME_MIN_FONT_SIZE, THEME_MAX_FONT_SIZE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render;
+    use crate::theme::{self, Theme};
+
+    #[test]
+    fn edit_theme() {
+        let mut theme = Theme::default();
+
+        *theme.color_mut("warning").unwrap() = render::color(1., 0.5, 0., 1.);
+        assert_eq!(1., theme.warning.x);
+        assert!(theme.color_mut("accent").is_none());
+
+        theme.set_font_size(100.);
+        assert_eq!(theme::THEME_MAX_FONT_SIZE, theme.font_size);
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::state;
+
+lazy_static! {
+    pub static ref TIME_CONTROL_STATE: Mutex<TimeControlState> =
+        Mutex::new(TimeControlState::default());
+}
+
+/// Game time state shared with other engine modules, see `time_control`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub paused: bool,
+    /// Multiplier of the frame delta, 1 is the real time.
+    pub scale: f32,
+    /// Set for the single frame requested by `step` while paused.
+    pub is_stepping: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            paused: false,
+            scale: 1.,
+            is_stepping: false,
+        }
+    }
+}
+
+impl TimeControl {
+    /// Whether the world should be updated this frame.
+    pub fn is_running(&self) -> bool {
+        !self.paused || self.is_stepping
+    }
+
+    /// Game time delta for the real frame `delta`, zero while paused.
+    pub fn scale_delta(&self, delta: Duration) -> Duration {
+        if self.is_running() {
+            delta.mul_f32(self.scale)
+        } else {
+            Duration::from_nanos(0)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TimeControlState {
+    pub current: TimeControl,
+    /// Step requested during the frame, applied on the next `begin_frame`.
+    pub step_requested: bool,
+}
+
+pub fn get_time_control_state<'a>() -> MutexGuard<'a, TimeControlState> {
+    state::recover(TIME_CONTROL_STATE.lock(), "time control state")
+}
+
+/// Current game time state, engine modules query it every frame.
+pub fn time_control() -> TimeControl {
+    get_time_control_state().current
+}
+
+pub fn pause(state: &mut TimeControlState) {
+    state.current.paused = true;
+}
+
+pub fn resume(state: &mut TimeControlState) {
+    state.current.paused = false;
+    state.step_requested = false;
+}
+
+pub fn set_scale(state: &mut TimeControlState, scale: f32) -> Result<(), String> {
+    if !scale.is_finite() || scale <= 0. {
+        return Err(format!("Time scale should be positive, found {}", scale));
+    }
+
+    state.current.scale = scale;
+    Ok(())
+}
+
+/// Requests a single frame of the world update while paused.
+pub fn request_step(state: &mut TimeControlState) -> Result<(), String> {
+    if !state.current.paused {
+        return Err(String::from("Time isn't paused"));
+    }
+
+    state.step_requested = true;
+    Ok(())
+}
+
+/// Called once at the beginning of the frame, turns the requested step into the stepping frame.
+pub fn begin_frame(state: &mut TimeControlState) {
+    state.current.is_stepping = state.step_requested;
+    state.step_requested = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time_control;
+    use std::time::Duration;
+
+    #[test]
+    fn pause_and_step() {
+        let mut state = time_control::TimeControlState::default();
+
+        assert!(time_control::request_step(&mut state).is_err());
+        assert!(time_control::set_scale(&mut state, 0.).is_err());
+        assert_eq!(Ok(()), time_control::set_scale(&mut state, 0.25));
+        assert_eq!(
+            Duration::from_millis(4),
+            state.current.scale_delta(Duration::from_millis(16))
+        );
+
+        time_control::pause(&mut state);
+        time_control::begin_frame(&mut state);
+        assert!(!state.current.is_running());
+
+        assert_eq!(Ok(()), time_control::request_step(&mut state));
+        time_control::begin_frame(&mut state);
+        assert!(state.current.is_running());
+
+        time_control::begin_frame(&mut state);
+        assert!(!state.current.is_running());
+        assert_eq!(
+            Duration::from_nanos(0),
+            state.current.scale_delta(Duration::from_millis(16))
+        );
+
+        time_control::resume(&mut state);
+        assert!(state.current.is_running());
+    }
+}
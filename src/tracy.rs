@@ -0,0 +1,62 @@
+//! Forwards timed blocks, frames, user counters and markers to Tracy.
+//!
+//! Tracy zones have to be closed on the thread that opened them,
+//! so only scoped timed blocks are forwarded, blocks opened with
+//! `push_timed_block` might be dropped on another thread.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use tracy_client::{Client, PlotName, Span};
+
+use crate::profiler::BlockLocation;
+use crate::state;
+
+lazy_static! {
+    // Tracy keeps plot names for the lifetime of the program, so each name is leaked once.
+    static ref PLOT_NAMES: Mutex<HashMap<&'static str, PlotName>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    static ZONES_STACK: RefCell<Vec<(BlockLocation, Span)>> = RefCell::new(Vec::new());
+}
+
+pub(crate) fn zone_begin(location: BlockLocation) {
+    let span = Client::start().span_alloc(
+        Some(location.name),
+        location.name,
+        location.file_name,
+        location.line,
+        0,
+    );
+
+    ZONES_STACK.with(|stack| stack.borrow_mut().push((location, span)));
+}
+
+pub(crate) fn zone_end(location: BlockLocation) {
+    ZONES_STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+
+        if let Some(pos) = stack.iter().rposition(|(item, _)| *item == location) {
+            stack.remove(pos);
+        }
+    });
+}
+
+pub(crate) fn frame_mark() {
+    Client::start().frame_mark();
+}
+
+pub(crate) fn plot(name: &'static str, value: f64) {
+    let plot_name = *state::recover(PLOT_NAMES.lock(), "tracy plot names")
+        .entry(name)
+        .or_insert_with(|| PlotName::new_leak(String::from(name)));
+
+    Client::start().plot(plot_name, value);
+}
+
+pub(crate) fn message(text: &str) {
+    Client::start().message(text, 0);
+}
@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use vm_math::{Rect, Vec2f};
+
+use crate::debug_variables::{self, OnChange};
+use crate::input::{InputEvent, Key};
+use crate::state::{DebugState, DebugVariable, GroupVariable};
+
+/// Hot is the variable under the mouse cursor, active is the variable
+/// pressed by the mouse and not released yet, it keeps receiving drags
+/// even if the cursor leaves its bounds. Focused is the string variable
+/// clicked for editing, it receives the keyboard input.
+#[derive(Default)]
+pub struct UiState {
+    pub mouse_position: Vec2f,
+    pub hot: Option<usize>,
+    pub active: Option<usize>,
+    pub focused: Option<usize>,
+}
+
+/// Handles mouse input of the debug variables, returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    handle_input_in(
+        &mut debug_state.ui,
+        &mut debug_state.variables,
+        &mut debug_state.on_change,
+        event,
+    )
+}
+
+/// Handles keyboard input of the focused string variable,
+/// returns true if the event was consumed.
+pub fn handle_text_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    handle_text_input_in(
+        &mut debug_state.ui,
+        &mut debug_state.variables,
+        &mut debug_state.on_change,
+        event,
+    )
+}
+
+pub(crate) fn handle_text_input_in(
+    ui: &mut UiState,
+    root: &mut GroupVariable,
+    on_change: &mut HashMap<usize, Mutex<OnChange>>,
+    event: &InputEvent,
+) -> bool {
+    let variable = match ui.focused.and_then(|id| find_by_id_mut(root, id)) {
+        Some(variable) => variable,
+        None => return false,
+    };
+
+    let string_variable = match variable {
+        DebugVariable::String(_, string_variable) => string_variable,
+        _ => return false,
+    };
+
+    match event {
+        InputEvent::Char(ch) if !ch.is_control() => {
+            if string_variable.push(*ch) {
+                debug_variables::notify_changed(on_change, variable);
+            }
+        }
+        InputEvent::KeyDown(Key::Backspace) => {
+            string_variable.pop();
+            debug_variables::notify_changed(on_change, variable);
+        }
+        InputEvent::KeyDown(Key::Enter) | InputEvent::KeyDown(Key::Escape) => {
+            ui.focused = None;
+            update_flags(root, ui);
+        }
+        // Releases of the keys pressed while editing shouldn't leak to the game.
+        InputEvent::Char(_) | InputEvent::KeyDown(_) | InputEvent::KeyUp(_) => {}
+        _ => return false,
+    }
+
+    true
+}
+
+pub(crate) fn handle_input_in(
+    ui: &mut UiState,
+    root: &mut GroupVariable,
+    on_change: &mut HashMap<usize, Mutex<OnChange>>,
+    event: &InputEvent,
+) -> bool {
+    let consumed = match *event {
+        InputEvent::MouseMove(point) => {
+            let delta = point.x - ui.mouse_position.x;
+            ui.mouse_position = point;
+
+            match ui.active {
+                Some(id) => {
+                    if let Some(variable) = find_by_id_mut(root, id) {
+                        if drag(variable, delta) {
+                            debug_variables::notify_changed(on_change, variable);
+                        }
+                    }
+
+                    true
+                }
+                None => {
+                    ui.hot = hit_test(root, point);
+                    false
+                }
+            }
+        }
+        InputEvent::MouseDown(point) => {
+            ui.mouse_position = point;
+            ui.hot = hit_test(root, point);
+            ui.active = ui.hot;
+
+            if ui.focused != ui.hot {
+                ui.focused = None;
+            }
+
+            if let Some(id) = ui.active {
+                if let Some(variable) = find_by_id_mut(root, id) {
+                    press(variable, point);
+                }
+            }
+
+            ui.active.is_some()
+        }
+        InputEvent::MouseUp(point) => {
+            ui.mouse_position = point;
+            ui.hot = hit_test(root, point);
+
+            match ui.active.take() {
+                Some(id) => {
+                    // Click only counts if the mouse is released over the pressed variable.
+                    if ui.hot == Some(id) {
+                        if let Some(variable) = find_by_id_mut(root, id) {
+                            if let DebugVariable::String(_, _) = variable {
+                                ui.focused = Some(id);
+                            }
+
+                            if click(variable) {
+                                debug_variables::notify_changed(on_change, variable);
+                            }
+                        }
+                    }
+
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => return false,
+    };
+
+    update_flags(root, ui);
+    consumed
+}
+
+/// Returns true if the value of the variable was changed.
+fn click(variable: &mut DebugVariable) -> bool {
+    match variable {
+        DebugVariable::Bool(_, variable) => variable.value = !variable.value,
+        DebugVariable::Enum(_, variable) => variable.next(),
+        DebugVariable::Group(_, group) => {
+            group.is_expanded = !group.is_expanded;
+            return false;
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+fn press(variable: &mut DebugVariable, point: Vec2f) {
+    match variable {
+        DebugVariable::Color(_, variable) => variable.press(point.x),
+        DebugVariable::Vec2(_, variable) => variable.press(point.x),
+        DebugVariable::Vec3(_, variable) => variable.press(point.x),
+        _ => {}
+    }
+}
+
+/// Returns true if the value of the variable was changed.
+fn drag(variable: &mut DebugVariable, delta: f32) -> bool {
+    match variable {
+        DebugVariable::Float(_, variable) => variable.drag(delta),
+        DebugVariable::Int(_, variable) => variable.drag(delta),
+        DebugVariable::Color(_, variable) => variable.drag(delta),
+        DebugVariable::Vec2(_, variable) => variable.drag(delta),
+        DebugVariable::Vec3(_, variable) => variable.drag(delta),
+        _ => return false,
+    }
+
+    true
+}
+
+pub fn rect_contains(rect: &Rect, point: Vec2f) -> bool {
+    point.x >= rect.position.x
+        && point.x < rect.position.x + rect.size.x
+        && point.y >= rect.position.y
+        && point.y < rect.position.y + rect.size.y
+}
+
+/// Finds variable under the `point`, children of collapsed groups are skipped.
+fn hit_test(group: &GroupVariable, point: Vec2f) -> Option<usize> {
+    for variable in group.variables.iter() {
+        // Display variables are informational only.
+        if let DebugVariable::Display(_, _) = variable {
+            continue;
+        }
+
+        if rect_contains(variable.bounds(), point) {
+            return Some(variable.id());
+        }
+
+        if let DebugVariable::Group(_, child) = variable {
+            if child.is_expanded {
+                if let Some(id) = hit_test(child, point) {
+                    return Some(id);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn find_by_id_mut(group: &mut GroupVariable, id: usize) -> Option<&mut DebugVariable> {
+    for variable in group.variables.iter_mut() {
+        if variable.id() == id {
+            return Some(variable);
+        }
+
+        if let DebugVariable::Group(_, child) = variable {
+            if let Some(variable) = find_by_id_mut(child, id) {
+                return Some(variable);
+            }
+        }
+    }
+
+    None
+}
+
+fn update_flags(group: &mut GroupVariable, ui: &UiState) {
+    for variable in group.variables.iter_mut() {
+        let id = Some(variable.id());
+        variable.set_hot(id == ui.hot);
+        variable.set_active(id == ui.active);
+
+        match variable {
+            DebugVariable::String(_, variable) => variable.is_focused = id == ui.focused,
+            DebugVariable::Group(_, child) => update_flags(child, ui),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debug_variables;
+    use crate::input::{InputEvent, Key};
+    use crate::state::{DebugVariable, GroupVariable};
+    use crate::ui;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use vm_math::{Rect, Vec2f};
+
+    fn row(y: f32) -> Rect {
+        Rect {
+            position: Vec2f::new(0., y),
+            size: Vec2f::new(200., 18.),
+        }
+    }
+
+    #[test]
+    fn click_and_drag() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+        let mut state = ui::UiState::default();
+        let mut on_change = HashMap::new();
+
+        let vsync_id = debug_variables::register_in(&mut root, "vsync", |g, id, name| {
+            g.add_bool(id, name, false)
+        });
+        debug_variables::register_in(&mut root, "speed", |g, id, name| {
+            g.add_float(id, name, 1.0, 0.0, 10.0, 0.1)
+        });
+
+        if let Some(DebugVariable::Bool(_, variable)) =
+            debug_variables::find_mut(&mut root, "vsync")
+        {
+            variable.bounds = row(0.);
+        }
+
+        if let Some(DebugVariable::Float(_, variable)) =
+            debug_variables::find_mut(&mut root, "speed")
+        {
+            variable.bounds = row(18.);
+        }
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let vsync_changes = changes.clone();
+        let callback: debug_variables::OnChange = Box::new(move |variable: &DebugVariable| {
+            if let DebugVariable::Bool(_, variable) = variable {
+                vsync_changes.lock().unwrap().push(variable.value);
+            }
+        });
+        on_change.insert(vsync_id, Mutex::new(callback));
+
+        let inside = Vec2f::new(10., 5.);
+
+        assert!(!ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseMove(inside)
+        ));
+        assert_eq!(Some(vsync_id), state.hot);
+        assert!(ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseDown(inside)
+        ));
+        assert!(ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseUp(inside)
+        ));
+
+        match debug_variables::find(&root, "vsync") {
+            Some(DebugVariable::Bool(_, variable)) => {
+                assert!(variable.value);
+                assert!(variable.is_hot);
+            }
+            _ => panic!("variable not found"),
+        }
+
+        assert_eq!(vec![true], *changes.lock().unwrap());
+
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseDown(Vec2f::new(10., 20.)),
+        );
+        // Dragging outside of the bounds keeps the variable active.
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseMove(Vec2f::new(30., 100.)),
+        );
+
+        match debug_variables::find(&root, "speed") {
+            Some(DebugVariable::Float(_, variable)) => {
+                assert!((variable.value - 3.0).abs() < 1e-5);
+                assert!(variable.is_active);
+            }
+            _ => panic!("variable not found"),
+        }
+
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseUp(Vec2f::new(30., 100.)),
+        );
+
+        assert_eq!(None, state.active);
+        assert_eq!(None, state.hot);
+    }
+
+    #[test]
+    fn edit_string() {
+        let mut root = GroupVariable::new("Debug Menu", Vec::new());
+        let mut state = ui::UiState::default();
+        let mut on_change = HashMap::new();
+
+        let address_id = debug_variables::register_in(&mut root, "address", |g, id, name| {
+            g.add_string(id, name, "localhost", 12)
+        });
+
+        if let Some(DebugVariable::String(_, variable)) =
+            debug_variables::find_mut(&mut root, "address")
+        {
+            variable.bounds = row(0.);
+        }
+
+        let typed = InputEvent::Char('x');
+
+        assert!(!ui::handle_text_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &typed
+        ));
+
+        let inside = Vec2f::new(10., 5.);
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseDown(inside),
+        );
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseUp(inside),
+        );
+
+        assert_eq!(Some(address_id), state.focused);
+
+        // Characters past the max length of 12 are dropped.
+        for ch in ":8080".chars() {
+            assert!(ui::handle_text_input_in(
+                &mut state,
+                &mut root,
+                &mut on_change,
+                &InputEvent::Char(ch)
+            ));
+        }
+
+        match debug_variables::find(&root, "address") {
+            Some(DebugVariable::String(_, variable)) => assert_eq!("localhost:80", variable.value),
+            _ => panic!("variable not found"),
+        }
+
+        ui::handle_text_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::KeyDown(Key::Backspace),
+        );
+
+        match debug_variables::find(&root, "address") {
+            Some(DebugVariable::String(_, variable)) => {
+                assert_eq!("localhost:8", variable.value);
+                assert!(variable.is_focused);
+            }
+            _ => panic!("variable not found"),
+        }
+
+        // Clicking outside of the focused variable ends the editing.
+        ui::handle_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &InputEvent::MouseDown(Vec2f::new(10., 100.)),
+        );
+
+        assert_eq!(None, state.focused);
+        assert!(!ui::handle_text_input_in(
+            &mut state,
+            &mut root,
+            &mut on_change,
+            &typed
+        ));
+    }
+}
@@ -0,0 +1,420 @@
+//! Floating overlay windows with title bars, moved by dragging the title
+//! and resized by dragging the bottom right corner.
+//!
+//! Quads of all windows are drawn before the texts, so texts of the window
+//! below might show through the window on top where they overlap.
+
+use std::collections::HashMap;
+
+use vm_math::{CameraMatrices, Rect, Vec2f};
+
+use crate::debug_variables::SavedValue;
+use crate::input::InputEvent;
+use crate::render::{self, DrawList};
+use crate::state::DebugState;
+use crate::theme::Theme;
+use crate::ui;
+
+pub const WINDOW_TITLE_HEIGHT: f32 = 18.;
+pub const WINDOW_MIN_WIDTH: f32 = 120.;
+pub const WINDOW_MIN_HEIGHT: f32 = 60.;
+/// Windows dragged off the screen keep this much of the title bar visible.
+pub const WINDOW_VISIBLE_TITLE_WIDTH: f32 = 40.;
+
+const WINDOW_RESIZE_HANDLE_SIZE: f32 = 10.;
+const WINDOW_SAVED_PREFIX: &str = "Windows/";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WindowId {
+    Profiler,
+    Menu,
+    Console,
+}
+
+pub struct Window {
+    pub id: WindowId,
+    pub title: &'static str,
+    /// Top left corner of the title bar in screen space.
+    pub position: Vec2f,
+    /// Title bar included.
+    pub size: Vec2f,
+    /// Windows are placed relative to the viewport on the first render,
+    /// unless the position is restored from disk.
+    pub placed: bool,
+    /// Hidden windows, e.g. closed console, don't handle input.
+    pub visible: bool,
+}
+
+impl Window {
+    fn new(id: WindowId, title: &'static str, size: Vec2f) -> Self {
+        Window {
+            id,
+            title,
+            position: Vec2f::ZERO,
+            size,
+            placed: false,
+            visible: true,
+        }
+    }
+
+    fn title_bounds(&self) -> Rect {
+        Rect {
+            position: self.position,
+            size: Vec2f::new(self.size.x, WINDOW_TITLE_HEIGHT),
+        }
+    }
+
+    fn resize_handle_bounds(&self) -> Rect {
+        Rect {
+            position: Vec2f::new(
+                self.position.x + self.size.x - WINDOW_RESIZE_HANDLE_SIZE,
+                self.position.y + self.size.y - WINDOW_RESIZE_HANDLE_SIZE,
+            ),
+            size: Vec2f::new(WINDOW_RESIZE_HANDLE_SIZE, WINDOW_RESIZE_HANDLE_SIZE),
+        }
+    }
+
+    fn bounds(&self) -> Rect {
+        Rect {
+            position: self.position,
+            size: self.size,
+        }
+    }
+
+    /// Top left corner of the content area in screen space.
+    pub fn content_position(&self) -> Vec2f {
+        Vec2f::new(self.position.x, self.position.y + WINDOW_TITLE_HEIGHT)
+    }
+
+    pub fn content_size(&self) -> Vec2f {
+        Vec2f::new(self.size.x, (self.size.y - WINDOW_TITLE_HEIGHT).max(0.))
+    }
+
+    fn place(&mut self, viewport_size: Vec2f) {
+        if !self.placed {
+            self.position = match self.id {
+                WindowId::Profiler => Vec2f::new(viewport_size.x - self.size.x - 10., 10.),
+                WindowId::Menu => Vec2f::new(
+                    viewport_size.x - self.size.x - 10.,
+                    viewport_size.y - self.size.y - 10.,
+                ),
+                WindowId::Console => Vec2f::new(10., viewport_size.y - self.size.y - 10.),
+            };
+            self.placed = true;
+        }
+
+        // Title bar stays reachable after the window is resized.
+        self.position = Vec2f::new(
+            self.position
+                .x
+                .max(WINDOW_VISIBLE_TITLE_WIDTH - self.size.x)
+                .min(viewport_size.x - WINDOW_VISIBLE_TITLE_WIDTH),
+            self.position
+                .y
+                .max(0.)
+                .min(viewport_size.y - WINDOW_TITLE_HEIGHT),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Drag {
+    /// Grab point relative to the window position.
+    Move(Vec2f),
+    /// Grab point relative to the bottom right corner.
+    Resize(Vec2f),
+}
+
+pub struct WindowsState {
+    /// Back to front, the last window is drawn on top and gets the input first.
+    pub windows: Vec<Window>,
+    drag: Option<(WindowId, Drag)>,
+}
+
+impl Default for WindowsState {
+    fn default() -> Self {
+        WindowsState {
+            windows: vec![
+                Window::new(WindowId::Profiler, "Profiler", Vec2f::new(500., 540.)),
+                Window::new(WindowId::Menu, "Debug Menu", Vec2f::new(340., 420.)),
+                Window::new(WindowId::Console, "Console", Vec2f::new(720., 340.)),
+            ],
+            drag: None,
+        }
+    }
+}
+
+impl WindowsState {
+    pub fn get(&self, id: WindowId) -> &Window {
+        self.windows
+            .iter()
+            .find(|window| window.id == id)
+            .expect("all windows are created on start")
+    }
+
+    fn get_mut(&mut self, id: WindowId) -> &mut Window {
+        self.windows
+            .iter_mut()
+            .find(|window| window.id == id)
+            .expect("all windows are created on start")
+    }
+
+    /// Ids back to front.
+    pub fn order(&self) -> Vec<WindowId> {
+        self.windows.iter().map(|window| window.id).collect()
+    }
+
+    pub fn bring_to_front(&mut self, id: WindowId) {
+        if let Some(index) = self.windows.iter().position(|window| window.id == id) {
+            let window = self.windows.remove(index);
+            self.windows.push(window);
+        }
+    }
+
+    /// Adds positions and sizes to the values saved with the debug variables.
+    pub fn collect_values(&self, values: &mut HashMap<String, SavedValue>) {
+        for window in self.windows.iter().filter(|window| window.placed) {
+            values.insert(
+                format!("{}{}", WINDOW_SAVED_PREFIX, window.title),
+                SavedValue::Vector(vec![
+                    window.position.x as f64,
+                    window.position.y as f64,
+                    window.size.x as f64,
+                    window.size.y as f64,
+                ]),
+            );
+        }
+    }
+
+    /// Restores positions and sizes from the values loaded with the debug variables.
+    pub fn apply_values(&mut self, values: &HashMap<String, SavedValue>) {
+        for window in self.windows.iter_mut() {
+            let key = format!("{}{}", WINDOW_SAVED_PREFIX, window.title);
+
+            if let Some(SavedValue::Vector(value)) = values.get(&key) {
+                if let [x, y, width, height] = value[..] {
+                    window.position = Vec2f::new(x as f32, y as f32);
+                    window.size = Vec2f::new(
+                        (width as f32).max(WINDOW_MIN_WIDTH),
+                        (height as f32).max(WINDOW_MIN_HEIGHT),
+                    );
+                    window.placed = true;
+                }
+            }
+        }
+    }
+}
+
+/// Handles dragging of the title bars and resize handles, clicked windows
+/// are brought to the front. Returns true if the event was consumed.
+pub fn handle_input(debug_state: &mut DebugState, event: &InputEvent) -> bool {
+    handle_input_in(&mut debug_state.windows, event)
+}
+
+fn handle_input_in(state: &mut WindowsState, event: &InputEvent) -> bool {
+    match *event {
+        InputEvent::MouseDown(point) => {
+            let window = state
+                .windows
+                .iter()
+                .rev()
+                .filter(|window| window.visible)
+                .find(|window| ui::rect_contains(&window.bounds(), point));
+
+            let window = match window {
+                Some(window) => window,
+                None => return false,
+            };
+
+            let id = window.id;
+            let drag = if ui::rect_contains(&window.title_bounds(), point) {
+                Some(Drag::Move(Vec2f::new(
+                    point.x - window.position.x,
+                    point.y - window.position.y,
+                )))
+            } else if ui::rect_contains(&window.resize_handle_bounds(), point) {
+                Some(Drag::Resize(Vec2f::new(
+                    window.position.x + window.size.x - point.x,
+                    window.position.y + window.size.y - point.y,
+                )))
+            } else {
+                None
+            };
+
+            state.bring_to_front(id);
+            state.drag = drag.map(|drag| (id, drag));
+
+            // Clicks inside the content are left to the widgets.
+            state.drag.is_some()
+        }
+        InputEvent::MouseMove(point) => {
+            let (id, drag) = match state.drag {
+                Some(drag) => drag,
+                None => return false,
+            };
+
+            let window = state.get_mut(id);
+
+            match drag {
+                Drag::Move(grab) => {
+                    window.position = Vec2f::new(point.x - grab.x, point.y - grab.y);
+                }
+                Drag::Resize(grab) => {
+                    window.size = Vec2f::new(
+                        (point.x + grab.x - window.position.x).max(WINDOW_MIN_WIDTH),
+                        (point.y + grab.y - window.position.y).max(WINDOW_MIN_HEIGHT),
+                    );
+                }
+            }
+
+            true
+        }
+        InputEvent::MouseUp(_) => state.drag.take().is_some(),
+        _ => false,
+    }
+}
+
+/// Draws the frame of the window and clips the draw list to its content,
+/// returns the window with the content position and size for the widgets.
+/// Hidden windows aren't drawn.
+pub fn begin<'a>(
+    state: &'a mut WindowsState,
+    id: WindowId,
+    visible: bool,
+    theme: &Theme,
+    draw_list: &mut DrawList,
+    camera: &CameraMatrices,
+    viewport_size: Vec2f,
+) -> Option<&'a Window> {
+    let is_dragged = state.drag.map_or(false, |(dragged, _)| dragged == id);
+    let window = state.get_mut(id);
+    window.visible = visible;
+
+    if !visible {
+        return None;
+    }
+
+    window.place(viewport_size);
+
+    let top = viewport_size.y - window.position.y;
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(window.position.x, top - window.size.y),
+        window.size,
+        theme.background,
+    );
+
+    let title_alpha = if is_dragged { 0.35 } else { 0.2 };
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(window.position.x, top - WINDOW_TITLE_HEIGHT),
+        Vec2f::new(window.size.x, WINDOW_TITLE_HEIGHT),
+        render::color(1., 1., 1., title_alpha),
+    );
+
+    draw_list.rect(
+        camera,
+        Vec2f::new(
+            window.position.x + window.size.x - WINDOW_RESIZE_HANDLE_SIZE,
+            top - window.size.y,
+        ),
+        Vec2f::new(WINDOW_RESIZE_HANDLE_SIZE, WINDOW_RESIZE_HANDLE_SIZE),
+        render::color(1., 1., 1., 0.3),
+    );
+
+    draw_list.text(
+        camera,
+        Vec2f::new(window.position.x + theme.padding, top - WINDOW_TITLE_HEIGHT),
+        String::from(window.title),
+    );
+
+    let content_size = window.content_size();
+
+    draw_list.push_clip(Rect {
+        position: Vec2f::new(window.position.x, top - window.size.y),
+        size: content_size,
+    });
+
+    Some(window)
+}
+
+/// Pops the content clip pushed by `begin`.
+pub fn end(draw_list: &mut DrawList) {
+    draw_list.pop_clip();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::input::InputEvent;
+    use crate::windows::{self, WindowId, WindowsState};
+    use vm_math::Vec2f;
+
+    fn placed_state() -> WindowsState {
+        let mut state = WindowsState::default();
+
+        for window in state.windows.iter_mut() {
+            window.position = Vec2f::new(100., 100.);
+            window.size = Vec2f::new(200., 150.);
+            window.placed = true;
+        }
+
+        state
+    }
+
+    #[test]
+    fn drag_and_resize() {
+        let mut state = placed_state();
+
+        assert!(windows::handle_input_in(
+            &mut state,
+            &InputEvent::MouseDown(Vec2f::new(110., 105.))
+        ));
+        assert_eq!(Some(WindowId::Console), state.order().last().copied());
+
+        windows::handle_input_in(&mut state, &InputEvent::MouseMove(Vec2f::new(60., 55.)));
+        assert!(windows::handle_input_in(
+            &mut state,
+            &InputEvent::MouseUp(Vec2f::new(60., 55.))
+        ));
+
+        let console = state.get(WindowId::Console);
+        assert_eq!((50., 50.), (console.position.x, console.position.y));
+
+        // Resize handle of the console, which is on top now.
+        windows::handle_input_in(&mut state, &InputEvent::MouseDown(Vec2f::new(248., 198.)));
+        windows::handle_input_in(&mut state, &InputEvent::MouseMove(Vec2f::new(100., 300.)));
+        windows::handle_input_in(&mut state, &InputEvent::MouseUp(Vec2f::new(100., 300.)));
+
+        let console = state.get(WindowId::Console);
+        assert_eq!(
+            (windows::WINDOW_MIN_WIDTH, 252.),
+            (console.size.x, console.size.y)
+        );
+
+        // Clicks inside the content bring the window to front but aren't consumed.
+        assert!(!windows::handle_input_in(
+            &mut state,
+            &InputEvent::MouseDown(Vec2f::new(250., 200.))
+        ));
+        assert_eq!(Some(WindowId::Menu), state.order().last().copied());
+    }
+
+    #[test]
+    fn saved_positions() {
+        let state = placed_state();
+        let mut values = HashMap::new();
+        state.collect_values(&mut values);
+
+        let mut restored = WindowsState::default();
+        restored.apply_values(&values);
+
+        let menu = restored.get(WindowId::Menu);
+        assert!(menu.placed);
+        assert_eq!((100., 100.), (menu.position.x, menu.position.y));
+        assert_eq!((200., 150.), (menu.size.x, menu.size.y));
+    }
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "macros")]
+
+use std::sync::Mutex;
+
+use debug_services::profile;
+use debug_services::profiler::{self, ProfileState};
+
+#[profile]
+fn physics_step(steps: u32) -> u32 {
+    steps + 1
+}
+
+#[profile("custom name")]
+fn named_step() {}
+
+#[test]
+fn profile_attribute() {
+    let profile_state = Mutex::new(ProfileState::default());
+    let profile_state = &mut profile_state.lock().unwrap();
+
+    profiler::frame_start(profile_state);
+    assert_eq!(3, physics_step(2));
+    named_step();
+    profiler::frame_end(profile_state);
+
+    let records = &profile_state.performance_counter_states[0].records;
+    let hits = |name| {
+        records
+            .iter()
+            .find(|record| record.name == name)
+            .map(|record| record.hits)
+    };
+
+    assert_eq!(Some(1), hits("profile_macro::physics_step"));
+    assert_eq!(Some(1), hits("custom name"));
+}